@@ -0,0 +1,100 @@
+//! Representation invariants owned by a task, which are written by sending it messages. See
+//! [`ReprActor`].
+use crate::{Repr, Violation};
+use alloc::boxed::Box;
+use core::fmt::Debug;
+use tokio::sync::{mpsc, oneshot};
+
+type Message<T, I> = Box<dyn FnOnce(&mut Repr<T, I>) + Send>;
+
+/// A handle to a representation invariant owned by a tokio task (an actor). Callers send closures
+/// to the actor, which applies them one at a time and replies with the result, so the value can be
+/// shared across an async application without locking it. Cloning the handle doesn't clone the
+/// value, and the actor stops once every handle is dropped.
+///
+/// Every message is awaited by its sender, so the channel doesn't need to be bounded.
+/// ```rust
+/// use repr_rs::Repr;
+/// use repr_rs::actor::ReprActor;
+/// #[derive(Debug, Clone)]
+/// struct MinMax { min: i32, max: i32 }
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let actor = ReprActor::spawn(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max));
+/// let handle = actor.clone();
+/// tokio::spawn(async move {
+///   handle.update(|mm| mm.max = 10).await.unwrap();
+/// }).await.unwrap();
+/// assert!(actor.update(|mm| mm.min = 20).await.is_err());
+/// assert_eq!(10, actor.read(|mm| mm.max).await);
+/// # })
+/// ```
+pub struct ReprActor<T: Debug, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	sender: mpsc::UnboundedSender<Message<T, I>>,
+}
+impl<T: Debug + Send + 'static, I: Fn(&T) -> bool + Send + 'static> ReprActor<T, I> {
+	/// Moves the representation invariant into a new task on the current tokio runtime.
+	///
+	/// # Panics
+	/// If called outside of a tokio runtime.
+	pub fn spawn(mut repr: Repr<T, I>) -> Self {
+		let (sender, mut receiver) = mpsc::unbounded_channel::<Message<T, I>>();
+		tokio::spawn(async move {
+			while let Some(message) = receiver.recv().await {
+				message(&mut repr);
+			}
+		});
+		Self { sender }
+	}
+	/// Sends `f` to the actor and waits for it to reply with the result of applying it.
+	async fn ask<R: Send + 'static>(&self, f: impl FnOnce(&mut Repr<T, I>) -> R + Send + 'static) -> R {
+		let (reply, result) = oneshot::channel();
+		let message: Message<T, I> = Box::new(move |repr| {
+			let _ = reply.send(f(repr));
+		});
+		if self.sender.send(message).is_err() {
+			panic!("the actor stopped because a message panicked");
+		}
+		result.await.expect("the actor stopped because a message panicked")
+	}
+	/// Applies `f` to a read-only view of the value, after every message sent before it.
+	///
+	/// # Panics
+	/// If a previous message panicked, which stops the actor.
+	pub async fn read<R: Send + 'static>(&self, f: impl FnOnce(&T) -> R + Send + 'static) -> R {
+		self.ask(move |repr| f(repr.read())).await
+	}
+	/// Applies `update` to a copy of the value and, if the copy satisfies the invariant, commits it
+	/// and replies with what `update` returned. If the copy violates the invariant, the value is
+	/// left untouched.
+	///
+	/// # Panics
+	/// If a previous message panicked, which stops the actor.
+	pub async fn update<R: Send + 'static>(&self, update: impl FnOnce(&mut T) -> R + Send + 'static) -> Result<R, Violation> where T: Clone {
+		self.ask(move |repr| {
+			let mut value = repr.read().clone();
+			let result = update(&mut value);
+			repr.set(value).map(|()| result).map_err(|(_, violation)| violation)
+		}).await
+	}
+	/// Sets the value if it satisfies the invariant. Otherwise the new value is handed back
+	/// alongside the [`Violation`]. See [`Repr::set`].
+	///
+	/// # Panics
+	/// If a previous message panicked, which stops the actor.
+	pub async fn set(&self, value: T) -> Result<(), (T, Violation)> {
+		self.ask(move |repr| repr.set(value)).await
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Clone for ReprActor<T, I> {
+	fn clone(&self) -> Self {
+		Self {
+			sender: self.sender.clone(),
+		}
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for ReprActor<T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		// The value is owned by the actor, so it can't be formatted without waiting for it.
+		write!(f, "ReprActor(..)")
+	}
+}
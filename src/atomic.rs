@@ -0,0 +1,128 @@
+//! Lock-free representation invariants for small [`Copy`] values. See [`AtomicRepr`].
+use crate::Violation;
+use core::fmt::Debug;
+use core::sync::atomic::Ordering;
+
+mod sealed {
+	pub trait Sealed {}
+}
+
+/// A value that can be stored in an atomic, i.e. `bool` or an integer type (64-bit integers only
+/// on targets with 64-bit atomics). This trait is sealed.
+pub trait AtomicValue: Copy + Debug + sealed::Sealed {
+	/// The atomic the value is stored in.
+	#[doc(hidden)]
+	type Atomic: Send + Sync;
+	#[doc(hidden)]
+	fn atomic(value: Self) -> Self::Atomic;
+	#[doc(hidden)]
+	fn load(atomic: &Self::Atomic, ordering: Ordering) -> Self;
+	#[doc(hidden)]
+	fn compare_exchange_weak(atomic: &Self::Atomic, current: Self, new: Self, success: Ordering, failure: Ordering) -> Result<Self, Self>;
+	#[doc(hidden)]
+	fn into_inner(atomic: Self::Atomic) -> Self;
+}
+macro_rules! atomic_value {
+	($($width:literal: $value:ty => $atomic:ident),* $(,)?) => {$(
+		#[cfg(target_has_atomic = $width)]
+		impl sealed::Sealed for $value {}
+		#[cfg(target_has_atomic = $width)]
+		impl AtomicValue for $value {
+			type Atomic = core::sync::atomic::$atomic;
+			fn atomic(value: Self) -> Self::Atomic {
+				Self::Atomic::new(value)
+			}
+			fn load(atomic: &Self::Atomic, ordering: Ordering) -> Self {
+				atomic.load(ordering)
+			}
+			fn compare_exchange_weak(atomic: &Self::Atomic, current: Self, new: Self, success: Ordering, failure: Ordering) -> Result<Self, Self> {
+				atomic.compare_exchange_weak(current, new, success, failure)
+			}
+			fn into_inner(atomic: Self::Atomic) -> Self {
+				atomic.into_inner()
+			}
+		}
+	)*};
+}
+atomic_value! {
+	"8": bool => AtomicBool,
+	"8": u8 => AtomicU8,
+	"8": i8 => AtomicI8,
+	"16": u16 => AtomicU16,
+	"16": i16 => AtomicI16,
+	"32": u32 => AtomicU32,
+	"32": i32 => AtomicI32,
+	"64": u64 => AtomicU64,
+	"64": i64 => AtomicI64,
+	"ptr": usize => AtomicUsize,
+	"ptr": isize => AtomicIsize,
+}
+
+/// A representation invariant over a value stored in an atomic, for counters and flags on hot
+/// paths where a guard and a lock are too heavy. Updates are computed from the current value,
+/// checked against the invariant, and only then committed with a compare-and-swap, so other
+/// threads never observe a value that violates the invariant.
+/// ```rust
+/// use repr_rs::AtomicRepr;
+/// let connections = AtomicRepr::with_msg(0u32, |n| *n <= 2, "too many connections");
+/// connections.fetch_update(|n| n + 1).unwrap();
+/// connections.fetch_update(|n| n + 1).unwrap();
+/// let violation = connections.fetch_update(|n| n + 1).unwrap_err();
+/// assert_eq!("too many connections", violation.message());
+/// assert_eq!(2, connections.load());
+/// ```
+pub struct AtomicRepr<T: AtomicValue, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	value: T::Atomic,
+	invariant: I,
+	violation_message: &'static str,
+}
+impl<T: AtomicValue, I: Fn(&T) -> bool> AtomicRepr<T, I> {
+	/// Creates a new representation invariant with the given value and invariant function.
+	pub fn new(value: T, invariant: I) -> Self {
+		Self::with_msg(value, invariant, "Invariant violated")
+	}
+	/// Creates a new representation invariant with the given value, invariant function, and
+	/// violation message.
+	pub fn with_msg(value: T, invariant: I, violation_message: &'static str) -> Self {
+		Self {
+			value: T::atomic(value),
+			invariant,
+			violation_message,
+		}
+	}
+	/// Loads the current value.
+	pub fn load(&self) -> T {
+		T::load(&self.value, Ordering::Acquire)
+	}
+	/// Stores a new value if it satisfies the invariant. Otherwise the value is left untouched.
+	pub fn store(&self, value: T) -> Result<(), Violation> {
+		self.fetch_update(|_| value).map(drop)
+	}
+	/// Computes a new value from the current one and, if it satisfies the invariant, commits it
+	/// with a compare-and-swap, returning the previous value. If another thread changed the value
+	/// in the meantime, the new value is computed (and checked) again from the value it committed,
+	/// so `update` may be called more than once. If the new value violates the invariant, the
+	/// value is left untouched.
+	pub fn fetch_update(&self, mut update: impl FnMut(T) -> T) -> Result<T, Violation> {
+		let mut current = self.load();
+		loop {
+			let new = update(current);
+			if !(self.invariant)(&new) {
+				return Err(Violation::new(self.violation_message, &new));
+			}
+			match T::compare_exchange_weak(&self.value, current, new, Ordering::AcqRel, Ordering::Acquire) {
+				Ok(previous) => return Ok(previous),
+				Err(actual) => current = actual,
+			}
+		}
+	}
+	/// Consumes the representation invariant and returns the value.
+	pub fn into_inner(self) -> T {
+		T::into_inner(self.value)
+	}
+}
+impl<T: AtomicValue, I: Fn(&T) -> bool> Debug for AtomicRepr<T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "AtomicRepr({:?})", self.load())
+	}
+}
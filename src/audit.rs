@@ -0,0 +1,75 @@
+//! An audit trail of every write to a representation invariant. See
+//! [`crate::Repr::with_audit_sink`].
+use crate::cache::stats::Stopwatch;
+use crate::Violation;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::panic::RefUnwindSafe;
+use core::time::Duration;
+
+/// A write to a representation invariant, as received by an audit sink.
+#[non_exhaustive]
+pub struct AuditRecord<'a, T> {
+	/// The [`Debug`] representation of the value before the write.
+	pub before: &'a str,
+	/// The value after the write. If the write was rejected (e.g. by [`crate::Repr::set`]), this is
+	/// the rejected value, which wasn't committed.
+	pub after: &'a T,
+	/// Whether the value satisfied the invariant. Writes whose check was skipped by the
+	/// [`crate::CheckPolicy`] are recorded as satisfying it.
+	pub outcome: Result<(), &'a Violation>,
+	/// How long the write took, from handing out the mutable view until the invariant was checked.
+	/// It's only measured with the `std` feature, and not on wasm, where there is no monotonic
+	/// clock.
+	pub duration: Duration,
+}
+
+pub(crate) type AuditSink<T> = dyn Fn(AuditRecord<'_, T>) + Send + Sync + RefUnwindSafe;
+
+/// The audit sinks of a representation invariant, and the write that is being audited.
+pub(crate) struct Audit<T> {
+	pub(crate) sinks: Vec<Arc<AuditSink<T>>>,
+	pending: Option<Pending>,
+}
+/// A write that has started but hasn't been checked yet.
+pub(crate) struct Pending {
+	before: String,
+	stopwatch: Stopwatch,
+}
+impl<T: Debug> Audit<T> {
+	pub(crate) const fn new() -> Self {
+		Self {
+			sinks: Vec::new(),
+			pending: None,
+		}
+	}
+	/// Captures the value before a write, if there's a sink to send it to.
+	pub(crate) fn begin(&mut self, before: &T) {
+		if self.sinks.is_empty() {
+			return;
+		}
+		self.pending = Some(Pending {
+			before: format!("{:?}", before),
+			stopwatch: Stopwatch::start(),
+		});
+	}
+	/// Takes the write that is being audited, if any.
+	pub(crate) fn take(&mut self) -> Option<Pending> {
+		self.pending.take()
+	}
+	/// Sends the record of a write to every sink.
+	pub(crate) fn emit(&self, pending: Pending, after: &T, outcome: Result<(), &Violation>) {
+		let duration = pending.stopwatch.elapsed();
+		for sink in &self.sinks {
+			sink(AuditRecord {
+				before: &pending.before,
+				after,
+				outcome,
+				duration,
+			});
+		}
+	}
+}
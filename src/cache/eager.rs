@@ -1,65 +1,855 @@
 use std::fmt::Debug;
-use crate::cache::{Cache, CacheableRepr};
+use crate::cache::spawner::{self, Spawner, Task};
+use crate::cache::stats::{CacheStats, Stopwatch};
+use crate::cache::{lazy, CacheKey, CacheableRepr, CachedRead};
+use crate::metrics::Metrics;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::any::Any;
+use alloc::vec::Vec;
+use core::time::Duration;
+use downcast_rs::{impl_downcast, Downcast};
 use std::future::Future;
-use std::sync::Arc;
-use std::sync::RwLock;
-use tokio::task::{spawn_blocking, JoinHandle};
+use crate::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crate::sync::lock::{Mutex, RwLock};
+use crate::sync::{thread, Arc};
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(not(target_arch = "wasm32"))]
+use std::task::Wake;
 
-pub(crate) struct CacheableRead<T, R: Clone + Sync + Send> {
-	read_fn: fn(&T) -> R,
-	cache: Arc<RwLock<Option<R>>>,
+/// An eager cache, type-erased so it can be stored alongside caches with other result types.
+pub(crate) trait EagerCache<T>: Downcast {
+	/// Invalidates the cache and returns the recomputation that recomputes it, unless one is
+	/// already scheduled.
+	fn invalidate(&self, snapshot: &mut Snapshot<'_, T>, spawner: &alloc::sync::Arc<dyn Spawner>) -> Option<Recomputation>;
+	/// The completion of the most recently scheduled recomputation, if one was ever scheduled.
+	fn in_flight(&self) -> Option<Completion>;
+	/// Sets whether outstanding recomputations are discarded when the cache is dropped.
+	/// See [`CacheableRepr::with_discard_on_drop`].
+	fn set_discard_on_drop(&mut self, discard_on_drop: bool);
+	fn priority(&self) -> Priority;
+	fn set_priority(&mut self, priority: Priority);
+	fn stats(&self) -> CacheStats;
+	/// The cached result, if the cache isn't being recomputed.
+	fn peek(&self) -> Option<Box<dyn Any>>;
+	/// An [`Upstream`] handle to the results of this cache, for caches derived from it.
+	fn upstream(&self) -> Box<dyn Any>;
+	/// The key of the cache this cache is derived from, if any.
+	fn input(&self) -> Option<&CacheKey>;
+	/// A copy of the cache holding the same result, with fresh statistics, for a clone of the
+	/// value. Derived caches are derived from the caches with the same key in the given caches.
+	/// Also returns whether the result was copied, as it isn't if it's being recomputed.
+	fn duplicate(&self, eager: &EagerCaches<T>) -> (Box<dyn EagerCache<T>>, bool);
 }
-impl<T: Clone + Sync + Send + 'static, R: Clone + Sync + Send + 'static> CacheableRead<T, R> {
-	pub(crate) fn new(read_fn: fn(&T) -> R) -> Self {
+impl_downcast!(EagerCache<T>);
+pub(crate) type EagerCaches<T> = BTreeMap<CacheKey, Box<dyn EagerCache<T>>>;
+
+/// How urgently an eager cache is recomputed after a write. See
+/// [`EagerCacheLookup::eager_with_priority`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+	/// Recomputed before all other caches, e.g. for values that are needed right after a write.
+	High,
+	/// The default.
+	#[default]
+	Normal,
+	/// Best-effort: only recomputed once the higher priority recomputations caused by the same
+	/// write have finished.
+	Low,
+}
+
+/// How a cache registered with [`EagerCacheLookup::register`] is kept up to date.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Strategy {
+	/// Recomputed on the first read after a write. See [`CacheableRepr::lazy`].
+	#[default]
+	Lazy,
+	/// Recomputed in the background after every write. See [`EagerCacheLookup::eager`].
+	Eager {
+		priority: Priority,
+	},
+}
+
+/// The eager caching configuration of a [`CacheableRepr`].
+#[derive(Clone)]
+pub(crate) struct EagerSettings {
+	pub(crate) spawner: Option<alloc::sync::Arc<dyn Spawner>>,
+	pub(crate) debounce: Duration,
+	pub(crate) discard_on_drop: bool,
+}
+impl EagerSettings {
+	pub(crate) const fn new() -> Self {
+		Self {
+			spawner: None,
+			debounce: Duration::ZERO,
+			discard_on_drop: true,
+		}
+	}
+	pub(crate) fn spawner(&self) -> alloc::sync::Arc<dyn Spawner> {
+		self.spawner.clone().unwrap_or_else(spawner::default_spawner)
+	}
+	/// How recomputations wait for writes to quiesce, if they do.
+	fn debounce(&self) -> Option<Debounce> {
+		(!self.debounce.is_zero()).then(|| Debounce {
+			window: self.debounce,
+			spawner: self.spawner(),
+		})
+	}
+	/// Hands recomputations to the spawner, each once the recomputations it waits for have
+	/// finished. Debounced recomputations first wait for writes to quiesce, and only then for the
+	/// recomputations they wait for, so the order between priorities is kept without debouncing low
+	/// priority caches twice. The rest are handed over as one batch, in order.
+	pub(crate) fn schedule(&self, recomputations: Vec<(Recomputation, Vec<Completion>)>) {
+		if let Some(debounce) = self.debounce() {
+			for (recomputation, after) in recomputations {
+				debounce.clone().settle(recomputation, after);
+			}
+			return;
+		}
+		let spawner = self.spawner();
+		let (ready, deferred): (Vec<_>, Vec<_>) = recomputations.into_iter().partition(|(_, after)| after.is_empty());
+		if !ready.is_empty() {
+			spawner.spawn_all(ready.into_iter().map(|(recomputation, _)| recomputation.task).collect());
+		}
+		for (recomputation, after) in deferred {
+			dispatch(spawner.clone(), after, recomputation.task);
+		}
+	}
+}
+
+/// Delays recomputations until writes quiesce. See [`CacheableRepr::with_eager_debounce`].
+#[derive(Clone)]
+struct Debounce {
+	window: Duration,
+	spawner: alloc::sync::Arc<dyn Spawner>,
+}
+impl Debounce {
+	/// Makes the recomputation wait until the value it's for hasn't been replaced for a whole
+	/// window, and then for the given recomputations. The window is waited out on the shared timer
+	/// and the recomputations are waited for without blocking, so neither ties up one of the
+	/// spawner's threads.
+	fn settle(self, recomputation: Recomputation, after: Vec<Completion>) {
+		recomputation.touched.store(false, Ordering::SeqCst);
+		spawner::after(self.window, Box::new(move || {
+			if recomputation.touched.load(Ordering::SeqCst) {
+				self.settle(recomputation, after);
+			} else {
+				dispatch(self.spawner, after, recomputation.task);
+			}
+		}));
+	}
+}
+
+/// The recomputation of an invalidated eager cache, waiting to be handed to the spawner.
+pub(crate) struct Recomputation {
+	task: Task,
+	/// Set whenever the value the recomputation is for is replaced, so it can be debounced.
+	touched: Arc<AtomicBool>,
+}
+
+/// A snapshot of the value for the recomputations caused by one write. The value is cloned at most
+/// once, when the first cache needs it, and then shared by every cache.
+pub(crate) struct Snapshot<'a, T> {
+	value: &'a T,
+	shared: Option<Arc<T>>,
+}
+impl<'a, T> Snapshot<'a, T> {
+	pub(crate) fn new(value: &'a T) -> Self {
+		Self { value, shared: None }
+	}
+	fn get(&mut self) -> Arc<T> where T: Clone {
+		self.shared.get_or_insert_with(|| Arc::new(self.value.clone())).clone()
+	}
+}
+
+/// An eager cache slot. It's empty while the value is being recomputed, and holds the panic
+/// message instead of a result if the read function panicked while recomputing it.
+pub(crate) type Slot<R> = Option<Result<R, String>>;
+
+/// A read function of an eager cache, called on a recomputation task.
+pub(crate) trait ReadFn<T, R>: Clone + Send + 'static {
+	fn call(&self, value: &T) -> R;
+	/// Calls the read function with the snapshot of the value shared by the recomputations caused
+	/// by a write.
+	fn call_shared(&self, value: &Arc<T>) -> R {
+		self.call(value)
+	}
+	/// Like [`ReadFn::call_shared`], but returns the outstanding recomputation it depends on
+	/// instead of waiting for it, so recomputation tasks never block their thread on one another.
+	fn try_call_shared(&self, value: &Arc<T>) -> Result<R, Completion> {
+		Ok(self.call_shared(value))
+	}
+	/// The key of the cache whose results this read function is derived from, if any.
+	fn input(&self) -> Option<&CacheKey> {
+		None
+	}
+	/// Derives this read function from the cache with the same input key in the given caches.
+	fn relink(&mut self, _eager: &EagerCaches<T>) {}
+	/// The future computing the result, if this is an async read function. Recomputations hand it
+	/// to the spawner to poll (see [`Spawner::spawn_future`]) instead of calling the read function.
+	#[cfg(not(target_arch = "wasm32"))]
+	fn call_async(&self, _value: &Arc<T>) -> Option<Pin<Box<dyn Future<Output = R> + Send>>> {
+		None
+	}
+}
+impl<T: 'static, R: 'static> ReadFn<T, R> for fn(&T) -> R {
+	fn call(&self, value: &T) -> R {
+		self(value)
+	}
+}
+impl<T: 'static, R: 'static> ReadFn<T, R> for alloc::sync::Arc<dyn Fn(&T) -> R + Send + Sync> {
+	fn call(&self, value: &T) -> R {
+		self(value)
+	}
+}
+/// An async read function. It's called with the shared snapshot of the value, and recomputations
+/// hand the returned future to the spawner.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct AsyncReadFn<T, F>(fn(Arc<T>) -> F);
+#[cfg(not(target_arch = "wasm32"))]
+impl<T, F> Clone for AsyncReadFn<T, F> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T, F> Copy for AsyncReadFn<T, F> {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Clone + 'static, F: Future + Send + 'static> ReadFn<T, F::Output> for AsyncReadFn<T, F> {
+	fn call(&self, value: &T) -> F::Output {
+		self.call_shared(&Arc::new(value.clone()))
+	}
+	fn call_shared(&self, value: &Arc<T>) -> F::Output {
+		block_on((self.0)(value.clone()))
+	}
+	fn call_async(&self, value: &Arc<T>) -> Option<Pin<Box<dyn Future<Output = F::Output> + Send>>> {
+		Some(Box::pin((self.0)(value.clone())))
+	}
+}
+/// A read function derived from the results of another eager cache instead of from the value. See
+/// [`EagerCacheLookup::eager_derived`].
+pub(crate) struct DerivedReadFn<U, R> {
+	input: CacheKey,
+	upstream: Upstream<U>,
+	derive: fn(&U) -> R,
+}
+impl<U, R> Clone for DerivedReadFn<U, R> {
+	fn clone(&self) -> Self {
+		Self {
+			input: self.input.clone(),
+			upstream: self.upstream.clone(),
+			derive: self.derive,
+		}
+	}
+}
+impl<T: 'static, U: Clone + Sync + Send + 'static, R: 'static> ReadFn<T, R> for DerivedReadFn<U, R> {
+	fn call(&self, _: &T) -> R {
+		(self.derive)(&self.upstream.wait())
+	}
+	fn try_call_shared(&self, _: &Arc<T>) -> Result<R, Completion> {
+		self.upstream.poll().map(|upstream| (self.derive)(&upstream))
+	}
+	fn input(&self) -> Option<&CacheKey> {
+		Some(&self.input)
+	}
+	fn relink(&mut self, eager: &EagerCaches<T>) {
+		if let Some(upstream) = eager.get(&self.input).and_then(|cache| cache.upstream().downcast::<Upstream<U>>().ok()) {
+			self.upstream = *upstream;
+		}
+	}
+}
+/// A handle to the results of an eager cache that other caches are derived from.
+pub(crate) struct Upstream<R> {
+	cache: Arc<RwLock<Slot<R>>>,
+	in_flight: Arc<Mutex<Option<Completion>>>,
+}
+impl<R> Clone for Upstream<R> {
+	fn clone(&self) -> Self {
+		Self {
+			cache: self.cache.clone(),
+			in_flight: self.in_flight.clone(),
+		}
+	}
+}
+impl<R: Clone> Upstream<R> {
+	/// Waits for the outstanding recomputation of the cache, and returns its result. This blocks,
+	/// so it's only called outside of recomputation tasks, which use [`Upstream::poll`] instead.
+	/// On wasm this can't block, and relies on the recomputation having already finished.
+	pub(crate) fn wait(&self) -> R {
+		loop {
+			match self.poll() {
+				Ok(result) => return result,
+				#[cfg(not(target_arch = "wasm32"))]
+				Err(completion) => block_on(completion),
+				#[cfg(target_arch = "wasm32")]
+				Err(_) => panic!("the input of a derived eager cache is still being recomputed"),
+			}
+		}
+	}
+	/// The result of the cache, or the outstanding recomputation to wait for if it's being
+	/// recomputed. Caches are emptied and their recomputation is recorded in one step, under the
+	/// slot's lock, so an empty slot always comes with a recomputation to wait for.
+	fn poll(&self) -> Result<R, Completion> {
+		let slot = self.cache.read();
+		match slot.as_ref() {
+			Some(Ok(value)) => Ok(value.clone()),
+			Some(Err(message)) => panic!("the input of a derived eager cache panicked: {}", message),
+			None => match self.in_flight.lock().clone() {
+				Some(completion) if !completion.is_done() => Err(completion),
+				_ => panic!("the input of a derived eager cache has no result"),
+			},
+		}
+	}
+}
+
+pub(crate) struct CacheableRead<T, R: Clone + Sync + Send, F = fn(&T) -> R> {
+	read_fn: F,
+	cache: Arc<RwLock<Slot<R>>>,
+	/// The latest value waiting to be recomputed by an already scheduled recomputation.
+	pending: Arc<Mutex<Option<Arc<T>>>>,
+	/// Whether the pending value was replaced since the recomputation was last debounced.
+	touched: Arc<AtomicBool>,
+	/// Incremented on every update, so results computed from superseded values can be discarded.
+	generation: Arc<AtomicU64>,
+	/// Resolves once the most recently scheduled recomputation has run.
+	in_flight: Arc<Mutex<Option<Completion>>>,
+	discard_on_drop: bool,
+	priority: Priority,
+	stats: Arc<Mutex<CacheStats>>,
+	/// Sends every recomputed result to the receivers of [`EagerCacheLookup::watch`], if any.
+	#[cfg(feature = "tokio")]
+	watch: Arc<Mutex<Option<tokio::sync::watch::Sender<R>>>>,
+	/// The key of the cache and the metrics its reads are reported to, if any.
+	metrics: Option<(CacheKey, alloc::sync::Arc<dyn Metrics>)>,
+}
+impl<T: Clone + Sync + Send + 'static, R: Clone + Sync + Send + 'static, F: ReadFn<T, R>> CacheableRead<T, R, F> {
+	pub(crate) fn new(read_fn: F, discard_on_drop: bool) -> Self {
 		Self {
 			read_fn,
 			cache: Default::default(),
+			pending: Arc::new(Mutex::new(None)),
+			touched: Arc::new(AtomicBool::new(false)),
+			generation: Arc::new(AtomicU64::new(0)),
+			in_flight: Arc::new(Mutex::new(None)),
+			discard_on_drop,
+			priority: Priority::Normal,
+			stats: Default::default(),
+			#[cfg(feature = "tokio")]
+			watch: Default::default(),
+			metrics: None,
 		}
 	}
+	/// Reports the reads of this cache to the metrics.
+	pub(crate) fn with_metrics(mut self, key: &CacheKey, metrics: Option<alloc::sync::Arc<dyn Metrics>>) -> Self {
+		self.metrics = metrics.map(|metrics| (key.clone(), metrics));
+		self
+	}
 	pub(crate) fn read(&self, arg: &T) -> R {
-		let res = self.cache.read().unwrap();
-		if let Some(cached) = res.as_ref() {
-			return cached.clone();
+		self.cached().unwrap_or_else(|| {
+			let stopwatch = Stopwatch::start();
+			let result = self.read_fn.call(arg);
+			self.record_miss(stopwatch.elapsed());
+			result
+		})
+	}
+	pub(crate) fn record_miss(&self, took: Duration) {
+		self.stats.lock().record_miss(took);
+		if let Some((key, metrics)) = &self.metrics {
+			metrics.cache_read(key, false);
+		}
+	}
+	/// The cached result, or `None` if it's being recomputed. Re-raises the panic if the read
+	/// function panicked while recomputing it.
+	pub(crate) fn cached(&self) -> Option<R> {
+		let res = self.cache.read();
+		match res.as_ref() {
+			Some(Ok(cached)) => {
+				self.stats.lock().hits += 1;
+				if let Some((key, metrics)) = &self.metrics {
+					metrics.cache_read(key, true);
+				}
+				Some(cached.clone())
+			}
+			Some(Err(message)) => {
+				let message = message.clone();
+				drop(res);
+				panic!("eager cache read function panicked: {}", message);
+			}
+			None => None,
 		}
-		(self.read_fn)(arg)
 	}
 	
-	pub(crate) fn update(&self, value: &T) -> JoinHandle<()> {
-		let mut writer = self.cache.write().unwrap();
+	/// Invalidates the cache, returning the recomputation and a future that resolves once it has
+	/// run. If a recomputation is already scheduled, it recomputes this value instead and no new
+	/// one is needed. The spawner is the one the recomputation is handed back to if it has to wait
+	/// for the cache it's derived from.
+	pub(crate) fn update(&self, snapshot: &mut Snapshot<'_, T>, spawner: &alloc::sync::Arc<dyn Spawner>) -> Option<(Recomputation, Completion)> {
+		let mut writer = self.cache.write();
+		let mut pending = self.pending.lock();
+		self.generation.fetch_add(1, Ordering::SeqCst);
+		let scheduled = pending.is_some();
+		*pending = Some(snapshot.get());
+		self.touched.store(true, Ordering::SeqCst);
+		drop(pending);
+		let completion = (!scheduled).then(|| {
+			let completion = Completion::default();
+			*self.in_flight.lock() = Some(completion.clone());
+			completion
+		});
+		// Emptied after the recomputation is recorded, for `Upstream::poll`.
 		*writer = None;
-		let cell = self.cache.clone();
-		let read_fn = self.read_fn;
-		let value = value.clone();
-		spawn_blocking(move || {
-			let value = value;
-			let mut writer = cell.write().unwrap();
-			*writer = Some(read_fn(&value));
+		drop(writer);
+		let completion = completion?;
+		let job = Job {
+			cell: self.cache.clone(),
+			pending: self.pending.clone(),
+			generation: self.generation.clone(),
+			read_fn: self.read_fn.clone(),
+			stats: self.stats.clone(),
+			#[cfg(feature = "tokio")]
+			watch: self.watch.clone(),
+			spawner: spawner.clone(),
+			taken: None,
+			_signal: CompletionSignal(completion.clone()),
+		};
+		Some((Recomputation { task: Box::new(move || job.run()), touched: self.touched.clone() }, completion))
+	}
+}
+/// A recomputation task of an eager cache.
+struct Job<T, R, F> {
+	cell: Arc<RwLock<Slot<R>>>,
+	pending: Arc<Mutex<Option<Arc<T>>>>,
+	generation: Arc<AtomicU64>,
+	read_fn: F,
+	stats: Arc<Mutex<CacheStats>>,
+	#[cfg(feature = "tokio")]
+	watch: Arc<Mutex<Option<tokio::sync::watch::Sender<R>>>>,
+	spawner: alloc::sync::Arc<dyn Spawner>,
+	/// The value and generation taken from the pending value, if the job was handed back to the
+	/// spawner after taking them.
+	taken: Option<(Arc<T>, u64)>,
+	_signal: CompletionSignal,
+}
+impl<T: Send + Sync + 'static, R: Clone + Send + Sync + 'static, F: ReadFn<T, R>> Job<T, R, F> {
+	fn run(mut self) {
+		let (value, expected) = match self.taken.take() {
+			Some(taken) => taken,
+			None => {
+				let mut pending = self.pending.lock();
+				match pending.take() {
+					Some(value) => (value, self.generation.load(Ordering::SeqCst)),
+					None => return,
+				}
+			}
+		};
+		let stopwatch = Stopwatch::start();
+		#[cfg(not(target_arch = "wasm32"))]
+		if let Some(future) = self.read_fn.call_async(&value) {
+			if self.generation.load(Ordering::SeqCst) != expected {
+				return;
+			}
+			// The future is polled by the spawner rather than blocked on here, so it can run on the
+			// spawner's runtime and doesn't hold up one of its threads while it's pending.
+			let spawner = self.spawner.clone();
+			spawner.spawn_future(Box::pin(async move {
+				let result = CatchUnwind(future).await.map_err(|payload| panic_message(&*payload));
+				store(&self.cell, &self.generation, expected, result);
+				self.recomputed(stopwatch);
+				drop(value);
+			}));
+			return;
+		}
+		match recompute(&self.cell, &self.generation, expected, |value| self.read_fn.try_call_shared(value), &value) {
+			Ok(true) => self.recomputed(stopwatch),
+			Ok(false) => {}
+			// The cache this one is derived from is being recomputed. Rather than waiting for it on
+			// this thread, which the spawner may need to recompute it, the job is handed back to the
+			// spawner once it has been.
+			Err(upstream) => {
+				self.taken = Some((value, expected));
+				let spawner = self.spawner.clone();
+				upstream.then(Box::new(move || spawner.spawn(Box::new(move || self.run()))));
+			}
+		}
+	}
+	fn recomputed(&self, stopwatch: Stopwatch) {
+		self.stats.lock().record_recompute(stopwatch.elapsed());
+		#[cfg(feature = "tokio")]
+		publish(&self.cell, &self.watch);
+	}
+}
+/// Hands the task to the spawner once all the given recomputations have finished. Nothing blocks
+/// while waiting: the task is handed over by whichever thread finishes the last of them, so a
+/// spawner with a single thread can't end up with its only thread waiting for a recomputation that
+/// is queued behind it.
+fn dispatch(spawner: alloc::sync::Arc<dyn Spawner>, mut after: Vec<Completion>, task: Task) {
+	match after.pop() {
+		None => spawner.spawn(task),
+		Some(completion) => completion.then(Box::new(move || dispatch(spawner, after, task))),
+	}
+}
+/// Sends the cached result to the watchers of the cache. The slot stays locked while sending, so
+/// results are sent in the order they were cached and watchers never go back to an older result.
+#[cfg(feature = "tokio")]
+fn publish<R: Clone>(cell: &RwLock<Slot<R>>, watch: &Mutex<Option<tokio::sync::watch::Sender<R>>>) {
+	if let Some(sender) = watch.lock().as_ref() {
+		if let Some(Ok(result)) = cell.read().as_ref() {
+			sender.send_replace(result.clone());
+		}
+	}
+}
+/// Recomputes a cached read of the value from the expected generation. The recomputation is
+/// skipped (or its result discarded) if the value has been superseded by a newer write in the
+/// meantime, so a slow recomputation can never overwrite the result for a newer value. If the read
+/// function panics, the panic message is stored so it can be re-raised by the next read. Returns
+/// whether the read function was called, or the recomputation the read function has to wait for
+/// before it can be called, in which case nothing is stored.
+pub(crate) fn recompute<T, R>(cell: &RwLock<Slot<R>>, generation: &AtomicU64, expected: u64, read_fn: impl FnOnce(&T) -> Result<R, Completion>, value: &T) -> Result<bool, Completion> {
+	if generation.load(Ordering::SeqCst) != expected {
+		return Ok(false);
+	}
+	let result = match panic::catch_unwind(AssertUnwindSafe(|| read_fn(value))) {
+		Ok(Ok(result)) => Ok(result),
+		Ok(Err(upstream)) => return Err(upstream),
+		Err(payload) => Err(panic_message(&*payload)),
+	};
+	store(cell, generation, expected, result);
+	Ok(true)
+}
+/// Stores the result of a recomputation from the expected generation, unless the value has been
+/// superseded while it was being computed.
+fn store<R>(cell: &RwLock<Slot<R>>, generation: &AtomicU64, expected: u64, result: Result<R, String>) {
+	let mut writer = cell.write();
+	if generation.load(Ordering::SeqCst) == expected {
+		*writer = Some(result);
+	}
+}
+/// Catches a panic while polling the future, like [`panic::catch_unwind`] does for a closure.
+#[cfg(not(target_arch = "wasm32"))]
+struct CatchUnwind<F>(F);
+#[cfg(not(target_arch = "wasm32"))]
+impl<F: Future + Unpin> Future for CatchUnwind<F> {
+	type Output = Result<F::Output, Box<dyn Any + Send>>;
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		match panic::catch_unwind(AssertUnwindSafe(|| Pin::new(&mut self.0).poll(cx))) {
+			Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+			Ok(Poll::Pending) => Poll::Pending,
+			Err(payload) => Poll::Ready(Err(payload)),
+		}
+	}
+}
+impl<T: 'static + Sync + Send + Clone, R: Clone + 'static + Send + Sync, F: ReadFn<T, R>> EagerCache<T> for CacheableRead<T, R, F> {
+	fn invalidate(&self, snapshot: &mut Snapshot<'_, T>, spawner: &alloc::sync::Arc<dyn Spawner>) -> Option<Recomputation> {
+		self.update(snapshot, spawner).map(|(recomputation, _)| recomputation)
+	}
+	fn in_flight(&self) -> Option<Completion> {
+		self.in_flight.lock().clone()
+	}
+	fn set_discard_on_drop(&mut self, discard_on_drop: bool) {
+		self.discard_on_drop = discard_on_drop;
+	}
+	fn priority(&self) -> Priority {
+		self.priority
+	}
+	fn set_priority(&mut self, priority: Priority) {
+		self.priority = priority;
+	}
+	fn stats(&self) -> CacheStats {
+		*self.stats.lock()
+	}
+	fn peek(&self) -> Option<Box<dyn Any>> {
+		match self.cache.read().as_ref() {
+			Some(Ok(cached)) => Some(Box::new(cached.clone())),
+			_ => None,
+		}
+	}
+	fn upstream(&self) -> Box<dyn Any> {
+		Box::new(Upstream {
+			cache: self.cache.clone(),
+			in_flight: self.in_flight.clone(),
 		})
 	}
+	fn input(&self) -> Option<&CacheKey> {
+		self.read_fn.input()
+	}
+	fn duplicate(&self, eager: &EagerCaches<T>) -> (Box<dyn EagerCache<T>>, bool) {
+		let mut read_fn = self.read_fn.clone();
+		read_fn.relink(eager);
+		let mut copy = Self::new(read_fn, self.discard_on_drop);
+		copy.priority = self.priority;
+		copy.metrics = self.metrics.clone();
+		let slot = self.cache.read().clone();
+		let copied = slot.is_some();
+		*copy.cache.write() = slot;
+		(Box::new(copy), copied)
+	}
 }
-impl<T: 'static + Sync + Send + Clone, R: Clone + 'static + Send + Sync> Cache<T> for CacheableRead<T, R> {
-	fn notify(&self, value: &T) {
-		self.update(value);
+/// Discards outstanding recomputations: scheduled ones find nothing pending and are skipped.
+/// Running ones can't be interrupted, so they still run to completion, but their result is
+/// thrown away because the generation has moved on.
+impl<T, R: Clone + Sync + Send, F> Drop for CacheableRead<T, R, F> {
+	fn drop(&mut self) {
+		if !self.discard_on_drop {
+			return;
+		}
+		*self.pending.lock() = None;
+		self.generation.fetch_add(1, Ordering::SeqCst);
 	}
 }
 
-#[cfg(feature = "eager")]
+impl<T: Debug + 'static, I: Fn(&T) -> bool> CacheableRepr<T, I> {
+	/// Gets the eager cache with the given key. If it doesn't exist yet, it's created with the read
+	/// function, and this waits until it has been computed for the first time.
+	async fn eager_cache<R: Clone + Sync + Send + 'static, F: ReadFn<T, R>>(&mut self, key: CacheKey, read_fn: F) -> (&CacheableRead<T, R, F>, &T) where T: Clone + Sync + Send {
+		self.touch_cache(&key);
+		let is_empty = !self.caches.get_mut().eager.contains_key(&key);
+		let discard_on_drop = self.eager.discard_on_drop;
+		let metrics = self.inner.metrics().cloned();
+		let cache = self.caches.get_mut().eager.entry(key.clone()).or_insert_with(|| Box::new(CacheableRead::<T, R, F>::new(read_fn, discard_on_drop).with_metrics(&key, metrics)));
+		let cache = cache.downcast_ref::<CacheableRead<T, R, F>>().expect("the cache key was used with a different result type");
+		let data = self.inner.inner.get_mut();
+		if is_empty {
+			let spawner = self.eager.spawner();
+			if let Some((recomputation, completion)) = cache.update(&mut Snapshot::new(data), &spawner) {
+				spawner.spawn(recomputation.task);
+				completion.await;
+			}
+		}
+		(cache, data)
+	}
+	/// Invalidates every eager cache and hands their recomputations to the spawner as one batch,
+	/// ordered by [`Priority`].
+	pub(crate) fn recompute_eager_caches(&mut self) {
+		if self.caches.get_mut().eager.is_empty() {
+			return;
+		}
+		let spawner = self.eager.spawner();
+		let mut snapshot = Snapshot::new(self.inner.inner.get_mut());
+		let caches = self.caches.get_mut();
+		let mut recomputations: Vec<((usize, Priority), Recomputation)> = caches.eager.iter()
+			.filter_map(|(key, cache)| cache.invalidate(&mut snapshot, &spawner).map(|recomputation| ((caches.depth(key), cache.priority()), recomputation)))
+			.collect();
+		if recomputations.is_empty() {
+			return;
+		}
+		// Caches are recomputed after the caches they're derived from.
+		recomputations.sort_by_key(|(order, _)| *order);
+		// Derived caches may wait for low priority caches themselves, so those don't wait for them.
+		let urgent: Vec<Completion> = caches.eager.values()
+			.filter(|cache| cache.priority() < Priority::Low && cache.input().is_none())
+			.filter_map(|cache| cache.in_flight())
+			.collect();
+		self.eager.schedule(recomputations.into_iter()
+			.map(|((_, priority), recomputation)| {
+				let after = if priority == Priority::Low { urgent.clone() } else { Vec::new() };
+				(recomputation, after)
+			})
+			.collect());
+	}
+	/// How many eager cache recomputations haven't finished yet.
+	pub(crate) fn pending_eager_tasks(&mut self) -> usize {
+		self.caches.get_mut().eager.values()
+			.filter_map(|cache| cache.in_flight())
+			.filter(|completion| !completion.is_done())
+			.count()
+	}
+	/// Copies the eager caches into a clone of this representation invariant, recomputing the ones
+	/// whose result couldn't be copied. See [`CacheableRepr::clone_with_caches`].
+	pub(crate) fn duplicate_eager_caches(&self, clone: &mut Self) {
+		let caches = self.caches.lock();
+		let mut keys: Vec<&CacheKey> = caches.eager.keys().collect();
+		// Caches are copied after the caches they're derived from, so they can be derived from the copies.
+		keys.sort_by_key(|key| caches.depth(key));
+		let clone_caches = clone.caches.get_mut();
+		let mut stale = Vec::new();
+		for key in keys {
+			let (copy, copied) = caches.eager[key].duplicate(&clone_caches.eager);
+			if !copied {
+				stale.push(key.clone());
+			}
+			clone_caches.eager.insert(key.clone(), copy);
+		}
+		let spawner = clone.eager.spawner();
+		let mut snapshot = Snapshot::new(clone.inner.inner.get_mut());
+		let recomputations = stale.iter()
+			.filter_map(|key| clone_caches.eager[key].invalidate(&mut snapshot, &spawner))
+			.map(|recomputation| (recomputation, Vec::new()))
+			.collect();
+		clone.eager.schedule(recomputations);
+	}
+	/// Waits until every outstanding eager cache recomputation has finished, so the next
+	/// [`EagerCacheLookup::eager`] call reads from the cache.
+	/// ```rust
+	/// # tokio::runtime::Runtime::new().unwrap().block_on(async move {
+	/// use std::sync::atomic::{AtomicU32, Ordering};
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// static READS: AtomicU32 = AtomicU32::new(0);
+	/// fn double(n: &i32) -> i32 {
+	///   READS.fetch_add(1, Ordering::Relaxed);
+	///   n * 2
+	/// }
+	/// let mut repr = CacheableRepr::new(1, |n| *n > 0);
+	/// repr.eager(double).await;
+	/// *repr.write() = 2;
+	/// repr.flush().await;
+	/// assert_eq!(2, READS.load(Ordering::Relaxed));
+	/// # })
+	/// ```
+	pub async fn flush(&mut self) {
+		let completions: Vec<Completion> = self.caches.get_mut().eager.values().filter_map(|cache| cache.in_flight()).collect();
+		for completion in completions {
+			completion.await;
+		}
+	}
+}
+
+impl<T: 'static> super::Caches<T> {
+	/// How many caches the eager cache is derived from, transitively.
+	fn depth(&self, key: &CacheKey) -> usize {
+		let mut depth = 0;
+		let mut key = key;
+		while let Some(input) = self.eager.get(key).and_then(|cache| cache.input()) {
+			depth += 1;
+			key = input;
+			if depth > self.eager.len() {
+				break;
+			}
+		}
+		depth
+	}
+	/// The keys of the eager caches derived from the cache, transitively, in the order they're
+	/// recomputed in.
+	pub(crate) fn derived_from(&self, key: &CacheKey) -> Vec<CacheKey> {
+		let mut derived: Vec<CacheKey> = Vec::new();
+		let mut inputs = alloc::vec![key.clone()];
+		while let Some(input) = inputs.pop() {
+			for (key, cache) in &self.eager {
+				if cache.input() == Some(&input) && !derived.contains(key) {
+					derived.push(key.clone());
+					inputs.push(key.clone());
+				}
+			}
+		}
+		derived.sort_by_key(|key| self.depth(key));
+		derived
+	}
+	/// Removes an eager cache along with the caches derived from it. Returns whether the cache
+	/// existed.
+	pub(crate) fn remove_eager(&mut self, key: &CacheKey) -> bool {
+		for derived in self.derived_from(key) {
+			self.eager.remove(&derived);
+			if !self.lazy.contains_key(&derived) {
+				self.lru.forget(&derived);
+			}
+		}
+		self.eager.remove(key).is_some()
+	}
+}
+
+/// Extracts the message from a panic payload.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+	if let Some(message) = payload.downcast_ref::<&str>() {
+		String::from(*message)
+	} else if let Some(message) = payload.downcast_ref::<String>() {
+		message.clone()
+	} else {
+		String::from("Box<dyn Any>")
+	}
+}
+
+/// A future that resolves once a spawned recomputation has finished (or panicked), independent of
+/// the executor it was spawned on. Several tasks may wait for the same recomputation (e.g. low
+/// priority and derived caches), so every waiter's waker is kept.
+#[derive(Clone, Default)]
+pub(crate) struct Completion(Arc<Mutex<CompletionState>>);
+#[derive(Default)]
+struct CompletionState {
+	done: bool,
+	wakers: Vec<Waker>,
+	/// Run once the recomputation has finished. See [`Completion::then`].
+	callbacks: Vec<Task>,
+}
+impl Completion {
+	fn is_done(&self) -> bool {
+		self.0.lock().done
+	}
+	/// Runs the callback once the recomputation has finished: right away if it already has,
+	/// otherwise on the thread that finishes it. The callback must be quick and mustn't block.
+	fn then(&self, callback: Task) {
+		let mut state = self.0.lock();
+		if state.done {
+			drop(state);
+			callback();
+		} else {
+			state.callbacks.push(callback);
+		}
+	}
+}
+impl Future for Completion {
+	type Output = ();
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+		let mut state = self.0.lock();
+		if state.done {
+			return Poll::Ready(());
+		}
+		if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+			state.wakers.push(cx.waker().clone());
+		}
+		Poll::Pending
+	}
+}
+/// Completes the [`Completion`] when dropped, so it also completes if the recomputation panics.
+struct CompletionSignal(Completion);
+impl Drop for CompletionSignal {
+	fn drop(&mut self) {
+		let (wakers, callbacks) = {
+			let mut state = self.0.0.lock();
+			state.done = true;
+			(core::mem::take(&mut state.wakers), core::mem::take(&mut state.callbacks))
+		};
+		for waker in wakers {
+			waker.wake();
+		}
+		for callback in callbacks {
+			callback();
+		}
+	}
+}
+
+#[cfg(feature = "eager-core")]
 pub trait EagerCacheLookup<T: Clone + Sync + Send + 'static, I: Fn(&T) -> bool> {
 	fn eager<R: Clone + Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> impl Future<Output=R>;
 	fn unregister<R: Clone + Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> bool;
+	#[cfg(not(target_arch = "wasm32"))]
+	fn eager_blocking<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> R;
+	fn eager_fresh<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> impl Future<Output=R>;
+	fn eager_with_priority<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R, priority: Priority) -> impl Future<Output=R>;
+	fn eager_keyed<R: Clone + Sync + Send + 'static>(&mut self, key: impl Into<CacheKey>, read_fn: impl Fn(&T) -> R + Send + Sync + core::panic::RefUnwindSafe + 'static) -> impl Future<Output=R>;
+	#[cfg(not(target_arch = "wasm32"))]
+	fn eager_async<F: Future + Send + 'static>(&mut self, read_fn: fn(Arc<T>) -> F) -> impl Future<Output=F::Output> where F::Output: Clone + Sync + Send + 'static;
+	fn eager_of<Q: CachedRead<T>>(&mut self) -> impl Future<Output=Q::Output> where Q::Output: Sync;
+	fn eager_derived<U: Clone + Sync + Send + 'static, R: Clone + Sync + Send + 'static>(&mut self, input: impl Into<CacheKey>, derive: fn(&U) -> R) -> impl Future<Output=R>;
+	#[cfg(feature = "tokio")]
+	fn watch<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> impl Future<Output=tokio::sync::watch::Receiver<R>>;
+	fn register<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R, strategy: Strategy);
+	fn with_eager_cache<R: Clone + Sync + Send + 'static>(self, read_fn: fn(&T) -> R) -> Self where Self: Sized;
+	fn get<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> R;
 }
-#[cfg(feature = "eager")]
+#[cfg(feature = "eager-core")]
 impl<T: Debug + Clone + Sync + Send + 'static, I: Fn(&T) -> bool> EagerCacheLookup<T, I> for CacheableRepr<T, I> {
 	/// Borrows a read-only view of the value in the representation invariant and caches the
 	/// result of the read function. The cache is keyed by the read function's address, so in general
 	/// you should use function references instead of closures. It is a bug to perform any side effects
 	/// in the read function (i.e. reading from a file). This cache is updated eagerly, so whenever
-	/// the value is mutated, all eager caches will be updated in parallel. See [`CacheableRepr::lazy`] for
-	/// a lazy version of this function.
+	/// the value is mutated, all eager caches will be updated in parallel. The value is cloned once
+	/// per write, and that snapshot is shared by every recomputation. See [`CacheableRepr::lazy`]
+	/// for a lazy version of this function.
 	///
 	/// ```rust
 	/// # tokio::runtime::Runtime::new().unwrap().block_on(async move {
-	/// use std::time::Duration;
 	/// use std::sync::atomic::{AtomicU32, Ordering};
 	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
 	/// #[derive(Debug, Clone)]
@@ -79,8 +869,8 @@ impl<T: Debug + Clone + Sync + Send + 'static, I: Fn(&T) -> bool> EagerCacheLook
 	/// assert_eq!(832040, fib_of_name_len2);
 	/// assert_eq!(1, READ_SPY.load(Ordering::Relaxed));
 	/// repr.write().name = "Alice".into();
-	/// // if we wait a bit we can see that a new value has been computed
-	/// tokio::time::sleep(Duration::from_millis(100)).await;
+	/// // once the background recomputation has finished we can see that a new value has been computed
+	/// repr.flush().await;
 	/// assert_eq!(2, READ_SPY.load(Ordering::Relaxed));
 	/// // Now when we fetch it again, we should see the new value without needing to recompute it
 	/// let fib_of_name_len3 = repr.eager(expensive_read).await;
@@ -90,23 +880,303 @@ impl<T: Debug + Clone + Sync + Send + 'static, I: Fn(&T) -> bool> EagerCacheLook
 	/// #   if n <= 1 { n } else { fib(n - 1) + fib(n - 2) }
 	/// # }
 	/// # })
-	#[allow(clippy::await_holding_refcell_ref)] // safe because the &mut self on this fn prevents other borrows
 	async fn eager<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> R {
-		let fn_identity = read_fn as *const fn(&T) -> R as usize;
-		let is_empty = !self.eager_caches.contains_key(&fn_identity);
-		let entry = self.eager_caches.entry(fn_identity);
-
-		let cache = entry.or_insert_with(|| Box::new(CacheableRead::<T, R>::new(read_fn)));
-		let cache = cache.downcast_mut::<CacheableRead<T, R>>().unwrap();
-		let data = self.inner.inner.get_mut();
-		if is_empty {
-			cache.update(data).await.unwrap();
+		let (cache, data) = self.eager_cache(CacheKey::of(read_fn), read_fn).await;
+		cache.read(data)
+	}
+	/// Like [`EagerCacheLookup::eager`], but the cache is keyed by the given key instead of by the
+	/// read function's address, so closures (including capturing ones) can be cached reliably. See
+	/// [`CacheableRepr::lazy_keyed`].
+	/// ```rust
+	/// # tokio::runtime::Runtime::new().unwrap().block_on(async move {
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// let mut repr = CacheableRepr::new(vec![3, 1, 2], |v: &Vec<i32>| !v.is_empty());
+	/// let scale = 2;
+	/// assert_eq!(12, repr.eager_keyed("scaled sum", move |v| v.iter().sum::<i32>() * scale).await);
+	/// repr.write().push(4);
+	/// assert_eq!(20, repr.eager_keyed("scaled sum", move |v| v.iter().sum::<i32>() * scale).await);
+	/// # })
+	/// ```
+	async fn eager_keyed<R: Clone + Sync + Send + 'static>(&mut self, key: impl Into<CacheKey>, read_fn: impl Fn(&T) -> R + Send + Sync + core::panic::RefUnwindSafe + 'static) -> R {
+		let read_fn: alloc::sync::Arc<dyn Fn(&T) -> R + Send + Sync> = alloc::sync::Arc::new(read_fn);
+		let (cache, data) = self.eager_cache(key.into(), read_fn).await;
+		cache.read(data)
+	}
+	/// Like [`EagerCacheLookup::eager`], but also sets the [`Priority`] the cache is recomputed
+	/// with after writes. High priority caches are handed to the spawner first, and low priority
+	/// caches wait until all other recomputations caused by the same write have finished.
+	/// ```rust
+	/// # tokio::runtime::Runtime::new().unwrap().block_on(async move {
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// use repr_rs::cache::eager::Priority;
+	/// fn label(n: &u32) -> String { format!("{n} items") }
+	/// fn histogram(n: &u32) -> Vec<u32> { (0..*n).map(|i| i % 3).collect() }
+	/// let mut repr = CacheableRepr::new(3, |n| *n < 100);
+	/// repr.eager_with_priority(label, Priority::High).await;
+	/// repr.eager_with_priority(histogram, Priority::Low).await;
+	/// *repr.write() = 4;
+	/// assert_eq!("4 items", repr.eager_fresh(label).await);
+	/// # })
+	/// ```
+	async fn eager_with_priority<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R, priority: Priority) -> R {
+		let value = self.eager(read_fn).await;
+		let fn_identity = CacheKey::of(read_fn);
+		if let Some(cache) = self.caches.get_mut().eager.get_mut(&fn_identity) {
+			cache.set_priority(priority);
+		}
+		value
+	}
+	/// Like [`EagerCacheLookup::eager`], but the cache is keyed by the type of the [`CachedRead`]
+	/// instead of by a function's address. See [`CacheableRepr::lazy_of`].
+	async fn eager_of<Q: CachedRead<T>>(&mut self) -> Q::Output where Q::Output: Sync {
+		let read_fn: fn(&T) -> Q::Output = Q::read;
+		let (cache, data) = self.eager_cache(CacheKey::of_type::<Q>(), read_fn).await;
+		cache.read(data)
+	}
+	/// Like [`EagerCacheLookup::eager`], but the result is derived from the result of another eager
+	/// cache (with the given key) instead of from the value, so chains of computations can be
+	/// cached without any link recomputing the ones before it. After a write, derived caches are
+	/// recomputed once the caches they're derived from have been, and they're removed along with
+	/// them. The cache is keyed by the derive function's address.
+	///
+	/// # Panics
+	/// Panics if there's no eager cache with the input key, or if its result type isn't `U`.
+	/// ```rust
+	/// # tokio::runtime::Runtime::new().unwrap().block_on(async move {
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// use repr_rs::cache::CacheKey;
+	/// fn words(text: &String) -> Vec<String> { text.split_whitespace().map(String::from).collect() }
+	/// fn longest(words: &Vec<String>) -> String { words.iter().max_by_key(|w| w.len()).cloned().unwrap_or_default() }
+	/// fn shout(word: &String) -> String { word.to_uppercase() }
+	/// let mut repr = CacheableRepr::new(String::from("a quick fox"), |s| !s.is_empty());
+	/// repr.eager(words).await;
+	/// assert_eq!("quick", repr.eager_derived(CacheKey::of(words), longest).await);
+	/// assert_eq!("QUICK", repr.eager_derived(CacheKey::of(longest), shout).await);
+	/// repr.write().push_str(" jumped");
+	/// assert_eq!("JUMPED", repr.eager_derived(CacheKey::of(longest), shout).await);
+	/// # })
+	/// ```
+	async fn eager_derived<U: Clone + Sync + Send + 'static, R: Clone + Sync + Send + 'static>(&mut self, input: impl Into<CacheKey>, derive: fn(&U) -> R) -> R {
+		let input = input.into();
+		let upstream = self.caches.get_mut().eager.get(&input)
+			.expect("the input of a derived cache must be an eager cache")
+			.upstream()
+			.downcast::<Upstream<U>>()
+			.expect("the input of a derived cache has a different result type");
+		let read_fn = DerivedReadFn { input, upstream: *upstream, derive };
+		let (cache, data) = self.eager_cache(CacheKey::of(derive), read_fn).await;
+		// Reading inline would block on the recomputation of the input, so await it instead.
+		if let Some(completion) = cache.in_flight() {
+			completion.await;
 		}
 		cache.read(data)
 	}
-	/// Unregisters an eager cache. Returns true if the cache was found and removed.
+	/// Subscribes to the result of the read function, which is cached eagerly (see
+	/// [`EagerCacheLookup::eager`]). The receiver starts with the current result, and every
+	/// background recomputation after a write sends the new one, so consumers can await
+	/// `changed()` instead of polling the cache. The channel is closed once the cache is
+	/// unregistered or the representation invariant is dropped.
+	/// ```rust
+	/// # tokio::runtime::Runtime::new().unwrap().block_on(async move {
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// fn total(values: &Vec<u32>) -> u32 { values.iter().sum() }
+	/// let mut repr = CacheableRepr::new(vec![1, 2], |v: &Vec<u32>| !v.is_empty());
+	/// let mut totals = repr.watch(total).await;
+	/// assert_eq!(3, *totals.borrow());
+	/// repr.write().push(3);
+	/// totals.changed().await.unwrap();
+	/// assert_eq!(6, *totals.borrow_and_update());
+	/// # })
+	/// ```
+	#[cfg(feature = "tokio")]
+	async fn watch<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> tokio::sync::watch::Receiver<R> {
+		let (cache, data) = self.eager_cache(CacheKey::of(read_fn), read_fn).await;
+		let mut sender = cache.watch.lock();
+		match sender.as_ref() {
+			Some(sender) => sender.subscribe(),
+			None => {
+				let (new_sender, receiver) = tokio::sync::watch::channel(cache.read(data));
+				*sender = Some(new_sender);
+				receiver
+			}
+		}
+	}
+	/// Unregisters an eager cache, along with the caches derived from it. Returns true if the
+	/// cache was found and removed.
 	fn unregister<R: Clone + Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> bool {
-		let fn_identity = read_fn as *const fn(&T) -> R as usize;
-		self.eager_caches.remove(&fn_identity).is_some()
+		let fn_identity = CacheKey::of(read_fn);
+		let caches = self.caches.get_mut();
+		let removed = caches.remove_eager(&fn_identity);
+		if !caches.lazy.contains_key(&fn_identity) {
+			caches.lru.forget(&fn_identity);
+		}
+		removed
+	}
+	/// Registers a cache for the read function with the given [`Strategy`], replacing the strategy
+	/// it was previously registered with. Eager caches start computing in the background right
+	/// away. Read the cache with [`EagerCacheLookup::get`], which doesn't depend on the strategy.
+	/// ```rust
+	/// use repr_rs::CacheableRepr;
+	/// use repr_rs::EagerCacheLookup;
+	/// use repr_rs::cache::eager::{Priority, Strategy};
+	/// use repr_rs::cache::spawner::ThreadPoolSpawner;
+	/// fn total(values: &Vec<u32>) -> u32 { values.iter().sum() }
+	/// fn largest(values: &Vec<u32>) -> Option<u32> { values.iter().copied().max() }
+	/// let mut repr = CacheableRepr::new(vec![1, 2, 3], |v| !v.is_empty())
+	///   .with_spawner(ThreadPoolSpawner::new(2));
+	/// repr.register(total, Strategy::Eager { priority: Priority::High });
+	/// repr.register(largest, Strategy::Lazy);
+	/// repr.write().push(4);
+	/// assert_eq!(10, repr.get(total));
+	/// assert_eq!(Some(4), repr.get(largest));
+	/// ```
+	fn register<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R, strategy: Strategy) {
+		let fn_identity = CacheKey::of(read_fn);
+		self.touch_cache(&fn_identity);
+		match strategy {
+			Strategy::Lazy => {
+				let caches = self.caches.get_mut();
+				caches.remove_eager(&fn_identity);
+				caches.lazy.entry(fn_identity.clone()).or_insert_with(|| alloc::sync::Arc::new(lazy::CacheableRead::<T, R>::new(read_fn)));
+			}
+			Strategy::Eager { priority } => {
+				self.caches.get_mut().lazy.remove(&fn_identity);
+				if let Some(cache) = self.caches.get_mut().eager.get_mut(&fn_identity) {
+					cache.set_priority(priority);
+					return;
+				}
+				let mut cache = CacheableRead::<T, R>::new(read_fn, self.eager.discard_on_drop).with_metrics(&fn_identity, self.inner.metrics().cloned());
+				cache.set_priority(priority);
+				let spawner = self.eager.spawner();
+				if let Some((recomputation, _)) = cache.update(&mut Snapshot::new(self.inner.inner.get_mut()), &spawner) {
+					spawner.spawn(recomputation.task);
+				}
+				self.caches.get_mut().eager.insert(fn_identity, Box::new(cache));
+			}
+		}
+	}
+	/// Registers an eager cache for the read function while building the representation invariant.
+	/// Its first computation starts in the background right away, on the spawner set so far (or the
+	/// default one), so several caches are computed in parallel and are likely ready by the time
+	/// they're first read. See [`EagerCacheLookup::register`].
+	/// ```rust
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// use repr_rs::cache::spawner::ThreadPoolSpawner;
+	/// fn total(values: &Vec<u32>) -> u32 { values.iter().sum() }
+	/// fn largest(values: &Vec<u32>) -> Option<u32> { values.iter().copied().max() }
+	/// let mut repr = CacheableRepr::new(vec![1, 2, 3], |v: &Vec<u32>| !v.is_empty())
+	///   .with_spawner(ThreadPoolSpawner::new(2))
+	///   .with_eager_cache(total)
+	///   .with_eager_cache(largest);
+	/// assert_eq!(6, repr.get(total));
+	/// assert_eq!(Some(3), repr.get(largest));
+	/// ```
+	fn with_eager_cache<R: Clone + Sync + Send + 'static>(mut self, read_fn: fn(&T) -> R) -> Self {
+		self.register(read_fn, Strategy::Eager { priority: Priority::Normal });
+		self
+	}
+	/// Reads the cache of the read function, whichever [`Strategy`] it was registered with. Read
+	/// functions that haven't been registered are cached lazily.
+	fn get<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> R {
+		let fn_identity = CacheKey::of(read_fn);
+		self.touch_cache(&fn_identity);
+		match self.caches.get_mut().eager.get(&fn_identity) {
+			Some(cache) => cache.downcast_ref::<CacheableRead<T, R>>()
+				.expect("the cache key was used with a different result type")
+				.read(self.inner.inner.get_mut()),
+			None => self.lazy(read_fn),
+		}
+	}
+	/// Like [`EagerCacheLookup::eager`], but blocks the current thread instead of being async, for
+	/// programs that don't use an async runtime (e.g. with a
+	/// [`crate::cache::spawner::ThreadPoolSpawner`]). Not available on wasm, where blocking the
+	/// only thread would keep the recomputation from ever running.
+	#[cfg(not(target_arch = "wasm32"))]
+	fn eager_blocking<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> R {
+		block_on(self.eager(read_fn))
+	}
+	/// Like [`EagerCacheLookup::eager`], but if a background recomputation is still in flight after
+	/// a write, this awaits it instead of recomputing the read on the calling task. Use this when
+	/// the read is too expensive to run twice; [`EagerCacheLookup::eager`] never waits on the
+	/// spawner.
+	/// ```rust
+	/// # tokio::runtime::Runtime::new().unwrap().block_on(async move {
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// #[derive(Debug, Clone)]
+	/// struct Person { name: String }
+	/// fn name_len(p: &Person) -> usize { p.name.len() }
+	/// let mut repr = CacheableRepr::new(Person { name: "Alice".into() }, |p| !p.name.is_empty());
+	/// assert_eq!(5, repr.eager_fresh(name_len).await);
+	/// repr.write().name = "Bob".into();
+	/// assert_eq!(3, repr.eager_fresh(name_len).await);
+	/// # })
+	/// ```
+	async fn eager_fresh<R: Clone + Sync + Send + 'static>(&mut self, read_fn: fn(&T) -> R) -> R {
+		let fn_identity = CacheKey::of(read_fn);
+		self.touch_cache(&fn_identity);
+		if !self.caches.get_mut().eager.contains_key(&fn_identity) {
+			return self.eager(read_fn).await;
+		}
+		let cache = self.caches.get_mut().eager.get(&fn_identity).expect("the eager cache was just found");
+		let cache = cache.downcast_ref::<CacheableRead<T, R>>().expect("the cache key was used with a different result type");
+		if let Some(completion) = cache.in_flight() {
+			completion.await;
+		}
+		cache.read(self.inner.inner.get_mut())
+	}
+	/// Like [`EagerCacheLookup::eager`], but for async read functions. The read function is called
+	/// with a snapshot of the value, which background recomputations share with the other caches
+	/// instead of cloning it again. Recomputations hand the returned future to the spawner with
+	/// [`Spawner::spawn_future`]: [`TokioSpawner`](crate::cache::spawner::TokioSpawner) (the
+	/// default) polls it on its runtime, so it can use tokio's timers and I/O, while spawners that
+	/// aren't runtimes block a thread on it, so it mustn't depend on a runtime there. This isn't
+	/// available on wasm, which has no threads to block.
+	/// ```rust
+	/// # tokio::runtime::Runtime::new().unwrap().block_on(async move {
+	/// use std::sync::Arc;
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// async fn word_count(text: Arc<String>) -> usize {
+	///   tokio::task::yield_now().await;
+	///   text.split_whitespace().count()
+	/// }
+	/// let mut repr = CacheableRepr::new(String::from("hello world"), |s| !s.is_empty());
+	/// assert_eq!(2, repr.eager_async(word_count).await);
+	/// repr.write().push_str(" again");
+	/// assert_eq!(3, repr.eager_async(word_count).await);
+	/// # })
+	/// ```
+	#[cfg(not(target_arch = "wasm32"))]
+	async fn eager_async<F: Future + Send + 'static>(&mut self, read_fn: fn(Arc<T>) -> F) -> F::Output where F::Output: Clone + Sync + Send + 'static {
+		let key = CacheKey::Fn(read_fn as *const fn(Arc<T>) -> F as usize);
+		let (cache, data) = self.eager_cache(key, AsyncReadFn(read_fn)).await;
+		match cache.cached() {
+			Some(cached) => cached,
+			None => {
+				let stopwatch = Stopwatch::start();
+				let result = read_fn(Arc::new(data.clone())).await;
+				cache.record_miss(stopwatch.elapsed());
+				result
+			}
+		}
+	}
+}
+
+/// Polls a future to completion on the current thread, parking it while the future is pending.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+	struct ThreadWaker(thread::Thread);
+	impl Wake for ThreadWaker {
+		fn wake(self: alloc::sync::Arc<Self>) {
+			self.0.unpark();
+		}
+	}
+	let waker = Waker::from(alloc::sync::Arc::new(ThreadWaker(thread::current())));
+	let mut cx = Context::from_waker(&waker);
+	let mut future = core::pin::pin!(future);
+	loop {
+		if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+			return output;
+		}
+		thread::park();
 	}
 }
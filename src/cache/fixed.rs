@@ -0,0 +1,128 @@
+use crate::{Repr, Violation};
+use core::fmt::Debug;
+use core::ops::{Deref, DerefMut};
+
+/// A representation invariant with a fixed-capacity lazy cache that doesn't allocate, for
+/// constrained targets where memoizing reads mustn't grow the heap. Up to `N` read functions
+/// (which must all return the same type `R`) are cached; when the cache is full, the oldest entry
+/// is evicted. Reads, cached reads and writes that keep the invariant never allocate, but the
+/// crate still depends on `alloc`, so a global allocator is required: a violated invariant is
+/// reported with a [`Violation`], whose description of the value is a `String`.
+///
+/// Like [`crate::CacheableRepr`], every cache is invalidated when the value is mutated.
+/// ```rust
+/// use repr_rs::FixedCacheableRepr;
+/// #[derive(Debug)]
+/// struct Reading { samples: [u16; 4] }
+/// let mut repr: FixedCacheableRepr<Reading, u16, 2> = FixedCacheableRepr::new(
+///   Reading { samples: [1, 2, 3, 4] },
+///   |r| r.samples.iter().all(|s| *s < 1024),
+/// );
+/// fn max(r: &Reading) -> u16 { *r.samples.iter().max().unwrap() }
+/// assert_eq!(4, repr.lazy(max));
+/// repr.write().samples[0] = 10;
+/// assert_eq!(10, repr.lazy(max));
+/// ```
+pub struct FixedCacheableRepr<T: Debug, R, const N: usize, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	inner: Repr<T, I>,
+	caches: [Option<(usize, R)>; N],
+	next: usize,
+}
+impl<T: Debug, R: Clone, const N: usize, I: Fn(&T) -> bool> FixedCacheableRepr<T, R, N, I> {
+	/// Creates a new representation invariant with the given value and invariant function.
+	pub fn new(inner: T, invariant: I) -> Self {
+		Self::from(Repr::new(inner, invariant))
+	}
+	/// Creates a new representation invariant with the given value, invariant function, and
+	/// violation message.
+	pub fn with_msg(inner: T, invariant: I, violation_message: &'static str) -> Self {
+		Self::from(Repr::with_msg(inner, invariant, violation_message))
+	}
+	/// Borrows a read-only view of the value in the representation invariant.
+	#[inline]
+	pub fn read(&self) -> &T {
+		self.inner.read()
+	}
+	/// Borrows a mutable view of the value in the representation invariant. All caches are
+	/// invalidated when the view is dropped.
+	#[inline]
+	pub fn write(&mut self) -> FixedReprMutator<'_, T, R, N, I> {
+		self.inner.begin_write();
+		FixedReprMutator { repr: self }
+	}
+	/// Consumes the representation invariant and returns the inner value.
+	#[inline]
+	pub fn into_inner(self) -> T {
+		self.inner.into_inner()
+	}
+	/// Checks that the invariant holds for the current value without panicking.
+	/// See [`Repr::validate`].
+	pub fn validate(&self) -> Result<(), Violation> {
+		self.inner.validate()
+	}
+	/// Evaluates the invariant for the current value without panicking. See [`Repr::is_valid`].
+	pub fn is_valid(&self) -> bool {
+		self.inner.is_valid()
+	}
+	/// Borrows a read-only view of the value in the representation invariant and caches the
+	/// result of the read function. The cache is keyed by the read function's address, like
+	/// [`crate::CacheableRepr::lazy`]. If all `N` entries are in use, the oldest one is evicted.
+	pub fn lazy(&mut self, read_fn: fn(&T) -> R) -> R {
+		let fn_identity = read_fn as *const fn(&T) -> R as usize;
+		let cached = self.caches.iter().flatten().find(|(identity, _)| *identity == fn_identity);
+		if let Some((_, result)) = cached {
+			return result.clone();
+		}
+		let result = read_fn(self.inner.read());
+		if N > 0 {
+			self.caches[self.next] = Some((fn_identity, result.clone()));
+			self.next = (self.next + 1) % N;
+		}
+		result
+	}
+	fn check(&mut self) {
+		if self.inner.check() {
+			self.caches = core::array::from_fn(|_| None);
+		}
+	}
+}
+impl<T: Debug, R: Clone, const N: usize, I: Fn(&T) -> bool> From<Repr<T, I>> for FixedCacheableRepr<T, R, N, I> {
+	fn from(inner: Repr<T, I>) -> Self {
+		Self {
+			inner,
+			caches: core::array::from_fn(|_| None),
+			next: 0,
+		}
+	}
+}
+impl<T: Debug, R, const N: usize, I: Fn(&T) -> bool> Deref for FixedCacheableRepr<T, R, N, I> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.inner.read()
+	}
+}
+impl<T: Debug, R, const N: usize, I: Fn(&T) -> bool> Debug for FixedCacheableRepr<T, R, N, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "FixedCacheableRepr({:?})", self.inner.read())
+	}
+}
+
+pub struct FixedReprMutator<'a, T: Debug, R: Clone, const N: usize, I: Fn(&T) -> bool> {
+	repr: &'a mut FixedCacheableRepr<T, R, N, I>,
+}
+impl<T: Debug, R: Clone, const N: usize, I: Fn(&T) -> bool> Deref for FixedReprMutator<'_, T, R, N, I> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.repr.inner.read_unchecked()
+	}
+}
+impl<T: Debug, R: Clone, const N: usize, I: Fn(&T) -> bool> DerefMut for FixedReprMutator<'_, T, R, N, I> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.repr.inner.inner.get_mut()
+	}
+}
+impl<T: Debug, R: Clone, const N: usize, I: Fn(&T) -> bool> Drop for FixedReprMutator<'_, T, R, N, I> {
+	fn drop(&mut self) {
+		self.repr.check();
+	}
+}
@@ -0,0 +1,107 @@
+use crate::cache::lock::Lock;
+use crate::cache::stats::{CacheStats, Stopwatch};
+use crate::CacheableRepr;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Identifies a [`CacheableRepr`] (by its [`Identity`]) and how often it has been written.
+pub(crate) type Stamp = (usize, u64);
+
+/// The last identity that was given to a [`CacheableRepr`].
+static LAST_IDENTITY: AtomicUsize = AtomicUsize::new(0);
+
+/// A unique identity of a [`CacheableRepr`], which is kept when it's moved and never reused after
+/// it's dropped (unlike its address). It's only assigned when it's first needed, so the
+/// constructors can stay `const`.
+pub(crate) struct Identity(AtomicUsize);
+impl Identity {
+	pub(crate) const fn new() -> Self {
+		Self(AtomicUsize::new(0))
+	}
+	pub(crate) fn get(&self) -> usize {
+		let id = self.0.load(Ordering::Relaxed);
+		if id != 0 {
+			return id;
+		}
+		let new = LAST_IDENTITY.fetch_add(1, Ordering::Relaxed) + 1;
+		match self.0.compare_exchange(0, new, Ordering::Relaxed, Ordering::Relaxed) {
+			Ok(_) => new,
+			Err(id) => id,
+		}
+	}
+}
+
+/// Caches a value derived from several [`CacheableRepr`]s, so related state doesn't have to be
+/// merged into one value just to share a cache. The cached value is recomputed when any of the
+/// representation invariants has been written since it was computed, or when it's read with
+/// different ones (or a different read function). A representation invariant that is moved is
+/// still the same one, and a clone is a different one.
+/// ```rust
+/// use repr_rs::CacheableRepr;
+/// use repr_rs::cache::joint::JointCache;
+/// fn balance(deposits: &Vec<i64>, withdrawals: &Vec<i64>) -> i64 {
+///   deposits.iter().sum::<i64>() - withdrawals.iter().sum::<i64>()
+/// }
+/// let mut deposits = CacheableRepr::new(vec![100, 50], |d: &Vec<i64>| d.iter().all(|d| *d > 0));
+/// let withdrawals = CacheableRepr::new(vec![30], |w: &Vec<i64>| w.iter().all(|w| *w > 0));
+/// let cache = JointCache::new();
+/// assert_eq!(120, cache.get2(&deposits, &withdrawals, balance));
+/// deposits.write().push(10);
+/// assert_eq!(130, cache.get2(&deposits, &withdrawals, balance));
+/// ```
+pub struct JointCache<R> {
+	state: Lock<State<R>>,
+}
+struct State<R> {
+	/// The stamps of the inputs and the address of the read function the result was computed with.
+	cached: Option<(Vec<Stamp>, usize, R)>,
+	stats: CacheStats,
+}
+impl<R: Clone> JointCache<R> {
+	/// Creates an empty cache.
+	pub fn new() -> Self {
+		Self {
+			state: Lock::new(State {
+				cached: None,
+				stats: CacheStats::default(),
+			}),
+		}
+	}
+	/// Reads the value derived from two representation invariants, computing it if it isn't cached.
+	pub fn get2<A: Debug + 'static, IA: Fn(&A) -> bool, B: Debug + 'static, IB: Fn(&B) -> bool>(&self, a: &CacheableRepr<A, IA>, b: &CacheableRepr<B, IB>, read_fn: fn(&A, &B) -> R) -> R {
+		self.get_with(alloc::vec![a.stamp(), b.stamp()], read_fn as *const () as usize, || read_fn(a.read(), b.read()))
+	}
+	/// Reads the value derived from three representation invariants, computing it if it isn't
+	/// cached.
+	pub fn get3<A: Debug + 'static, IA: Fn(&A) -> bool, B: Debug + 'static, IB: Fn(&B) -> bool, C: Debug + 'static, IC: Fn(&C) -> bool>(&self, a: &CacheableRepr<A, IA>, b: &CacheableRepr<B, IB>, c: &CacheableRepr<C, IC>, read_fn: fn(&A, &B, &C) -> R) -> R {
+		self.get_with(alloc::vec![a.stamp(), b.stamp(), c.stamp()], read_fn as *const () as usize, || read_fn(a.read(), b.read(), c.read()))
+	}
+	/// Gets the [`CacheStats`] of this cache.
+	pub fn stats(&self) -> CacheStats {
+		self.state.lock().stats
+	}
+	fn get_with(&self, stamps: Vec<Stamp>, read_fn: usize, compute: impl FnOnce() -> R) -> R {
+		{
+			let mut state = self.state.lock();
+			if let Some((cached_stamps, cached_fn, cached)) = &state.cached {
+				if *cached_stamps == stamps && *cached_fn == read_fn {
+					let cached = cached.clone();
+					state.stats.hits += 1;
+					return cached;
+				}
+			}
+		}
+		let stopwatch = Stopwatch::start();
+		let result = compute();
+		let mut state = self.state.lock();
+		state.stats.record_miss(stopwatch.elapsed());
+		state.cached = Some((stamps, read_fn, result.clone()));
+		result
+	}
+}
+impl<R: Clone> Default for JointCache<R> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -1,28 +1,387 @@
-use crate::cache::Cache;
-use std::cell::RefCell;
+use crate::cache::lock::Lock;
+use crate::cache::stats::{CacheStats, Stopwatch};
+use crate::cache::{Cache, ErrorCaching};
+use alloc::sync::Arc;
+use core::any::{Any, TypeId};
+use core::marker::PhantomData;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use downcast_rs::{impl_downcast, Downcast};
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use std::time::{Duration, Instant};
 
-pub(crate) struct CacheableRead<T, R: Clone> {
+pub(crate) struct CacheableRead<T, R: Clone, F = fn(&T) -> R> {
+	read_fn: F,
+	state: Lock<State<R>>,
+	_marker: PhantomData<fn(&T) -> R>,
+}
+struct State<R> {
+	cached: Option<R>,
+	stats: CacheStats,
+	/// How long a cached result stays fresh, and when it was computed.
+	#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+	ttl: Option<Duration>,
+	#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+	computed_at: Option<Instant>,
+}
+impl<R> State<R> {
+	#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+	fn is_expired(&self) -> bool {
+		match (self.ttl, self.computed_at) {
+			(Some(ttl), Some(computed_at)) => computed_at.elapsed() >= ttl,
+			_ => false,
+		}
+	}
+}
+impl<T, R: Clone, F: Fn(&T) -> R> CacheableRead<T, R, F> {
+	pub(crate) fn new(read_fn: F) -> Self {
+		Self {
+			read_fn,
+			state: Lock::new(State {
+				cached: None,
+				stats: CacheStats::default(),
+				#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+				ttl: None,
+				#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+				computed_at: None,
+			}),
+			_marker: PhantomData,
+		}
+	}
+	#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+	pub(crate) fn set_ttl(&self, ttl: Option<Duration>) {
+		self.state.lock().ttl = ttl;
+	}
+	/// The cached result, if there's a fresh one.
+	pub(crate) fn peek(&self) -> Option<R> {
+		let state = self.state.lock();
+		#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+		if state.is_expired() {
+			return None;
+		}
+		state.cached.clone()
+	}
+	/// Caches a result that was computed earlier, e.g. before a restart.
+	#[cfg(feature = "serde")]
+	pub(crate) fn restore(&self, result: R) {
+		let mut state = self.state.lock();
+		state.cached = Some(result);
+		#[cfg(not(target_arch = "wasm32"))]
+		if state.ttl.is_some() {
+			state.computed_at = Some(Instant::now());
+		}
+	}
+	pub(crate) fn read(&self, arg: &T) -> R {
+		{
+			let mut state = self.state.lock();
+			#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+			if state.is_expired() {
+				state.cached = None;
+			}
+			if let Some(cached) = state.cached.clone() {
+				state.stats.hits += 1;
+				return cached;
+			}
+		}
+		// The lock isn't held while reading, so concurrent readers of an empty cache may each call
+		// the read function, but a read function can never deadlock on its own cache.
+		let stopwatch = Stopwatch::start();
+		let result = (self.read_fn)(arg);
+		let mut state = self.state.lock();
+		state.stats.record_miss(stopwatch.elapsed());
+		state.cached = Some(result.clone());
+		#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+		if state.ttl.is_some() {
+			state.computed_at = Some(Instant::now());
+		}
+		result
+	}
+}
+/// Makes a read function of any type cloneable. The returned type only depends on `T` and `R`, so
+/// caches of different closures have the same type.
+pub(crate) fn shared<T, R>(read_fn: Arc<dyn Fn(&T) -> R + Send + Sync>) -> impl Fn(&T) -> R + Clone + Send + Sync {
+	move |value: &T| read_fn(value)
+}
+impl<T: 'static, R: Clone + Send + 'static, F: Fn(&T) -> R + Clone + Send + Sync + 'static> Cache<T> for CacheableRead<T, R, F> {
+	fn notify(&self, _: &T) {
+		self.state.lock().cached = None;
+	}
+	fn stats(&self) -> CacheStats {
+		self.state.lock().stats
+	}
+	fn duplicate(&self) -> Arc<dyn Cache<T>> {
+		let copy = Self::new(self.read_fn.clone());
+		{
+			let state = self.state.lock();
+			let mut copied = copy.state.lock();
+			copied.cached = state.cached.clone();
+			#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+			{
+				copied.ttl = state.ttl;
+				copied.computed_at = state.computed_at;
+			}
+		}
+		Arc::new(copy)
+	}
+}
+
+/// The cache of a read function that takes an argument, holding the results of the most recently
+/// used arguments.
+pub(crate) struct MemoizedRead<T, A, R> {
+	read_fn: fn(&T, A) -> R,
+	max_entries: usize,
+	state: Lock<MemoizedState<A, R>>,
+}
+struct MemoizedState<A, R> {
+	/// The result for each argument, and when it was last used.
+	entries: BTreeMap<A, (R, u64)>,
+	/// The arguments by when they were last used, from the least to the most recently used.
+	recency: BTreeMap<u64, A>,
+	/// Incremented on every use of an entry.
+	clock: u64,
+	stats: CacheStats,
+}
+impl<A: Ord + Clone, R> MemoizedState<A, R> {
+	/// Marks the entry of the argument as the most recently used one.
+	fn touch(&mut self, arg: &A) {
+		self.clock += 1;
+		if let Some((_, used)) = self.entries.get_mut(arg) {
+			self.recency.remove(used);
+			*used = self.clock;
+			self.recency.insert(self.clock, arg.clone());
+		}
+	}
+}
+impl<T, A: Ord + Clone, R: Clone> MemoizedRead<T, A, R> {
+	pub(crate) fn new(read_fn: fn(&T, A) -> R, max_entries: usize) -> Self {
+		Self {
+			read_fn,
+			max_entries,
+			state: Lock::new(MemoizedState {
+				entries: BTreeMap::new(),
+				recency: BTreeMap::new(),
+				clock: 0,
+				stats: CacheStats::default(),
+			}),
+		}
+	}
+	pub(crate) fn read(&self, value: &T, arg: A) -> R {
+		{
+			let mut state = self.state.lock();
+			if let Some((result, _)) = state.entries.get(&arg) {
+				let result = result.clone();
+				state.touch(&arg);
+				state.stats.hits += 1;
+				return result;
+			}
+		}
+		let stopwatch = Stopwatch::start();
+		let result = (self.read_fn)(value, arg.clone());
+		let mut state = self.state.lock();
+		state.stats.record_miss(stopwatch.elapsed());
+		// Another reader may have computed the same argument in the meantime.
+		if let Some((_, used)) = state.entries.remove(&arg) {
+			state.recency.remove(&used);
+		} else if state.entries.len() >= self.max_entries {
+			if let Some((_, oldest)) = state.recency.pop_first() {
+				state.entries.remove(&oldest);
+			}
+		}
+		state.clock += 1;
+		let used = state.clock;
+		state.entries.insert(arg.clone(), (result.clone(), used));
+		state.recency.insert(used, arg);
+		result
+	}
+}
+impl<T: 'static, A: Ord + Clone + Send + 'static, R: Clone + Send + 'static> Cache<T> for MemoizedRead<T, A, R> {
+	fn notify(&self, _: &T) {
+		let mut state = self.state.lock();
+		state.entries.clear();
+		state.recency.clear();
+	}
+	fn stats(&self) -> CacheStats {
+		self.state.lock().stats
+	}
+	fn duplicate(&self) -> Arc<dyn Cache<T>> {
+		let copy = Self::new(self.read_fn, self.max_entries);
+		{
+			let state = self.state.lock();
+			let mut copied = copy.state.lock();
+			copied.entries = state.entries.clone();
+			copied.recency = state.recency.clone();
+			copied.clock = state.clock;
+		}
+		Arc::new(copy)
+	}
+}
+
+/// The cache of a read function that can fail, which caches errors according to an
+/// [`ErrorCaching`] policy.
+pub(crate) struct FallibleRead<T, R, E> {
+	read_fn: fn(&T) -> Result<R, E>,
+	state: Lock<FallibleState<R, E>>,
+}
+struct FallibleState<R, E> {
+	cached: Option<Result<R, E>>,
+	stats: CacheStats,
+	/// When the cached error was returned by the read function.
+	#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+	failed_at: Option<Instant>,
+}
+impl<R, E> FallibleState<R, E> {
+	fn is_fresh(&self, error_caching: ErrorCaching) -> bool {
+		match (&self.cached, error_caching) {
+			(None, _) => false,
+			(Some(Ok(_)), _) => true,
+			(Some(Err(_)), ErrorCaching::Retry) => false,
+			(Some(Err(_)), ErrorCaching::Cache) => true,
+			#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+			(Some(Err(_)), ErrorCaching::CacheFor(ttl)) => self.failed_at.is_some_and(|failed_at| failed_at.elapsed() < ttl),
+		}
+	}
+}
+impl<T, R: Clone, E: Clone> FallibleRead<T, R, E> {
+	pub(crate) fn new(read_fn: fn(&T) -> Result<R, E>) -> Self {
+		Self {
+			read_fn,
+			state: Lock::new(FallibleState {
+				cached: None,
+				stats: CacheStats::default(),
+				#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+				failed_at: None,
+			}),
+		}
+	}
+	pub(crate) fn read(&self, arg: &T, error_caching: ErrorCaching) -> Result<R, E> {
+		{
+			let mut state = self.state.lock();
+			if state.is_fresh(error_caching) {
+				state.stats.hits += 1;
+				return state.cached.clone().expect("a fresh cache holds a result");
+			}
+		}
+		let stopwatch = Stopwatch::start();
+		let result = (self.read_fn)(arg);
+		let mut state = self.state.lock();
+		state.stats.record_miss(stopwatch.elapsed());
+		#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+		{
+			state.failed_at = match (&result, error_caching) {
+				(Err(_), ErrorCaching::CacheFor(_)) => Some(Instant::now()),
+				_ => None,
+			};
+		}
+		state.cached = Some(result.clone());
+		result
+	}
+}
+impl<T: 'static, R: Clone + Send + 'static, E: Clone + Send + 'static> Cache<T> for FallibleRead<T, R, E> {
+	fn notify(&self, _: &T) {
+		self.state.lock().cached = None;
+	}
+	fn stats(&self) -> CacheStats {
+		self.state.lock().stats
+	}
+	fn duplicate(&self) -> Arc<dyn Cache<T>> {
+		let copy = Self::new(self.read_fn);
+		{
+			let state = self.state.lock();
+			let mut copied = copy.state.lock();
+			copied.cached = state.cached.clone();
+			#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+			{
+				copied.failed_at = state.failed_at;
+			}
+		}
+		Arc::new(copy)
+	}
+}
+
+/// The cache of a read function whose result can be updated from deltas instead of being
+/// recomputed. Each type of delta has its own update function.
+pub(crate) struct IncrementalRead<T, R> {
 	read_fn: fn(&T) -> R,
-	cache: RefCell<Option<R>>,
+	state: Lock<IncrementalState<T, R>>,
+}
+struct IncrementalState<T, R> {
+	cached: Option<R>,
+	stats: CacheStats,
+	updates: BTreeMap<TypeId, Box<dyn Update<T, R>>>,
 }
-impl<T, R: Clone> CacheableRead<T, R> {
+impl<T: 'static, R: Clone + 'static> IncrementalRead<T, R> {
 	pub(crate) fn new(read_fn: fn(&T) -> R) -> Self {
 		Self {
 			read_fn,
-			cache: RefCell::new(None),
+			state: Lock::new(IncrementalState {
+				cached: None,
+				stats: CacheStats::default(),
+				updates: BTreeMap::new(),
+			}),
 		}
 	}
-	pub(crate) fn read(&self, arg: &T) -> R {
-		if let Some(cached) = self.cache.borrow().as_ref() {
-			return cached.clone();
+	/// Updates the result with the update function after writes with deltas of type `D`, replacing
+	/// the update function that was used for them before.
+	pub(crate) fn read<D: 'static>(&self, arg: &T, update_fn: fn(&T, &D, R) -> R) -> R {
+		{
+			let mut state = self.state.lock();
+			match state.updates.get_mut(&TypeId::of::<D>()).and_then(|update| update.downcast_mut::<UpdateFn<T, D, R>>()) {
+				Some(update) => update.0 = update_fn,
+				None => {
+					state.updates.insert(TypeId::of::<D>(), Box::new(UpdateFn(update_fn)));
+				}
+			}
+			if let Some(cached) = state.cached.clone() {
+				state.stats.hits += 1;
+				return cached;
+			}
 		}
+		let stopwatch = Stopwatch::start();
 		let result = (self.read_fn)(arg);
-		self.cache.replace(Some(result.clone()));
+		let mut state = self.state.lock();
+		state.stats.record_miss(stopwatch.elapsed());
+		state.cached = Some(result.clone());
 		result
 	}
 }
-impl<T: 'static, R: Clone + 'static> Cache<T> for CacheableRead<T, R> {
+impl<T: 'static, R: Clone + Send + 'static> Cache<T> for IncrementalRead<T, R> {
 	fn notify(&self, _: &T) {
-		self.cache.replace(None);
+		self.state.lock().cached = None;
+	}
+	fn notify_delta(&self, value: &T, delta: &dyn Any) {
+		let mut state = self.state.lock();
+		let previous = state.cached.take();
+		if let (Some(previous), Some(update)) = (previous, state.updates.get(&delta.type_id())) {
+			state.cached = Some(update.update(value, delta, previous));
+		}
+	}
+	fn stats(&self) -> CacheStats {
+		self.state.lock().stats
+	}
+	fn duplicate(&self) -> Arc<dyn Cache<T>> {
+		let state = self.state.lock();
+		let copy = Self::new(self.read_fn);
+		{
+			let mut copied = copy.state.lock();
+			copied.cached = state.cached.clone();
+			copied.updates = state.updates.iter().map(|(delta, update)| (*delta, update.duplicate())).collect();
+		}
+		Arc::new(copy)
+	}
+}
+/// The update function of an [`IncrementalRead`] for one type of delta.
+trait Update<T, R>: Downcast + Send {
+	/// Updates the previous result from the delta, which is always of the type of this update.
+	fn update(&self, value: &T, delta: &dyn Any, previous: R) -> R;
+	fn duplicate(&self) -> Box<dyn Update<T, R>>;
+}
+impl_downcast!(Update<T, R>);
+struct UpdateFn<T, D, R>(fn(&T, &D, R) -> R);
+impl<T: 'static, D: 'static, R: 'static> Update<T, R> for UpdateFn<T, D, R> {
+	fn update(&self, value: &T, delta: &dyn Any, previous: R) -> R {
+		(self.0)(value, delta.downcast_ref::<D>().expect("updates are looked up by the type of the delta"), previous)
+	}
+	fn duplicate(&self) -> Box<dyn Update<T, R>> {
+		Box::new(UpdateFn(self.0))
 	}
 }
@@ -0,0 +1,89 @@
+use core::ops::{Deref, DerefMut};
+
+/// A mutual exclusion lock for the caches. With `std` this is a [`std::sync::Mutex`] that ignores
+/// poisoning, as the caches only ever hold either nothing or a complete result, or a parking_lot
+/// mutex with the `parking_lot` feature. Without `std` it's a spin lock, which is fine as the lock
+/// is never held while a read function runs.
+pub(crate) struct Lock<T> {
+	#[cfg(all(feature = "std", not(feature = "parking_lot")))]
+	inner: std::sync::Mutex<T>,
+	#[cfg(feature = "parking_lot")]
+	inner: parking_lot::Mutex<T>,
+	#[cfg(not(feature = "std"))]
+	locked: core::sync::atomic::AtomicBool,
+	#[cfg(not(feature = "std"))]
+	value: core::cell::UnsafeCell<T>,
+}
+impl<T> Lock<T> {
+	pub(crate) const fn new(value: T) -> Self {
+		Self {
+			#[cfg(all(feature = "std", not(feature = "parking_lot")))]
+			inner: std::sync::Mutex::new(value),
+			#[cfg(feature = "parking_lot")]
+			inner: parking_lot::Mutex::new(value),
+			#[cfg(not(feature = "std"))]
+			locked: core::sync::atomic::AtomicBool::new(false),
+			#[cfg(not(feature = "std"))]
+			value: core::cell::UnsafeCell::new(value),
+		}
+	}
+	pub(crate) fn lock(&self) -> LockGuard<'_, T> {
+		#[cfg(all(feature = "std", not(feature = "parking_lot")))]
+		return LockGuard(self.inner.lock().unwrap_or_else(std::sync::PoisonError::into_inner));
+		#[cfg(feature = "parking_lot")]
+		return LockGuard(self.inner.lock());
+		#[cfg(not(feature = "std"))]
+		{
+			use core::sync::atomic::Ordering;
+			while self.locked.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+				core::hint::spin_loop();
+			}
+			LockGuard(self)
+		}
+	}
+	pub(crate) fn get_mut(&mut self) -> &mut T {
+		#[cfg(all(feature = "std", not(feature = "parking_lot")))]
+		return self.inner.get_mut().unwrap_or_else(std::sync::PoisonError::into_inner);
+		#[cfg(feature = "parking_lot")]
+		return self.inner.get_mut();
+		#[cfg(not(feature = "std"))]
+		self.value.get_mut()
+	}
+}
+/// # Safety
+/// The value is only accessed while the lock is held (or through a mutable borrow of the lock), so
+/// it is never accessed from two threads at once.
+#[cfg(not(feature = "std"))]
+unsafe impl<T: Send> Sync for Lock<T> {}
+
+#[cfg(all(feature = "std", not(feature = "parking_lot")))]
+pub(crate) struct LockGuard<'a, T>(std::sync::MutexGuard<'a, T>);
+#[cfg(feature = "parking_lot")]
+pub(crate) struct LockGuard<'a, T>(parking_lot::MutexGuard<'a, T>);
+#[cfg(not(feature = "std"))]
+pub(crate) struct LockGuard<'a, T>(&'a Lock<T>);
+impl<T> Deref for LockGuard<'_, T> {
+	type Target = T;
+	fn deref(&self) -> &T {
+		#[cfg(feature = "std")]
+		return &self.0;
+		// Safety: the lock is held for as long as the guard exists.
+		#[cfg(not(feature = "std"))]
+		unsafe { &*self.0.value.get() }
+	}
+}
+impl<T> DerefMut for LockGuard<'_, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		#[cfg(feature = "std")]
+		return &mut self.0;
+		// Safety: the lock is held for as long as the guard exists.
+		#[cfg(not(feature = "std"))]
+		unsafe { &mut *self.0.value.get() }
+	}
+}
+#[cfg(not(feature = "std"))]
+impl<T> Drop for LockGuard<'_, T> {
+	fn drop(&mut self) {
+		self.0.locked.store(false, core::sync::atomic::Ordering::Release);
+	}
+}
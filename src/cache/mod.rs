@@ -1,35 +1,159 @@
 pub(crate) mod lazy;
-#[cfg(feature = "eager")]
+mod lock;
+pub mod fixed;
+pub mod joint;
+#[cfg(feature = "serde")]
+pub mod persist;
+pub mod query;
+pub mod stats;
+#[cfg(feature = "eager-core")]
 pub mod eager;
+#[cfg(feature = "eager-core")]
+pub mod spawner;
 
-use crate::Repr;
+use crate::cache::lock::Lock;
+use crate::lens::{Lens, ReprAt};
+use crate::metrics::{self, Metrics};
+use crate::repr::{CurrentVersion, ReprProjection};
+use crate::{CheckPolicy, Invariant, Repr, Violation};
 use downcast_rs::{impl_downcast, Downcast};
-use std::collections::BTreeMap;
-use std::fmt::{Debug, Display};
-use std::hash::{Hash, Hasher};
-use std::ops::{Deref, DerefMut};
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::any::{Any, TypeId};
+use core::borrow::Borrow;
+use core::fmt::{Debug, Display};
+use core::hash::{Hash, Hasher};
+use core::ops::{Deref, DerefMut};
+use core::panic::{RefUnwindSafe, UnwindSafe};
 
-pub(crate) trait Cache<T>: Downcast {
+pub(crate) trait Cache<T>: Downcast + Send + Sync {
 	fn notify(&self, _value: &T);
+	/// Notifies the cache of a write described by a delta. See [`CacheableRepr::write_with_delta`].
+	fn notify_delta(&self, value: &T, _delta: &dyn Any) {
+		self.notify(value);
+	}
+	fn stats(&self) -> stats::CacheStats;
+	/// A copy of the cache holding the same result, with fresh statistics.
+	fn duplicate(&self) -> Arc<dyn Cache<T>>;
 }
 impl_downcast!(Cache<T>);
 
+/// Identifies a cache of a [`CacheableRepr`]. The caches of read functions are keyed by the
+/// function's address, which is only stable within one build of a program and differs for every
+/// closure. Identical functions may even share an address if the compiler merges them. Caches can
+/// also be given a stable key of their own, see [`CacheableRepr::lazy_keyed`], or be keyed by a
+/// type, see [`CachedRead`].
+/// ```rust
+/// use repr_rs::cache::CacheKey;
+/// fn double(n: &i32) -> i32 { n * 2 }
+/// assert_eq!(CacheKey::of(double), CacheKey::of(double));
+/// assert_eq!(CacheKey::from("double"), CacheKey::Named("double".into()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CacheKey {
+	/// The address of a read function.
+	Fn(usize),
+	/// A key chosen by the user.
+	Named(Cow<'static, str>),
+	/// The type implementing [`CachedRead`].
+	Type(TypeId),
+	/// The address of a read function cached by another method than [`CacheableRepr::lazy`] (e.g.
+	/// [`CacheableRepr::lazy_try`]), and the name of that method. These methods cache their
+	/// results differently, so each of them keeps its own cache of a read function.
+	Method(usize, &'static str),
+}
+impl CacheKey {
+	/// The key of the cache of a read function.
+	pub fn of<T, R>(read_fn: fn(&T) -> R) -> Self {
+		Self::Fn(read_fn as *const fn(&T) -> R as usize)
+	}
+	/// The key of the cache of a read function cached by the given method.
+	fn of_method<T, R>(read_fn: fn(&T) -> R, method: &'static str) -> Self {
+		Self::Method(read_fn as *const fn(&T) -> R as usize, method)
+	}
+	/// The address of the read function, if the cache is keyed by one.
+	fn address(&self) -> Option<usize> {
+		match self {
+			Self::Fn(address) | Self::Method(address, _) => Some(*address),
+			Self::Named(_) | Self::Type(_) => None,
+		}
+	}
+	/// The key of the cache of a [`CachedRead`].
+	pub fn of_type<Q: 'static>() -> Self {
+		Self::Type(TypeId::of::<Q>())
+	}
+}
+
+/// A read function identified by its type rather than by its address, so its cache has a
+/// well-defined identity: no two of them can share a cache, and the key is the same in every
+/// build. Read it with [`CacheableRepr::lazy_of`] (or `eager_of` with the `eager` feature).
+/// ```rust
+/// use repr_rs::CacheableRepr;
+/// use repr_rs::cache::CachedRead;
+/// struct Total;
+/// impl CachedRead<Vec<u32>> for Total {
+///   type Output = u32;
+///   fn read(values: &Vec<u32>) -> u32 { values.iter().sum() }
+/// }
+/// let mut repr = CacheableRepr::new(vec![1, 2], |v: &Vec<u32>| !v.is_empty());
+/// assert_eq!(3, repr.lazy_of::<Total>());
+/// repr.write().push(3);
+/// assert_eq!(6, repr.lazy_of::<Total>());
+/// ```
+pub trait CachedRead<T>: 'static {
+	type Output: Clone + Send + 'static;
+	fn read(value: &T) -> Self::Output;
+}
+impl From<&'static str> for CacheKey {
+	fn from(key: &'static str) -> Self {
+		Self::Named(Cow::Borrowed(key))
+	}
+}
+impl From<String> for CacheKey {
+	fn from(key: String) -> Self {
+		Self::Named(Cow::Owned(key))
+	}
+}
+
+/// Whether [`CacheableRepr::lazy_try`] caches the errors of a read function, or calls it again
+/// on the next read. Successful results are always cached until the next write.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum ErrorCaching {
+	/// Call the read function again on every read until it succeeds. This is the default.
+	#[default]
+	Retry,
+	/// Cache errors until the next write, like successful results.
+	Cache,
+	/// Cache errors until the next write, or until they're older than the given duration. Not
+	/// available on wasm, where there is no clock to time them with.
+	#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+	CacheFor(std::time::Duration),
+}
+
 /// Wraps a value and ensures that an invariant is maintained while allowing that value to be
 /// mutated. The invariant is checked after every mutation.
 /// Additionally, this struct allows for cacheable reads of the value. This is useful when the
 /// read function is expensive. By default, the caching is lazy, so after a value is read once that
 /// same read function will fetch the cached value unless the value has been mutated.
 ///
-/// With the feature `eager` enabled, the [`crate::EagerCacheLookup`] trait is implemented for this struct
+/// With the feature `eager` (or `eager-core` and another runtime) enabled, the [`crate::EagerCacheLookup`] trait is implemented for this struct
 /// and can be used to cache values eagerly. Whenever the value is mutated, all eager caches
 /// will be updated in parallel.
 /// 
 /// This struct requires that the value has a `'static` lifetime. If you need to store a value
 /// with a non-static lifetime consider using [`Repr`].
-pub struct CacheableRepr<T: Debug + 'static, I: Fn(&T) -> bool> {
+pub struct CacheableRepr<T: Debug + 'static, I: Fn(&T) -> bool = fn(&T) -> bool> {
 	inner: Repr<T, I>,
-	caches: BTreeMap<usize, Box<dyn Cache<T>>>,
-	eager_caches: BTreeMap<usize, Box<dyn Cache<T>>>,
+	caches: Lock<Caches<T>>,
+	queries: Lock<query::QueryStore<T>>,
+	/// Incremented on every write.
+	revision: u64,
+	identity: joint::Identity,
+	#[cfg(feature = "eager-core")]
+	eager: eager::EagerSettings,
+	on_evict: Option<Arc<OnEvict>>,
 }
 impl<T: Debug + 'static, I: Fn(&T) -> bool> CacheableRepr<T, I> {
 	/// Creates a new representation invariant with the given value and invariant function.
@@ -45,8 +169,13 @@ impl<T: Debug + 'static, I: Fn(&T) -> bool> CacheableRepr<T, I> {
 	pub const fn new(inner: T, invariant: I) -> Self {
 		let repr = Repr::new(inner, invariant);
 		Self {
-			caches: BTreeMap::new(),
-			eager_caches: BTreeMap::new(),
+			caches: Lock::new(Caches::new()),
+			queries: Lock::new(query::QueryStore::new()),
+			revision: 0,
+			identity: joint::Identity::new(),
+			#[cfg(feature = "eager-core")]
+			eager: eager::EagerSettings::new(),
+			on_evict: None,
 			inner: repr,
 		}
 	}
@@ -64,11 +193,230 @@ impl<T: Debug + 'static, I: Fn(&T) -> bool> CacheableRepr<T, I> {
 	pub const fn with_msg(inner: T, invariant: I, violation_message: &'static str) -> Self {
 		let repr = Repr::with_msg(inner, invariant, violation_message);
 		Self {
-			caches: BTreeMap::new(),
-			eager_caches: BTreeMap::new(),
+			caches: Lock::new(Caches::new()),
+			queries: Lock::new(query::QueryStore::new()),
+			revision: 0,
+			identity: joint::Identity::new(),
+			#[cfg(feature = "eager-core")]
+			eager: eager::EagerSettings::new(),
+			on_evict: None,
 			inner: repr,
 		}
 	}
+	/// Sets the [`CheckPolicy`] that decides when the invariant is checked after a mutation.
+	/// Caches are invalidated after every mutation regardless of the policy.
+	/// ```rust
+	/// use repr_rs::{CacheableRepr, CheckPolicy};
+	/// #[derive(Debug)]
+	/// struct MinMax { min: i32, max: i32 }
+	/// let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+	///   .with_check_policy(CheckPolicy::DebugOnly);
+	/// repr.write().min = 4;
+	/// ```
+	pub fn with_check_policy(mut self, check_policy: CheckPolicy) -> Self {
+		self.inner = self.inner.with_check_policy(check_policy);
+		self
+	}
+	/// Sets how many extra times the invariant is evaluated after each check (in debug builds) to
+	/// detect non-deterministic invariants. See [`Repr::with_determinism_checks`].
+	pub fn with_determinism_checks(mut self, checks: usize) -> Self {
+		self.inner = self.inner.with_determinism_checks(checks);
+		self
+	}
+	/// Disables the non-determinism detector for this representation invariant.
+	/// See [`Repr::without_determinism_check`].
+	pub fn without_determinism_check(self) -> Self {
+		self.with_determinism_checks(0)
+	}
+	/// Sets a function that explains why the invariant was violated.
+	/// See [`Repr::with_explanation`].
+	pub fn with_explanation(mut self, explanation: impl Fn(&T) -> String + Send + Sync + RefUnwindSafe + 'static) -> Self {
+		self.inner = self.inner.with_explanation(explanation);
+		self
+	}
+	/// Skips writes that leave the value as it was, without invalidating caches.
+	/// See [`Repr::with_change_detection`].
+	pub fn with_change_detection(mut self) -> Self where T: Clone + PartialEq {
+		self.inner = self.inner.with_change_detection();
+		self
+	}
+	/// Keeps the last `capacity` committed values with the time they were written.
+	/// See [`Repr::with_history`].
+	#[cfg(all(feature = "history", not(target_arch = "wasm32")))]
+	pub fn with_history(mut self, capacity: usize) -> Self where T: Clone {
+		self.inner = self.inner.with_history(capacity);
+		self
+	}
+	/// Reports the checks, cache reads, and outstanding eager recomputations of this
+	/// representation invariant to the given [`Metrics`]. See [`Repr::with_metrics`].
+	pub fn with_metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+		self.inner = self.inner.with_metrics(metrics);
+		self
+	}
+	/// Calls `warn` whenever checking the invariant after a write takes longer than `threshold`.
+	/// See [`Repr::with_slow_check_warning`].
+	pub fn with_slow_check_warning(mut self, threshold: core::time::Duration, warn: impl Fn(&T, core::time::Duration) + Send + Sync + RefUnwindSafe + 'static) -> Self {
+		self.inner = self.inner.with_slow_check_warning(threshold, warn);
+		self
+	}
+	/// Attaches a sink that receives an [`crate::audit::AuditRecord`] of every write.
+	/// See [`Repr::with_audit_sink`].
+	pub fn with_audit_sink(mut self, sink: impl Fn(crate::audit::AuditRecord<'_, T>) + Send + Sync + RefUnwindSafe + 'static) -> Self {
+		self.inner = self.inner.with_audit_sink(sink);
+		self
+	}
+	/// Limits how many caches (lazy and eager together) this representation invariant keeps. When
+	/// a new read function is cached and the limit has been reached, the least recently used cache
+	/// is evicted. This keeps memory bounded when caching with many distinct closures. The limit is
+	/// at least 1.
+	/// ```rust
+	/// use repr_rs::CacheableRepr;
+	/// use repr_rs::cache::CacheKey;
+	/// use std::sync::atomic::{AtomicUsize, Ordering};
+	/// static EVICTIONS: AtomicUsize = AtomicUsize::new(0);
+	/// fn double(n: &i32) -> i32 { n * 2 }
+	/// fn triple(n: &i32) -> i32 { n * 3 }
+	/// let mut repr = CacheableRepr::new(1, |n| *n > 0)
+	///   .with_max_caches(1)
+	///   .with_eviction_callback(|key| {
+	///     assert_eq!(&CacheKey::of(double), key);
+	///     EVICTIONS.fetch_add(1, Ordering::Relaxed);
+	///   });
+	/// assert_eq!(2, repr.lazy(double));
+	/// // Evicts the cache of `double`.
+	/// assert_eq!(3, repr.lazy(triple));
+	/// assert_eq!(1, EVICTIONS.load(Ordering::Relaxed));
+	/// ```
+	pub fn with_max_caches(mut self, max: usize) -> Self {
+		self.caches.get_mut().lru.max = Some(max.max(1));
+		self
+	}
+	/// Limits how many arguments each read function of [`CacheableRepr::lazy_with`] keeps results
+	/// for (64 by default). A limit of 0 is treated as 1, so the result for the most recently used
+	/// argument is always kept.
+	pub fn with_max_memo_entries(mut self, max: usize) -> Self {
+		self.caches.get_mut().max_memo_entries = max.max(1);
+		self
+	}
+	/// Computes and caches the result of a read function right away, so the first
+	/// [`CacheableRepr::lazy`] read of it doesn't pay for computing it. To compute many caches in
+	/// parallel in the background instead, use `with_eager_cache` with the `eager` feature.
+	/// ```rust
+	/// use repr_rs::CacheableRepr;
+	/// fn total(values: &Vec<u32>) -> u32 { values.iter().sum() }
+	/// fn largest(values: &Vec<u32>) -> Option<u32> { values.iter().copied().max() }
+	/// let repr = CacheableRepr::new(vec![1, 2, 3], |v: &Vec<u32>| !v.is_empty())
+	///   .with_warm_cache(total)
+	///   .with_warm_cache(largest);
+	/// assert_eq!(Some(6), repr.peek(total));
+	/// assert_eq!(Some(Some(3)), repr.peek(largest));
+	/// ```
+	pub fn with_warm_cache<R: Clone + Send + 'static>(self, read_fn: fn(&T) -> R) -> Self {
+		self.lazy(read_fn);
+		self
+	}
+	/// Sets a function that is called with the key of a cache whenever it's evicted because of
+	/// [`CacheableRepr::with_max_caches`].
+	pub fn with_eviction_callback(mut self, on_evict: impl Fn(&CacheKey) + Send + Sync + RefUnwindSafe + 'static) -> Self {
+		self.on_evict = Some(Arc::new(on_evict));
+		self
+	}
+	/// Sets the [`spawner::Spawner`] that eager caches are recomputed on. By default, the spawner
+	/// for the enabled runtime feature is used. See [`spawner::Spawner`] for an example.
+	#[cfg(feature = "eager-core")]
+	pub fn with_spawner(mut self, spawner: impl spawner::Spawner + 'static) -> Self {
+		self.eager.spawner = Some(Arc::new(spawner));
+		self
+	}
+	/// Limits how many eager caches of this representation invariant are recomputed at once, on the
+	/// spawner set so far (or the default one). See [`spawner::BoundedSpawner`].
+	/// ```rust
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// use repr_rs::cache::spawner::ThreadPoolSpawner;
+	/// let mut repr = CacheableRepr::new(1, |n| *n > 0)
+	///   .with_spawner(ThreadPoolSpawner::new(8))
+	///   .with_max_concurrent_recomputations(2);
+	/// assert_eq!(2, repr.eager_blocking(|n| n * 2));
+	/// ```
+	#[cfg(feature = "eager-core")]
+	pub fn with_max_concurrent_recomputations(self, max_concurrent: usize) -> Self {
+		let spawner = self.eager.spawner();
+		self.with_spawner(spawner::BoundedSpawner::new(spawner, max_concurrent))
+	}
+	/// Waits for writes to quiesce before recomputing eager caches: a recomputation only starts once
+	/// no write has happened for the given window, and then only the latest value is recomputed. This
+	/// avoids recomputing every intermediate state of a burst of writes. Until then, reads of the
+	/// eager caches are computed on demand. Low priority caches wait for the higher priority
+	/// recomputations once the window has passed, as they do without debouncing. The window is
+	/// waited out on a shared timer thread, which then hands the recomputations to the spawner. A
+	/// `TokioSpawner` created outside of a runtime spawns onto the runtime of the calling thread,
+	/// which the timer thread doesn't have, so its recomputations would never run; create it inside
+	/// the runtime or with `TokioSpawner::new`. This isn't available on wasm, where there are no
+	/// threads to time it on.
+	/// ```rust
+	/// use std::time::Duration;
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// use repr_rs::cache::spawner::ThreadPoolSpawner;
+	/// let mut repr = CacheableRepr::new(0, |n| *n >= 0)
+	///   .with_spawner(ThreadPoolSpawner::new(2))
+	///   .with_eager_debounce(Duration::from_millis(10));
+	/// assert_eq!(0, repr.eager_blocking(|n| n * 2));
+	/// for _ in 0..1000 {
+	///   // Only the final value is recomputed in the background.
+	///   *repr.write() += 1;
+	/// }
+	/// assert_eq!(2000, repr.eager_blocking(|n| n * 2));
+	/// ```
+	#[cfg(all(feature = "eager-core", not(target_arch = "wasm32")))]
+	pub fn with_eager_debounce(mut self, window: core::time::Duration) -> Self {
+		self.eager.debounce = window;
+		self
+	}
+	/// Sets whether outstanding eager cache recomputations are discarded when this representation
+	/// invariant (or one of its eager caches) is dropped. This is enabled by default, so no new
+	/// recomputation starts for a value nobody can read anymore. Nothing is aborted: recomputations
+	/// that haven't started are skipped when the spawner gets to them, and ones that are already
+	/// running still run to completion, but their results are thrown away.
+	/// ```rust
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// use repr_rs::cache::spawner::ThreadPoolSpawner;
+	/// let mut repr = CacheableRepr::new(1, |n| *n > 0)
+	///   .with_spawner(ThreadPoolSpawner::new(1))
+	///   .with_discard_on_drop(false);
+	/// assert_eq!(2, repr.eager_blocking(|n| n * 2));
+	/// *repr.write() = 2;
+	/// // The recomputation for 2 still runs in the background.
+	/// drop(repr);
+	/// ```
+	#[cfg(feature = "eager-core")]
+	pub fn with_discard_on_drop(mut self, discard_on_drop: bool) -> Self {
+		self.eager.discard_on_drop = discard_on_drop;
+		for cache in self.caches.get_mut().eager.values_mut() {
+			cache.set_discard_on_drop(discard_on_drop);
+		}
+		self
+	}
+	/// Recomputes eager caches on the runtime of the given handle, so that the value can be
+	/// mutated from threads that aren't running in a tokio runtime.
+	/// ```rust
+	/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+	/// let runtime = tokio::runtime::Runtime::new().unwrap();
+	/// let mut repr = CacheableRepr::new(1, |n| *n > 0).with_tokio_handle(runtime.handle().clone());
+	/// assert_eq!(2, runtime.block_on(repr.eager(|n| n * 2)));
+	/// // Not running in the runtime, but the recomputation is still spawned onto it.
+	/// *repr.write() = 2;
+	/// assert_eq!(4, runtime.block_on(repr.eager(|n| n * 2)));
+	/// ```
+	#[cfg(feature = "tokio")]
+	pub fn with_tokio_handle(self, handle: tokio::runtime::Handle) -> Self {
+		self.with_spawner(spawner::TokioSpawner::new(handle))
+	}
+	/// Sets the [`CheckPolicy`] that decides when the invariant is re-checked on
+	/// [`CacheableRepr::read`]. See [`Repr::with_read_check_policy`].
+	pub fn with_read_check_policy(mut self, read_check_policy: CheckPolicy) -> Self {
+		self.inner = self.inner.with_read_check_policy(read_check_policy);
+		self
+	}
 	/// Borrows a read-only view of the value in the representation invariant.
 	/// ```rust
 	/// use repr_rs::CacheableRepr;
@@ -118,12 +466,51 @@ impl<T: Debug + 'static, I: Fn(&T) -> bool> CacheableRepr<T, I> {
 	/// assert_eq!(5, view.max);
 	/// ```
 	#[inline]
-	pub fn write(&mut self) -> ReprMutator<T, I> {
-		// Can be `const` when `const_mut_refs` is stabilised.
+	pub fn write(&mut self) -> ReprMutator<'_, T, I> {
+		self.inner.begin_write();
 		ReprMutator {
 			repr: self,
 		}
 	}
+	/// Borrows a mutable view of the value if the version is still `expected`.
+	/// See [`Repr::try_write_if_version`].
+	pub fn try_write_if_version(&mut self, expected: u64) -> Result<ReprMutator<'_, T, I>, CurrentVersion> {
+		let version = self.version();
+		if version != expected {
+			return Err(CurrentVersion(version));
+		}
+		Ok(self.write())
+	}
+	/// Mutates the value with the given function, and describes the mutation with a delta that
+	/// caches registered with [`CacheableRepr::lazy_incremental`] for this type of delta use to
+	/// update their results instead of recomputing them. Other caches are invalidated as usual.
+	/// The invariant is checked after the mutation, like with [`CacheableRepr::write`].
+	/// ```rust
+	/// use repr_rs::CacheableRepr;
+	/// struct Deposit(i64);
+	/// fn total(ledger: &Vec<i64>) -> i64 { ledger.iter().sum() }
+	/// fn add_deposit(_: &Vec<i64>, deposit: &Deposit, total: i64) -> i64 { total + deposit.0 }
+	/// let mut repr = CacheableRepr::new(vec![10, 20], |ledger: &Vec<i64>| !ledger.is_empty());
+	/// assert_eq!(30, repr.lazy_incremental(total, add_deposit));
+	/// repr.write_with_delta(Deposit(5), |ledger, deposit| ledger.push(deposit.0));
+	/// // Updated to 35 by `add_deposit`, without summing the ledger again.
+	/// assert_eq!(35, repr.lazy_incremental(total, add_deposit));
+	/// ```
+	pub fn write_with_delta<D: 'static>(&mut self, delta: D, apply: impl FnOnce(&mut T, &D)) {
+		let mut mutator = self.write();
+		apply(&mut mutator, &delta);
+		// If applying the delta panics, the mutator is dropped and invalidates the caches as usual.
+		core::mem::forget(mutator);
+		if self.inner.check() {
+			self.notify_caches_with(Some(&delta));
+		}
+	}
+	/// Borrows a mutable view of part of the value, focused by a [`Lens`]. The invariant of the
+	/// whole value is checked (and caches are invalidated) when the view is dropped.
+	/// See [`Repr::at`].
+	pub fn at<L: Lens<T>>(&mut self, lens: L) -> ReprAt<ReprMutator<'_, T, I>, L> {
+		ReprAt::new(self.write(), lens)
+	}
 	/// Consumes the representation invariant and returns the inner value.
 	/// ```rust
 	/// use repr_rs::Repr;
@@ -137,10 +524,95 @@ impl<T: Debug + 'static, I: Fn(&T) -> bool> CacheableRepr<T, I> {
 	pub fn into_inner(self) -> T {
 		self.inner.into_inner()
 	}
+	/// Replaces the value with a new one if it satisfies the invariant, returning the old value.
+	/// Caches are invalidated if the value is replaced. See [`Repr::replace`].
+	pub fn replace(&mut self, value: T) -> Result<T, (T, Violation)> {
+		let version = self.inner.version();
+		let old = self.inner.replace(value)?;
+		if self.inner.version() != version {
+			self.notify_caches();
+		}
+		Ok(old)
+	}
+	/// Sets the value to a new one if it satisfies the invariant. Caches are invalidated if the
+	/// value is set. See [`Repr::set`].
+	pub fn set(&mut self, value: T) -> Result<(), (T, Violation)> {
+		self.replace(value).map(drop)
+	}
+	/// Swaps the values of two representation invariants, as long as each value satisfies the
+	/// other's invariant. Caches of both are invalidated if the values are swapped.
+	/// See [`Repr::swap`].
+	pub fn swap<J: Fn(&T) -> bool>(&mut self, other: &mut CacheableRepr<T, J>) -> Result<(), Violation> {
+		self.inner.swap(&mut other.inner)?;
+		self.notify_caches();
+		other.notify_caches();
+		Ok(())
+	}
+	/// Checks that the invariant holds for the current value without panicking.
+	/// See [`Repr::validate`].
+	pub fn validate(&self) -> Result<(), Violation> {
+		self.inner.validate()
+	}
+	/// Evaluates the invariant for the current value without panicking. See [`Repr::is_valid`].
+	pub fn is_valid(&self) -> bool {
+		self.inner.is_valid()
+	}
+	/// How many writes have been committed. See [`Repr::version`].
+	pub fn version(&self) -> u64 {
+		self.inner.version()
+	}
+	/// Whether a panic occurred while the value was being mutated. See [`Repr::is_poisoned`].
+	pub fn is_poisoned(&self) -> bool {
+		self.inner.is_poisoned()
+	}
+	/// Clears the poisoned flag if the value satisfies the invariant. See [`Repr::clear_poison`].
+	pub fn clear_poison(&mut self) -> Result<(), Violation> {
+		self.inner.clear_poison()
+	}
+	/// The values kept since [`CacheableRepr::with_history`] was set, oldest first.
+	/// See [`Repr::history`].
+	#[cfg(all(feature = "history", not(target_arch = "wasm32")))]
+	pub fn history(&self) -> impl DoubleEndedIterator<Item = &crate::history::Snapshot<T>> + ExactSizeIterator {
+		self.inner.history()
+	}
+	/// Registers a hook that is called with the current value before every write. See
+	/// [`Repr::before_write`].
+	pub fn before_write(&mut self, hook: impl Fn(&T) + Send + Sync + RefUnwindSafe + 'static) {
+		self.inner.before_write(hook);
+	}
+	/// Registers a hook that is called with the new value after every write, once the invariant
+	/// check has passed. See [`Repr::after_write`].
+	pub fn after_write(&mut self, hook: impl Fn(&T) + Send + Sync + RefUnwindSafe + 'static) {
+		self.inner.after_write(hook);
+	}
+	/// Registers an observer that is called with the new value after every write that passes the
+	/// invariant check. See [`Repr::on_change`].
+	pub fn on_change(&mut self, observer: impl Fn(&T) + Send + Sync + RefUnwindSafe + 'static) {
+		self.inner.on_change(observer);
+	}
+	/// Subscribes to the writes committed from now on. See [`Repr::events`].
+	#[cfg(feature = "stream")]
+	pub fn events(&mut self) -> crate::events::ChangeStream<T> where T: Clone {
+		self.inner.events()
+	}
+	/// Exposes the value as a [`futures_signals::signal::ReadOnlyMutable`]. See [`Repr::signal`].
+	#[cfg(feature = "signals")]
+	pub fn signal(&mut self) -> futures_signals::signal::ReadOnlyMutable<T> where T: Clone + Send + Sync + 'static {
+		self.inner.signal()
+	}
+	/// Exposes a value derived from the value as a [`futures_signals::signal::ReadOnlyMutable`],
+	/// which is recomputed once per committed write. See [`Repr::derived_signal`].
+	#[cfg(feature = "signals")]
+	pub fn derived_signal<R: Send + Sync + 'static>(&mut self, derive: impl Fn(&T) -> R + Send + Sync + RefUnwindSafe + 'static) -> futures_signals::signal::ReadOnlyMutable<R> {
+		self.inner.derived_signal(derive)
+	}
 	/// Borrows a read-only view of the value in the representation invariant and caches the
 	/// result of the read function. The cache is keyed by the read function's address, so in general
 	/// you should use function references instead of closures. It is a bug to perform any side effects
-	/// in the read function (i.e. reading from a file).
+	/// in the read function (i.e. reading from a file). This only needs a shared reference, so it can
+	/// be used by many readers at once; the caches are locked briefly, but never while the read
+	/// function runs. The same read function can also be cached by the other `lazy_*` methods, which
+	/// each keep a cache of their own.
 	/// ```rust
 	/// use std::sync::atomic::{AtomicU32, Ordering};
 	/// use repr_rs::CacheableRepr;
@@ -167,29 +639,364 @@ impl<T: Debug + 'static, I: Fn(&T) -> bool> CacheableRepr<T, I> {
 	/// # fn fib(n: usize) -> usize {
 	/// #   if n <= 1 { n } else { fib(n - 1) + fib(n - 2) }
 	/// # }
-	pub fn lazy<R: Clone + 'static>(&mut self, read_fn: fn(&T) -> R) -> R {
-		let fn_identity = read_fn as *const fn(&T) -> R as usize;
-		let entry = self.caches.entry(fn_identity);
+	pub fn lazy<R: Clone + Send + 'static>(&self, read_fn: fn(&T) -> R) -> R {
+		self.with_lazy_cache(CacheKey::of(read_fn), read_fn, |cache| cache.read(self.read()))
+	}
+	/// Like [`CacheableRepr::lazy`], but the cache is keyed by the given key instead of by the
+	/// read function's address. This can cache closures (including capturing ones) reliably, as
+	/// every closure has its own address. The read function is only used when the cache for the
+	/// key is created, so a key should always be used with the same read function.
+	///
+	/// # Panics
+	/// Panics if the key was used with a different result type before.
+	/// ```rust
+	/// use repr_rs::CacheableRepr;
+	/// let mut repr = CacheableRepr::new(vec![3, 1, 2], |v: &Vec<i32>| !v.is_empty());
+	/// let offset = 10;
+	/// assert_eq!(11, repr.lazy_keyed("min", move |v| v.iter().min().unwrap() + offset));
+	/// assert_eq!(11, repr.lazy_keyed("min", move |v| v.iter().min().unwrap() + offset));
+	/// repr.write().push(0);
+	/// assert_eq!(10, repr.lazy_keyed("min", move |v| v.iter().min().unwrap() + offset));
+	/// ```
+	pub fn lazy_keyed<R: Clone + Send + 'static>(&self, key: impl Into<CacheKey>, read_fn: impl Fn(&T) -> R + Send + Sync + RefUnwindSafe + 'static) -> R {
+		self.with_lazy_cache(key.into(), lazy::shared(Arc::new(read_fn)), |cache| cache.read(self.read()))
+	}
+	/// Like [`CacheableRepr::lazy`], but the cache is keyed by the type of the [`CachedRead`]
+	/// instead of by a function's address.
+	pub fn lazy_of<Q: CachedRead<T>>(&self) -> Q::Output {
+		let read_fn: fn(&T) -> Q::Output = Q::read;
+		self.with_lazy_cache(CacheKey::of_type::<Q>(), read_fn, |cache| cache.read(self.read()))
+	}
+	/// Like [`CacheableRepr::lazy`], but the result is stored in an [`Arc`] and shared with every
+	/// reader, so results that are expensive to clone (or can't be cloned at all) can be cached.
+	/// The results are cached separately from [`CacheableRepr::lazy`] of the same read function.
+	/// ```rust
+	/// use std::sync::Arc;
+	/// use repr_rs::CacheableRepr;
+	/// struct Index(Vec<usize>);
+	/// fn index(words: &Vec<String>) -> Index {
+	///   Index(words.iter().map(String::len).collect())
+	/// }
+	/// let repr = CacheableRepr::new(vec!["a".to_string(), "bc".to_string()], |words| !words.is_empty());
+	/// let first = repr.lazy_arc(index);
+	/// assert_eq!(vec![1, 2], first.0);
+	/// assert!(Arc::ptr_eq(&first, &repr.lazy_arc(index)));
+	/// ```
+	pub fn lazy_arc<R: Send + Sync + 'static>(&self, read_fn: fn(&T) -> R) -> Arc<R> {
+		self.with_lazy_cache(CacheKey::of_method(read_fn, "lazy_arc"), move |value: &T| Arc::new(read_fn(value)), |cache| cache.read(self.read()))
+	}
+	/// Like [`CacheableRepr::lazy`], but after a [`CacheableRepr::write_with_delta`] with a delta
+	/// of type `D`, the cached result is updated by calling the update function with the new value,
+	/// the delta and the previous result, instead of calling the read function again. After any
+	/// other write, the result is recomputed on the next read as usual. The read function can be
+	/// updated from several types of delta, with an update function for each, and the update
+	/// function given for a type of delta replaces the one given for it before. The results are
+	/// cached separately from [`CacheableRepr::lazy`] of the same read function. See
+	/// [`CacheableRepr::write_with_delta`] for an example.
+	pub fn lazy_incremental<D: 'static, R: Clone + Send + 'static>(&self, read_fn: fn(&T) -> R, update_fn: fn(&T, &D, R) -> R) -> R {
+		self.with_cache(CacheKey::of_method(read_fn, "lazy_incremental"), || lazy::IncrementalRead::new(read_fn), |cache| cache.read(self.read(), update_fn))
+	}
+	/// Like [`CacheableRepr::lazy`], for read functions that can fail. Successful results are
+	/// cached until the next write, and whether errors are cached is decided by the
+	/// [`ErrorCaching`] policy. The results are cached separately from [`CacheableRepr::lazy`] of
+	/// the same read function.
+	/// ```rust
+	/// use repr_rs::CacheableRepr;
+	/// use repr_rs::cache::ErrorCaching;
+	/// fn parse(s: &String) -> Result<i32, String> {
+	///   s.parse().map_err(|_| format!("{s} isn't a number"))
+	/// }
+	/// let mut repr = CacheableRepr::new("one".to_string(), |s| !s.is_empty());
+	/// assert!(repr.lazy_try(parse, ErrorCaching::Retry).is_err());
+	/// *repr.write() = "1".into();
+	/// assert_eq!(Ok(1), repr.lazy_try(parse, ErrorCaching::Retry));
+	/// ```
+	pub fn lazy_try<R: Clone + Send + 'static, E: Clone + Send + 'static>(&self, read_fn: fn(&T) -> Result<R, E>, error_caching: ErrorCaching) -> Result<R, E> {
+		self.with_cache(CacheKey::of_method(read_fn, "lazy_try"), || lazy::FallibleRead::new(read_fn), |cache| cache.read(self.read(), error_caching))
+	}
+	/// Calls the function with the lazy cache with the given key, creating the cache with the read
+	/// function if it doesn't exist yet. The caches aren't locked while the function runs.
+	fn with_lazy_cache<R: Clone + Send + 'static, F: Fn(&T) -> R + Clone + Send + Sync + 'static, X>(&self, key: CacheKey, read_fn: F, f: impl FnOnce(&lazy::CacheableRead<T, R, F>) -> X) -> X {
+		self.with_cache(key, || lazy::CacheableRead::new(read_fn), f)
+	}
+	/// Like [`CacheableRepr::with_lazy_cache`], for any kind of lazy cache. Every method caches
+	/// read functions under keys of its own, so only a key chosen by the user can be used with
+	/// two kinds of cache.
+	fn with_cache<C: Cache<T>, X>(&self, key: CacheKey, new_cache: impl FnOnce() -> C, f: impl FnOnce(&C) -> X) -> X {
+		let (cache, evicted) = {
+			let mut caches = self.caches.lock();
+			let evicted = caches.touch(&key);
+			let cache = caches.lazy.entry(key.clone()).or_insert_with(|| Arc::new(new_cache())).clone();
+			(cache, evicted)
+		};
+		if let Some(evicted) = evicted {
+			self.evicted(&evicted);
+		}
+		let cache = cache.downcast_ref::<C>().expect("the cache key was used with a different result type or caching method");
+		metrics::observe(self.inner.metrics().map(|metrics| &**metrics), &key, || cache.stats(), || f(cache))
+	}
+	/// Like [`CacheableRepr::lazy`], for read functions that take an argument. A result is cached
+	/// for each argument, and every write invalidates all of them. Each read function keeps the
+	/// results of at most [`CacheableRepr::with_max_memo_entries`] arguments (64 by default),
+	/// evicting the least recently used one beyond that. The results are indexed by their argument,
+	/// so arguments are [`Ord`].
+	/// ```rust
+	/// use repr_rs::CacheableRepr;
+	/// fn distance_from(points: &Vec<i32>, origin: i32) -> i32 {
+	///   points.iter().map(|p| (p - origin).abs()).sum()
+	/// }
+	/// let mut repr = CacheableRepr::new(vec![1, 5], |points: &Vec<i32>| !points.is_empty());
+	/// assert_eq!(4, repr.lazy_with(distance_from, 1));
+	/// assert_eq!(4, repr.lazy_with(distance_from, 5));
+	/// assert_eq!(6, repr.lazy_with(distance_from, 0));
+	/// repr.write().push(10);
+	/// assert_eq!(16, repr.lazy_with(distance_from, 0));
+	/// ```
+	pub fn lazy_with<A: Ord + Clone + Send + 'static, R: Clone + Send + 'static>(&self, read_fn: fn(&T, A) -> R, arg: A) -> R {
+		let key = CacheKey::Fn(read_fn as *const fn(&T, A) -> R as usize);
+		let max_entries = self.caches.lock().max_memo_entries;
+		self.with_cache(key, || lazy::MemoizedRead::new(read_fn, max_entries), |cache| cache.read(self.read(), arg))
+	}
+	/// Like [`CacheableRepr::lazy`], but the cached result expires after the given time-to-live,
+	/// so it's also recomputed on the next read once it's older than that, even without a write.
+	/// This is useful for read functions that also depend on the time. The results are cached
+	/// separately from [`CacheableRepr::lazy`] of the same read function, and each read checks
+	/// the result against its own time-to-live. Not available on wasm, where there is no clock to
+	/// time it with.
+	/// ```rust
+	/// use std::time::Duration;
+	/// use repr_rs::CacheableRepr;
+	/// fn summary(names: &Vec<String>) -> String { names.join(", ") }
+	/// let mut repr = CacheableRepr::new(vec!["Alice".to_string()], |names| !names.is_empty());
+	/// assert_eq!("Alice", repr.lazy_with_ttl(summary, Duration::from_secs(60)));
+	/// // Fresh for the next minute, so this is read from the cache.
+	/// assert_eq!("Alice", repr.lazy_with_ttl(summary, Duration::from_secs(60)));
+	/// ```
+	#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+	pub fn lazy_with_ttl<R: Clone + Send + 'static>(&self, read_fn: fn(&T) -> R, ttl: std::time::Duration) -> R {
+		self.with_lazy_cache(CacheKey::of_method(read_fn, "lazy_with_ttl"), read_fn, |cache| {
+			cache.set_ttl(Some(ttl));
+			cache.read(self.read())
+		})
+	}
 
-		let cache = entry.or_insert_with(|| Box::new(lazy::CacheableRead::<T, R>::new(read_fn)));
-		let cache = cache.downcast_mut::<lazy::CacheableRead<T, R>>().unwrap();
+	/// Gets the result of a query, a memoized computation over the value and over other queries.
+	/// Queries track what they read through their [`query::QueryContext`], and are only recomputed
+	/// when something they read has changed since: queries that read the value are recomputed after
+	/// every write, but a query that only reads other queries is reused as long as their results
+	/// are equal to before (early cutoff). This makes chains of derived values cheap to keep up to
+	/// date when most writes don't affect most of them. Queries are keyed by their address, and
+	/// they're computed while the queries of this representation invariant are locked.
+	///
+	/// # Panics
+	/// Panics if a query depends on itself.
+	/// ```rust
+	/// use std::sync::atomic::{AtomicU32, Ordering};
+	/// use repr_rs::CacheableRepr;
+	/// use repr_rs::cache::query::QueryContext;
+	/// static RENDERS: AtomicU32 = AtomicU32::new(0);
+	/// fn line_count(q: &QueryContext<String>) -> usize {
+	///   q.value().lines().count()
+	/// }
+	/// fn gutter(q: &QueryContext<String>) -> String {
+	///   RENDERS.fetch_add(1, Ordering::Relaxed);
+	///   (1..=q.query(line_count)).map(|n| format!("{n}\n")).collect()
+	/// }
+	/// let mut repr = CacheableRepr::new(String::from("fn main() {\n}"), |_| true);
+	/// assert_eq!("1\n2\n", repr.query(gutter));
+	/// // The line count stays the same, so the gutter isn't rendered again.
+	/// repr.write().insert(11, ' ');
+	/// assert_eq!("1\n2\n", repr.query(gutter));
+	/// assert_eq!(1, RENDERS.load(Ordering::Relaxed));
+	/// ```
+	pub fn query<R: Clone + PartialEq + Send + 'static>(&self, query: fn(&query::QueryContext<'_, T>) -> R) -> R {
+		let mut queries = self.queries.lock();
+		// If a query panics, the memoized queries are dropped and computed again when next needed.
+		queries.revision = self.revision;
+		let store = core::cell::RefCell::new(core::mem::take(&mut *queries));
+		let result = query::fetch(self.read(), &store, query);
+		*queries = store.into_inner();
+		result
+	}
+	/// Gets the cached result of a read function without ever calling it. Returns `None` if the
+	/// read function isn't cached, if its cache was invalidated by a write and not read since, or if
+	/// it's an eager cache that is still being recomputed.
+	/// ```rust
+	/// use repr_rs::CacheableRepr;
+	/// fn double(n: &i32) -> i32 { n * 2 }
+	/// let mut repr = CacheableRepr::new(1, |n| *n > 0);
+	/// assert_eq!(None, repr.peek(double));
+	/// repr.lazy(double);
+	/// assert_eq!(Some(2), repr.peek(double));
+	/// *repr.write() = 2;
+	/// assert_eq!(None, repr.peek(double));
+	/// ```
+	pub fn peek<R: Clone + Send + 'static>(&self, read_fn: fn(&T) -> R) -> Option<R> {
+		let fn_identity = CacheKey::of(read_fn);
+		let caches = self.caches.lock();
+		if let Some(cache) = caches.lazy.get(&fn_identity) {
+			return cache.downcast_ref::<lazy::CacheableRead<T, R>>().and_then(|cache| cache.peek());
+		}
+		#[cfg(feature = "eager-core")]
+		if let Some(cache) = caches.eager.get(&fn_identity) {
+			return cache.peek().and_then(|cached| cached.downcast::<R>().ok()).map(|cached| *cached);
+		}
+		None
+	}
+	/// Invalidates the cache of a read function, e.g. because its result also depends on something
+	/// outside of the value that has changed. Lazy caches are recomputed on their next read, and
+	/// eager caches are recomputed in the background right away. This includes the caches of the
+	/// read function kept by other methods, like [`CacheableRepr::lazy_arc`]. Returns whether the
+	/// read function was cached.
+	/// ```rust
+	/// use std::sync::atomic::{AtomicI32, Ordering};
+	/// use repr_rs::CacheableRepr;
+	/// static FACTOR: AtomicI32 = AtomicI32::new(2);
+	/// fn scaled(n: &i32) -> i32 { n * FACTOR.load(Ordering::Relaxed) }
+	/// let mut repr = CacheableRepr::new(1, |n| *n > 0);
+	/// assert_eq!(2, repr.lazy(scaled));
+	/// FACTOR.store(3, Ordering::Relaxed);
+	/// assert!(repr.invalidate(scaled));
+	/// assert_eq!(3, repr.lazy(scaled));
+	/// ```
+	pub fn invalidate<R: 'static>(&mut self, read_fn: fn(&T) -> R) -> bool {
+		let fn_identity = CacheKey::of(read_fn);
 		let data = self.inner.inner.get_mut();
-		cache.read(data)
+		let caches = self.caches.get_mut();
+		let mut found = false;
+		for cache in caches.lazy.iter().filter(|(key, _)| key.address() == fn_identity.address()).map(|(_, cache)| cache) {
+			cache.notify(data);
+			found = true;
+		}
+		#[cfg(feature = "eager-core")]
+		if caches.eager.contains_key(&fn_identity) {
+			// The caches derived from this one are invalidated along with it.
+			let spawner = self.eager.spawner();
+			let mut snapshot = eager::Snapshot::new(data);
+			let recomputations = core::iter::once(fn_identity.clone()).chain(caches.derived_from(&fn_identity))
+				.filter_map(|key| caches.eager[&key].invalidate(&mut snapshot, &spawner))
+				.map(|recomputation| (recomputation, Vec::new()))
+				.collect();
+			self.eager.schedule(recomputations);
+			found = true;
+		}
+		found
+	}
+	/// Removes every cache, lazy and eager, so nothing is cached until the read functions are read
+	/// again. Outstanding eager recomputations are discarded (skipped, or their results thrown away
+	/// if they're already running) unless that was disabled with `with_discard_on_drop`.
+	pub fn clear_caches(&mut self) {
+		let caches = self.caches.get_mut();
+		caches.lazy.clear();
+		#[cfg(feature = "eager-core")]
+		caches.eager.clear();
+		caches.lru.last_used.clear();
+		*self.queries.get_mut() = query::QueryStore::new();
+	}
+	/// Gets the [`stats::CacheStats`] of every cache.
+	/// Use [`CacheableRepr::cache_stats_of`] to get the statistics of one read function.
+	pub fn cache_stats(&self) -> BTreeMap<CacheKey, stats::CacheStats> {
+		let caches = self.caches.lock();
+		#[allow(unused_mut)]
+		let mut stats: BTreeMap<CacheKey, stats::CacheStats> = caches.lazy.iter()
+			.map(|(key, cache)| (key.clone(), cache.stats()))
+			.collect();
+		#[cfg(feature = "eager-core")]
+		stats.extend(caches.eager.iter().map(|(key, cache)| (key.clone(), cache.stats())));
+		stats
+	}
+	/// Gets the [`stats::CacheStats`] of the cache of a read function, if it's cached.
+	/// ```rust
+	/// use repr_rs::CacheableRepr;
+	/// fn double(n: &i32) -> i32 { n * 2 }
+	/// let mut repr = CacheableRepr::new(1, |n| *n > 0);
+	/// repr.lazy(double);
+	/// repr.lazy(double);
+	/// *repr.write() = 2;
+	/// repr.lazy(double);
+	/// let stats = repr.cache_stats_of(double).unwrap();
+	/// assert_eq!((1, 2), (stats.hits, stats.misses));
+	/// ```
+	pub fn cache_stats_of<R: 'static>(&self, read_fn: fn(&T) -> R) -> Option<stats::CacheStats> {
+		let fn_identity = CacheKey::of(read_fn);
+		self.cache_stats().remove(&fn_identity)
+	}
+	/// Marks the cache of a read function as used, evicting the least recently used cache if this
+	/// adds a cache beyond the limit.
+	#[cfg(feature = "eager-core")]
+	pub(crate) fn touch_cache(&mut self, key: &CacheKey) {
+		if let Some(evicted) = self.caches.get_mut().touch(key) {
+			self.evicted(&evicted);
+		}
+	}
+	/// Identifies this representation invariant and how often it has been written, so caches
+	/// outside of it can tell whether it has changed.
+	pub(crate) fn stamp(&self) -> joint::Stamp {
+		(self.identity.get(), self.revision)
+	}
+	fn evicted(&self, key: &CacheKey) {
+		if let Some(on_evict) = &self.on_evict {
+			on_evict(key);
+		}
 	}
-
 	fn check(&mut self) {
-		self.inner.check();
+		if self.inner.check() {
+			self.notify_caches();
+		}
+	}
+	fn notify_caches(&mut self) {
+		self.notify_caches_with(None);
+	}
+	fn notify_caches_with(&mut self, delta: Option<&dyn Any>) {
+		self.revision = self.revision.wrapping_add(1);
 		let data = self.inner.inner.get_mut();
-		for cache in self.caches.values().chain(self.eager_caches.values()) {
-			cache.notify(data);
+		for cache in self.caches.get_mut().lazy.values() {
+			match delta {
+				Some(delta) => cache.notify_delta(data, delta),
+				None => cache.notify(data),
+			}
 		}
+		#[cfg(feature = "eager-core")]
+		{
+			self.recompute_eager_caches();
+			if let Some(metrics) = self.inner.metrics().cloned() {
+				metrics.pending_eager_tasks(self.pending_eager_tasks());
+			}
+		}
+	}
+}
+impl<T: Debug + Default + 'static, I: Fn(&T) -> bool> CacheableRepr<T, I> {
+	/// Takes the value out of the representation invariant, leaving [`Default::default`] in its
+	/// place. Caches are invalidated if the value is taken. See [`Repr::take`].
+	pub fn take(&mut self) -> Result<T, Violation> {
+		self.replace(T::default()).map_err(|(_, violation)| violation)
+	}
+}
+impl<T: Invariant + Default + 'static> Default for CacheableRepr<T> {
+	/// Creates a representation invariant over the default value, using the invariant from its
+	/// [`Invariant`] implementation. See [`Repr::default`].
+	fn default() -> Self {
+		Self::from(Repr::default())
+	}
+}
+impl<T: Invariant + 'static> CacheableRepr<T> {
+	/// Creates a representation invariant over the value, using the invariant from its
+	/// [`Invariant`] implementation. Fails if the value violates the invariant.
+	/// See [`Repr::try_new`].
+	pub fn try_new(value: T) -> Result<Self, Violation> {
+		Repr::try_new(value).map(Self::from)
 	}
 }
 impl<T: Debug + 'static, I: Fn(&T) -> bool> From<Repr<T, I>> for CacheableRepr<T, I> {
 	fn from(value: Repr<T, I>) -> Self {
 		Self {
-			caches: BTreeMap::new(),
-			eager_caches: BTreeMap::new(),
+			caches: Lock::new(Caches::new()),
+			queries: Lock::new(query::QueryStore::new()),
+			revision: 0,
+			identity: joint::Identity::new(),
+			#[cfg(feature = "eager-core")]
+			eager: eager::EagerSettings::new(),
+			on_evict: None,
 			inner: value,
 		}
 	}
@@ -211,15 +1018,66 @@ unsafe impl<T: Debug + Sync, I: Fn(&T) -> bool + Sync> Sync for CacheableRepr<T,
 /// We exclusively own all inner values here (both the repr and the caches), so we can safely
 /// implement Send for this type.
 unsafe impl<T: Debug + Send, I: Fn(&T) -> bool + Send> Send for CacheableRepr<T, I> {}
+/// See the [`UnwindSafe`] implementation of [`Repr`], which this relies on for the value. The caches
+/// can be filled through a shared borrow, but they only ever hold either nothing or the complete
+/// result of a read function, so a panic while reading can't leave them holding a stale or partial
+/// value. The spawner, the eviction callback and the read functions of keyed caches are required to
+/// be [`RefUnwindSafe`] like the hooks of a [`Repr`].
+impl<T: Debug + UnwindSafe, I: Fn(&T) -> bool + UnwindSafe> UnwindSafe for CacheableRepr<T, I> {}
+/// See the [`UnwindSafe`] implementation.
+impl<T: Debug + RefUnwindSafe, I: Fn(&T) -> bool + RefUnwindSafe> RefUnwindSafe for CacheableRepr<T, I> {}
 impl<T: Debug, I: Fn(&T) -> bool> AsRef<T> for CacheableRepr<T, I> {
 	fn as_ref(&self) -> &T {
 		self.read()
 	}
 }
+impl<T: Debug, I: Fn(&T) -> bool> Deref for CacheableRepr<T, I> {
+	type Target = T;
+	fn deref(&self) -> &T {
+		self.read()
+	}
+}
 impl<T: Debug + Clone, I: Fn(&T) -> bool + Clone> Clone for CacheableRepr<T, I> {
+	#[allow(unused_mut)]
 	fn clone(&self) -> Self {
-		let clone = self.inner.clone();
-		Self::from(clone)
+		let mut clone = Self::from(self.inner.clone());
+		#[cfg(feature = "eager-core")]
+		{
+			clone.eager = self.eager.clone();
+		}
+		{
+			let caches = self.caches.lock();
+			let clone_caches = clone.caches.get_mut();
+			clone_caches.lru.max = caches.lru.max;
+			clone_caches.max_memo_entries = caches.max_memo_entries;
+		}
+		clone.on_evict = self.on_evict.clone();
+		clone
+	}
+}
+impl<T: Debug + Clone, I: Fn(&T) -> bool + Clone> CacheableRepr<T, I> {
+	/// Like [`Clone::clone`], but the clone starts with the same caches instead of none. Cached
+	/// results are copied, and eager caches that are being recomputed are recomputed for the clone
+	/// as well. Queries aren't copied, so they're recomputed when the clone first needs them.
+	/// ```rust
+	/// use repr_rs::CacheableRepr;
+	/// fn double(n: &i32) -> i32 { n * 2 }
+	/// let repr = CacheableRepr::new(1, |n| *n > 0);
+	/// repr.lazy(double);
+	/// let clone = repr.clone_with_caches();
+	/// assert_eq!(Some(2), clone.peek(double));
+	/// ```
+	pub fn clone_with_caches(&self) -> Self {
+		let mut clone = self.clone();
+		{
+			let caches = self.caches.lock();
+			let clone_caches = clone.caches.get_mut();
+			clone_caches.lazy = caches.lazy.iter().map(|(key, cache)| (key.clone(), cache.duplicate())).collect();
+			clone_caches.lru = caches.lru.clone();
+		}
+		#[cfg(feature = "eager-core")]
+		self.duplicate_eager_caches(&mut clone);
+		clone
 	}
 }
 impl<T: Debug + Hash, I: Fn(&T) -> bool> Hash for CacheableRepr<T, I> {
@@ -233,14 +1091,39 @@ impl<T: Debug + PartialEq, I: Fn(&T) -> bool> PartialEq for CacheableRepr<T, I>
 	}
 }
 impl<T: Debug + Eq, I: Fn(&T) -> bool> Eq for CacheableRepr<T, I> {}
+impl<T: Debug + PartialOrd, I: Fn(&T) -> bool> PartialOrd for CacheableRepr<T, I> {
+	fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+		self.inner.partial_cmp(&other.inner)
+	}
+}
+impl<T: Debug + Ord, I: Fn(&T) -> bool> Ord for CacheableRepr<T, I> {
+	fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+		self.inner.cmp(&other.inner)
+	}
+}
+impl<T: Debug + PartialEq, I: Fn(&T) -> bool> PartialEq<T> for CacheableRepr<T, I> {
+	fn eq(&self, other: &T) -> bool {
+		self.inner.eq(other)
+	}
+}
+impl<T: Debug + PartialOrd, I: Fn(&T) -> bool> PartialOrd<T> for CacheableRepr<T, I> {
+	fn partial_cmp(&self, other: &T) -> Option<core::cmp::Ordering> {
+		self.inner.partial_cmp(other)
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Borrow<T> for CacheableRepr<T, I> {
+	fn borrow(&self) -> &T {
+		self.read()
+	}
+}
 
 impl<T: Debug, I: Fn(&T) -> bool> Debug for CacheableRepr<T, I> {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		write!(f, "Repr({:?})", self.read())
 	}
 }
 impl <T: Debug + Display, I: Fn(&T) -> bool> Display for CacheableRepr<T, I> {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		write!(f, "{}", self.read())
 	}
 }
@@ -273,6 +1156,18 @@ impl<'a, T: Debug, I: Fn(&T) -> bool> AsMut<T> for ReprMutator<'a, T, I> {
 		self.deref_mut()
 	}
 }
+impl<T: Debug, I: Fn(&T) -> bool> ReprMutator<'_, T, I> {
+	/// Narrows this mutable view to a part of the value, which has its own invariant.
+	/// See [`crate::repr::ReprMutator::project`].
+	pub fn project<U: Debug + ?Sized, J: Fn(&U) -> bool>(&mut self, f: impl FnOnce(&mut T) -> &mut U, invariant: J) -> ReprProjection<'_, U, J> {
+		ReprProjection::new(f(self.deref_mut()), invariant, "Invariant violated")
+	}
+	/// Like [`ReprMutator::project`], with the message to panic with if the sub-invariant is
+	/// violated. See [`crate::repr::ReprMutator::project_with_msg`].
+	pub fn project_with_msg<U: Debug + ?Sized, J: Fn(&U) -> bool>(&mut self, f: impl FnOnce(&mut T) -> &mut U, invariant: J, violation_message: &'static str) -> ReprProjection<'_, U, J> {
+		ReprProjection::new(f(self.deref_mut()), invariant, violation_message)
+	}
+}
 impl<T: Debug, I: Fn(&T) -> bool> Drop for ReprMutator<'_, T, I> {
 	fn drop(&mut self) {
 		self.repr.check();
@@ -293,12 +1188,78 @@ impl<'a, T: Debug + PartialEq> PartialEq for ReprMutator<'a, T, fn(&T) -> bool>
 }
 impl<'a, T: Debug + Eq> Eq for ReprMutator<'a, T, fn(&T) -> bool> {}
 impl<'a, T: Debug + PartialOrd> PartialOrd for ReprMutator<'a, T, fn(&T) -> bool> {
-	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+	fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
 		self.deref().partial_cmp(other.deref())
 	}
 }
 impl<'a, T: Debug + Ord> Ord for ReprMutator<'a, T, fn(&T) -> bool> {
-	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+	fn cmp(&self, other: &Self) -> core::cmp::Ordering {
 		self.deref().cmp(other.deref())
 	}
 }
+
+/// The caches of a [`CacheableRepr`]. They're behind a [`Lock`] so that lazy caches can be created
+/// through a shared reference.
+struct Caches<T> {
+	lazy: BTreeMap<CacheKey, Arc<dyn Cache<T>>>,
+	#[cfg(feature = "eager-core")]
+	eager: eager::EagerCaches<T>,
+	lru: Lru,
+	/// How many arguments each read function of [`CacheableRepr::lazy_with`] keeps results for.
+	max_memo_entries: usize,
+}
+impl<T: 'static> Caches<T> {
+	const fn new() -> Self {
+		Self {
+			lazy: BTreeMap::new(),
+			#[cfg(feature = "eager-core")]
+			eager: BTreeMap::new(),
+			lru: Lru::new(),
+			max_memo_entries: 64,
+		}
+	}
+	/// Marks a cache as used, evicting and returning the least recently used cache if this adds a
+	/// cache beyond the limit.
+	fn touch(&mut self, key: &CacheKey) -> Option<CacheKey> {
+		let evicted = self.lru.touch(key)?;
+		self.lazy.remove(&evicted);
+		#[cfg(feature = "eager-core")]
+		self.remove_eager(&evicted);
+		Some(evicted)
+	}
+}
+
+type OnEvict = dyn Fn(&CacheKey) + Send + Sync + RefUnwindSafe;
+/// Tracks when each cache of a [`CacheableRepr`] was last used, to evict the least recently used
+/// one once there are more caches than allowed.
+#[derive(Clone)]
+struct Lru {
+	max: Option<usize>,
+	clock: u64,
+	last_used: BTreeMap<CacheKey, u64>,
+}
+impl Lru {
+	const fn new() -> Self {
+		Self {
+			max: None,
+			clock: 0,
+			last_used: BTreeMap::new(),
+		}
+	}
+	/// Marks a cache as used, and returns the cache to evict if that adds a cache beyond the limit.
+	fn touch(&mut self, key: &CacheKey) -> Option<CacheKey> {
+		self.clock += 1;
+		let is_new = self.last_used.insert(key.clone(), self.clock).is_none();
+		let max = self.max?;
+		if !is_new || self.last_used.len() <= max {
+			return None;
+		}
+		let evicted = self.last_used.iter().min_by_key(|(_, last_used)| **last_used)?.0.clone();
+		self.last_used.remove(&evicted);
+		Some(evicted)
+	}
+	#[cfg(feature = "eager-core")]
+	fn forget(&mut self, key: &CacheKey) {
+		self.last_used.remove(key);
+	}
+}
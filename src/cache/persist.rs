@@ -0,0 +1,77 @@
+use crate::cache::{lazy, CacheKey, CacheableRepr, CachedRead};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Debug;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// The cached result of a [`CachedRead`], saved with [`CacheableRepr::save_cache`] so it can be
+/// restored (e.g. after a restart) with [`CacheableRepr::restore_cache`]. It serializes as a
+/// fingerprint of the value the result was computed for, followed by the result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedCache<R> {
+	fingerprint: u64,
+	result: R,
+}
+impl<R: Serialize> Serialize for SavedCache<R> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		(self.fingerprint, &self.result).serialize(serializer)
+	}
+}
+impl<'de, R: Deserialize<'de>> Deserialize<'de> for SavedCache<R> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let (fingerprint, result) = <(u64, R)>::deserialize(deserializer)?;
+		Ok(Self { fingerprint, result })
+	}
+}
+
+impl<T: Debug + Hash + 'static, I: Fn(&T) -> bool> CacheableRepr<T, I> {
+	/// Saves the cached result of a [`CachedRead`], if it's cached. Saved caches are keyed by the
+	/// type of the [`CachedRead`], which is stable across restarts, and they're only restored if the
+	/// value is the same as when they were saved.
+	/// ```rust
+	/// use repr_rs::CacheableRepr;
+	/// use repr_rs::cache::{CacheKey, CachedRead};
+	/// use repr_rs::cache::persist::SavedCache;
+	/// struct Total;
+	/// impl CachedRead<Vec<u32>> for Total {
+	///   type Output = u32;
+	///   fn read(values: &Vec<u32>) -> u32 { values.iter().sum() }
+	/// }
+	/// let repr = CacheableRepr::new(vec![1, 2, 3], |v: &Vec<u32>| !v.is_empty());
+	/// repr.lazy_of::<Total>();
+	/// let bytes = serde_json::to_vec(&repr.save_cache::<Total>().unwrap()).unwrap();
+	///
+	/// // After a restart:
+	/// let restarted = CacheableRepr::new(vec![1, 2, 3], |v: &Vec<u32>| !v.is_empty());
+	/// let saved: SavedCache<u32> = serde_json::from_slice(&bytes).unwrap();
+	/// assert!(restarted.restore_cache::<Total>(saved));
+	/// // Read from the restored cache, without summing the values.
+	/// assert_eq!(6, restarted.lazy_of::<Total>());
+	/// let stats = restarted.cache_stats()[&CacheKey::of_type::<Total>()];
+	/// assert_eq!((1, 0), (stats.hits, stats.misses));
+	/// ```
+	pub fn save_cache<Q: CachedRead<T>>(&self) -> Option<SavedCache<Q::Output>> {
+		let result = self.caches.lock().lazy.get(&CacheKey::of_type::<Q>())?
+			.downcast_ref::<lazy::CacheableRead<T, Q::Output>>()?
+			.peek()?;
+		Some(SavedCache {
+			fingerprint: self.fingerprint(),
+			result,
+		})
+	}
+	/// Restores the cache of a [`CachedRead`] saved with [`CacheableRepr::save_cache`]. Returns
+	/// whether it was restored, which it isn't if the value has changed since it was saved.
+	pub fn restore_cache<Q: CachedRead<T>>(&self, saved: SavedCache<Q::Output>) -> bool {
+		if saved.fingerprint != self.fingerprint() {
+			return false;
+		}
+		let read_fn: fn(&T) -> Q::Output = Q::read;
+		self.with_lazy_cache(CacheKey::of_type::<Q>(), read_fn, |cache| cache.restore(saved.result));
+		true
+	}
+	/// Identifies the value, to tell whether saved caches were computed for it.
+	fn fingerprint(&self) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		self.read().hash(&mut hasher);
+		hasher.finish()
+	}
+}
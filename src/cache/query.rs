@@ -0,0 +1,171 @@
+use crate::cache::CacheKey;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use downcast_rs::{impl_downcast, Downcast};
+
+/// What a query is given to compute its result: the value of the [`crate::CacheableRepr`], and the
+/// other queries. Both are tracked as dependencies of the query, so it's only recomputed when one
+/// of them has changed. See [`crate::CacheableRepr::query`].
+pub struct QueryContext<'a, T> {
+	value: &'a T,
+	store: &'a RefCell<QueryStore<T>>,
+	dependencies: RefCell<Vec<Dependency>>,
+}
+impl<'a, T: 'static> QueryContext<'a, T> {
+	/// Borrows the value, making the query depend on it. A query that reads the value is
+	/// recomputed after every write.
+	pub fn value(&self) -> &'a T {
+		self.depend_on(Dependency::Value);
+		self.value
+	}
+	/// Gets the result of another query, making this query depend on it. A query that only reads
+	/// other queries isn't recomputed as long as their results stay the same.
+	pub fn query<R: Clone + PartialEq + Send + 'static>(&self, query: fn(&QueryContext<'_, T>) -> R) -> R {
+		let key = key_of(query);
+		self.depend_on(Dependency::Query(key));
+		fetch(self.value, self.store, query)
+	}
+	fn depend_on(&self, dependency: Dependency) {
+		let mut dependencies = self.dependencies.borrow_mut();
+		if !dependencies.contains(&dependency) {
+			dependencies.push(dependency);
+		}
+	}
+}
+
+/// The memoized queries of a [`crate::CacheableRepr`], and the revision of its value.
+pub(crate) struct QueryStore<T> {
+	/// The revision of the value the queries are being computed for.
+	pub(crate) revision: u64,
+	memos: BTreeMap<CacheKey, Box<dyn AnyMemo<T>>>,
+	/// The queries that are being computed, to detect cycles.
+	active: BTreeSet<CacheKey>,
+}
+impl<T> QueryStore<T> {
+	pub(crate) const fn new() -> Self {
+		Self {
+			revision: 0,
+			memos: BTreeMap::new(),
+			active: BTreeSet::new(),
+		}
+	}
+}
+impl<T> Default for QueryStore<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[derive(PartialEq)]
+enum Dependency {
+	Value,
+	Query(CacheKey),
+}
+
+trait AnyMemo<T>: Downcast + Send {
+	/// Brings the result up to date with the revision, recomputing it only if a dependency has
+	/// changed since it was last verified. Returns the revision the result last changed in.
+	fn refresh(&mut self, value: &T, store: &RefCell<QueryStore<T>>, revision: u64) -> u64;
+	fn verified_at(&self) -> u64;
+	fn changed_at(&self) -> u64;
+}
+impl_downcast!(AnyMemo<T>);
+
+struct Memo<T, R> {
+	query: fn(&QueryContext<'_, T>) -> R,
+	result: Option<R>,
+	dependencies: Vec<Dependency>,
+	changed_at: u64,
+	verified_at: u64,
+}
+impl<T: 'static, R: Clone + PartialEq + Send + 'static> AnyMemo<T> for Memo<T, R> {
+	fn refresh(&mut self, value: &T, store: &RefCell<QueryStore<T>>, revision: u64) -> u64 {
+		if self.result.is_some() {
+			let verified_at = self.verified_at;
+			let changed = self.dependencies.iter().any(|dependency| match dependency {
+				// Every write changes the value.
+				Dependency::Value => true,
+				Dependency::Query(key) => refresh(value, store, key, None) > verified_at,
+			});
+			if !changed {
+				self.verified_at = revision;
+				return self.changed_at;
+			}
+		}
+		let context = QueryContext {
+			value,
+			store,
+			dependencies: RefCell::new(Vec::new()),
+		};
+		let result = (self.query)(&context);
+		self.dependencies = context.dependencies.into_inner();
+		// Early cutoff: if the result is the same, the queries that depend on it stay valid.
+		if self.result.as_ref() != Some(&result) {
+			self.result = Some(result);
+			self.changed_at = revision;
+		}
+		self.verified_at = revision;
+		self.changed_at
+	}
+	fn verified_at(&self) -> u64 {
+		self.verified_at
+	}
+	fn changed_at(&self) -> u64 {
+		self.changed_at
+	}
+}
+
+fn key_of<T, R>(query: fn(&QueryContext<'_, T>) -> R) -> CacheKey {
+	CacheKey::Fn(query as *const () as usize)
+}
+
+/// Gets the up to date result of a query, computing it if needed.
+pub(crate) fn fetch<T: 'static, R: Clone + PartialEq + Send + 'static>(value: &T, store: &RefCell<QueryStore<T>>, query: fn(&QueryContext<'_, T>) -> R) -> R {
+	let key = key_of(query);
+	let new_memo = || -> Box<dyn AnyMemo<T>> {
+		Box::new(Memo {
+			query,
+			result: None,
+			dependencies: Vec::new(),
+			changed_at: 0,
+			verified_at: 0,
+		})
+	};
+	refresh(value, store, &key, Some(&new_memo));
+	let store = store.borrow();
+	let memo = store.memos[&key].downcast_ref::<Memo<T, R>>().expect("the query key was used with a different result type");
+	memo.result.clone().expect("a refreshed query has a result")
+}
+
+/// Brings the query with the key up to date, creating it if it doesn't exist and a constructor is
+/// given. Returns the revision its result last changed in, which is the current revision for
+/// queries that no longer exist.
+fn refresh<T: 'static>(value: &T, store: &RefCell<QueryStore<T>>, key: &CacheKey, new_memo: Option<&dyn Fn() -> Box<dyn AnyMemo<T>>>) -> u64 {
+	let (mut memo, revision) = {
+		let mut store = store.borrow_mut();
+		if store.active.contains(key) {
+			panic!("query cycle detected: a query depends on itself");
+		}
+		let revision = store.revision;
+		let memo = match (store.memos.remove(key), new_memo) {
+			(Some(memo), _) if memo.verified_at() == revision => {
+				let changed_at = memo.changed_at();
+				store.memos.insert(key.clone(), memo);
+				return changed_at;
+			}
+			(Some(memo), _) => memo,
+			(None, Some(new_memo)) => new_memo(),
+			(None, None) => return revision,
+		};
+		store.active.insert(key.clone());
+		(memo, revision)
+	};
+	// The memo is taken out of the store while it's refreshed, so its query can use the store.
+	let changed_at = memo.refresh(value, store, revision);
+	let mut store = store.borrow_mut();
+	store.active.remove(key);
+	store.memos.insert(key.clone(), memo);
+	changed_at
+}
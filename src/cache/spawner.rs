@@ -0,0 +1,453 @@
+//! Executors that eager caches are recomputed on. A [`Spawner`] is chosen per
+//! [`crate::CacheableRepr`] with [`crate::CacheableRepr::with_spawner`]; otherwise the spawner for
+//! the enabled runtime feature is used (`wasm`, then `tokio`, then `async-std`, then `smol`, then
+//! `rayon`), or a shared [`ThreadPoolSpawner`] if none are enabled.
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::collections::{BinaryHeap, VecDeque};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::Debug;
+use core::time::Duration;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{mpsc, Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::Instant;
+#[cfg(any(not(target_arch = "wasm32"), feature = "wasm"))]
+use core::future::Future;
+#[cfg(any(not(target_arch = "wasm32"), feature = "wasm"))]
+use core::pin::Pin;
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+use core::task::{Context, Poll};
+
+/// A recomputation of an eager cache.
+pub type Task = Box<dyn FnOnce() + Send>;
+/// A recomputation of an eager cache with an async read function. See [`Spawner::spawn_future`].
+#[cfg(not(target_arch = "wasm32"))]
+pub type FutureTask = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Runs eager cache recomputations in the background. Recomputations may block, so they should
+/// run somewhere blocking is allowed (e.g. a runtime's blocking pool). Spawners are
+/// [`panic::RefUnwindSafe`] so that the representation invariants holding them are too.
+/// ```rust
+/// use repr_rs::CacheableRepr;
+/// use repr_rs::cache::spawner::{Spawner, Task};
+/// struct ThreadSpawner;
+/// impl Spawner for ThreadSpawner {
+///   fn spawn(&self, task: Task) {
+///     std::thread::spawn(task);
+///   }
+/// }
+/// let repr = CacheableRepr::new(1, |n| *n > 0).with_spawner(ThreadSpawner);
+/// ```
+pub trait Spawner: Send + Sync + panic::RefUnwindSafe {
+	/// Runs the task in the background.
+	fn spawn(&self, task: Task);
+	/// Runs all the recomputations caused by a single write in the background. The tasks are
+	/// ordered by [`crate::cache::eager::Priority`], so spawners that queue tasks should start them
+	/// in order. By default, each task is spawned separately.
+	fn spawn_all(&self, tasks: Vec<Task>) {
+		for task in tasks {
+			self.spawn(task);
+		}
+	}
+	/// Runs the recomputation of an async read function (see
+	/// [`crate::EagerCacheLookup::eager_async`]) in the background. By default, the future is
+	/// blocked on by a task, which ties up one of the spawner's threads until it completes and
+	/// only works for futures that don't need a runtime (e.g. tokio's timers and I/O need a tokio
+	/// runtime). Spawners of async runtimes poll the future on their runtime instead:
+	/// [`TokioSpawner`] supports futures that use tokio, and [`AsyncStdSpawner`] and
+	/// [`SmolSpawner`] support futures that use their own runtime.
+	#[cfg(not(target_arch = "wasm32"))]
+	fn spawn_future(&self, future: FutureTask) {
+		self.spawn(Box::new(move || crate::cache::eager::block_on(future)));
+	}
+}
+
+impl<S: Spawner + ?Sized> Spawner for Arc<S> {
+	fn spawn(&self, task: Task) {
+		(**self).spawn(task);
+	}
+	fn spawn_all(&self, tasks: Vec<Task>) {
+		(**self).spawn_all(tasks);
+	}
+	#[cfg(not(target_arch = "wasm32"))]
+	fn spawn_future(&self, future: FutureTask) {
+		(**self).spawn_future(future);
+	}
+}
+
+/// Limits how many recomputations run at once on another spawner, queueing the rest. Without a
+/// limit, a write to a value with many eager caches spawns all of their recomputations at once,
+/// which can starve the underlying pool.
+/// ```rust
+/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+/// use repr_rs::cache::spawner::{BoundedSpawner, ThreadPoolSpawner};
+/// let mut repr = CacheableRepr::new(1, |n| *n > 0)
+///   .with_spawner(BoundedSpawner::new(ThreadPoolSpawner::new(8), 2));
+/// assert_eq!(2, repr.eager_blocking(|n| n * 2));
+/// ```
+pub struct BoundedSpawner<S> {
+	inner: Arc<S>,
+	max_concurrent: usize,
+	state: Arc<Mutex<BoundedState>>,
+}
+#[derive(Default)]
+struct BoundedState {
+	running: usize,
+	queue: VecDeque<Work>,
+}
+/// A recomputation queued by a [`BoundedSpawner`].
+enum Work {
+	Task(Task),
+	#[cfg(not(target_arch = "wasm32"))]
+	Future(FutureTask),
+}
+impl<S: Spawner + 'static> BoundedSpawner<S> {
+	/// Runs at most `max_concurrent` (at least one) recomputations at once on the inner spawner.
+	pub fn new(inner: S, max_concurrent: usize) -> Self {
+		Self {
+			inner: Arc::new(inner),
+			max_concurrent: max_concurrent.max(1),
+			state: Default::default(),
+		}
+	}
+	fn enqueue(&self, work: impl IntoIterator<Item = Work>) {
+		let workers = {
+			let mut state = self.state.lock().unwrap();
+			state.queue.extend(work);
+			let workers = (self.max_concurrent - state.running).min(state.queue.len());
+			state.running += workers;
+			workers
+		};
+		for _ in 0..workers {
+			let (inner, state) = (self.inner.clone(), self.state.clone());
+			self.inner.spawn(Box::new(move || drain(inner, state)));
+		}
+	}
+}
+impl<S: Spawner + 'static> Spawner for BoundedSpawner<S> {
+	fn spawn(&self, task: Task) {
+		self.enqueue([Work::Task(task)]);
+	}
+	fn spawn_all(&self, tasks: Vec<Task>) {
+		self.enqueue(tasks.into_iter().map(Work::Task));
+	}
+	/// Queues the future like any other recomputation. Once its turn comes, it's handed to the
+	/// inner spawner, and holds its slot until it completes.
+	#[cfg(not(target_arch = "wasm32"))]
+	fn spawn_future(&self, future: FutureTask) {
+		self.enqueue([Work::Future(future)]);
+	}
+}
+/// Runs queued tasks until the queue is empty, then frees up this worker's slot. A queued future
+/// takes the slot with it, and a new worker takes over once it completes.
+#[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+fn drain<S: Spawner + 'static>(inner: Arc<S>, state: Arc<Mutex<BoundedState>>) {
+	loop {
+		let work = {
+			let mut state = state.lock().unwrap();
+			match state.queue.pop_front() {
+				Some(work) => work,
+				None => {
+					state.running -= 1;
+					return;
+				}
+			}
+		};
+		match work {
+			// A panicking recomputation mustn't take the worker's slot with it.
+			Work::Task(task) => drop(panic::catch_unwind(AssertUnwindSafe(task))),
+			#[cfg(not(target_arch = "wasm32"))]
+			Work::Future(future) => {
+				let slot = Slot { inner: inner.clone(), state };
+				inner.spawn_future(Box::pin(async move {
+					future.await;
+					drop(slot);
+				}));
+				return;
+			}
+		}
+	}
+}
+/// The slot of a [`BoundedSpawner`] held by a running future. It's handed back to a new worker
+/// once the future completes, or is dropped because it panicked.
+#[cfg(not(target_arch = "wasm32"))]
+struct Slot<S: Spawner + 'static> {
+	inner: Arc<S>,
+	state: Arc<Mutex<BoundedState>>,
+}
+#[cfg(not(target_arch = "wasm32"))]
+impl<S: Spawner + 'static> Drop for Slot<S> {
+	fn drop(&mut self) {
+		let (inner, state) = (self.inner.clone(), self.state.clone());
+		self.inner.spawn(Box::new(move || drain(inner, state)));
+	}
+}
+impl<S: Debug> Debug for BoundedSpawner<S> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_struct("BoundedSpawner").field("inner", &self.inner).field("max_concurrent", &self.max_concurrent).finish()
+	}
+}
+
+/// Spawns recomputations onto the blocking pool of a tokio runtime. By default this is the runtime
+/// the spawner is created in. A spawner created outside of a runtime uses the ambient runtime
+/// instead, which panics when the value is mutated outside of a runtime; use
+/// [`TokioSpawner::new`] to always spawn onto a specific runtime instead.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone)]
+pub struct TokioSpawner {
+	handle: Option<tokio::runtime::Handle>,
+}
+#[cfg(feature = "tokio")]
+impl Default for TokioSpawner {
+	fn default() -> Self {
+		Self { handle: tokio::runtime::Handle::try_current().ok() }
+	}
+}
+#[cfg(feature = "tokio")]
+impl TokioSpawner {
+	/// Creates a spawner that spawns onto the runtime of the given handle, even when used from a
+	/// thread that isn't running in that runtime.
+	/// ```rust
+	/// use repr_rs::cache::spawner::TokioSpawner;
+	/// let runtime = tokio::runtime::Runtime::new().unwrap();
+	/// let spawner = TokioSpawner::new(runtime.handle().clone());
+	/// ```
+	pub fn new(handle: tokio::runtime::Handle) -> Self {
+		Self { handle: Some(handle) }
+	}
+}
+#[cfg(feature = "tokio")]
+impl Spawner for TokioSpawner {
+	fn spawn(&self, task: Task) {
+		match &self.handle {
+			Some(handle) => drop(handle.spawn_blocking(task)),
+			None => drop(tokio::task::spawn_blocking(task)),
+		}
+	}
+	/// Spawns the future onto the runtime as a task, so it can use tokio's timers and I/O.
+	fn spawn_future(&self, future: FutureTask) {
+		match &self.handle {
+			Some(handle) => drop(handle.spawn(future)),
+			None => drop(tokio::spawn(future)),
+		}
+	}
+}
+
+/// Spawns recomputations onto async-std's blocking pool.
+#[cfg(feature = "async-std")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct AsyncStdSpawner;
+#[cfg(feature = "async-std")]
+impl Spawner for AsyncStdSpawner {
+	fn spawn(&self, task: Task) {
+		drop(async_std::task::spawn_blocking(task));
+	}
+	/// Spawns the future onto async-std's executor as a task.
+	fn spawn_future(&self, future: FutureTask) {
+		drop(async_std::task::spawn(future));
+	}
+}
+
+/// Spawns recomputations onto smol's blocking pool.
+#[cfg(feature = "smol")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SmolSpawner;
+#[cfg(feature = "smol")]
+impl Spawner for SmolSpawner {
+	fn spawn(&self, task: Task) {
+		smol::unblock(task).detach();
+	}
+	/// Spawns the future onto smol's global executor as a task.
+	fn spawn_future(&self, future: FutureTask) {
+		smol::spawn(future).detach();
+	}
+}
+
+/// Runs recomputations on the global rayon thread pool. All the recomputations caused by a single
+/// write are fanned out across the pool from one rayon job, which is much lighter than spawning a
+/// blocking task for each of them. This also works in programs without an async runtime.
+#[cfg(feature = "rayon")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RayonSpawner;
+#[cfg(feature = "rayon")]
+impl Spawner for RayonSpawner {
+	fn spawn(&self, task: Task) {
+		rayon::spawn(task);
+	}
+	fn spawn_all(&self, tasks: Vec<Task>) {
+		rayon::spawn(move || rayon::scope_fifo(|scope| {
+			for task in tasks {
+				scope.spawn_fifo(move |_| task());
+			}
+		}));
+	}
+}
+
+/// Spawns recomputations onto the browser's event loop, as there are no blocking threads on wasm.
+/// Each recomputation is its own task that first yields to the event loop, so the event loop gets a
+/// turn between the recomputations caused by a write. A recomputation itself still runs to
+/// completion on the event loop, so a slow read function blocks rendering while it runs.
+///
+/// Recomputations run one after the other, in the order they were spawned. Nothing on wasm can
+/// block waiting for a recomputation, so `eager_blocking`, `eager_async` and `with_eager_debounce`
+/// aren't available.
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct WasmSpawner;
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+impl Spawner for WasmSpawner {
+	fn spawn(&self, task: Task) {
+		wasm_bindgen_futures::spawn_local(async move {
+			YieldNow(false).await;
+			task();
+		});
+	}
+}
+/// A future that is pending the first time it's polled, yielding to the executor.
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+struct YieldNow(bool);
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+impl Future for YieldNow {
+	type Output = ();
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+		if self.0 {
+			return Poll::Ready(());
+		}
+		self.0 = true;
+		cx.waker().wake_by_ref();
+		Poll::Pending
+	}
+}
+
+/// Runs recomputations on a fixed pool of plain threads, for programs that don't use an async
+/// runtime. This is the default when no runtime feature is enabled. A recomputation that panics
+/// doesn't take its worker thread down with it.
+/// ```rust
+/// use repr_rs::{CacheableRepr, EagerCacheLookup};
+/// use repr_rs::cache::spawner::ThreadPoolSpawner;
+/// let mut repr = CacheableRepr::new(1, |n| *n > 0).with_spawner(ThreadPoolSpawner::new(2));
+/// assert_eq!(2, repr.eager_blocking(|n| n * 2));
+/// ```
+pub struct ThreadPoolSpawner {
+	sender: mpsc::Sender<Task>,
+}
+impl ThreadPoolSpawner {
+	/// Starts a pool with the given number of worker threads (at least one). The workers exit once
+	/// the spawner is dropped and all queued recomputations have run.
+	pub fn new(threads: usize) -> Self {
+		let (sender, receiver) = mpsc::channel::<Task>();
+		let receiver = Arc::new(Mutex::new(receiver));
+		for _ in 0..threads.max(1) {
+			let receiver = receiver.clone();
+			thread::spawn(move || loop {
+				let task = match receiver.lock().unwrap().recv() {
+					Ok(task) => task,
+					Err(_) => return,
+				};
+				let _ = panic::catch_unwind(AssertUnwindSafe(task));
+			});
+		}
+		Self { sender }
+	}
+}
+impl Spawner for ThreadPoolSpawner {
+	fn spawn(&self, task: Task) {
+		self.sender.send(task).expect("the thread pool's workers have exited");
+	}
+}
+impl Debug for ThreadPoolSpawner {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.write_str("ThreadPoolSpawner")
+	}
+}
+
+/// Runs the task on the shared timer thread once the delay has passed. The task must be quick, as
+/// every other timer waits for it; it should hand any real work to a spawner.
+pub(crate) fn after(delay: Duration, task: Task) {
+	static TIMER: OnceLock<Arc<Timer>> = OnceLock::new();
+	static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+	let timer = TIMER.get_or_init(|| {
+		let timer = Arc::new(Timer {
+			queue: Mutex::new(BinaryHeap::new()),
+			ready: Condvar::new(),
+		});
+		let worker = timer.clone();
+		thread::spawn(move || worker.run());
+		timer
+	});
+	let id = NEXT_ID.fetch_add(1, AtomicOrdering::Relaxed);
+	let mut queue = timer.queue.lock().unwrap();
+	queue.push(Timed { deadline: Instant::now() + delay, id, task });
+	timer.ready.notify_one();
+}
+/// The tasks waiting for their delay to pass, soonest first.
+struct Timer {
+	queue: Mutex<BinaryHeap<Timed>>,
+	ready: Condvar,
+}
+impl Timer {
+	fn run(&self) {
+		let mut queue = self.queue.lock().unwrap();
+		loop {
+			let now = Instant::now();
+			match queue.peek() {
+				None => queue = self.ready.wait(queue).unwrap(),
+				Some(next) if next.deadline > now => {
+					let timeout = next.deadline - now;
+					queue = self.ready.wait_timeout(queue, timeout).unwrap().0;
+				}
+				Some(_) => {
+					let timed = queue.pop().unwrap();
+					drop(queue);
+					let _ = panic::catch_unwind(AssertUnwindSafe(timed.task));
+					queue = self.queue.lock().unwrap();
+				}
+			}
+		}
+	}
+}
+struct Timed {
+	deadline: Instant,
+	/// Breaks ties between tasks with the same deadline.
+	id: u64,
+	task: Task,
+}
+impl PartialEq for Timed {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+impl Eq for Timed {}
+impl PartialOrd for Timed {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+impl Ord for Timed {
+	// Reversed, so the max-heap pops the soonest deadline first.
+	fn cmp(&self, other: &Self) -> Ordering {
+		(other.deadline, other.id).cmp(&(self.deadline, self.id))
+	}
+}
+
+/// The spawner for the enabled runtime feature.
+#[allow(unreachable_code)]
+pub(crate) fn default_spawner() -> Arc<dyn Spawner> {
+	#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+	return Arc::new(WasmSpawner);
+	// Bound to the ambient runtime, so debounced recomputations can be spawned from the timer thread.
+	#[cfg(feature = "tokio")]
+	return Arc::new(TokioSpawner::default());
+	#[cfg(feature = "async-std")]
+	return Arc::new(AsyncStdSpawner);
+	#[cfg(feature = "smol")]
+	return Arc::new(SmolSpawner);
+	#[cfg(feature = "rayon")]
+	return Arc::new(RayonSpawner);
+	static POOL: OnceLock<Arc<ThreadPoolSpawner>> = OnceLock::new();
+	let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+	POOL.get_or_init(|| Arc::new(ThreadPoolSpawner::new(threads))).clone()
+}
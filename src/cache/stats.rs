@@ -0,0 +1,49 @@
+use core::time::Duration;
+
+/// Statistics of the cache of one read function, returned by
+/// [`crate::CacheableRepr::cache_stats`]. Recomputation times are only measured with the `std`
+/// feature, and not on wasm, where there is no monotonic clock.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct CacheStats {
+	/// Reads that were served from the cache.
+	pub hits: u64,
+	/// Reads that had to call the read function because nothing was cached.
+	pub misses: u64,
+	/// How often the read function was called, both for misses and for eager recomputations.
+	pub recomputations: u64,
+	/// How long the most recent call of the read function took.
+	pub last_recompute: Duration,
+	/// How long all calls of the read function took together.
+	pub total_recompute: Duration,
+}
+impl CacheStats {
+	pub(crate) fn record_recompute(&mut self, took: Duration) {
+		self.recomputations += 1;
+		self.last_recompute = took;
+		self.total_recompute += took;
+	}
+	pub(crate) fn record_miss(&mut self, took: Duration) {
+		self.misses += 1;
+		self.record_recompute(took);
+	}
+}
+
+/// Measures how long a read function takes, if there's a clock to measure it with.
+pub(crate) struct Stopwatch {
+	#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+	start: std::time::Instant,
+}
+impl Stopwatch {
+	pub(crate) fn start() -> Self {
+		Self {
+			#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+			start: std::time::Instant::now(),
+		}
+	}
+	pub(crate) fn elapsed(&self) -> Duration {
+		#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+		return self.start.elapsed();
+		#[cfg(not(all(feature = "std", not(target_arch = "wasm32"))))]
+		Duration::ZERO
+	}
+}
@@ -0,0 +1,124 @@
+//! Representation invariants with interior mutability, for single-threaded code that is structured
+//! around shared references. See [`ReprCell`].
+use crate::Repr;
+use core::cell::{BorrowError, BorrowMutError, Ref, RefCell, RefMut};
+use core::fmt::Debug;
+use core::ops::{Deref, DerefMut};
+
+/// A representation invariant that can be written through a shared reference, like a
+/// [`RefCell`]. Borrows are tracked at runtime: reading while the value is being written (or
+/// writing while it is being read) panics, and the `try_` methods return an error instead. The
+/// invariant is checked when a write borrow ends, like with [`Repr::write`].
+/// ```rust
+/// use repr_rs::{Repr, ReprCell};
+/// #[derive(Debug)]
+/// struct MinMax { min: i32, max: i32 }
+/// struct Widget { range: ReprCell<MinMax> }
+/// impl Widget {
+///   fn widen(&self) {
+///     self.range.write().max += 1;
+///   }
+/// }
+/// let widget = Widget {
+///   range: ReprCell::new(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)),
+/// };
+/// widget.widen();
+/// assert_eq!(6, widget.range.read().max);
+/// ```
+pub struct ReprCell<T: Debug, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	inner: RefCell<Repr<T, I>>,
+}
+impl<T: Debug, I: Fn(&T) -> bool> ReprCell<T, I> {
+	/// Wraps the representation invariant.
+	pub const fn new(repr: Repr<T, I>) -> Self {
+		Self {
+			inner: RefCell::new(repr),
+		}
+	}
+	/// Borrows a read-only view of the value.
+	///
+	/// # Panics
+	/// If the value is being written.
+	#[track_caller]
+	pub fn read(&self) -> ReprCellRef<'_, T, I> {
+		ReprCellRef {
+			borrow: self.inner.borrow(),
+		}
+	}
+	/// Borrows a read-only view of the value, unless it is being written.
+	pub fn try_read(&self) -> Result<ReprCellRef<'_, T, I>, BorrowError> {
+		self.inner.try_borrow().map(|borrow| ReprCellRef { borrow })
+	}
+	/// Borrows a mutable view of the value. The invariant is checked when the view is dropped.
+	///
+	/// # Panics
+	/// If the value is being read or written.
+	#[track_caller]
+	pub fn write(&self) -> ReprCellRefMut<'_, T, I> {
+		ReprCellRefMut::new(self.inner.borrow_mut())
+	}
+	/// Borrows a mutable view of the value, unless it is being read or written.
+	pub fn try_write(&self) -> Result<ReprCellRefMut<'_, T, I>, BorrowMutError> {
+		self.inner.try_borrow_mut().map(ReprCellRefMut::new)
+	}
+	/// Borrows the representation invariant mutably. No runtime tracking is needed, because the
+	/// cell itself is borrowed mutably.
+	pub fn get_mut(&mut self) -> &mut Repr<T, I> {
+		self.inner.get_mut()
+	}
+	/// Consumes the cell, returning the representation invariant.
+	pub fn into_inner(self) -> Repr<T, I> {
+		self.inner.into_inner()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for ReprCell<T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self.inner.try_borrow() {
+			Ok(repr) => write!(f, "ReprCell({:?})", repr.read()),
+			Err(_) => write!(f, "ReprCell(<borrowed>)"),
+		}
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> From<Repr<T, I>> for ReprCell<T, I> {
+	fn from(repr: Repr<T, I>) -> Self {
+		Self::new(repr)
+	}
+}
+
+/// A read-only view of the value of a [`ReprCell`].
+pub struct ReprCellRef<'a, T: Debug, I: Fn(&T) -> bool> {
+	borrow: Ref<'a, Repr<T, I>>,
+}
+impl<T: Debug, I: Fn(&T) -> bool> Deref for ReprCellRef<'_, T, I> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.borrow.read()
+	}
+}
+
+/// A mutable view of the value of a [`ReprCell`]. The invariant is checked when it's dropped.
+pub struct ReprCellRefMut<'a, T: Debug, I: Fn(&T) -> bool> {
+	borrow: RefMut<'a, Repr<T, I>>,
+}
+impl<'a, T: Debug, I: Fn(&T) -> bool> ReprCellRefMut<'a, T, I> {
+	fn new(mut borrow: RefMut<'a, Repr<T, I>>) -> Self {
+		borrow.begin_write();
+		Self { borrow }
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Deref for ReprCellRefMut<'_, T, I> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.borrow.read_unchecked()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> DerefMut for ReprCellRefMut<'_, T, I> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.borrow.inner.get_mut()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Drop for ReprCellRefMut<'_, T, I> {
+	fn drop(&mut self) {
+		self.borrow.check();
+	}
+}
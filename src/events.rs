@@ -0,0 +1,115 @@
+//! Streams of the changes made to a representation invariant. See [`crate::Repr::events`].
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use futures_core::Stream;
+use std::sync::Mutex;
+
+/// A committed write to a representation invariant: the value after the write, which satisfied
+/// the invariant (unless the check was skipped by the [`crate::CheckPolicy`]).
+#[non_exhaustive]
+pub struct ChangeEvent<T> {
+	value: Arc<T>,
+}
+impl<T> ChangeEvent<T> {
+	/// A snapshot of the value after the write. It's shared by every stream that received the
+	/// write.
+	pub fn value(&self) -> &Arc<T> {
+		&self.value
+	}
+}
+impl<T> Clone for ChangeEvent<T> {
+	fn clone(&self) -> Self {
+		Self { value: self.value.clone() }
+	}
+}
+impl<T: core::fmt::Debug> core::fmt::Debug for ChangeEvent<T> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_struct("ChangeEvent").field("value", &self.value).finish()
+	}
+}
+
+/// A [`Stream`] of the writes committed to a representation invariant after it was created with
+/// [`crate::Repr::events`]. Events are buffered until they're polled, and the stream ends once
+/// the representation invariant is dropped.
+pub struct ChangeStream<T> {
+	queue: Arc<Mutex<Queue<T>>>,
+}
+struct Queue<T> {
+	events: VecDeque<ChangeEvent<T>>,
+	waker: Option<Waker>,
+	closed: bool,
+}
+impl<T> Stream for ChangeStream<T> {
+	type Item = ChangeEvent<T>;
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		let mut queue = self.queue.lock().unwrap();
+		if let Some(event) = queue.events.pop_front() {
+			return Poll::Ready(Some(event));
+		}
+		if queue.closed {
+			return Poll::Ready(None);
+		}
+		queue.waker = Some(cx.waker().clone());
+		Poll::Pending
+	}
+}
+
+/// The streams subscribed to a representation invariant.
+pub(crate) struct Subscribers<T> {
+	/// Clones the value into the snapshot shared by the events of a write. It's set by the first
+	/// subscription, which is the only place that knows `T: Clone`.
+	snapshot: Option<fn(&T) -> T>,
+	queues: Vec<Arc<Mutex<Queue<T>>>>,
+}
+impl<T> Subscribers<T> {
+	pub(crate) const fn new() -> Self {
+		Self {
+			snapshot: None,
+			queues: Vec::new(),
+		}
+	}
+	pub(crate) fn subscribe(&mut self) -> ChangeStream<T> where T: Clone {
+		self.snapshot = Some(T::clone);
+		let queue = Arc::new(Mutex::new(Queue {
+			events: VecDeque::new(),
+			waker: None,
+			closed: false,
+		}));
+		self.queues.push(queue.clone());
+		ChangeStream { queue }
+	}
+	/// Sends the value to every stream that hasn't been dropped. The value is only cloned if
+	/// there's a stream to send it to.
+	pub(crate) fn publish(&mut self, value: &T) {
+		self.queues.retain(|queue| Arc::strong_count(queue) > 1);
+		let Some(snapshot) = self.snapshot.filter(|_| !self.queues.is_empty()) else {
+			return;
+		};
+		let event = ChangeEvent { value: Arc::new(snapshot(value)) };
+		for queue in &self.queues {
+			let mut queue = queue.lock().unwrap();
+			queue.events.push_back(event.clone());
+			if let Some(waker) = queue.waker.take() {
+				waker.wake();
+			}
+		}
+	}
+}
+/// Ends the streams, once they've yielded the events they've already received.
+impl<T> Drop for Subscribers<T> {
+	fn drop(&mut self) {
+		for queue in &self.queues {
+			let mut queue = match queue.lock() {
+				Ok(queue) => queue,
+				Err(poisoned) => poisoned.into_inner(),
+			};
+			queue.closed = true;
+			if let Some(waker) = queue.waker.take() {
+				waker.wake();
+			}
+		}
+	}
+}
@@ -0,0 +1,70 @@
+use crate::{CacheableRepr, Repr};
+use garde::Validate;
+use std::fmt::Debug;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+impl<T: Debug + Validate + 'static> Repr<T> {
+	/// Creates a new representation invariant whose invariant is the value's [`Validate`]
+	/// implementation. When the invariant is violated, the [`garde::Report`] is used as the violation
+	/// message.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// use garde::Validate;
+	/// #[derive(Debug, Validate)]
+	/// struct User {
+	///   #[garde(length(min = 3))]
+	///   username: String,
+	/// }
+	/// let mut repr = Repr::from_garde(User { username: "alice".into() });
+	/// let (_, violation) = repr.set(User { username: "al".into() }).unwrap_err();
+	/// assert_eq!("username: length is lower than 3", violation.message().trim_end());
+	/// ```
+	pub fn from_garde(value: T) -> Self where T::Context: Default {
+		Repr::new(value, (|value: &T| value.validate().is_ok()) as fn(&T) -> bool)
+			.with_explanation(|value: &T| explain_report(value.validate()))
+	}
+	/// Creates a new representation invariant whose invariant is the value's [`Validate`]
+	/// implementation, evaluated with the given garde context.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// use garde::Validate;
+	/// struct Limits { max_len: usize }
+	/// #[derive(Debug, Validate)]
+	/// #[garde(context(Limits))]
+	/// struct Post {
+	///   #[garde(custom(|body: &str, limits: &Limits| {
+	///     if body.len() <= limits.max_len { Ok(()) } else { Err(garde::Error::new("too long")) }
+	///   }))]
+	///   body: String,
+	/// }
+	/// let mut repr = Repr::from_garde_with(Post { body: "hi".into() }, Limits { max_len: 5 });
+	/// repr.write().body.push_str("!!!");
+	/// assert!(repr.replace(Post { body: "hello world".into() }).is_err());
+	/// ```
+	pub fn from_garde_with(value: T, context: T::Context) -> Repr<T, impl Fn(&T) -> bool> where T::Context: Send + Sync + RefUnwindSafe + 'static {
+		let context = Arc::new(context);
+		let explanation_context = Arc::clone(&context);
+		Repr::new(value, move |value: &T| value.validate_with(&context).is_ok())
+			.with_explanation(move |value: &T| explain_report(value.validate_with(&explanation_context)))
+	}
+}
+impl<T: Debug + Validate + 'static> CacheableRepr<T> {
+	/// Creates a new representation invariant whose invariant is the value's [`Validate`]
+	/// implementation. See [`Repr::from_garde`].
+	pub fn from_garde(value: T) -> Self where T::Context: Default {
+		Self::from(Repr::from_garde(value))
+	}
+	/// Creates a new representation invariant whose invariant is the value's [`Validate`]
+	/// implementation, evaluated with the given garde context. See [`Repr::from_garde_with`].
+	pub fn from_garde_with(value: T, context: T::Context) -> CacheableRepr<T, impl Fn(&T) -> bool> where T::Context: Send + Sync + RefUnwindSafe + 'static {
+		CacheableRepr::from(Repr::from_garde_with(value, context))
+	}
+}
+
+fn explain_report(result: Result<(), garde::Report>) -> String {
+	match result {
+		Ok(()) => String::from("Invariant violated"),
+		Err(report) => report.to_string(),
+	}
+}
@@ -0,0 +1,100 @@
+//! Checked mutations of values that aren't owned by a representation invariant. See [`guard`].
+use crate::repr::panicking;
+use crate::Violation;
+use core::fmt::Debug;
+use core::ops::{Deref, DerefMut};
+
+/// Borrows a mutable view of a value that is owned elsewhere (e.g. an FFI buffer or a field of a
+/// foreign struct), and checks the invariant when the view is dropped, like [`crate::Repr::write`].
+/// Use [`Guard::finish`] to get the [`Violation`] instead of panicking, e.g. before returning
+/// across an FFI boundary.
+/// ```rust
+/// use repr_rs::guard;
+/// #[derive(Debug)]
+/// struct MinMax { min: i32, max: i32 }
+/// let mut range = MinMax { min: 1, max: 5 };
+/// {
+///   let mut range = guard(&mut range, |mm| mm.min < mm.max);
+///   range.max = 10;
+/// }
+/// let mut checked = guard(&mut range, |mm| mm.min < mm.max);
+/// checked.min = 20;
+/// assert!(checked.finish().is_err());
+/// assert_eq!(20, range.min);
+/// ```
+/// The value can be unsized, so slices, string slices, and trait objects can be checked without a
+/// wrapper type.
+/// ```rust
+/// use repr_rs::guard;
+/// use repr_rs::predicate::strictly_increasing;
+/// let mut buffer = [1, 2, 3, 4];
+/// let mut window = guard(&mut buffer[1..], strictly_increasing);
+/// window[0] = 5;
+/// assert!(window.finish().is_err());
+/// let mut name = String::from("repr");
+/// let mut name = guard(name.as_mut_str(), |s: &str| s.is_ascii());
+/// name.make_ascii_uppercase();
+/// assert!(name.finish().is_ok());
+/// ```
+pub fn guard<T: Debug + ?Sized, I: Fn(&T) -> bool>(value: &mut T, invariant: I) -> Guard<'_, T, I> {
+	guard_with_msg(value, invariant, "Invariant violated")
+}
+/// Like [`guard`], with the given violation message.
+pub fn guard_with_msg<'a, T: Debug + ?Sized, I: Fn(&T) -> bool>(value: &'a mut T, invariant: I, violation_message: &'static str) -> Guard<'a, T, I> {
+	Guard {
+		value,
+		invariant,
+		violation_message,
+		finished: false,
+	}
+}
+
+/// A mutable view of a value that is owned elsewhere, created by [`guard`]. The invariant is
+/// checked when it's dropped.
+pub struct Guard<'a, T: Debug + ?Sized, I: Fn(&T) -> bool> {
+	value: &'a mut T,
+	invariant: I,
+	violation_message: &'static str,
+	finished: bool,
+}
+impl<T: Debug + ?Sized, I: Fn(&T) -> bool> Guard<'_, T, I> {
+	/// Ends the mutation and checks the invariant without panicking. The value isn't rolled back if
+	/// the invariant is violated.
+	pub fn finish(mut self) -> Result<(), Violation> {
+		self.finished = true;
+		self.validate()
+	}
+	fn validate(&self) -> Result<(), Violation> {
+		if (self.invariant)(self.value) {
+			Ok(())
+		} else {
+			Err(Violation::new(self.violation_message, self.value))
+		}
+	}
+}
+impl<T: Debug + ?Sized, I: Fn(&T) -> bool> Deref for Guard<'_, T, I> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.value
+	}
+}
+impl<T: Debug + ?Sized, I: Fn(&T) -> bool> DerefMut for Guard<'_, T, I> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.value
+	}
+}
+impl<T: Debug + ?Sized, I: Fn(&T) -> bool> Debug for Guard<'_, T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "Guard({:?})", self.value)
+	}
+}
+impl<T: Debug + ?Sized, I: Fn(&T) -> bool> Drop for Guard<'_, T, I> {
+	fn drop(&mut self) {
+		if self.finished || panicking() {
+			return;
+		}
+		if let Err(violation) = self.validate() {
+			panic!("{}", violation);
+		}
+	}
+}
@@ -0,0 +1,63 @@
+//! A history of the values a representation invariant had, for debugging. When an invariant is
+//! finally violated, the history shows the trajectory that led there. See
+//! [`crate::Repr::with_history`]. Timestamps aren't available on wasm32, so neither is this module.
+use alloc::collections::VecDeque;
+use std::time::SystemTime;
+
+/// A value that a representation invariant had, and when it got it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<T> {
+	value: T,
+	at: SystemTime,
+}
+impl<T> Snapshot<T> {
+	/// The value after the write.
+	pub fn value(&self) -> &T {
+		&self.value
+	}
+	/// When the write was committed.
+	pub fn at(&self) -> SystemTime {
+		self.at
+	}
+}
+
+/// A ring buffer of the last values of a representation invariant.
+pub(crate) struct History<T> {
+	/// Clones values into the history. It's set when the history is enabled, which is the only
+	/// place that knows `T: Clone`.
+	snapshot: Option<fn(&T) -> T>,
+	capacity: usize,
+	pub(crate) snapshots: VecDeque<Snapshot<T>>,
+}
+impl<T> History<T> {
+	pub(crate) const fn new() -> Self {
+		Self {
+			snapshot: None,
+			capacity: 0,
+			snapshots: VecDeque::new(),
+		}
+	}
+	/// Keeps the last `capacity` values, starting with the current one if the history is empty.
+	pub(crate) fn enable(&mut self, capacity: usize, current: &T) where T: Clone {
+		self.snapshot = Some(T::clone);
+		self.capacity = capacity;
+		while self.snapshots.len() > capacity {
+			self.snapshots.pop_front();
+		}
+		if self.snapshots.is_empty() {
+			self.record(current);
+		}
+	}
+	pub(crate) fn record(&mut self, value: &T) {
+		let Some(snapshot) = self.snapshot.filter(|_| self.capacity > 0) else {
+			return;
+		};
+		if self.snapshots.len() == self.capacity {
+			self.snapshots.pop_front();
+		}
+		self.snapshots.push_back(Snapshot {
+			value: snapshot(value),
+			at: SystemTime::now(),
+		});
+	}
+}
@@ -0,0 +1,117 @@
+use core::fmt::Debug;
+
+/// A representation invariant defined by a type rather than by a closure. Representation
+/// invariants over a type implementing this trait can be created without passing an invariant
+/// function, e.g. through [`Default`].
+/// ```rust
+/// use repr_rs::{Invariant, Repr};
+/// #[derive(Debug, Default)]
+/// struct MinMax { min: i32, max: i32 }
+/// impl Invariant for MinMax {
+///   const VIOLATION_MESSAGE: &'static str = "min must be less than or equal to max";
+///   fn invariant(&self) -> bool {
+///     self.min <= self.max
+///   }
+/// }
+/// #[derive(Default)]
+/// struct Config { range: Repr<MinMax> }
+/// let mut config = Config::default();
+/// config.range.write().max = 5;
+/// ```
+pub trait Invariant: Debug {
+	/// The message used when the invariant is violated.
+	const VIOLATION_MESSAGE: &'static str = "Invariant violated";
+	/// Whether the representation invariant holds for this value.
+	fn invariant(&self) -> bool;
+}
+
+/// Declares a struct together with its representation invariant, implementing [`Invariant`] for it.
+/// The invariant is also mirrored as contracts for formal verification tools, so the runtime check
+/// and the verified contract can't drift apart:
+/// - Under Prusti (`cfg(prusti)`), the struct gets a `#[prusti_contracts::invariant(...)]` type
+///   invariant and [`Invariant::invariant`] is marked `#[pure]`, so it can be used in
+///   `#[requires(...)]`/`#[ensures(...)]` clauses.
+/// - Under Kani (`cfg(kani)`), `kani::Invariant` is implemented with the same invariant.
+///
+/// The invariant is written as a block over `self`, and the violation message is optional. Crates
+/// using this macro should declare the `prusti` and `kani` cfgs in their `check-cfg` lint config.
+/// ```rust
+/// use repr_rs::{contract, Repr};
+/// contract! {
+///   #[derive(Debug, Default)]
+///   pub struct MinMax { pub min: i32, pub max: i32 }
+///   invariant(self) { self.min <= self.max }
+///   message "min must be less than or equal to max"
+/// }
+/// let mut repr = Repr::<MinMax>::default();
+/// repr.write().max = 5;
+/// assert!(repr.set(MinMax { min: 6, max: 5 }).is_err());
+/// ```
+#[macro_export]
+macro_rules! contract {
+	(
+		$(#[$meta:meta])*
+		$vis:vis struct $name:ident { $($fields:tt)* }
+		invariant($self:tt) { $($invariant:tt)* }
+		$(message $msg:literal)?
+	) => {
+		$(#[$meta])*
+		#[cfg_attr(prusti, prusti_contracts::invariant($($invariant)*))]
+		$vis struct $name { $($fields)* }
+		impl $crate::Invariant for $name {
+			$(const VIOLATION_MESSAGE: &'static str = $msg;)?
+			#[cfg_attr(prusti, prusti_contracts::pure)]
+			fn invariant(&$self) -> bool {
+				$($invariant)*
+			}
+		}
+		#[cfg(kani)]
+		impl kani::Invariant for $name {
+			fn is_safe(&self) -> bool {
+				<Self as $crate::Invariant>::invariant(self)
+			}
+		}
+	};
+}
+
+/// Declares a struct with nested representation invariants (e.g. [`crate::Repr`] or
+/// [`crate::CacheableRepr`] fields) and a cross-field invariant, like [`contract!`]. The generated
+/// [`Invariant`] implementation also checks that every nested field listed in `nested(...)` is
+/// valid, so a `Repr` over the struct checks the children and the cross-field invariant together
+/// after every write. This catches children that were replaced with one that was never checked
+/// (as [`crate::Repr::new`] doesn't check the initial value).
+/// ```rust
+/// use repr_rs::{composite, Repr};
+/// composite! {
+///   #[derive(Debug)]
+///   pub struct Schedule {
+///     pub opens: Repr<u32>,
+///     pub closes: Repr<u32>,
+///   }
+///   nested(opens, closes)
+///   invariant(self) { *self.opens < *self.closes }
+///   message "the schedule must open before it closes"
+/// }
+/// let hour = |hour: &u32| *hour < 24;
+/// let mut schedule = Repr::try_new(Schedule { opens: Repr::new(9, hour), closes: Repr::new(17, hour) }).unwrap();
+/// *schedule.write().closes.write() = 18;
+/// assert!(Repr::try_new(Schedule { opens: Repr::new(9, hour), closes: Repr::new(8, hour) }).is_err());
+/// assert!(Repr::try_new(Schedule { opens: Repr::new(24, hour), closes: Repr::new(25, hour) }).is_err());
+/// ```
+#[macro_export]
+macro_rules! composite {
+	(
+		$(#[$meta:meta])*
+		$vis:vis struct $name:ident { $($fields:tt)* }
+		nested($($nested:ident),+ $(,)?)
+		invariant($self:tt) { $($invariant:tt)* }
+		$(message $msg:literal)?
+	) => {
+		$crate::contract! {
+			$(#[$meta])*
+			$vis struct $name { $($fields)* }
+			invariant($self) { $($self.$nested.is_valid() &&)+ { $($invariant)* } }
+			$(message $msg)?
+		}
+	};
+}
@@ -0,0 +1,138 @@
+//! Event sourcing for representation invariants: writes are expressed as named commands, which
+//! are recorded in a [`Journal`] alongside whether they passed the invariant, so the state can be
+//! audited and reproduced by replaying the journal onto the initial state.
+use crate::{Repr, Violation};
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+/// A named mutation of a value, applied with [`JournaledRepr::apply`].
+pub trait Command<T> {
+	/// The name the command is recorded under, e.g. for audit logs.
+	fn name(&self) -> &str;
+	/// Mutates the value. Commands should be deterministic, so replaying a journal reproduces the
+	/// same state.
+	fn apply(&self, value: &mut T);
+}
+
+/// A command that was applied to a [`JournaledRepr`], and whether it passed the invariant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry<C> {
+	command: C,
+	outcome: Result<(), Violation>,
+}
+impl<C> Entry<C> {
+	/// The command that was applied.
+	pub fn command(&self) -> &C {
+		&self.command
+	}
+	/// The [`Violation`] if the command was rejected because it would have violated the invariant,
+	/// which left the value untouched.
+	pub fn outcome(&self) -> Result<(), &Violation> {
+		self.outcome.as_ref().map(|_| ())
+	}
+}
+
+/// The commands applied to a [`JournaledRepr`], in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Journal<C> {
+	entries: Vec<Entry<C>>,
+}
+impl<C> Journal<C> {
+	/// Creates an empty journal.
+	pub const fn new() -> Self {
+		Self { entries: Vec::new() }
+	}
+	/// The recorded commands, oldest first.
+	pub fn entries(&self) -> &[Entry<C>] {
+		&self.entries
+	}
+	/// Applies every command of this journal to the initial state, in order, recording them in a
+	/// new journal. Commands that were rejected are applied (and rejected) again, so replaying a
+	/// journal of deterministic commands onto the state it started from reproduces both the state
+	/// and the journal.
+	pub fn replay<T: Debug + Clone, I: Fn(&T) -> bool>(&self, initial: Repr<T, I>) -> JournaledRepr<T, C, I> where C: Command<T> + Clone {
+		let mut repr = JournaledRepr::new(initial);
+		for entry in &self.entries {
+			let _ = repr.apply(entry.command.clone());
+		}
+		repr
+	}
+}
+impl<C> Default for Journal<C> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// A representation invariant that is only written with [`Command`]s, which are recorded in a
+/// [`Journal`].
+/// ```rust
+/// use repr_rs::Repr;
+/// use repr_rs::journal::{Command, JournaledRepr};
+/// #[derive(Debug, Clone, PartialEq)]
+/// enum Account { Deposit(i64), Withdraw(i64) }
+/// impl Command<i64> for Account {
+///   fn name(&self) -> &str {
+///     match self {
+///       Account::Deposit(_) => "deposit",
+///       Account::Withdraw(_) => "withdraw",
+///     }
+///   }
+///   fn apply(&self, balance: &mut i64) {
+///     match self {
+///       Account::Deposit(amount) => *balance += amount,
+///       Account::Withdraw(amount) => *balance -= amount,
+///     }
+///   }
+/// }
+/// let new_account = || Repr::with_msg(0, |balance: &i64| *balance >= 0, "overdrawn");
+/// let mut account = JournaledRepr::new(new_account());
+/// account.apply(Account::Deposit(10)).unwrap();
+/// assert!(account.apply(Account::Withdraw(20)).is_err());
+/// account.apply(Account::Withdraw(5)).unwrap();
+/// assert_eq!(5, *account.read());
+/// assert_eq!("overdrawn", account.journal().entries()[1].outcome().unwrap_err().message());
+///
+/// let replayed = account.journal().replay(new_account());
+/// assert_eq!(5, *replayed.read());
+/// assert_eq!(account.journal(), replayed.journal());
+/// ```
+pub struct JournaledRepr<T: Debug, C, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	repr: Repr<T, I>,
+	journal: Journal<C>,
+}
+impl<T: Debug + Clone, C: Command<T>, I: Fn(&T) -> bool> JournaledRepr<T, C, I> {
+	/// Starts journaling the writes to the representation invariant, from its current state.
+	pub const fn new(repr: Repr<T, I>) -> Self {
+		Self {
+			repr,
+			journal: Journal::new(),
+		}
+	}
+	/// Borrows a read-only view of the value.
+	#[inline]
+	pub fn read(&self) -> &T {
+		self.repr.read()
+	}
+	/// Applies the command to a copy of the value and, if the result satisfies the invariant,
+	/// commits it (see [`Repr::set`]). Otherwise the value is left untouched and the
+	/// [`Violation`] is returned. Either way the command is recorded in the journal.
+	pub fn apply(&mut self, command: C) -> Result<(), Violation> {
+		let mut value = self.repr.read().clone();
+		command.apply(&mut value);
+		let outcome = self.repr.set(value).map_err(|(_, violation)| violation);
+		self.journal.entries.push(Entry {
+			command,
+			outcome: outcome.clone(),
+		});
+		outcome
+	}
+	/// The commands applied so far.
+	pub fn journal(&self) -> &Journal<C> {
+		&self.journal
+	}
+	/// Stops journaling, returning the representation invariant and its journal.
+	pub fn into_parts(self) -> (Repr<T, I>, Journal<C>) {
+		(self.repr, self.journal)
+	}
+}
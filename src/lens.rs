@@ -0,0 +1,107 @@
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+/// Focuses on a part of a value, both for reading and for mutation. Lenses can be composed with
+/// [`Lens::then`] to reach deeply nested fields, and are used with [`crate::Repr::at`] to mutate
+/// part of a value while still checking the invariant of the whole value.
+///
+/// The easiest way to create a lens for a (nested) field is the [`crate::lens!`] macro.
+pub trait Lens<T> {
+	type Target;
+	fn get<'a>(&self, value: &'a T) -> &'a Self::Target;
+	fn get_mut<'a>(&self, value: &'a mut T) -> &'a mut Self::Target;
+	/// Composes this lens with another lens that focuses on part of this lens's target.
+	fn then<L: Lens<Self::Target>>(self, next: L) -> Then<Self, L> where Self: Sized {
+		Then { first: self, next }
+	}
+}
+
+/// A [`Lens`] made from a pair of accessor functions.
+pub struct FieldLens<T, U, G, M> {
+	get: G,
+	get_mut: M,
+	_marker: PhantomData<fn(&T) -> &U>,
+}
+impl<T, U, G: Fn(&T) -> &U, M: Fn(&mut T) -> &mut U> FieldLens<T, U, G, M> {
+	/// Creates a lens from a pair of accessor functions.
+	/// ```rust
+	/// use repr_rs::lens::{FieldLens, Lens};
+	/// struct Server { port: u16 }
+	/// let port = FieldLens::new(|s: &Server| &s.port, |s: &mut Server| &mut s.port);
+	/// let mut server = Server { port: 80 };
+	/// *port.get_mut(&mut server) = 443;
+	/// assert_eq!(443, *port.get(&server));
+	/// ```
+	pub fn new(get: G, get_mut: M) -> Self {
+		Self { get, get_mut, _marker: PhantomData }
+	}
+}
+impl<T, U, G: Fn(&T) -> &U, M: Fn(&mut T) -> &mut U> Lens<T> for FieldLens<T, U, G, M> {
+	type Target = U;
+	fn get<'a>(&self, value: &'a T) -> &'a U {
+		(self.get)(value)
+	}
+	fn get_mut<'a>(&self, value: &'a mut T) -> &'a mut U {
+		(self.get_mut)(value)
+	}
+}
+
+/// Two composed lenses. See [`Lens::then`]. The intermediate target must be `'static` because the
+/// compiler can't prove that it outlives borrows of the outer value otherwise.
+pub struct Then<A, B> {
+	first: A,
+	next: B,
+}
+impl<T, A: Lens<T>, B: Lens<A::Target>> Lens<T> for Then<A, B> where A::Target: 'static {
+	type Target = B::Target;
+	fn get<'a>(&self, value: &'a T) -> &'a Self::Target {
+		self.next.get(self.first.get(value))
+	}
+	fn get_mut<'a>(&self, value: &'a mut T) -> &'a mut Self::Target {
+		self.next.get_mut(self.first.get_mut(value))
+	}
+}
+
+/// Creates a [`FieldLens`] for a (possibly nested) field of a type.
+/// ```rust
+/// use repr_rs::lens;
+/// use repr_rs::lens::Lens;
+/// struct Tls { cert: String }
+/// struct Server { tls: Tls }
+/// let cert = lens!(Server => tls.cert);
+/// let server = Server { tls: Tls { cert: "cert.pem".into() } };
+/// assert_eq!("cert.pem", cert.get(&server));
+/// ```
+#[macro_export]
+macro_rules! lens {
+	($t:ty => $($field:tt).+) => {
+		$crate::lens::FieldLens::new(|value: &$t| &value.$($field).+, |value: &mut $t| &mut value.$($field).+)
+	};
+}
+
+/// A mutable view of part of a value guarded by a representation invariant, created by
+/// [`crate::Repr::at`]. The invariant of the whole value is checked when this view is dropped.
+pub struct ReprAt<G, L> {
+	guard: G,
+	lens: L,
+}
+impl<G: DerefMut, L: Lens<G::Target>> ReprAt<G, L> where G::Target: Sized {
+	pub(crate) fn new(guard: G, lens: L) -> Self {
+		Self { guard, lens }
+	}
+	/// Focuses this view further with another lens.
+	pub fn at<N: Lens<L::Target>>(self, next: N) -> ReprAt<G, Then<L, N>> where L::Target: 'static {
+		ReprAt::new(self.guard, self.lens.then(next))
+	}
+}
+impl<G: DerefMut, L: Lens<G::Target>> Deref for ReprAt<G, L> where G::Target: Sized {
+	type Target = L::Target;
+	fn deref(&self) -> &Self::Target {
+		self.lens.get(&self.guard)
+	}
+}
+impl<G: DerefMut, L: Lens<G::Target>> DerefMut for ReprAt<G, L> where G::Target: Sized {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.lens.get_mut(&mut self.guard)
+	}
+}
@@ -1,26 +1,143 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 // TODO: when this is stable, we can make `read` and `write` const-fns.
 // #![feature(const_mut_refs)]
 
+extern crate alloc;
+
 pub mod repr;
+#[cfg(feature = "actor")]
+pub mod actor;
+pub mod atomic;
+pub mod audit;
 pub mod cache;
+pub mod cell;
+pub mod guard;
+pub mod invariant;
+#[cfg(all(feature = "history", not(target_arch = "wasm32")))]
+pub mod history;
+pub mod journal;
+pub mod lens;
+pub mod map;
+#[cfg(feature = "std")]
+pub mod lock;
+pub mod metrics;
+pub mod pair;
+pub mod policy;
+pub mod predicate;
+pub mod prelude;
+#[cfg(feature = "rcu")]
+pub mod rcu;
+pub mod reader;
+#[cfg(feature = "stream")]
+pub mod events;
+#[cfg(feature = "shared")]
+pub mod shared;
+#[cfg(feature = "std")]
+pub mod sync;
+pub mod testing;
+pub mod undo;
+pub mod vec;
+pub mod violation;
+#[cfg(feature = "serde")]
+pub mod serde_support;
+#[cfg(feature = "schemars")]
+mod schemars_support;
+#[cfg(feature = "validator")]
+mod validator_support;
+#[cfg(feature = "garde")]
+mod garde_support;
+#[cfg(feature = "proptest")]
+pub mod proptest_support;
+#[cfg(feature = "quickcheck")]
+pub mod quickcheck_support;
 
-#[cfg(feature = "eager")]
+pub use atomic::AtomicRepr;
+#[cfg(feature = "eager-core")]
 pub use cache::eager::EagerCacheLookup;
 pub use cache::CacheableRepr;
+pub use cache::fixed::FixedCacheableRepr;
+pub use cell::ReprCell;
+pub use guard::{guard, Guard};
+pub use invariant::Invariant;
+#[cfg(feature = "std")]
+pub use lock::{ReprMutex, ReprRwLock};
+#[cfg(feature = "std")]
+pub use map::ReprHashMap;
+pub use map::ReprBTreeMap;
+pub use pair::ReprPair;
+pub use policy::CheckPolicy;
+#[cfg(feature = "rcu")]
+pub use rcu::ArcRepr;
 pub use repr::Repr;
+#[cfg(feature = "shared")]
+pub use shared::SharedRepr;
+pub use vec::ReprVec;
+pub use violation::Violation;
+
+// Without std, only the core write, check, and violation paths are tested.
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_tests {
+	use crate::{CacheableRepr, Repr};
+	use alloc::vec;
+	use alloc::vec::Vec;
+
+	#[derive(Debug, Clone, PartialEq)]
+	struct MinMax {
+		min: i32,
+		max: i32,
+	}
+
+	#[test]
+	fn should_write_values_that_hold() {
+		let mut repr = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		repr.write().min = 4;
+		assert_eq!(4, repr.read().min);
+		assert_eq!(1, repr.version());
+		assert!(repr.is_valid());
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	#[should_panic(expected = "min must be less than max")]
+	fn should_panic_with_the_violation_message() {
+		let mut repr = Repr::with_msg(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max, "min must be less than max");
+		repr.write().min = 6;
+	}
 
-#[cfg(test)]
+	#[test]
+	fn should_hand_back_values_that_violate() {
+		let mut repr = Repr::with_msg(vec![1, 2], |v: &Vec<i32>| !v.is_empty(), "must not be empty");
+		let (rejected, violation) = repr.set(Vec::new()).unwrap_err();
+		assert!(rejected.is_empty());
+		assert_eq!("must not be empty", violation.message());
+		assert_eq!("[]", violation.state());
+		assert_eq!(vec![1, 2], *repr.read());
+	}
+
+	#[test]
+	fn should_invalidate_lazy_caches_after_writes() {
+		fn width(mm: &MinMax) -> i32 {
+			mm.max - mm.min
+		}
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!(4, repr.lazy(width));
+		repr.write().min = 2;
+		assert_eq!(3, repr.lazy(width));
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
 	use std::borrow::Cow;
 	use crate::repr::Repr;
-	use crate::CacheableRepr;
+	use crate::{lens, CacheableRepr, CheckPolicy, Invariant, ReprPair};
 	use std::cell::RefCell;
-	use std::collections::HashMap;
+	use std::collections::{BTreeSet, HashMap};
 	use std::rc::Rc;
 	use std::sync::{Arc};
 	use tokio::sync::RwLock;
 
-	#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
 	struct MinMax {
 		min: i32,
 		max: i32,
@@ -36,6 +153,13 @@ mod tests {
 		assert_eq!(repr.read().max, 5);
 	}
 
+	#[cfg(all(feature = "release-unchecked", not(debug_assertions)))]
+	#[test]
+	fn should_keep_unconfigured_reprs_small_without_checks() {
+		// The value, the invariant, the message, the version, and the pointer to everything else.
+		assert_eq!(size_of::<(i32, fn(&i32) -> bool, &'static str, u64, usize)>(), size_of::<Repr<i32>>());
+	}
+
 	#[test]
 	fn reading_as_ref() {
 		let repr = Repr::new(
@@ -59,6 +183,7 @@ mod tests {
 		assert_eq!(1, a);
 	}
 
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
 	#[test]
 	#[should_panic]
 	fn should_propagate_panic() {
@@ -81,6 +206,7 @@ mod tests {
 		repr.write().max = 10;
 	}
 
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
 	#[test]
 	#[should_panic]
 	fn banned_mutation() {
@@ -91,6 +217,7 @@ mod tests {
 		repr.write().min = 6;
 	}
 
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
 	#[test]
 	#[should_panic]
 	fn should_try_to_detect_non_deterministic_invariants() {
@@ -106,6 +233,7 @@ mod tests {
 		repr.write();
 	}
 
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
 	#[test]
 	#[should_panic]
 	fn banned_mutation_with_msg() {
@@ -195,7 +323,7 @@ mod tests {
 
 	#[test]
 	fn should_read_from_cache() {
-		let mut repr = CacheableRepr::new(
+		let repr = CacheableRepr::new(
 			MinMax { min: 1, max: 5 },
 			|mm| mm.min < mm.max,
 		);
@@ -233,182 +361,1806 @@ mod tests {
 	}
 
 	#[test]
-	fn should_hash_by_inner() {
-		let mut repr1 = Repr::new(
-			MinMax { min: 1, max: 5 },
-			|mm| mm.min < mm.max,
-		);
-		let mut repr2 = Repr::new(
-			MinMax { min: 1, max: 5 },
-			|mm| mm.min < mm.max,
-		);
-		let mut map = HashMap::new();
-		let repr1_w: &mut MinMax = &mut repr1.write();
-		let repr2_w: &mut MinMax = &mut repr2.write();
-		map.insert(&repr1_w, 1);
-		assert!(map.contains_key(&repr1_w));
-		assert!(map.contains_key(&repr2_w));
-		repr2_w.max = 10;
-		assert!(map.contains_key(&repr1_w));
-		assert!(!map.contains_key(&repr2_w));
+	fn should_recompute_expired_lazy_caches() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		use std::time::Duration;
+		static READS: AtomicU32 = AtomicU32::new(0);
+		fn get_min(mm: &MinMax) -> i32 {
+			READS.fetch_add(1, Ordering::SeqCst);
+			mm.min
+		}
+		let repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!(1, repr.lazy_with_ttl(get_min, Duration::from_millis(50)));
+		assert_eq!(1, repr.lazy_with_ttl(get_min, Duration::from_millis(50)));
+		assert_eq!(1, READS.load(Ordering::SeqCst));
+		std::thread::sleep(Duration::from_millis(60));
+		assert_eq!(1, repr.lazy_with_ttl(get_min, Duration::from_millis(50)));
+		assert_eq!(2, READS.load(Ordering::SeqCst));
+		// Plain lazy reads of the same read function have a cache of their own, which doesn't expire.
+		assert_eq!(1, repr.lazy(get_min));
+		std::thread::sleep(Duration::from_millis(60));
+		assert_eq!(1, repr.lazy(get_min));
+		assert_eq!(3, READS.load(Ordering::SeqCst));
 	}
 
 	#[test]
-	fn should_be_moveable_across_threads() {
-		let mut repr = Repr::new(
-			MinMax { min: 1, max: 5 },
-			|mm| mm.min < mm.max,
-		);
-		fn get_min(mm: &MinMax) -> i32 { mm.min }
-		fn get_max(mm: &MinMax) -> i32 { mm.max }
+	fn should_evict_the_least_recently_used_cache() {
+		use crate::cache::CacheKey;
+		use std::sync::atomic::{AtomicU32, Ordering};
+		use std::sync::Mutex;
+		static READS: AtomicU32 = AtomicU32::new(0);
+		static EVICTED: Mutex<Vec<CacheKey>> = Mutex::new(Vec::new());
+		fn get_min(mm: &MinMax) -> i32 {
+			READS.fetch_add(1, Ordering::SeqCst);
+			mm.min
+		}
+		fn get_max(mm: &MinMax) -> i32 {
+			mm.max
+		}
+		fn get_sum(mm: &MinMax) -> i32 {
+			mm.min + mm.max
+		}
+		let repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+			.with_max_caches(2)
+			.with_eviction_callback(|key| EVICTED.lock().unwrap().push(key.clone()));
+		assert_eq!(1, repr.lazy(get_min));
+		assert_eq!(5, repr.lazy(get_max));
+		assert_eq!(1, repr.lazy(get_min));
+		assert_eq!(6, repr.lazy(get_sum));
+		assert_eq!(vec![CacheKey::of(get_max)], *EVICTED.lock().unwrap());
+		assert_eq!(1, repr.lazy(get_min));
+		assert_eq!(1, READS.load(Ordering::SeqCst));
+	}
 
-		{
-			let mm = repr.as_ref();
-			assert_eq!(1, get_min(mm));
-			assert_eq!(5, get_max(mm));
+	#[test]
+	fn should_recompute_invalidated_and_cleared_caches() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static READS: AtomicU32 = AtomicU32::new(0);
+		fn get_min(mm: &MinMax) -> i32 {
+			READS.fetch_add(1, Ordering::SeqCst);
+			mm.min
 		}
-		{
-			let mut mm = repr.write();
-			mm.max = 100;
-			mm.min = 50;
+		fn get_max(mm: &MinMax) -> i32 {
+			mm.max
 		}
-		std::thread::spawn(move || {
-			let mm = repr.as_ref();
-			assert_eq!(50, get_min(mm));
-			assert_eq!(100, get_max(mm));
-		}).join().unwrap();
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert!(!repr.invalidate(get_min));
+		assert_eq!(1, repr.lazy(get_min));
+		assert_eq!(5, repr.lazy(get_max));
+		assert!(repr.invalidate(get_min));
+		assert_eq!(1, repr.lazy(get_min));
+		assert_eq!(2, READS.load(Ordering::SeqCst));
+		repr.clear_caches();
+		assert!(repr.cache_stats().is_empty());
+		assert_eq!(1, repr.lazy(get_min));
+		assert_eq!(3, READS.load(Ordering::SeqCst));
 	}
 
-	#[tokio::test(flavor = "multi_thread")]
-	async fn should_be_moveable_across_tasks() {
-		let mut repr = Repr::new(
-			MinMax { min: 1, max: 5 },
-			|mm| mm.min < mm.max,
-		);
-		fn get_min(mm: &MinMax) -> i32 { mm.min }
-		fn get_max(mm: &MinMax) -> i32 { mm.max }
+	#[test]
+	fn should_cache_capturing_closures_by_key() {
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		use std::sync::atomic::{AtomicU32, Ordering};
+		let reads = std::sync::Arc::new(AtomicU32::new(0));
+		let read = |reads: std::sync::Arc<AtomicU32>| move |mm: &MinMax| {
+			reads.fetch_add(1, Ordering::SeqCst);
+			mm.max - mm.min
+		};
+		assert_eq!(4, repr.lazy_keyed("range", read(reads.clone())));
+		assert_eq!(4, repr.lazy_keyed("range", read(reads.clone())));
+		assert_eq!(1, reads.load(Ordering::SeqCst));
+		repr.write().min = 2;
+		assert_eq!(3, repr.lazy_keyed(String::from("range"), read(reads.clone())));
+		assert_eq!(2, reads.load(Ordering::SeqCst));
+	}
 
-		{
-			let mm = repr.as_ref();
-			assert_eq!(1, get_min(mm));
-			assert_eq!(5, get_max(mm));
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_read_lazy_caches_through_shared_references() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static READS: AtomicU32 = AtomicU32::new(0);
+		fn get_min(mm: &MinMax) -> i32 {
+			READS.fetch_add(1, Ordering::SeqCst);
+			mm.min
 		}
-		{
-			let mut mm = repr.write();
-			mm.max = 100;
-			mm.min = 50;
+		let repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!(1, repr.lazy(get_min));
+		std::thread::scope(|scope| {
+			for _ in 0..4 {
+				scope.spawn(|| assert_eq!(1, repr.lazy(get_min)));
+			}
+		});
+		assert_eq!(1, READS.load(Ordering::SeqCst));
+	}
+
+	#[test]
+	fn should_memoize_lazy_reads_per_argument() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static READS: AtomicU32 = AtomicU32::new(0);
+		fn offset_min(mm: &MinMax, offset: i32) -> i32 {
+			READS.fetch_add(1, Ordering::SeqCst);
+			mm.min + offset
 		}
-		tokio::spawn(async move {
-			let mm = repr.as_ref();
-			assert_eq!(50, get_min(mm));
-			assert_eq!(100, get_max(mm));
-		}).await.unwrap();
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+			.with_max_memo_entries(2);
+		assert_eq!(2, repr.lazy_with(offset_min, 1));
+		assert_eq!(3, repr.lazy_with(offset_min, 2));
+		assert_eq!(2, repr.lazy_with(offset_min, 1));
+		assert_eq!(2, READS.load(Ordering::SeqCst));
+		// Evicts the least recently used argument, 2.
+		assert_eq!(4, repr.lazy_with(offset_min, 3));
+		assert_eq!(2, repr.lazy_with(offset_min, 1));
+		assert_eq!(3, READS.load(Ordering::SeqCst));
+		assert_eq!(3, repr.lazy_with(offset_min, 2));
+		assert_eq!(4, READS.load(Ordering::SeqCst));
+		repr.write().min = 2;
+		assert_eq!(3, repr.lazy_with(offset_min, 1));
+		assert_eq!(5, READS.load(Ordering::SeqCst));
 	}
 
-	#[tokio::test(flavor = "multi_thread")]
-	async fn should_be_shareable_across_threads() {
-		let mut repr = Repr::new(
-			MinMax { min: 1, max: 5 },
-			|mm| mm.min < mm.max,
-		);
-		fn get_min(mm: &MinMax) -> i32 { mm.min }
-		fn get_max(mm: &MinMax) -> i32 { mm.max }
+	#[test]
+	fn should_keep_at_least_one_memoized_argument() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static READS: AtomicU32 = AtomicU32::new(0);
+		fn offset_max(mm: &MinMax, offset: i32) -> i32 {
+			READS.fetch_add(1, Ordering::SeqCst);
+			mm.max + offset
+		}
+		let repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+			.with_max_memo_entries(0);
+		assert_eq!(6, repr.lazy_with(offset_max, 1));
+		assert_eq!(6, repr.lazy_with(offset_max, 1));
+		assert_eq!(1, READS.load(Ordering::SeqCst));
+		assert_eq!(7, repr.lazy_with(offset_max, 2));
+		assert_eq!(6, repr.lazy_with(offset_max, 1));
+		assert_eq!(3, READS.load(Ordering::SeqCst));
+	}
 
-		{
-			let mm = repr.as_ref();
-			assert_eq!(1, get_min(mm));
-			assert_eq!(5, get_max(mm));
+	#[test]
+	fn should_cache_errors_according_to_the_policy() {
+		use crate::cache::ErrorCaching;
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static READS: AtomicU32 = AtomicU32::new(0);
+		fn checked_range(mm: &MinMax) -> Result<i32, String> {
+			READS.fetch_add(1, Ordering::SeqCst);
+			if mm.min < 0 { Err("negative".into()) } else { Ok(mm.max - mm.min) }
+		}
+		let mut repr = CacheableRepr::new(MinMax { min: -1, max: 5 }, |mm| mm.min < mm.max);
+		assert!(repr.lazy_try(checked_range, ErrorCaching::Retry).is_err());
+		assert!(repr.lazy_try(checked_range, ErrorCaching::Retry).is_err());
+		assert_eq!(2, READS.load(Ordering::SeqCst));
+		// Errors are always stored, so this is the error from the last read.
+		assert!(repr.lazy_try(checked_range, ErrorCaching::Cache).is_err());
+		assert_eq!(2, READS.load(Ordering::SeqCst));
+		assert!(repr.lazy_try(checked_range, ErrorCaching::CacheFor(std::time::Duration::ZERO)).is_err());
+		assert!(repr.lazy_try(checked_range, ErrorCaching::CacheFor(std::time::Duration::ZERO)).is_err());
+		assert_eq!(4, READS.load(Ordering::SeqCst));
+		repr.write().min = 1;
+		assert_eq!(Ok(4), repr.lazy_try(checked_range, ErrorCaching::Retry));
+		assert_eq!(Ok(4), repr.lazy_try(checked_range, ErrorCaching::Retry));
+		assert_eq!(5, READS.load(Ordering::SeqCst));
+	}
+
+	#[test]
+	fn should_keep_a_cache_per_method_for_the_same_read_function() {
+		use crate::cache::ErrorCaching;
+		fn checked_range(mm: &MinMax) -> Result<i32, String> {
+			if mm.min < 0 { Err("negative".into()) } else { Ok(mm.max - mm.min) }
+		}
+		fn widen(_: &MinMax, by: &i32, range: Result<i32, String>) -> Result<i32, String> {
+			range.map(|range| range + by)
+		}
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!(Ok(4), repr.lazy(checked_range));
+		assert_eq!(Ok(4), repr.lazy_try(checked_range, ErrorCaching::Cache));
+		assert_eq!(Ok(4), *repr.lazy_arc(checked_range));
+		assert_eq!(Ok(4), repr.lazy_incremental(checked_range, widen));
+		repr.write_with_delta(2, |mm, by| mm.max += by);
+		assert_eq!(Ok(6), repr.lazy(checked_range));
+		assert_eq!(Ok(6), repr.lazy_incremental(checked_range, widen));
+		assert!(repr.invalidate(checked_range));
+		assert_eq!(4, repr.cache_stats().len());
+	}
+
+	#[test]
+	fn should_share_results_that_arent_clone() {
+		#[derive(Debug, PartialEq)]
+		struct Range(i32);
+		fn range(mm: &MinMax) -> Range {
+			Range(mm.max - mm.min)
+		}
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		let first = repr.lazy_arc(range);
+		assert_eq!(Range(4), *first);
+		assert!(std::sync::Arc::ptr_eq(&first, &repr.lazy_arc(range)));
+		repr.write().min = 2;
+		assert_eq!(Range(3), *repr.lazy_arc(range));
+		// Results that were handed out are unaffected by writes.
+		assert_eq!(Range(4), *first);
+	}
+
+	#[test]
+	fn should_update_incremental_caches_from_deltas() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static READS: AtomicU32 = AtomicU32::new(0);
+		struct Widen(i32);
+		fn range(mm: &MinMax) -> i32 {
+			READS.fetch_add(1, Ordering::SeqCst);
+			mm.max - mm.min
+		}
+		fn widen(_: &MinMax, widen: &Widen, range: i32) -> i32 {
+			range + widen.0
+		}
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!(4, repr.lazy_incremental(range, widen));
+		repr.write_with_delta(Widen(2), |mm, widen| mm.max += widen.0);
+		assert_eq!(6, repr.lazy_incremental(range, widen));
+		assert_eq!(1, READS.load(Ordering::SeqCst));
+		// Deltas of other types, and writes without deltas, fall back to recomputing.
+		repr.write_with_delta(3, |mm, n| mm.max += n);
+		assert_eq!(9, repr.lazy_incremental(range, widen));
+		assert_eq!(2, READS.load(Ordering::SeqCst));
+		repr.write().min = 2;
+		assert_eq!(8, repr.lazy_incremental(range, widen));
+		assert_eq!(3, READS.load(Ordering::SeqCst));
+	}
+
+	#[test]
+	fn should_update_incremental_caches_from_deltas_of_several_types() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static READS: AtomicU32 = AtomicU32::new(0);
+		#[derive(Debug)]
+		struct Ledger(Vec<i64>);
+		struct Deposit(i64);
+		struct Withdrawal(i64);
+		fn total(ledger: &Ledger) -> i64 {
+			READS.fetch_add(1, Ordering::SeqCst);
+			ledger.0.iter().sum()
+		}
+		fn add_deposit(_: &Ledger, deposit: &Deposit, total: i64) -> i64 { total + deposit.0 }
+		fn sub_withdrawal(_: &Ledger, withdrawal: &Withdrawal, total: i64) -> i64 { total - withdrawal.0 }
+		fn ignore_deposit(_: &Ledger, _: &Deposit, total: i64) -> i64 { total }
+		let mut repr = CacheableRepr::new(Ledger(vec![10, 20]), |ledger| !ledger.0.is_empty());
+		assert_eq!(30, repr.lazy_incremental(total, add_deposit));
+		assert_eq!(30, repr.lazy_incremental(total, sub_withdrawal));
+		repr.write_with_delta(Deposit(5), |ledger, deposit| ledger.0.push(deposit.0));
+		assert_eq!(35, repr.lazy_incremental(total, sub_withdrawal));
+		repr.write_with_delta(Withdrawal(15), |ledger, withdrawal| ledger.0.push(-withdrawal.0));
+		assert_eq!(20, repr.lazy_incremental(total, add_deposit));
+		assert_eq!(1, READS.load(Ordering::SeqCst));
+		// The latest update function for a type of delta is the one that's used.
+		assert_eq!(20, repr.lazy_incremental(total, ignore_deposit));
+		repr.write_with_delta(Deposit(5), |ledger, deposit| ledger.0.push(deposit.0));
+		assert_eq!(20, repr.lazy_incremental(total, ignore_deposit));
+		assert_eq!(1, READS.load(Ordering::SeqCst));
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	#[should_panic]
+	fn should_check_invariants_after_writes_with_deltas() {
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		repr.write_with_delta(10, |mm, n| mm.min += n);
+	}
+
+	#[test]
+	fn should_only_recompute_queries_whose_inputs_changed() {
+		use crate::cache::query::QueryContext;
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static RANGES: AtomicU32 = AtomicU32::new(0);
+		static LABELS: AtomicU32 = AtomicU32::new(0);
+		fn range(q: &QueryContext<MinMax>) -> i32 {
+			RANGES.fetch_add(1, Ordering::SeqCst);
+			q.value().max - q.value().min
+		}
+		fn label(q: &QueryContext<MinMax>) -> String {
+			LABELS.fetch_add(1, Ordering::SeqCst);
+			format!("{} wide", q.query(range))
 		}
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!("4 wide", repr.query(label));
+		assert_eq!("4 wide", repr.query(label));
+		assert_eq!((1, 1), (RANGES.load(Ordering::SeqCst), LABELS.load(Ordering::SeqCst)));
 		{
 			let mut mm = repr.write();
-			mm.max = 100;
-			mm.min = 50;
+			mm.min += 1;
+			mm.max += 1;
 		}
-		std::thread::scope(|s| {
-			s.spawn(|| {
-				let mm = repr.as_ref();
-				assert_eq!(50, get_min(mm));
-				assert_eq!(100, get_max(mm));
-			});
-		});
+		// The range is recomputed, but it's the same, so the label isn't.
+		assert_eq!("4 wide", repr.query(label));
+		assert_eq!((2, 1), (RANGES.load(Ordering::SeqCst), LABELS.load(Ordering::SeqCst)));
+		repr.write().max = 10;
+		assert_eq!("8 wide", repr.query(label));
+		assert_eq!((3, 2), (RANGES.load(Ordering::SeqCst), LABELS.load(Ordering::SeqCst)));
 	}
 
-	#[tokio::test(flavor = "multi_thread")]
-	async fn should_work_with_shared_ownership_and_rwlock() {
-		let repr = Arc::new(RwLock::new(Repr::new(
-			MinMax { min: 1, max: 5 },
-			|mm| mm.min < mm.max,
-		)));
-		fn get_min(mm: &MinMax) -> i32 { mm.min }
-		fn get_max(mm: &MinMax) -> i32 { mm.max }
+	#[test]
+	#[should_panic(expected = "query cycle detected")]
+	fn should_detect_query_cycles() {
+		use crate::cache::query::QueryContext;
+		fn ping(q: &QueryContext<MinMax>) -> i32 {
+			q.query(pong)
+		}
+		fn pong(q: &QueryContext<MinMax>) -> i32 {
+			q.query(ping)
+		}
+		let repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		repr.query(ping);
+	}
 
-		{
-			let lock = repr.read().await;
-			let mm = lock.read();
-			assert_eq!(1, get_min(mm));
-			assert_eq!(5, get_max(mm));
+	#[test]
+	fn should_cache_values_derived_from_several_reprs() {
+		use crate::cache::joint::JointCache;
+		fn overlap(a: &MinMax, b: &MinMax) -> i32 {
+			(a.max.min(b.max) - a.min.max(b.min)).max(0)
 		}
-		{
-			let mut lock = repr.write().await;
-			let mut mm = lock.write();
-			mm.max = 100;
-			mm.min = 50;
+		fn span(a: &MinMax, b: &MinMax, c: &MinMax) -> i32 {
+			a.max.max(b.max).max(c.max) - a.min.min(b.min).min(c.min)
 		}
-		let r = repr.clone();
-		tokio::spawn(async move {
-			let mut lock = r.write().await;
-			let mut mm = lock.write();
-			assert_eq!(50, get_min(&mm));
-			assert_eq!(100, get_max(&mm));
-			mm.min = 10;
-			mm.max = 20;
-		}).await.unwrap();
-		{
-			let lock = repr.read().await;
-			let mm = lock.read();
-			assert_eq!(10, get_min(mm));
-			assert_eq!(20, get_max(mm));
+		let mut a = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		let b = CacheableRepr::new(MinMax { min: 3, max: 9 }, |mm| mm.min < mm.max);
+		let c = CacheableRepr::new(MinMax { min: 0, max: 2 }, |mm| mm.min < mm.max);
+		let overlaps = JointCache::new();
+		assert_eq!(2, overlaps.get2(&a, &b, overlap));
+		assert_eq!(2, overlaps.get2(&a, &b, overlap));
+		a.write().max = 8;
+		assert_eq!(5, overlaps.get2(&a, &b, overlap));
+		// Different inputs aren't served from the cache.
+		assert_eq!(0, overlaps.get2(&b, &c, overlap));
+		let stats = overlaps.stats();
+		assert_eq!((1, 3), (stats.hits, stats.misses));
+		let spans = JointCache::new();
+		assert_eq!(9, spans.get3(&a, &b, &c, span));
+	}
+
+	#[test]
+	fn should_not_reuse_joint_results_of_dropped_reprs() {
+		use crate::cache::joint::JointCache;
+		fn sum(a: &i32, b: &i32) -> i32 {
+			a + b
 		}
+		fn sum_of_new(cache: &JointCache<i32>, x: i32) -> i32 {
+			let a = CacheableRepr::new(x, |v: &i32| *v > 0);
+			let b = CacheableRepr::new(x, |v: &i32| *v > 0);
+			cache.get2(&a, &b, sum)
+		}
+		let cache = JointCache::new();
+		assert_eq!(2, sum_of_new(&cache, 1));
+		assert_eq!(200, sum_of_new(&cache, 100));
+		// Moving a representation invariant keeps its cached results.
+		let a = CacheableRepr::new(1, |v: &i32| *v > 0);
+		let b = CacheableRepr::new(2, |v: &i32| *v > 0);
+		assert_eq!(3, cache.get2(&a, &b, sum));
+		let moved = Box::new(a);
+		assert_eq!(3, cache.get2(&moved, &b, sum));
+		assert_eq!(1, cache.stats().hits);
 	}
 
-	#[cfg(feature = "eager")]
-	mod eager {
+	#[test]
+	fn should_key_cached_reads_by_type() {
+		use crate::cache::{CacheKey, CachedRead};
 		use std::sync::atomic::{AtomicU32, Ordering};
-		use std::time::Duration;
-		use crate::tests::MinMax;
-		use crate::{CacheableRepr, EagerCacheLookup};
-
-		#[tokio::test(flavor = "multi_thread")]
-		async fn should_read_from_cache() {
-			let mut repr = CacheableRepr::new(
-				MinMax { min: 1, max: 5 },
-				|mm| mm.min < mm.max,
-			);
-			fn get_min(mm: &MinMax) -> i32 {
+		static READS: AtomicU32 = AtomicU32::new(0);
+		struct Min;
+		impl CachedRead<MinMax> for Min {
+			type Output = i32;
+			fn read(mm: &MinMax) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
 				mm.min
 			}
-			assert_eq!(1, repr.eager(get_min).await);
-			assert_eq!(1, repr.eager(get_min).await);
-			// repr.borrow_mut()n = 4;
 		}
-		#[tokio::test(flavor = "multi_thread")]
-		async fn should_invalidate_cache_on_mutation() {
-			let mut repr = CacheableRepr::new(
-				MinMax { min: 1, max: 5 },
-				|mm| mm.min < mm.max,
-			);
-			fn get_min(mm: &MinMax) -> i32 {
+		// The same read function, but a cache of its own.
+		struct SameMin;
+		impl CachedRead<MinMax> for SameMin {
+			type Output = i32;
+			fn read(mm: &MinMax) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
 				mm.min
 			}
-			assert_eq!(1, repr.eager(get_min).await);
-			assert_eq!(1, repr.eager(get_min).await);
-			repr.write().min = 4;
-			assert_eq!(4, repr.eager(get_min).await);
-			assert_eq!(4, repr.eager(get_min).await);
 		}
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!(1, repr.lazy_of::<Min>());
+		assert_eq!(1, repr.lazy_of::<SameMin>());
+		assert_eq!(1, repr.lazy_of::<Min>());
+		assert_eq!(2, READS.load(Ordering::SeqCst));
+		assert!(repr.cache_stats().contains_key(&CacheKey::of_type::<SameMin>()));
+		repr.write().min = 2;
+		assert_eq!(2, repr.lazy_of::<Min>());
+		assert_eq!(3, READS.load(Ordering::SeqCst));
+	}
 
-		#[tokio::test(flavor = "multi_thread")]
-		async fn should_allow_static_closures_for_cache_reads() {
+	#[test]
+	fn should_copy_caches_into_warm_clones() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static READS: AtomicU32 = AtomicU32::new(0);
+		fn get_min(mm: &MinMax) -> i32 {
+			READS.fetch_add(1, Ordering::SeqCst);
+			mm.min
+		}
+		let repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!(1, repr.lazy(get_min));
+		assert_eq!(5, repr.lazy_keyed("max", |mm: &MinMax| mm.max));
+		let mut clone = repr.clone_with_caches();
+		assert_eq!(1, clone.lazy(get_min));
+		assert_eq!(1, READS.load(Ordering::SeqCst));
+		assert_eq!(2, clone.cache_stats().len());
+		assert!(repr.clone().cache_stats().is_empty());
+		// The caches are independent of each other.
+		clone.write().min = 2;
+		assert_eq!(2, clone.lazy(get_min));
+		assert_eq!(Some(1), repr.peek(get_min));
+	}
+
+	#[test]
+	fn should_warm_caches_when_built() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static READS: AtomicU32 = AtomicU32::new(0);
+		fn get_min(mm: &MinMax) -> i32 {
+			READS.fetch_add(1, Ordering::SeqCst);
+			mm.min
+		}
+		let repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+			.with_warm_cache(get_min);
+		assert_eq!(1, READS.load(Ordering::SeqCst));
+		assert_eq!(1, repr.lazy(get_min));
+		assert_eq!(1, READS.load(Ordering::SeqCst));
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_call_observers_after_committed_writes() {
+		use std::panic::{catch_unwind, AssertUnwindSafe};
+		use std::sync::{Arc, Mutex};
+		let log = Arc::new(Mutex::new(Vec::new()));
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		let observed = log.clone();
+		repr.on_change(move |mm| observed.lock().unwrap().push(("first", mm.min)));
+		let observed = log.clone();
+		repr.on_change(move |mm| observed.lock().unwrap().push(("second", mm.min)));
+		repr.write().min = 2;
+		let _ = catch_unwind(AssertUnwindSafe(|| repr.write().min = 10));
+		assert_eq!(vec![("first", 2), ("second", 2)], *log.lock().unwrap());
+	}
+
+	#[test]
+	fn should_call_write_hooks_around_writes() {
+		use std::sync::{Arc, Mutex};
+		let log = Arc::new(Mutex::new(Vec::new()));
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		let before = log.clone();
+		repr.before_write(move |mm| before.lock().unwrap().push(("before", mm.min)));
+		let after = log.clone();
+		repr.after_write(move |mm| after.lock().unwrap().push(("after", mm.min)));
+		repr.write().min = 2;
+		assert!(repr.replace(MinMax { min: 6, max: 5 }).is_err());
+		repr.set(MinMax { min: 3, max: 5 }).unwrap();
+		assert_eq!(vec![("before", 1), ("after", 2), ("before", 2), ("before", 2), ("after", 3)], *log.lock().unwrap());
+	}
+
+	#[test]
+	fn should_journal_commands_with_their_outcome() {
+		use crate::journal::{Command, JournaledRepr};
+		#[derive(Debug, Clone, PartialEq)]
+		struct SetMin(i32);
+		impl Command<MinMax> for SetMin {
+			fn name(&self) -> &str {
+				"set min"
+			}
+			fn apply(&self, mm: &mut MinMax) {
+				mm.min = self.0;
+			}
+		}
+		let mut repr = JournaledRepr::new(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max));
+		repr.apply(SetMin(2)).unwrap();
+		assert!(repr.apply(SetMin(7)).is_err());
+		assert_eq!(2, repr.read().min);
+		let (repr, journal) = repr.into_parts();
+		let outcomes: Vec<(&str, bool)> = journal.entries().iter().map(|entry| (entry.command().name(), entry.outcome().is_ok())).collect();
+		assert_eq!(vec![("set min", true), ("set min", false)], outcomes);
+		let replayed = journal.replay(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max));
+		assert_eq!(repr.read(), replayed.read());
+	}
+
+	#[test]
+	fn should_validate_undone_values() {
+		use std::sync::atomic::{AtomicI32, Ordering};
+		use crate::undo::UndoableRepr;
+		static MIN: AtomicI32 = AtomicI32::new(0);
+		let mut repr = UndoableRepr::new(Repr::new(1, |n| *n >= MIN.load(Ordering::SeqCst)).without_determinism_check())
+			.with_history_depth(2);
+		*repr.write() = 2;
+		*repr.write() = 3;
+		repr.set(4).unwrap();
+		MIN.store(3, Ordering::SeqCst);
+		assert!(repr.undo().unwrap());
+		assert!(repr.undo().is_err());
+		assert_eq!(3, *repr.read());
+		MIN.store(0, Ordering::SeqCst);
+		assert!(repr.undo().unwrap());
+		// Only the last two writes are kept.
+		assert!(!repr.can_undo());
+		assert_eq!(2, *repr.read());
+		*repr.write() = 5;
+		assert!(!repr.can_redo());
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_audit_every_write() {
+		use std::panic::{catch_unwind, AssertUnwindSafe};
+		use std::sync::{Arc, Mutex};
+		let trail = Arc::new(Mutex::new(Vec::new()));
+		let sink = trail.clone();
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+			.with_audit_sink(move |record| {
+				let violation = record.outcome.err().map(|violation| violation.message().to_string());
+				sink.lock().unwrap().push((record.before.to_string(), record.after.min, violation));
+			});
+		repr.write().min = 2;
+		let _ = catch_unwind(AssertUnwindSafe(|| repr.write().min = 6));
+		let mut clone = repr.clone();
+		clone.set(MinMax { min: 3, max: 5 }).unwrap();
+		assert_eq!(vec![
+			("MinMax { min: 1, max: 5 }".to_string(), 2, None),
+			("MinMax { min: 2, max: 5 }".to_string(), 6, Some("Invariant violated".to_string())),
+			("MinMax { min: 6, max: 5 }".to_string(), 3, None),
+		], *trail.lock().unwrap());
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_report_metrics() {
+		use crate::cache::CacheKey;
+		use crate::metrics::Metrics;
+		use crate::Violation;
+		use std::panic::{catch_unwind, AssertUnwindSafe};
+		use std::sync::atomic::{AtomicU32, Ordering};
+		#[derive(Default)]
+		struct Counters {
+			checks: AtomicU32,
+			violations: AtomicU32,
+			hits: AtomicU32,
+			misses: AtomicU32,
+		}
+		impl Metrics for Counters {
+			fn check(&self, _duration: std::time::Duration) {
+				self.checks.fetch_add(1, Ordering::Relaxed);
+			}
+			fn violation(&self, _violation: &Violation) {
+				self.violations.fetch_add(1, Ordering::Relaxed);
+			}
+			fn cache_read(&self, _key: &CacheKey, hit: bool) {
+				let counter = if hit { &self.hits } else { &self.misses };
+				counter.fetch_add(1, Ordering::Relaxed);
+			}
+		}
+		fn width(mm: &MinMax) -> i32 {
+			mm.max - mm.min
+		}
+		let counters = Arc::new(Counters::default());
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+			.with_metrics(counters.clone());
+		assert_eq!(4, repr.lazy(width));
+		assert_eq!(4, repr.lazy(width));
+		repr.write().min = 2;
+		assert_eq!(3, repr.lazy(width));
+		assert!(repr.set(MinMax { min: 6, max: 5 }).is_err());
+		let _ = catch_unwind(AssertUnwindSafe(|| repr.write().min = 6));
+		assert_eq!(3, counters.checks.load(Ordering::Relaxed));
+		assert_eq!(2, counters.violations.load(Ordering::Relaxed));
+		assert_eq!((1, 2), (counters.hits.load(Ordering::Relaxed), counters.misses.load(Ordering::Relaxed)));
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_warn_about_slow_checks() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		use std::time::Duration;
+		let warnings = Arc::new(AtomicU32::new(0));
+		let counter = warnings.clone();
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| {
+			if mm.min == 3 {
+				std::thread::sleep(Duration::from_millis(20));
+			}
+			mm.min < mm.max
+		}).with_slow_check_warning(Duration::from_millis(10), move |mm, took| {
+			assert_eq!(3, mm.min);
+			assert!(took >= Duration::from_millis(10));
+			counter.fetch_add(1, Ordering::Relaxed);
+		});
+		repr.write().min = 2;
+		assert_eq!(0, warnings.load(Ordering::Relaxed));
+		repr.write().min = 3;
+		assert_eq!(1, warnings.load(Ordering::Relaxed));
+		repr.clone().set(MinMax { min: 3, max: 4 }).unwrap();
+		assert_eq!(2, warnings.load(Ordering::Relaxed));
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_version_committed_writes() {
+		use std::panic::{catch_unwind, AssertUnwindSafe};
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!(0, repr.version());
+		repr.write().min = 2;
+		repr.set(MinMax { min: 3, max: 4 }).unwrap();
+		assert!(repr.set(MinMax { min: 6, max: 4 }).is_err());
+		assert_eq!(2, repr.version());
+		let mut other = CacheableRepr::new(MinMax { min: 0, max: 9 }, |mm| mm.min < mm.max);
+		let mut clone = repr.clone();
+		repr.swap(&mut other).unwrap();
+		assert_eq!((3, 1), (repr.version(), other.version()));
+		assert_eq!(2, clone.version());
+		let _ = catch_unwind(AssertUnwindSafe(|| clone.write().min = 6));
+		assert_eq!(2, clone.version());
+	}
+
+	#[test]
+	fn should_only_write_the_expected_version() {
+		use crate::repr::CurrentVersion;
+		fn range(mm: &MinMax) -> i32 {
+			mm.max - mm.min
+		}
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!(4, repr.lazy(range));
+		let seen = repr.version();
+		repr.try_write_if_version(seen).unwrap().min = 2;
+		assert_eq!(3, repr.lazy(range));
+		assert_eq!(CurrentVersion(1), repr.try_write_if_version(seen).map(drop).unwrap_err());
+		assert_eq!(2, repr.read().min);
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_skip_unchanged_writes() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static READS: AtomicU32 = AtomicU32::new(0);
+		static CHECKS: AtomicU32 = AtomicU32::new(0);
+		fn range(mm: &MinMax) -> i32 {
+			READS.fetch_add(1, Ordering::SeqCst);
+			mm.max - mm.min
+		}
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| {
+			CHECKS.fetch_add(1, Ordering::SeqCst);
+			mm.min < mm.max
+		}).with_change_detection().with_determinism_checks(0);
+		assert_eq!(4, repr.lazy(range));
+		repr.write().min = 1;
+		repr.set(MinMax { min: 1, max: 5 }).unwrap();
+		repr.write_with_delta((), |mm, _| mm.max = 5);
+		assert_eq!(4, repr.lazy(range));
+		assert_eq!((1, 0, 0), (READS.load(Ordering::SeqCst), CHECKS.load(Ordering::SeqCst), repr.version()));
+		repr.write().min = 2;
+		assert_eq!(3, repr.lazy(range));
+		assert_eq!((2, 1, 1), (READS.load(Ordering::SeqCst), CHECKS.load(Ordering::SeqCst), repr.version()));
+	}
+
+	#[test]
+	fn should_not_snapshot_values_that_are_replaced_whole() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static CLONES: AtomicU32 = AtomicU32::new(0);
+		#[derive(Debug, PartialEq)]
+		struct Counted(i32);
+		impl Clone for Counted {
+			fn clone(&self) -> Self {
+				CLONES.fetch_add(1, Ordering::SeqCst);
+				Self(self.0)
+			}
+		}
+		let mut repr = Repr::new(Counted(1), |counted| counted.0 > 0).with_change_detection();
+		repr.set(Counted(2)).unwrap();
+		let mut other = Repr::new(Counted(3), |counted| counted.0 > 0).with_change_detection();
+		repr.swap(&mut other).unwrap();
+		assert_eq!(0, CLONES.load(Ordering::SeqCst));
+		repr.write().0 = 4;
+		assert_eq!(1, CLONES.load(Ordering::SeqCst));
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_write_cells_through_shared_references() {
+		use crate::ReprCell;
+		use std::panic::{catch_unwind, AssertUnwindSafe};
+		let cell = ReprCell::new(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max));
+		cell.write().min = 2;
+		{
+			let view = cell.read();
+			assert_eq!(2, view.min);
+			assert!(cell.try_write().is_err());
+		}
+		{
+			let _view = cell.write();
+			assert!(cell.try_read().is_err());
+		}
+		assert!(catch_unwind(AssertUnwindSafe(|| cell.write().min = 6)).is_err());
+		assert!(!cell.into_inner().is_valid());
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_check_the_invariant_when_a_lock_is_released() {
+		use crate::{ReprMutex, ReprRwLock};
+		use std::thread;
+		let mutex = Arc::new(ReprMutex::new(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)));
+		let handle = mutex.clone();
+		assert!(thread::spawn(move || handle.lock().unwrap().min = 6).join().is_err());
+		let mut guard = mutex.lock().unwrap_err().into_inner();
+		assert_eq!(6, guard.min);
+		guard.min = 2;
+		drop(guard);
+
+		let lock = ReprRwLock::new(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max));
+		thread::scope(|scope| {
+			for max in 6..10 {
+				let lock = &lock;
+				scope.spawn(move || lock.write().unwrap().max = max);
+			}
+		});
+		assert!(lock.read().unwrap().max >= 6);
+		assert_eq!(4, lock.into_inner().version());
+		let lock = ReprRwLock::new(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max));
+		assert!(thread::scope(|scope| scope.spawn(|| lock.write().unwrap().max = 0).join()).is_err());
+		assert!(lock.read().is_err());
+		lock.write().unwrap_err().into_inner().max = 5;
+		assert!(lock.into_inner().is_valid());
+	}
+
+	#[test]
+	fn should_only_commit_atomic_updates_that_hold() {
+		use crate::AtomicRepr;
+		use std::thread;
+		let even = AtomicRepr::new(0i64, |n| n % 2 == 0);
+		thread::scope(|scope| {
+			for _ in 0..4 {
+				scope.spawn(|| {
+					for _ in 0..100 {
+						even.fetch_update(|n| n + 2).unwrap();
+						assert!(even.fetch_update(|n| n + 1).is_err());
+					}
+				});
+			}
+		});
+		assert_eq!(800, even.load());
+		assert_eq!("Invariant violated\nState was: 3", even.store(3).unwrap_err().to_string());
+		even.store(4).unwrap();
+		assert_eq!(4, even.into_inner());
+		let flag = AtomicRepr::new(false, |_| true);
+		assert_eq!(Ok(false), flag.fetch_update(|b| !b));
+		assert!(flag.load());
+	}
+
+	#[cfg(feature = "rcu")]
+	#[test]
+	fn should_read_snapshots_while_writing() {
+		use crate::ArcRepr;
+		use std::thread;
+		let repr = ArcRepr::new(MinMax { min: 0, max: 1 }, |mm| mm.min < mm.max);
+		thread::scope(|scope| {
+			for _ in 0..2 {
+				scope.spawn(|| {
+					for _ in 0..100 {
+						repr.update(|mm| {
+							mm.min += 1;
+							mm.max += 1;
+						}).unwrap();
+					}
+				});
+			}
+			scope.spawn(|| {
+				for _ in 0..100 {
+					let snapshot = repr.load();
+					assert_eq!(1, snapshot.max - snapshot.min);
+				}
+			});
+		});
+		assert_eq!(MinMax { min: 200, max: 201 }, *repr.load());
+		let (rejected, _) = repr.store(MinMax { min: 3, max: 3 }).unwrap_err();
+		assert_eq!(3, rejected.min);
+		repr.store(MinMax { min: 3, max: 4 }).unwrap();
+		assert_eq!(3, repr.into_inner().min);
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[cfg(feature = "rcu")]
+	#[test]
+	fn should_only_publish_writes_that_hold() {
+		use std::panic::{catch_unwind, AssertUnwindSafe};
+		let (mut writer, reader) = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max).split();
+		writer.write().min = 2;
+		assert_eq!(2, reader.load().min);
+		let _ = catch_unwind(AssertUnwindSafe(|| writer.write().min = 6));
+		assert!(writer.set(MinMax { min: 7, max: 5 }).is_err());
+		assert_eq!(2, reader.load().min);
+		let late = writer.reader();
+		writer.set(MinMax { min: 3, max: 4 }).unwrap();
+		drop(writer);
+		assert_eq!(3, late.load().min);
+		assert_eq!(3, reader.clone().load().min);
+	}
+
+	#[test]
+	fn should_share_read_only_views() {
+		let mut repr = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		repr.write().max = 6;
+		let reader = repr.reader();
+		let other = reader.clone();
+		assert_eq!(6, other.read().max);
+		assert_eq!(1, reader.min);
+		assert_eq!(1, other.version());
+		assert!(reader.validate().is_ok());
+		assert_eq!("ReprReader(MinMax { min: 1, max: 6 })", format!("{other:?}"));
+	}
+
+	#[cfg(feature = "watch")]
+	#[tokio::test]
+	async fn should_publish_snapshots_of_valid_writes() {
+		let mut repr = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		let mut snapshots = repr.snapshot_channel();
+		let first = snapshots.borrow_and_update().clone();
+		repr.write().max = 10;
+		assert!(snapshots.has_changed().unwrap());
+		assert_eq!(10, snapshots.borrow_and_update().max);
+		assert!(repr.set(MinMax { min: 20, max: 10 }).is_err());
+		assert!(!snapshots.has_changed().unwrap());
+		assert_eq!(5, first.max);
+		drop(repr);
+		assert!(snapshots.changed().await.is_err());
+	}
+
+	#[cfg(feature = "signals")]
+	#[test]
+	fn should_signal_values_that_hold() {
+		use futures::executor::block_on;
+		use futures::StreamExt;
+		use futures_signals::signal::SignalExt;
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		let mut values = repr.signal().signal_cloned().to_stream();
+		let width = repr.derived_signal(|mm| mm.max - mm.min);
+		assert_eq!(Some(MinMax { min: 1, max: 5 }), block_on(values.next()));
+		repr.write().max = 10;
+		assert_eq!(Some(MinMax { min: 1, max: 10 }), block_on(values.next()));
+		assert!(repr.set(MinMax { min: 20, max: 10 }).is_err());
+		assert_eq!(9, width.get());
+		drop(repr);
+		assert_eq!(None, block_on(values.next()));
+	}
+
+	#[test]
+	fn should_only_check_new_and_modified_elements() {
+		let checks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+		let counted = checks.clone();
+		let mut repr = crate::ReprVec::new(move |mm: &MinMax| {
+			counted.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+			mm.min < mm.max
+		}).with_collection_invariant(|items| items.len() <= 3);
+		repr.extend([MinMax { min: 1, max: 2 }, MinMax { min: 2, max: 3 }]).unwrap();
+		repr.push(MinMax { min: 3, max: 4 }).unwrap();
+		assert_eq!(3, checks.load(std::sync::atomic::Ordering::Relaxed));
+		assert!(repr.push(MinMax { min: 4, max: 5 }).is_err());
+		assert!(repr.insert(0, MinMax { min: 5, max: 4 }).is_err());
+		repr.get_mut(0).unwrap().max = 10;
+		assert_eq!(6, checks.load(std::sync::atomic::Ordering::Relaxed));
+		assert_eq!(MinMax { min: 3, max: 4 }, repr.pop().unwrap().unwrap());
+		assert!(repr.extend([MinMax { min: 1, max: 2 }, MinMax { min: 1, max: 2 }]).is_err());
+		assert!(repr.extend([MinMax { min: 2, max: 1 }]).is_err());
+		assert_eq!(2, repr.read().len());
+		repr.write().retain(|mm| mm.max == 10);
+		assert_eq!(vec![MinMax { min: 1, max: 10 }], repr.into_inner());
+	}
+
+	#[test]
+	#[should_panic(expected = "Invariant violated")]
+	fn should_check_every_element_after_bulk_writes() {
+		let mut repr = crate::ReprVec::new(|n: &i32| *n > 0);
+		repr.extend([1, 2, 3]).unwrap();
+		repr.write().iter_mut().for_each(|n| *n -= 2);
+	}
+
+	#[test]
+	fn should_roll_back_map_writes_that_violate() {
+		let mut repr = crate::ReprHashMap::new(|name: &String, mm: &MinMax| !name.is_empty() && mm.min < mm.max)
+			.with_map_invariant(|map| map.values().map(|mm| mm.max - mm.min).sum::<i32>() <= 10);
+		assert_eq!(None, repr.insert("a".to_string(), MinMax { min: 0, max: 5 }).unwrap());
+		repr.insert("b".to_string(), MinMax { min: 0, max: 5 }).unwrap();
+		assert!(repr.insert(String::new(), MinMax { min: 0, max: 1 }).is_err());
+		let (rejected, _) = repr.insert("a".to_string(), MinMax { min: 0, max: 6 }).unwrap_err();
+		assert_eq!(6, rejected.max);
+		assert_eq!(5, repr.read()["a"].max);
+		assert!(repr.insert("c".to_string(), MinMax { min: 0, max: 1 }).is_err());
+		assert!(!repr.read().contains_key("c"));
+		repr.get_mut(&"a".to_string()).unwrap().min = 1;
+		assert!(repr.get_mut(&"c".to_string()).is_none());
+		assert_eq!(Some(MinMax { min: 1, max: 5 }), repr.remove(&"a".to_string()).unwrap());
+		assert_eq!(None, repr.remove(&"a".to_string()).unwrap());
+		assert!(repr.is_valid());
+	}
+
+	#[test]
+	#[should_panic(expected = "Invariant violated")]
+	fn should_check_modified_entries() {
+		let mut repr = crate::ReprBTreeMap::new(|_: &i32, n: &i32| *n > 0)
+			.with_map_invariant(|map| !map.is_empty());
+		repr.insert(1, 1).unwrap();
+		assert!(repr.remove(&1).is_err());
+		*repr.get_mut(&1).unwrap() = 0;
+	}
+
+	#[test]
+	fn should_check_entries_inserted_and_modified_through_the_entry_api() {
+		use crate::map::ReprMapEntry;
+		let mut repr = crate::ReprBTreeMap::new(|_: &&str, mm: &MinMax| mm.min < mm.max)
+			.with_map_invariant(|map| map.values().map(|mm| mm.max - mm.min).sum::<i32>() <= 10);
+		repr.entry("a").or_insert(MinMax { min: 0, max: 5 }).unwrap().max = 6;
+		repr.entry("a").and_modify(|mm| mm.min = 1).or_insert(MinMax { min: 0, max: 1 }).unwrap();
+		assert_eq!(MinMax { min: 1, max: 6 }, repr.read()["a"]);
+		let (rejected, _) = repr.entry("b").or_insert_with(|| MinMax { min: 0, max: 6 }).unwrap_err();
+		assert_eq!(6, rejected.max);
+		assert!(!repr.read().contains_key("b"));
+		match repr.entry("b") {
+			ReprMapEntry::Vacant(entry) => assert!(entry.insert(MinMax { min: 2, max: 1 }).is_err()),
+			ReprMapEntry::Occupied(_) => unreachable!(),
+		}
+		match repr.entry("a") {
+			ReprMapEntry::Occupied(mut entry) => {
+				assert_eq!(6, entry.get().max);
+				assert!(entry.insert(MinMax { min: 0, max: 11 }).is_err());
+				assert_eq!(MinMax { min: 1, max: 6 }, entry.remove().unwrap());
+			}
+			ReprMapEntry::Vacant(_) => unreachable!(),
+		}
+		assert!(repr.read().is_empty());
+		let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			repr.insert("c", MinMax { min: 0, max: 5 }).unwrap();
+			repr.entry("c").and_modify(|mm| mm.max = 20);
+		}));
+		assert!(result.is_err());
+	}
+
+	#[test]
+	fn should_check_each_element_as_it_is_iterated() {
+		let mut repr = crate::ReprVec::new(|mm: &MinMax| mm.min < mm.max)
+			.with_collection_invariant(|items| items.windows(2).all(|pair| pair[0].max <= pair[1].min));
+		repr.extend([MinMax { min: 1, max: 2 }, MinMax { min: 3, max: 4 }]).unwrap();
+		let mut iter = repr.iter_mut();
+		while let Some(mut mm) = iter.next() {
+			mm.min *= 10;
+			mm.max *= 10;
+		}
+		drop(iter);
+		assert_eq!(&[MinMax { min: 10, max: 20 }, MinMax { min: 30, max: 40 }], repr.read());
+		let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			let mut iter = repr.iter_mut();
+			iter.next().unwrap().max = 35;
+		}));
+		assert!(result.is_err());
+		let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			repr.iter_mut().next().unwrap().min = 40;
+		}));
+		assert!(result.is_err());
+	}
+
+	#[test]
+	fn should_only_compare_neighbours_of_written_elements() {
+		use std::cmp::Ordering;
+		use std::sync::atomic::{self, AtomicUsize};
+		static COMPARISONS: AtomicUsize = AtomicUsize::new(0);
+		#[derive(Debug)]
+		struct Counted(i32);
+		impl PartialEq for Counted {
+			fn eq(&self, other: &Self) -> bool {
+				COMPARISONS.fetch_add(1, atomic::Ordering::Relaxed);
+				self.0 == other.0
+			}
+		}
+		impl PartialOrd for Counted {
+			fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+				COMPARISONS.fetch_add(1, atomic::Ordering::Relaxed);
+				self.0.partial_cmp(&other.0)
+			}
+		}
+		let mut repr = crate::ReprVec::new(|_: &Counted| true).with_sorted_elements().with_unique_elements();
+		repr.extend((0..100).map(|n| Counted(n * 2))).unwrap();
+		COMPARISONS.store(0, atomic::Ordering::Relaxed);
+		assert!(repr.insert(50, Counted(100)).is_err());
+		assert!(repr.insert(50, Counted(97)).is_err());
+		repr.insert(50, Counted(99)).unwrap();
+		assert!(repr.remove(50).is_ok());
+		repr.get_mut(10).unwrap().0 = 21;
+		assert!(COMPARISONS.load(atomic::Ordering::Relaxed) <= 20);
+		assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| repr.get_mut(10).unwrap().0 = 22)).is_err());
+		let mut repr = crate::ReprVec::new(|_: &i32| true).with_unique_elements();
+		repr.extend([3, 1, 2]).unwrap();
+		assert!(repr.push(1).is_err());
+		assert!(repr.remove(0).is_ok());
+		repr.insert(0, 3).unwrap();
+		assert!(repr.is_valid());
+	}
+
+	#[test]
+	fn should_keep_refinement_invariants() {
+		use crate::prelude::{Bounded, NonEmptyString, NonEmptyVec, Percentage, SortedVec};
+		let mut level = Bounded::<1, 5>::new(2).unwrap();
+		assert_eq!(4, level.checked_add(2).unwrap().get());
+		assert!(level.checked_sub(2).is_err());
+		assert!(level.checked_add(i64::MAX).is_err());
+		assert_eq!((1, 5), (Bounded::<1, 5>::MIN, Bounded::<1, 5>::MAX));
+		assert_eq!(9, level.set(9).unwrap_err().0);
+		level.set(5).unwrap();
+		assert!(NonEmptyString::try_from(String::new()).is_err());
+		let mut name = NonEmptyString::new("a".into()).unwrap();
+		name.push('b');
+		assert_eq!("ab", name.to_string());
+		let mut stack = NonEmptyVec::new(vec![1, 2]).unwrap();
+		assert_eq!((&1, &2), (stack.first(), stack.last()));
+		assert_eq!(Some(2), stack.pop());
+		assert!(stack.set(Vec::new()).is_err());
+		assert_eq!("25%", Percentage::new(25.0).unwrap().to_string());
+		assert_eq!(100.0, Percentage::new(60.0).unwrap().saturating_add(Percentage::new(60.0).unwrap()).get());
+		let mut sorted = SortedVec::new();
+		sorted.extend([3, 1, 2]);
+		assert_eq!(3, sorted.insert(3));
+		sorted.retain(|n| n % 2 == 1);
+		assert_eq!(vec![1, 3, 3], sorted.into_inner());
+	}
+
+	#[test]
+	fn should_provide_common_predicates() {
+		use crate::predicate::*;
+		assert!(Repr::new(0.5, finite).is_valid());
+		assert!(!Repr::new(f32::NAN, finite).is_valid());
+		assert!(!Repr::new(0, positive).is_valid());
+		assert!(Repr::new(0, non_negative).is_valid());
+		assert!(!Repr::new(" \t".to_string(), not_blank).is_valid());
+		assert!(!Repr::new("é", ascii).is_valid());
+		assert!(!Repr::new(vec![0xff_u8], utf8).is_valid());
+		assert!(Repr::new("é", char_count_in(1..2)).is_valid());
+		assert!(!Repr::new("é", len_in_bytes(1..2)).is_valid());
+		assert!(!Repr::new([1, 2, 3], len_in(..3)).is_valid());
+		assert!(Repr::new(vec![3, 3, 1], decreasing).is_valid());
+		assert!(!Repr::new(vec![3, 3, 1], strictly_decreasing).is_valid());
+		assert!(Repr::new(vec![1, 3, 3], increasing).is_valid());
+		let mut range = Repr::new(MinMax { min: 1, max: 5 }, |mm| in_range(mm.min..=mm.max)(&3));
+		assert!(range.set(MinMax { min: 4, max: 5 }).is_err());
+	}
+
+	#[test]
+	fn should_check_externally_owned_values_on_scope_exit() {
+		let mut buffer = [MinMax { min: 1, max: 5 }, MinMax { min: 2, max: 3 }];
+		crate::guard(&mut buffer[1], |mm| mm.min < mm.max).max = 4;
+		let mut slot = crate::guard::guard_with_msg(&mut buffer[0], |mm| mm.min < mm.max, "min must be less than max");
+		slot.min = 6;
+		assert_eq!("min must be less than max", slot.finish().unwrap_err().message());
+		let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			crate::guard(&mut buffer[1], |mm| mm.min < mm.max).min = 7;
+		}));
+		assert!(result.is_err());
+		assert_eq!([6, 7], buffer.map(|mm| mm.min));
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_check_unsized_values() {
+		trait Bounds: core::fmt::Debug {
+			fn width(&self) -> i32;
+			fn widen(&mut self, by: i32);
+		}
+		impl Bounds for MinMax {
+			fn width(&self) -> i32 {
+				self.max - self.min
+			}
+			fn widen(&mut self, by: i32) {
+				self.max += by;
+			}
+		}
+		let mut range = MinMax { min: 1, max: 5 };
+		let bounds: &mut dyn Bounds = &mut range;
+		let mut bounds = crate::guard(bounds, |b| b.width() > 0);
+		bounds.widen(-4);
+		assert!(bounds.finish().is_err());
+
+		let mut repr = Repr::new(vec![1, 2, 3], |v: &Vec<i32>| !v.is_empty());
+		{
+			let mut values = repr.write();
+			let mut tail = values.project(|v| &mut v[1..], crate::predicate::strictly_increasing);
+			tail[1] = 4;
+		}
+		let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			let mut values = repr.write();
+			values.project(|v| v.as_mut_slice(), crate::predicate::strictly_increasing)[0] = 9;
+		}));
+		assert!(result.is_err());
+	}
+
+	#[test]
+	fn should_hash_by_inner() {
+		let mut repr1 = Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		);
+		let mut repr2 = Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		);
+		let mut map = HashMap::new();
+		let repr1_w: &mut MinMax = &mut repr1.write();
+		let repr2_w: &mut MinMax = &mut repr2.write();
+		map.insert(&repr1_w, 1);
+		assert!(map.contains_key(&repr1_w));
+		assert!(map.contains_key(&repr2_w));
+		repr2_w.max = 10;
+		assert!(map.contains_key(&repr1_w));
+		assert!(!map.contains_key(&repr2_w));
+	}
+
+	#[test]
+	fn should_be_moveable_across_threads() {
+		let mut repr = Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		);
+		fn get_min(mm: &MinMax) -> i32 { mm.min }
+		fn get_max(mm: &MinMax) -> i32 { mm.max }
+
+		{
+			let mm = repr.as_ref();
+			assert_eq!(1, get_min(mm));
+			assert_eq!(5, get_max(mm));
+		}
+		{
+			let mut mm = repr.write();
+			mm.max = 100;
+			mm.min = 50;
+		}
+		std::thread::spawn(move || {
+			let mm = repr.as_ref();
+			assert_eq!(50, get_min(mm));
+			assert_eq!(100, get_max(mm));
+		}).join().unwrap();
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn should_be_moveable_across_tasks() {
+		let mut repr = Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		);
+		fn get_min(mm: &MinMax) -> i32 { mm.min }
+		fn get_max(mm: &MinMax) -> i32 { mm.max }
+
+		{
+			let mm = repr.as_ref();
+			assert_eq!(1, get_min(mm));
+			assert_eq!(5, get_max(mm));
+		}
+		{
+			let mut mm = repr.write();
+			mm.max = 100;
+			mm.min = 50;
+		}
+		tokio::spawn(async move {
+			let mm = repr.as_ref();
+			assert_eq!(50, get_min(mm));
+			assert_eq!(100, get_max(mm));
+		}).await.unwrap();
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn should_be_shareable_across_threads() {
+		let mut repr = Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		);
+		fn get_min(mm: &MinMax) -> i32 { mm.min }
+		fn get_max(mm: &MinMax) -> i32 { mm.max }
+
+		{
+			let mm = repr.as_ref();
+			assert_eq!(1, get_min(mm));
+			assert_eq!(5, get_max(mm));
+		}
+		{
+			let mut mm = repr.write();
+			mm.max = 100;
+			mm.min = 50;
+		}
+		std::thread::scope(|s| {
+			s.spawn(|| {
+				let mm = repr.as_ref();
+				assert_eq!(50, get_min(mm));
+				assert_eq!(100, get_max(mm));
+			});
+		});
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn should_work_with_shared_ownership_and_rwlock() {
+		let repr = Arc::new(RwLock::new(Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		)));
+		fn get_min(mm: &MinMax) -> i32 { mm.min }
+		fn get_max(mm: &MinMax) -> i32 { mm.max }
+
+		{
+			let lock = repr.read().await;
+			let mm = lock.read();
+			assert_eq!(1, get_min(mm));
+			assert_eq!(5, get_max(mm));
+		}
+		{
+			let mut lock = repr.write().await;
+			let mut mm = lock.write();
+			mm.max = 100;
+			mm.min = 50;
+		}
+		let r = repr.clone();
+		tokio::spawn(async move {
+			let mut lock = r.write().await;
+			let mut mm = lock.write();
+			assert_eq!(50, get_min(&mm));
+			assert_eq!(100, get_max(&mm));
+			mm.min = 10;
+			mm.max = 20;
+		}).await.unwrap();
+		{
+			let lock = repr.read().await;
+			let mm = lock.read();
+			assert_eq!(10, get_min(mm));
+			assert_eq!(20, get_max(mm));
+		}
+	}
+
+	#[test]
+	fn should_skip_checks_with_never_policy() {
+		let mut repr = Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		).with_check_policy(CheckPolicy::Never);
+		repr.write().min = 6;
+		assert_eq!(6, repr.read().min);
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	#[should_panic]
+	fn should_check_every_nth_mutation_with_sampled_policy() {
+		let mut repr = Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		).with_check_policy(CheckPolicy::Sampled(2));
+		repr.write().min = 6;
+		repr.write().min = 7;
+	}
+
+	#[test]
+	fn should_allow_disabling_determinism_checks() {
+		let value = Rc::new(RefCell::new(true));
+		let mut repr = Repr::new(
+			value.clone(),
+			|_| {
+				let res = *value.borrow();
+				value.replace(false);
+				res
+			},
+		).with_determinism_checks(0);
+		repr.write();
+	}
+
+	#[test]
+	fn should_allow_opting_out_of_determinism_checks() {
+		let value = Rc::new(RefCell::new(true));
+		let mut repr = CacheableRepr::new(
+			value.clone(),
+			|_| {
+				let res = *value.borrow();
+				value.replace(false);
+				res
+			},
+		).without_determinism_check();
+		repr.write();
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	#[should_panic]
+	fn should_detect_external_mutation_on_read_in_paranoid_mode() {
+		use std::cell::Cell;
+		let repr = Repr::new(
+			Cell::new(1),
+			|n| n.get() > 0,
+		).with_read_check_policy(CheckPolicy::Always);
+		repr.read().set(-1);
+		repr.read();
+	}
+
+	#[test]
+	fn should_validate_without_panicking() {
+		use std::cell::Cell;
+		let repr = CacheableRepr::with_msg(
+			Cell::new(1),
+			|n| n.get() > 0,
+			"must be positive",
+		);
+		assert_eq!(Ok(()), repr.validate());
+		repr.read().set(-1);
+		let violation = repr.validate().unwrap_err();
+		assert_eq!("must be positive", violation.message());
+		assert_eq!("Cell { value: -1 }", violation.state());
+		assert_eq!("must be positive\nState was: Cell { value: -1 }", violation.to_string());
+	}
+
+	#[test]
+	fn should_query_validity() {
+		use std::cell::Cell;
+		let repr = Repr::new(
+			Cell::new(1),
+			|n| n.get() > 0,
+		);
+		assert!(repr.is_valid());
+		repr.read().set(-1);
+		assert!(!repr.is_valid());
+	}
+
+	#[test]
+	fn should_replace_with_valid_values_only() {
+		let mut repr = CacheableRepr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		);
+		assert_eq!(1, repr.lazy(|mm| mm.min));
+		let old = repr.replace(MinMax { min: 2, max: 3 }).unwrap();
+		assert_eq!(MinMax { min: 1, max: 5 }, old);
+		assert_eq!(2, repr.lazy(|mm| mm.min));
+		let (rejected, violation) = repr.replace(MinMax { min: 4, max: 3 }).unwrap_err();
+		assert_eq!(MinMax { min: 4, max: 3 }, rejected);
+		assert_eq!("MinMax { min: 4, max: 3 }", violation.state());
+		assert_eq!(2, repr.lazy(|mm| mm.min));
+		assert!(repr.set(MinMax { min: 0, max: 1 }).is_ok());
+		assert_eq!(0, repr.lazy(|mm| mm.min));
+	}
+
+	#[test]
+	fn should_take_when_default_is_valid() {
+		let mut repr = CacheableRepr::new(
+			vec![1, 2, 3],
+			|v| v.len() < 5,
+		);
+		assert_eq!(3, repr.lazy(|v| v.len()));
+		assert_eq!(vec![1, 2, 3], repr.take().unwrap());
+		assert_eq!(0, repr.lazy(|v| v.len()));
+		let mut repr = Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		);
+		assert!(repr.take().is_err());
+		assert_eq!(MinMax { min: 1, max: 5 }, *repr.read());
+	}
+
+	#[test]
+	fn should_swap_only_when_both_invariants_hold() {
+		let mut a = CacheableRepr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		);
+		let mut b = CacheableRepr::new(
+			MinMax { min: 2, max: 3 },
+			|mm| mm.max < 10,
+		);
+		assert_eq!(1, a.lazy(|mm| mm.min));
+		assert_eq!(2, b.lazy(|mm| mm.min));
+		a.swap(&mut b).unwrap();
+		assert_eq!(2, a.lazy(|mm| mm.min));
+		assert_eq!(1, b.lazy(|mm| mm.min));
+		b.write().max = 0;
+		assert!(a.swap(&mut b).is_err());
+		assert_eq!(MinMax { min: 2, max: 3 }, *a.read());
+		assert_eq!(MinMax { min: 1, max: 0 }, *b.read());
+	}
+
+	#[test]
+	fn should_map_into_a_new_repr() {
+		let repr = Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		);
+		let range = repr.map(|mm| mm.max - mm.min, |range| *range > 0);
+		assert_eq!(4, *range.read());
+		let result = range.try_map(|range| -range, |range| *range > 0);
+		assert_eq!(-4, result.unwrap_err().0);
+	}
+
+	#[test]
+	#[should_panic]
+	fn should_panic_when_mapping_into_an_invalid_repr() {
+		let repr = Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		);
+		repr.map(|mm| MinMax { min: mm.max, max: mm.min }, |mm| mm.min < mm.max);
+	}
+
+	#[test]
+	fn should_check_relational_invariants() {
+		let mut account = ReprPair::new(
+			0i64,
+			Vec::<i64>::new(),
+			|balance, ledger| *balance == ledger.iter().sum::<i64>(),
+		);
+		{
+			let mut account = account.write();
+			account.0 += 10;
+			account.1.push(10);
+		}
+		assert_eq!((&10, &vec![10]), account.read());
+		account.write_second().push(0);
+		assert_eq!(&vec![10, 0], account.second());
+		assert!(account.is_valid());
+	}
+
+	#[test]
+	#[should_panic]
+	fn should_panic_when_relational_invariant_is_violated() {
+		let mut account = ReprPair::new(
+			0i64,
+			Vec::<i64>::new(),
+			|balance, ledger| *balance == ledger.iter().sum::<i64>(),
+		);
+		account.write_second().push(10);
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	#[should_panic(expected = "MinMax { min: 6, max: 5 }")]
+	fn should_check_sub_invariants_of_projections() {
+		#[derive(Debug)]
+		struct Config {
+			range: MinMax,
+			name: String,
+		}
+		let mut repr = CacheableRepr::new(
+			Config { range: MinMax { min: 1, max: 5 }, name: "config".into() },
+			|c| !c.name.is_empty(),
+		);
+		{
+			let mut config = repr.write();
+			config.project(|c| &mut c.range, |mm| mm.min < mm.max).min = 4;
+			config.name = "renamed".into();
+		}
+		assert_eq!(4, repr.read().range.min);
+		repr.write().project(|c| &mut c.range, |mm| mm.min < mm.max).min = 6;
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	#[should_panic(expected = "min must be less than max")]
+	fn should_panic_with_the_message_of_violated_projections() {
+		let mut repr = CacheableRepr::new((MinMax { min: 1, max: 5 }, String::from("config")), |(_, name)| !name.is_empty());
+		let mut config = repr.write();
+		let mut range = config.project_with_msg(|(range, _)| range, |mm| mm.min < mm.max, "min must be less than max");
+		*range.project_with_msg(|mm| &mut mm.max, |max| *max > 0, "max must be positive") = 7;
+		range.min = 8;
+	}
+
+	#[test]
+	fn should_mutate_nested_fields_through_lenses() {
+		#[derive(Debug)]
+		struct Server {
+			range: MinMax,
+		}
+		let mut repr = CacheableRepr::new(
+			Server { range: MinMax { min: 1, max: 5 } },
+			|s| s.range.min < s.range.max,
+		);
+		assert_eq!(1, repr.lazy(|s| s.range.min));
+		*repr.at(lens!(Server => range.min)) = 4;
+		assert_eq!(4, repr.lazy(|s| s.range.min));
+		*repr.at(lens!(Server => range)).at(lens!(MinMax => max)) = 10;
+		assert_eq!(10, repr.read().range.max);
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	#[should_panic]
+	fn should_check_outer_invariant_after_lens_mutation() {
+		use crate::lens::Lens;
+		#[derive(Debug)]
+		struct Server {
+			range: MinMax,
+		}
+		let mut repr = Repr::new(
+			Server { range: MinMax { min: 1, max: 5 } },
+			|s| s.range.min < s.range.max,
+		);
+		*repr.at(lens!(Server => range).then(lens!(MinMax => min))) = 6;
+	}
+
+	#[test]
+	fn should_deref_for_reads() {
+		let mut repr = Repr::new(
+			MinMax { min: 1, max: 5 },
+			|mm| mm.min < mm.max,
+		);
+		assert_eq!(1, repr.min);
+		repr.write().min = 4;
+		assert_eq!(4, repr.min);
+		let cacheable = CacheableRepr::from(repr);
+		assert_eq!(5, cacheable.max);
+	}
+
+	#[test]
+	#[allow(clippy::mutable_key_type)] // the inner value can only be mutated through `&mut Repr`
+	fn should_compare_and_look_up_by_inner_value() {
+		let name = Repr::new(
+			String::from("Alice"),
+			|s| !s.is_empty(),
+		);
+		assert!(name == String::from("Alice"));
+		assert!(name < String::from("Bob"));
+		let mut ages = HashMap::new();
+		ages.insert(name, 30);
+		assert_eq!(Some(&30), ages.get(&String::from("Alice")));
+	}
+
+	#[test]
+	#[allow(clippy::mutable_key_type)] // the inner value can only be mutated through `&mut Repr`
+	fn should_order_by_inner_value() {
+		fn is_positive(n: &i32) -> bool { *n > 0 }
+		let set: BTreeSet<_> = [3, 1, 2].into_iter()
+			.map(|n| Repr::new(n, is_positive as fn(&i32) -> bool))
+			.collect();
+		assert_eq!(vec![1, 2, 3], set.iter().map(|r| *r.read()).collect::<Vec<_>>());
+		let mut reprs = [
+			CacheableRepr::new(2, is_positive),
+			CacheableRepr::new(1, is_positive),
+		];
+		reprs.sort();
+		assert_eq!(1, *reprs[0].read());
+	}
+
+	#[derive(Debug, Clone, PartialEq, Default)]
+	struct Percentage(u8);
+	impl Invariant for Percentage {
+		const VIOLATION_MESSAGE: &'static str = "percentages must be at most 100";
+		fn invariant(&self) -> bool {
+			self.0 <= 100
+		}
+	}
+
+	#[test]
+	fn should_default_with_trait_based_invariants() {
+		#[derive(Default)]
+		struct Progress {
+			done: Repr<Percentage>,
+			cached: CacheableRepr<Percentage>,
+		}
+		let mut progress = Progress::default();
+		assert_eq!(Percentage(0), *progress.done.read());
+		progress.done.write().0 = 50;
+		assert_eq!(50, progress.done.read().0);
+		assert_eq!(0, progress.cached.lazy(|p| p.0));
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	#[should_panic(expected = "percentages must be at most 100")]
+	fn should_check_trait_based_invariants() {
+		let mut done = Repr::<Percentage>::default();
+		done.write().0 = 101;
+	}
+
+	#[test]
+	#[should_panic]
+	fn should_panic_when_default_violates_invariant() {
+		#[derive(Debug, Default)]
+		struct NonEmpty(String);
+		impl Invariant for NonEmpty {
+			fn invariant(&self) -> bool {
+				!self.0.is_empty()
+			}
+		}
+		Repr::<NonEmpty>::default();
+	}
+
+	#[test]
+	fn should_try_to_create_trait_based_invariants() {
+		let done = Repr::try_new(Percentage(50)).unwrap();
+		assert_eq!(Percentage(50), *done.read());
+		let violation = CacheableRepr::try_new(Percentage(150)).unwrap_err();
+		assert_eq!("percentages must be at most 100", violation.message());
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	#[should_panic(expected = "at most 3 elements are allowed, but there were 4")]
+	fn should_explain_violations() {
+		let mut repr = CacheableRepr::new(
+			vec![1, 2, 3],
+			|v| v.len() <= 3,
+		).with_explanation(|v| format!("at most 3 elements are allowed, but there were {}", v.len()));
+		repr.write().push(4);
+	}
+
+	#[test]
+	fn should_find_minimal_failing_sequences() {
+		let repr = Repr::new(MinMax { min: 1, max: 3 }, |mm| mm.min < mm.max);
+		let failure = crate::testing::fuzz_operations(&repr, &[
+			|mm| mm.max += 1,
+			|mm| mm.max -= 1,
+			|mm| { mm.min += 1; mm.max += 1; },
+		], 100).unwrap_err();
+		assert_eq!(&[1, 1], failure.sequence());
+		assert_eq!("Invariant violated", failure.violation().message());
+		assert_eq!(MinMax { min: 1, max: 3 }, *repr.read());
+	}
+
+	#[test]
+	fn should_not_fail_when_operations_preserve_the_invariant() {
+		let repr = Repr::new(MinMax { min: 1, max: 3 }, |mm| mm.min < mm.max);
+		assert!(crate::testing::fuzz_operations(&repr, &[
+			|mm| mm.max += 1,
+			|mm| { mm.min -= 1; mm.max -= 1; },
+		], 100).is_ok());
+	}
+
+	crate::contract! {
+		#[derive(Debug, Clone, Default)]
+		struct Slice { start: usize, len: usize }
+		invariant(self) { self.start.checked_add(self.len).is_some() }
+		message "the slice must not overflow"
+	}
+
+	#[test]
+	fn should_declare_invariants_with_contracts() {
+		let mut slice = Repr::<Slice>::default();
+		slice.write().start = 10;
+		let violation = slice.set(Slice { start: usize::MAX, len: 1 }).unwrap_err().1;
+		assert_eq!("the slice must not overflow", violation.message());
+	}
+
+	crate::composite! {
+		#[derive(Debug)]
+		struct Window { range: Repr<MinMax>, cached: CacheableRepr<MinMax>, label: String }
+		nested(range, cached)
+		invariant(self) { self.range.max <= self.cached.min && !self.label.is_empty() }
+	}
+
+	#[test]
+	fn should_check_nested_reprs_with_composites() {
+		let window = |range: MinMax, cached: MinMax| Window {
+			range: Repr::new(range, |mm| mm.min < mm.max),
+			cached: CacheableRepr::new(cached, |mm| mm.min < mm.max),
+			label: "window".into(),
+		};
+		let mut repr = Repr::try_new(window(MinMax { min: 1, max: 5 }, MinMax { min: 5, max: 9 })).unwrap();
+		repr.write().cached.write().max = 10;
+		assert!(repr.set(window(MinMax { min: 1, max: 6 }, MinMax { min: 5, max: 9 })).is_err());
+		let mut invalid = window(MinMax { min: 1, max: 5 }, MinMax { min: 5, max: 9 });
+		invalid.range = Repr::new(MinMax { min: 2, max: 1 }, |mm| mm.min < mm.max);
+		assert_eq!("Invariant violated", repr.set(invalid).unwrap_err().1.message());
+		assert_eq!(10, repr.cached.max);
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_be_unwind_safe() {
+		let repr = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		let cacheable = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		let result = std::panic::catch_unwind(|| {
+			assert_eq!(repr.min, cacheable.min);
+			panic!("supervised");
+		});
+		assert!(result.is_err());
+		let result = std::panic::catch_unwind(move || {
+			let mut repr = repr;
+			repr.write().min = 6;
+		});
+		assert!(result.is_err());
+	}
+
+	#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+	#[test]
+	fn should_stay_unwind_safe_when_configured() {
+		#[derive(Default)]
+		struct Checks(std::sync::atomic::AtomicUsize);
+		impl crate::metrics::Metrics for Checks {
+			fn check(&self, _duration: core::time::Duration) {
+				self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+			}
+		}
+		let writes = Arc::new(std::sync::Mutex::new(Vec::new()));
+		let log = writes.clone();
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+			.with_metrics(Checks::default())
+			.with_explanation(|mm| format!("{} >= {}", mm.min, mm.max))
+			.with_eviction_callback(|_| {});
+		repr.after_write(move |mm| log.lock().unwrap().push(mm.min));
+		let result = std::panic::catch_unwind(|| {
+			assert_eq!(1, repr.lazy_keyed("min", |mm| mm.min));
+			panic!("supervised");
+		});
+		assert!(result.is_err());
+		repr.write().min = 2;
+		assert_eq!(vec![2], *writes.lock().unwrap());
+	}
+
+	#[test]
+	fn should_poison_instead_of_double_panicking() {
+		let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!(1, repr.lazy(|mm| mm.min));
+		let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			let mut mm = repr.write();
+			mm.min = 6;
+			let mut max = mm.project(|mm| &mut mm.max, |max| *max > 0);
+			*max = 0;
+			panic!("interrupted before setting max");
+		}));
+		assert!(result.is_err());
+		assert!(repr.is_poisoned());
+		assert_eq!(6, repr.lazy(|mm| mm.min));
+		assert!(repr.clear_poison().is_err());
+		repr.write().max = 10;
+		assert!(repr.clear_poison().is_ok());
+		assert!(!repr.is_poisoned());
+	}
+
+	#[test]
+	fn should_evict_the_oldest_fixed_cache_entry() {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		static READS: AtomicU32 = AtomicU32::new(0);
+		fn min(mm: &MinMax) -> i32 { READS.fetch_add(1, Ordering::Relaxed); mm.min }
+		fn max(mm: &MinMax) -> i32 { READS.fetch_add(1, Ordering::Relaxed); mm.max }
+		fn span(mm: &MinMax) -> i32 { READS.fetch_add(1, Ordering::Relaxed); mm.max - mm.min }
+		let mut repr: crate::FixedCacheableRepr<MinMax, i32, 2> = crate::FixedCacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+		assert_eq!(1, repr.lazy(min));
+		assert_eq!(5, repr.lazy(max));
+		assert_eq!(1, repr.lazy(min));
+		assert_eq!(2, READS.load(Ordering::Relaxed));
+		assert_eq!(4, repr.lazy(span));
+		assert_eq!(5, repr.lazy(max));
+		assert_eq!(1, repr.lazy(min));
+		assert_eq!(4, READS.load(Ordering::Relaxed));
+		repr.write().min = 2;
+		assert_eq!(3, repr.lazy(span));
+		assert_eq!(5, READS.load(Ordering::Relaxed));
+	}
+
+	#[cfg(feature = "tokio")]
+	mod eager {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		use std::time::Duration;
+		use crate::tests::MinMax;
+		use crate::{CacheableRepr, EagerCacheLookup};
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_read_from_cache() {
+			let mut repr = CacheableRepr::new(
+				MinMax { min: 1, max: 5 },
+				|mm| mm.min < mm.max,
+			);
+			fn get_min(mm: &MinMax) -> i32 {
+				mm.min
+			}
+			assert_eq!(1, repr.eager(get_min).await);
+			assert_eq!(1, repr.eager(get_min).await);
+			// repr.borrow_mut()n = 4;
+		}
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_invalidate_cache_on_mutation() {
+			let mut repr = CacheableRepr::new(
+				MinMax { min: 1, max: 5 },
+				|mm| mm.min < mm.max,
+			);
+			fn get_min(mm: &MinMax) -> i32 {
+				mm.min
+			}
+			assert_eq!(1, repr.eager(get_min).await);
+			assert_eq!(1, repr.eager(get_min).await);
+			repr.write().min = 4;
+			assert_eq!(4, repr.eager(get_min).await);
+			assert_eq!(4, repr.eager(get_min).await);
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_allow_static_closures_for_cache_reads() {
 			let mut repr = CacheableRepr::new(
 				MinMax { min: 1, max: 5 },
 				|mm| mm.min < mm.max,
@@ -420,106 +2172,1448 @@ mod tests {
 			assert_eq!(4, repr.eager(|mm| mm.min).await);
 		}
 
-		#[ignore]
+		#[ignore]
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_work_with_expensive_computations() {
+			let mut repr = CacheableRepr::new(
+				MinMax { min: 1, max: 40 },
+				|mm| mm.min < mm.max,
+			);
+
+			fn fib(n: u64) -> u64 {
+				if n <= 1 {
+					return n;
+				}
+				fib(n - 1) + fib(n - 2)
+			}
+			fn plain_fib(mm: &MinMax) -> u64 { fib(mm.max as u64) }
+			fn plus2(mm: &MinMax) -> u64 { fib((mm.max + 2) as u64) }
+			fn plus3(mm: &MinMax) -> u64 { fib((mm.max + 3) as u64) }
+			fn plus4(mm: &MinMax) -> u64 { fib((mm.max + 4) as u64) }
+			fn plus5(mm: &MinMax) -> u64 { fib((mm.max + 5) as u64) }
+
+			assert_eq!(102334155, repr.eager(plain_fib).await);
+			assert_eq!(267914296, repr.eager(plus2).await);
+			assert_eq!(433494437, repr.eager(plus3).await);
+			assert_eq!(701408733, repr.eager(plus4).await);
+			assert_eq!(1134903170, repr.eager(plus5).await);
+			repr.write().max = 42;
+			assert_eq!(267914296, repr.eager(plain_fib).await);
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_be_able_to_unregister_caches() {
+			let mut repr = CacheableRepr::new(
+				MinMax { min: 1, max: 5 },
+				|mm| mm.min < mm.max,
+			);
+			fn get_min(mm: &MinMax) -> i32 {
+				mm.min
+			}
+			fn get_min2(mm: &MinMax) -> i32 {
+				mm.min
+			}
+			assert_eq!(1, repr.eager(get_min).await);
+			assert_eq!(1, repr.eager(get_min2).await);
+			assert_eq!(1, repr.eager(get_min).await);
+			assert!(repr.unregister(get_min2));
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		#[should_panic(expected = "eager cache read function panicked: random panic")]
+		async fn should_propagate_panic_in_eager_cache() {
+			let mut repr = CacheableRepr::new(
+				MinMax { min: 1, max: 5 },
+				|mm| mm.min < mm.max,
+			);
+			fn get_min(mm: &MinMax) -> i32 {
+				mm.min
+			}
+			fn get_min2(mm: &MinMax) -> i32 {
+				if mm.min == 1 {
+					mm.min
+				} else {
+					panic!("random panic")
+				}
+			}
+			assert_eq!(1, repr.eager(get_min).await);
+			assert_eq!(1, repr.eager(get_min2).await);
+			assert_eq!(1, repr.eager(get_min).await);
+			repr.write().min = 2;
+			repr.flush().await;
+			repr.eager(get_min2).await;
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn counting_eager_cache_hits() {
+			#[derive(Debug, Clone)]
+			struct Person { name: String }
+			let mut repr = CacheableRepr::new(Person { name: "Alice and Bob together at last".into() }, |p| !p.name.is_empty());
+			static READ_SPY: AtomicU32 = AtomicU32::new(0);
+			fn expensive_read(p: &Person) -> usize {
+			  // Just for demonstration purposes.
+			  // Do not do side effects in your read functions!
+			  READ_SPY.fetch_add(1, Ordering::Relaxed);
+			  fib(p.name.len())
+			}
+			let fib_of_name_len = repr.eager(expensive_read).await;
+			assert_eq!(832040, fib_of_name_len);
+			// this does not recompute the fibonacci number, it just gets it from the cache!
+			let fib_of_name_len2 = repr.eager(expensive_read).await;
+			assert_eq!(832040, fib_of_name_len2);
+			assert_eq!(1, READ_SPY.load(Ordering::Relaxed));
+			repr.write().name = "Alice".into();
+			// once the background recomputation has finished we can see that a new value has been computed
+			repr.flush().await;
+			assert_eq!(2, READ_SPY.load(Ordering::Relaxed));
+			// Now when we fetch it again, we should see the new value without needing to recompute it
+			let fib_of_name_len3 = repr.eager(expensive_read).await;
+			assert_eq!(5, fib_of_name_len3);
+			assert_eq!(2, READ_SPY.load(Ordering::Relaxed));
+			fn fib(n: usize) -> usize {
+			  if n <= 1 { n } else { fib(n - 1) + fib(n - 2) }
+			}
+		}
+
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_recompute_on_custom_spawners() {
+			struct ThreadSpawner;
+			impl crate::cache::spawner::Spawner for ThreadSpawner {
+				fn spawn(&self, task: crate::cache::spawner::Task) {
+					std::thread::spawn(task);
+				}
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(ThreadSpawner);
+			assert_eq!(1, repr.eager(|mm| mm.min).await);
+			repr.write().min = 4;
+			assert_eq!(4, repr.eager(|mm| mm.min).await);
+		}
+
+
+		#[test]
+		fn should_recompute_on_an_injected_runtime_outside_of_it() {
+			let runtime = tokio::runtime::Runtime::new().unwrap();
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_tokio_handle(runtime.handle().clone());
+			assert_eq!(1, runtime.block_on(repr.eager(|mm| mm.min)));
+			std::thread::scope(|s| {
+				s.spawn(|| repr.write().min = 4);
+			});
+			assert_eq!(4, runtime.block_on(repr.eager(|mm| mm.min)));
+		}
+
+
+		#[test]
+		fn should_recompute_on_a_thread_pool_without_a_runtime() {
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(crate::cache::spawner::ThreadPoolSpawner::new(2));
+			assert_eq!(1, repr.eager_blocking(|mm| mm.min));
+			repr.write().min = 4;
+			assert_eq!(4, repr.eager_blocking(|mm| mm.min));
+		}
+
+
+		#[test]
+		fn should_bound_concurrent_recomputations() {
+			use std::sync::atomic::AtomicUsize;
+			static RUNNING: AtomicUsize = AtomicUsize::new(0);
+			static MAX_RUNNING: AtomicUsize = AtomicUsize::new(0);
+			fn slow<const N: i32>(mm: &MinMax) -> i32 {
+				let running = RUNNING.fetch_add(1, Ordering::SeqCst) + 1;
+				MAX_RUNNING.fetch_max(running, Ordering::SeqCst);
+				std::thread::sleep(Duration::from_millis(20));
+				RUNNING.fetch_sub(1, Ordering::SeqCst);
+				mm.min + N
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(crate::cache::spawner::ThreadPoolSpawner::new(8))
+				.with_max_concurrent_recomputations(2);
+			let reads = [slow::<1>, slow::<2>, slow::<3>, slow::<4>, slow::<5>, slow::<6>];
+			for read in reads {
+				repr.eager_blocking(read);
+			}
+			repr.write().min = 2;
+			std::thread::sleep(Duration::from_millis(500));
+			assert_eq!(2, MAX_RUNNING.load(Ordering::SeqCst));
+			for (n, read) in (1..).zip(reads) {
+				assert_eq!(2 + n, repr.eager_blocking(read));
+			}
+		}
+
+
+		#[test]
+		fn should_only_recompute_the_final_state_of_a_burst() {
+			static READS: AtomicU32 = AtomicU32::new(0);
+			fn min(mm: &MinMax) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
+				mm.min
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 0, max: 1000 }, |mm| mm.min < mm.max)
+				.with_spawner(crate::cache::spawner::ThreadPoolSpawner::new(2))
+				.with_eager_debounce(Duration::from_millis(50));
+			assert_eq!(0, repr.eager_blocking(min));
+			for _ in 0..100 {
+				repr.write().min += 1;
+			}
+			std::thread::sleep(Duration::from_millis(300));
+			assert_eq!(2, READS.load(Ordering::SeqCst));
+			assert_eq!(100, repr.eager_blocking(min));
+			assert_eq!(2, READS.load(Ordering::SeqCst));
+		}
+
+
+		#[test]
+		fn should_not_hold_a_spawner_thread_while_debouncing() {
+			let spawner = alloc::sync::Arc::new(crate::cache::spawner::ThreadPoolSpawner::new(1));
+			let mut debounced = CacheableRepr::new(MinMax { min: 0, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(spawner.clone())
+				.with_eager_debounce(Duration::from_secs(60));
+			assert_eq!(0, debounced.eager_blocking(|mm| mm.min));
+			debounced.write().min = 1;
+			let mut repr = CacheableRepr::new(MinMax { min: 0, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(spawner);
+			assert_eq!(5, repr.eager_blocking(|mm| mm.max));
+		}
+
+		#[test]
+		fn should_discard_recomputations_superseded_by_newer_writes() {
+			fn slow_min(mm: &MinMax) -> i32 {
+				if mm.min == 1 {
+					std::thread::sleep(Duration::from_millis(200));
+				}
+				mm.min
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 0, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(crate::cache::spawner::ThreadPoolSpawner::new(2));
+			assert_eq!(0, repr.eager_blocking(slow_min));
+			repr.write().min = 1;
+			std::thread::sleep(Duration::from_millis(20));
+			repr.write().min = 2;
+			std::thread::sleep(Duration::from_millis(400));
+			assert_eq!(2, repr.eager_blocking(slow_min));
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn eager_fresh_should_await_the_background_recomputation() {
+			static READS: AtomicU32 = AtomicU32::new(0);
+			fn slow_min(mm: &MinMax) -> i32 {
+				std::thread::sleep(Duration::from_millis(50));
+				READS.fetch_add(1, Ordering::SeqCst);
+				mm.min
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			assert_eq!(1, repr.eager_fresh(slow_min).await);
+			repr.write().min = 3;
+			assert_eq!(3, repr.eager_fresh(slow_min).await);
+			assert_eq!(2, READS.load(Ordering::SeqCst));
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn flush_should_wait_for_every_eager_cache() {
+			static READS: AtomicU32 = AtomicU32::new(0);
+			fn slow_min(mm: &MinMax) -> i32 {
+				std::thread::sleep(Duration::from_millis(50));
+				READS.fetch_add(1, Ordering::SeqCst);
+				mm.min
+			}
+			fn slow_max(mm: &MinMax) -> i32 {
+				std::thread::sleep(Duration::from_millis(50));
+				READS.fetch_add(1, Ordering::SeqCst);
+				mm.max
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			repr.eager(slow_min).await;
+			repr.eager(slow_max).await;
+			repr.write().max = 6;
+			repr.flush().await;
+			assert_eq!(4, READS.load(Ordering::SeqCst));
+			assert_eq!(6, repr.eager(slow_max).await);
+			assert_eq!(4, READS.load(Ordering::SeqCst));
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_discard_recomputations_when_dropped() {
+			static READS: AtomicU32 = AtomicU32::new(0);
+			fn get_min(mm: &MinMax) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
+				mm.min
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_eager_debounce(Duration::from_millis(50));
+			assert_eq!(1, repr.eager(get_min).await);
+			repr.write().min = 2;
+			drop(repr);
+			tokio::time::sleep(Duration::from_millis(200)).await;
+			assert_eq!(1, READS.load(Ordering::SeqCst));
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_keep_recomputing_after_drop_when_opted_out() {
+			static READS: AtomicU32 = AtomicU32::new(0);
+			fn get_min(mm: &MinMax) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
+				mm.min
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_eager_debounce(Duration::from_millis(50))
+				.with_discard_on_drop(false);
+			assert_eq!(1, repr.eager(get_min).await);
+			repr.write().min = 2;
+			drop(repr);
+			tokio::time::sleep(Duration::from_millis(200)).await;
+			assert_eq!(2, READS.load(Ordering::SeqCst));
+		}
+
+		#[test]
+		fn should_debounce_onto_the_runtime_the_spawner_was_created_in() {
+			static READS: AtomicU32 = AtomicU32::new(0);
+			fn get_min(mm: &MinMax) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
+				mm.min
+			}
+			let runtime = tokio::runtime::Runtime::new().unwrap();
+			let spawner = runtime.block_on(async { crate::cache::spawner::TokioSpawner::default() });
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(spawner)
+				.with_eager_debounce(Duration::from_millis(10));
+			assert_eq!(1, repr.eager_blocking(get_min));
+			// Neither this thread nor the timer thread is in the runtime.
+			repr.write().min = 2;
+			std::thread::sleep(Duration::from_millis(200));
+			assert_eq!(2, READS.load(Ordering::SeqCst));
+			assert_eq!(2, repr.eager_blocking(get_min));
+			assert_eq!(2, READS.load(Ordering::SeqCst));
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_cache_async_read_functions() {
+			static READS: AtomicU32 = AtomicU32::new(0);
+			async fn get_min(mm: crate::sync::Arc<MinMax>) -> i32 {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+				READS.fetch_add(1, Ordering::SeqCst);
+				mm.min
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			assert_eq!(1, repr.eager_async(get_min).await);
+			assert_eq!(1, repr.eager_async(get_min).await);
+			assert_eq!(1, READS.load(Ordering::SeqCst));
+			repr.write().min = 3;
+			repr.flush().await;
+			assert_eq!(2, READS.load(Ordering::SeqCst));
+			assert_eq!(3, repr.eager_async(get_min).await);
+			assert_eq!(2, READS.load(Ordering::SeqCst));
+		}
+
+		#[test]
+		fn should_poll_async_recomputations_on_the_runtime() {
+			use std::sync::atomic::AtomicBool;
+			static WAITING: AtomicBool = AtomicBool::new(false);
+			static RELEASED: tokio::sync::Notify = tokio::sync::Notify::const_new();
+			async fn wait(mm: crate::sync::Arc<MinMax>) -> i32 {
+				if mm.min > 1 {
+					WAITING.store(true, Ordering::SeqCst);
+					RELEASED.notified().await;
+				}
+				mm.min
+			}
+			async fn release(mm: crate::sync::Arc<MinMax>) -> i32 {
+				if mm.min > 1 {
+					while !WAITING.load(Ordering::SeqCst) {
+						tokio::time::sleep(Duration::from_millis(1)).await;
+					}
+					RELEASED.notify_one();
+				}
+				mm.max
+			}
+			// With a single blocking thread, whichever recomputation ran first would hold it while
+			// waiting for the other one if the spawner blocked on them.
+			let runtime = tokio::runtime::Builder::new_multi_thread().max_blocking_threads(1).enable_all().build().unwrap();
+			runtime.block_on(async {
+				let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+				assert_eq!(1, repr.eager_async(wait).await);
+				assert_eq!(5, repr.eager_async(release).await);
+				repr.write().min = 2;
+				tokio::time::timeout(Duration::from_secs(5), repr.flush()).await.unwrap();
+				assert_eq!(2, repr.eager_async(wait).await);
+				assert_eq!(5, repr.eager_async(release).await);
+			});
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_share_the_snapshot_with_async_read_functions() {
+			static CLONES: AtomicU32 = AtomicU32::new(0);
+			#[derive(Debug)]
+			struct Counted(i32);
+			impl Clone for Counted {
+				fn clone(&self) -> Self {
+					CLONES.fetch_add(1, Ordering::SeqCst);
+					Self(self.0)
+				}
+			}
+			async fn get(counted: crate::sync::Arc<Counted>) -> i32 {
+				counted.0
+			}
+			fn double(counted: &Counted) -> i32 {
+				counted.0 * 2
+			}
+			let mut repr = CacheableRepr::new(Counted(1), |counted| counted.0 > 0);
+			assert_eq!(1, repr.eager_async(get).await);
+			assert_eq!(2, repr.eager(double).await);
+			let before = CLONES.load(Ordering::SeqCst);
+			repr.write().0 = 2;
+			repr.flush().await;
+			// Cloned once for the snapshot, which both recomputations share.
+			assert_eq!(before + 1, CLONES.load(Ordering::SeqCst));
+			assert_eq!(2, repr.eager_async(get).await);
+			assert_eq!(4, repr.eager(double).await);
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_defer_low_priority_recomputations() {
+			use crate::cache::eager::Priority;
+			use std::sync::Mutex;
+			static LOG: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+			fn urgent(mm: &MinMax) -> i32 {
+				std::thread::sleep(Duration::from_millis(50));
+				LOG.lock().unwrap().push("high");
+				mm.min
+			}
+			fn best_effort(mm: &MinMax) -> i32 {
+				LOG.lock().unwrap().push("low");
+				mm.max
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			repr.eager_with_priority(best_effort, Priority::Low).await;
+			repr.eager_with_priority(urgent, Priority::High).await;
+			LOG.lock().unwrap().clear();
+			repr.write().min = 2;
+			repr.flush().await;
+			assert_eq!(vec!["high", "low"], *LOG.lock().unwrap());
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_wake_every_low_priority_recomputation() {
+			use crate::cache::eager::Priority;
+			use crate::cache::spawner::ThreadPoolSpawner;
+			fn slow(mm: &MinMax) -> i32 {
+				std::thread::sleep(Duration::from_millis(50));
+				mm.min
+			}
+			fn low_a(mm: &MinMax) -> i32 {
+				mm.max
+			}
+			fn low_b(mm: &MinMax) -> i32 {
+				mm.max + 1
+			}
+			fn low_c(mm: &MinMax) -> i32 {
+				mm.max + 2
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(ThreadPoolSpawner::new(8));
+			repr.eager(slow).await;
+			for low in [low_a, low_b, low_c] {
+				repr.eager_with_priority(low, Priority::Low).await;
+			}
+			repr.write().max = 6;
+			tokio::time::timeout(Duration::from_secs(10), repr.flush()).await
+				.expect("every deferred recomputation should be woken");
+			assert_eq!(8, repr.eager(low_c).await);
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_not_deadlock_a_single_thread_deferring_low_priority_recomputations() {
+			use crate::cache::eager::Priority;
+			use crate::cache::spawner::ThreadPoolSpawner;
+			fn high(mm: &MinMax) -> i32 {
+				mm.min
+			}
+			fn low(mm: &MinMax) -> i32 {
+				mm.max
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(ThreadPoolSpawner::new(1))
+				.with_eager_debounce(Duration::from_millis(20));
+			repr.eager_with_priority(high, Priority::High).await;
+			repr.eager_with_priority(low, Priority::Low).await;
+			repr.write().max = 6;
+			tokio::time::timeout(Duration::from_secs(10), repr.flush()).await
+				.expect("the only thread should never wait for the high priority recomputation");
+			assert_eq!(1, repr.eager(high).await);
+			assert_eq!(6, repr.eager(low).await);
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_order_debounced_recomputations_by_priority() {
+			use crate::cache::eager::Priority;
+			use crate::cache::spawner::ThreadPoolSpawner;
+			use std::sync::Mutex;
+			static LOG: Mutex<Vec<(&'static str, i32)>> = Mutex::new(Vec::new());
+			fn urgent(mm: &MinMax) -> i32 {
+				std::thread::sleep(Duration::from_millis(50));
+				LOG.lock().unwrap().push(("high", mm.min));
+				mm.min
+			}
+			fn best_effort(mm: &MinMax) -> i32 {
+				LOG.lock().unwrap().push(("low", mm.min));
+				mm.max
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 0, max: 100 }, |mm| mm.min < mm.max)
+				.with_spawner(ThreadPoolSpawner::new(2))
+				.with_eager_debounce(Duration::from_millis(20));
+			repr.eager_with_priority(best_effort, Priority::Low).await;
+			repr.eager_with_priority(urgent, Priority::High).await;
+			LOG.lock().unwrap().clear();
+			for _ in 0..10 {
+				repr.write().min += 1;
+			}
+			tokio::time::timeout(Duration::from_secs(10), repr.flush()).await
+				.expect("the debounced recomputations should finish");
+			// Each cache is recomputed once for the whole burst, the low priority one last.
+			assert_eq!(vec![("high", 10), ("low", 10)], *LOG.lock().unwrap());
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_read_registered_caches_with_either_strategy() {
+			use crate::cache::eager::{Priority, Strategy};
+			static READS: AtomicU32 = AtomicU32::new(0);
+			fn get_min(mm: &MinMax) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
+				mm.min
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			repr.register(get_min, Strategy::Eager { priority: Priority::Normal });
+			repr.flush().await;
+			assert_eq!(1, READS.load(Ordering::SeqCst));
+			repr.write().min = 2;
+			repr.flush().await;
+			assert_eq!(2, READS.load(Ordering::SeqCst));
+			assert_eq!(2, repr.get(get_min));
+			assert_eq!(2, READS.load(Ordering::SeqCst));
+
+			repr.register(get_min, Strategy::Lazy);
+			repr.write().min = 3;
+			repr.flush().await;
+			assert_eq!(2, READS.load(Ordering::SeqCst));
+			assert_eq!(3, repr.get(get_min));
+			assert_eq!(3, repr.get(get_min));
+			assert_eq!(3, READS.load(Ordering::SeqCst));
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_count_eager_cache_stats() {
+			fn slow_min(mm: &MinMax) -> i32 {
+				std::thread::sleep(Duration::from_millis(10));
+				mm.min
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			assert_eq!(1, repr.eager(slow_min).await);
+			repr.write().min = 2;
+			repr.flush().await;
+			assert_eq!(2, repr.eager(slow_min).await);
+			let stats = repr.cache_stats_of(slow_min).unwrap();
+			assert_eq!((2, 0, 2), (stats.hits, stats.misses, stats.recomputations));
+			assert!(stats.last_recompute >= Duration::from_millis(10));
+			assert!(stats.total_recompute >= Duration::from_millis(20));
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_peek_eager_caches_without_computing() {
+			fn slow_min(mm: &MinMax) -> i32 {
+				std::thread::sleep(Duration::from_millis(50));
+				mm.min
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			assert_eq!(None, repr.peek(slow_min));
+			assert_eq!(1, repr.eager(slow_min).await);
+			assert_eq!(Some(1), repr.peek(slow_min));
+			repr.write().min = 2;
+			assert_eq!(None, repr.peek(slow_min));
+			repr.flush().await;
+			assert_eq!(Some(2), repr.peek(slow_min));
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_cache_capturing_closures_eagerly_by_key() {
+			let reads = std::sync::Arc::new(AtomicU32::new(0));
+			let read = |reads: std::sync::Arc<AtomicU32>| move |mm: &MinMax| {
+				reads.fetch_add(1, Ordering::SeqCst);
+				mm.max - mm.min
+			};
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			assert_eq!(4, repr.eager_keyed("range", read(reads.clone())).await);
+			assert_eq!(4, repr.eager_keyed("range", read(reads.clone())).await);
+			repr.write().min = 2;
+			repr.flush().await;
+			assert_eq!(3, repr.eager_keyed("range", read(reads.clone())).await);
+			assert_eq!(2, reads.load(Ordering::SeqCst));
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_snapshot_the_value_once_per_write() {
+			use std::sync::atomic::{AtomicU32, Ordering};
+			static CLONES: AtomicU32 = AtomicU32::new(0);
+			#[derive(Debug)]
+			struct Document(Vec<i32>);
+			impl Clone for Document {
+				fn clone(&self) -> Self {
+					CLONES.fetch_add(1, Ordering::SeqCst);
+					Document(self.0.clone())
+				}
+			}
+			fn len(doc: &Document) -> usize {
+				doc.0.len()
+			}
+			fn sum(doc: &Document) -> i32 {
+				doc.0.iter().sum()
+			}
+			let mut repr = CacheableRepr::new(Document(vec![1, 2]), |doc| !doc.0.is_empty());
+			assert_eq!(2, repr.eager(len).await);
+			assert_eq!(3, repr.eager(sum).await);
+			assert_eq!(2, CLONES.load(Ordering::SeqCst));
+			repr.write().0.push(3);
+			repr.flush().await;
+			assert_eq!(3, repr.eager(len).await);
+			assert_eq!(6, repr.eager(sum).await);
+			assert_eq!(3, CLONES.load(Ordering::SeqCst));
+		}
+
 		#[tokio::test(flavor = "multi_thread")]
-		async fn should_work_with_expensive_computations() {
-			let mut repr = CacheableRepr::new(
-				MinMax { min: 1, max: 40 },
-				|mm| mm.min < mm.max,
-			);
+		async fn should_recompute_derived_caches_after_their_inputs() {
+			use crate::cache::CacheKey;
+			use std::sync::atomic::{AtomicU32, Ordering};
+			static READS: AtomicU32 = AtomicU32::new(0);
+			fn range(mm: &MinMax) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
+				std::thread::sleep(Duration::from_millis(20));
+				mm.max - mm.min
+			}
+			fn doubled(range: &i32) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
+				range * 2
+			}
+			fn label(doubled: &i32) -> String {
+				READS.fetch_add(1, Ordering::SeqCst);
+				format!("{doubled} units")
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			assert_eq!(4, repr.eager(range).await);
+			assert_eq!(8, repr.eager_derived(CacheKey::of(range), doubled).await);
+			assert_eq!("8 units", repr.eager_derived(CacheKey::of(doubled), label).await);
+			assert_eq!(3, READS.load(Ordering::SeqCst));
+			repr.write().min = 2;
+			repr.flush().await;
+			assert_eq!(6, READS.load(Ordering::SeqCst));
+			assert_eq!("6 units", repr.eager_derived(CacheKey::of(doubled), label).await);
+			assert_eq!(3, repr.cache_stats().len());
+			// Derived caches are removed along with their inputs.
+			assert!(repr.unregister(range));
+			assert!(repr.cache_stats().is_empty());
+		}
 
-			fn fib(n: u64) -> u64 {
-				if n <= 1 {
-					return n;
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_wake_every_cache_derived_from_one_input() {
+			use crate::cache::spawner::ThreadPoolSpawner;
+			use crate::cache::CacheKey;
+			fn range(mm: &MinMax) -> i32 {
+				std::thread::sleep(Duration::from_millis(50));
+				mm.max - mm.min
+			}
+			fn doubled(range: &i32) -> i32 {
+				range * 2
+			}
+			fn tripled(range: &i32) -> i32 {
+				range * 3
+			}
+			fn negated(range: &i32) -> i32 {
+				-range
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(ThreadPoolSpawner::new(8));
+			repr.eager(range).await;
+			for derive in [doubled, tripled, negated] {
+				repr.eager_derived(CacheKey::of(range), derive).await;
+			}
+			repr.write().min = 2;
+			tokio::time::timeout(Duration::from_secs(10), repr.flush()).await
+				.expect("every derived recomputation should be woken");
+			assert_eq!(9, repr.eager_derived(CacheKey::of(range), tripled).await);
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_not_deadlock_a_single_thread_waiting_for_the_input_of_a_derived_cache() {
+			use crate::cache::eager::Priority;
+			use crate::cache::spawner::ThreadPoolSpawner;
+			use crate::cache::CacheKey;
+			fn urgent(mm: &MinMax) -> i32 {
+				mm.min
+			}
+			fn range(mm: &MinMax) -> i32 {
+				mm.max - mm.min
+			}
+			fn doubled(range: &i32) -> i32 {
+				range * 2
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(ThreadPoolSpawner::new(1));
+			repr.eager_with_priority(urgent, Priority::High).await;
+			// The input is deferred behind the high priority cache, so the derived cache is handed to
+			// the spawner before it.
+			repr.eager_with_priority(range, Priority::Low).await;
+			repr.eager_derived(CacheKey::of(range), doubled).await;
+			repr.write().min = 2;
+			tokio::time::timeout(Duration::from_secs(10), repr.flush()).await
+				.expect("the only thread should never wait for the input of a derived cache");
+			assert_eq!(6, repr.eager_derived(CacheKey::of(range), doubled).await);
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_key_eager_cached_reads_by_type() {
+			use crate::cache::CachedRead;
+			struct Range;
+			impl CachedRead<MinMax> for Range {
+				type Output = i32;
+				fn read(mm: &MinMax) -> i32 {
+					mm.max - mm.min
 				}
-				fib(n - 1) + fib(n - 2)
 			}
-			fn plain_fib(mm: &MinMax) -> u64 { fib(mm.max as u64) }
-			fn plus2(mm: &MinMax) -> u64 { fib((mm.max + 2) as u64) }
-			fn plus3(mm: &MinMax) -> u64 { fib((mm.max + 3) as u64) }
-			fn plus4(mm: &MinMax) -> u64 { fib((mm.max + 4) as u64) }
-			fn plus5(mm: &MinMax) -> u64 { fib((mm.max + 5) as u64) }
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			assert_eq!(4, repr.eager_of::<Range>().await);
+			repr.write().min = 2;
+			repr.flush().await;
+			assert_eq!(3, repr.eager_of::<Range>().await);
+			let stats = repr.cache_stats()[&crate::cache::CacheKey::of_type::<Range>()];
+			assert_eq!((2, 0, 2), (stats.hits, stats.misses, stats.recomputations));
+		}
 
-			assert_eq!(102334155, repr.eager(plain_fib).await);
-			assert_eq!(267914296, repr.eager(plus2).await);
-			assert_eq!(433494437, repr.eager(plus3).await);
-			assert_eq!(701408733, repr.eager(plus4).await);
-			assert_eq!(1134903170, repr.eager(plus5).await);
-			repr.write().max = 42;
-			assert_eq!(267914296, repr.eager(plain_fib).await);
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_copy_eager_caches_into_warm_clones() {
+			use crate::cache::CacheKey;
+			use std::sync::atomic::{AtomicU32, Ordering};
+			static READS: AtomicU32 = AtomicU32::new(0);
+			fn range(mm: &MinMax) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
+				mm.max - mm.min
+			}
+			fn doubled(range: &i32) -> i32 {
+				range * 2
+			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			repr.eager(range).await;
+			assert_eq!(8, repr.eager_derived(CacheKey::of(range), doubled).await);
+			let mut clone = repr.clone_with_caches();
+			assert_eq!(4, clone.eager(range).await);
+			assert_eq!(1, READS.load(Ordering::SeqCst));
+			clone.write().min = 2;
+			// The derived cache of the clone is derived from the clone's own input.
+			assert_eq!(6, clone.eager_derived(CacheKey::of(range), doubled).await);
+			assert_eq!(8, repr.eager_derived(CacheKey::of(range), doubled).await);
 		}
 
 		#[tokio::test(flavor = "multi_thread")]
-		async fn should_be_able_to_unregister_caches() {
-			let mut repr = CacheableRepr::new(
-				MinMax { min: 1, max: 5 },
-				|mm| mm.min < mm.max,
-			);
+		async fn should_report_eager_metrics() {
+			use crate::cache::CacheKey;
+			use crate::metrics::Metrics;
+			use std::sync::{Arc, Mutex};
+			#[derive(Default)]
+			struct Recorded {
+				reads: Mutex<Vec<bool>>,
+				pending: Mutex<Vec<usize>>,
+			}
+			impl Metrics for Recorded {
+				fn cache_read(&self, _key: &CacheKey, hit: bool) {
+					self.reads.lock().unwrap().push(hit);
+				}
+				fn pending_eager_tasks(&self, pending: usize) {
+					self.pending.lock().unwrap().push(pending);
+				}
+			}
+			fn range(mm: &MinMax) -> i32 {
+				mm.max - mm.min
+			}
+			let recorded = Arc::new(Recorded::default());
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_metrics(recorded.clone());
+			assert_eq!(4, repr.eager(range).await);
+			assert_eq!(4, repr.eager(range).await);
+			repr.write().min = 2;
+			assert_eq!(3, repr.eager(range).await);
+			// The first read waits for the initial computation, and reads during a recomputation
+			// call the read function themselves.
+			assert_eq!(vec![true, true], recorded.reads.lock().unwrap()[..2]);
+			assert_eq!(3, recorded.reads.lock().unwrap().len());
+			assert_eq!(1, recorded.pending.lock().unwrap().len());
+			assert!(recorded.pending.lock().unwrap()[0] <= 1);
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_compute_eager_caches_registered_when_built() {
+			use std::sync::atomic::{AtomicU32, Ordering};
+			static READS: AtomicU32 = AtomicU32::new(0);
 			fn get_min(mm: &MinMax) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
 				mm.min
 			}
-			fn get_min2(mm: &MinMax) -> i32 {
-				mm.min
+			fn get_max(mm: &MinMax) -> i32 {
+				READS.fetch_add(1, Ordering::SeqCst);
+				mm.max
 			}
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_eager_cache(get_min)
+				.with_eager_cache(get_max);
+			repr.flush().await;
+			assert_eq!(2, READS.load(Ordering::SeqCst));
 			assert_eq!(1, repr.eager(get_min).await);
-			assert_eq!(1, repr.eager(get_min2).await);
-			assert_eq!(1, repr.eager(get_min).await);
-			assert!(repr.unregister(get_min2));
+			assert_eq!(5, repr.eager(get_max).await);
+			assert_eq!(2, READS.load(Ordering::SeqCst));
 		}
 
-		#[ignore]
 		#[tokio::test(flavor = "multi_thread")]
-		#[should_panic]
-		async fn should_propagate_panic_in_eager_cache() {
-			let mut repr = CacheableRepr::new(
-				MinMax { min: 1, max: 5 },
-				|mm| mm.min < mm.max,
-			);
-			fn get_min(mm: &MinMax) -> i32 {
-				mm.min
+		async fn should_send_recomputed_results_to_watchers() {
+			fn get_max(mm: &MinMax) -> i32 {
+				mm.max
 			}
-			fn get_min2(mm: &MinMax) -> i32 {
-				if mm.min == 1 {
-					mm.min
-				} else {
-					panic!("random panic")
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			let mut first = repr.watch(get_max).await;
+			let mut second = repr.watch(get_max).await;
+			assert_eq!(5, *first.borrow());
+			repr.write().max = 8;
+			first.changed().await.unwrap();
+			second.changed().await.unwrap();
+			assert_eq!(8, *first.borrow_and_update());
+			assert_eq!(8, *second.borrow_and_update());
+			repr.unregister(get_max);
+			assert!(first.changed().await.is_err());
+		}
+	}
+	#[cfg(feature = "serde")]
+	mod serialization {
+		use serde::de::DeserializeSeed;
+		use serde::{Deserialize, Serialize};
+		use crate::serde_support::CacheableReprSeed;
+		use crate::{CacheableRepr, Invariant, Repr};
+
+		#[test]
+		fn should_serialize_transparently() {
+			#[derive(Debug, Serialize)]
+			struct Range {
+				min: i32,
+				max: i32,
+			}
+			#[derive(Serialize)]
+			struct Config {
+				range: Repr<Range, fn(&Range) -> bool>,
+				name: CacheableRepr<String>,
+			}
+			let config = Config {
+				range: Repr::new(Range { min: 1, max: 5 }, |r| r.min < r.max),
+				name: CacheableRepr::new("config".into(), |s: &String| !s.is_empty()),
+			};
+			let json = serde_json::to_string(&config).unwrap();
+			assert_eq!(r#"{"range":{"min":1,"max":5},"name":"config"}"#, json);
+		}
+
+		#[test]
+		fn should_deserialize_trait_based_invariants() {
+			#[derive(Debug, Deserialize)]
+			struct Range {
+				min: i32,
+				max: i32,
+			}
+			impl Invariant for Range {
+				const VIOLATION_MESSAGE: &'static str = "min must be less than max";
+				fn invariant(&self) -> bool {
+					self.min < self.max
 				}
 			}
-			assert_eq!(1, repr.eager(get_min).await);
-			assert_eq!(1, repr.eager(get_min2).await);
-			assert_eq!(1, repr.eager(get_min).await);
+			#[derive(Deserialize)]
+			struct Config {
+				range: Repr<Range>,
+				cached_range: CacheableRepr<Range>,
+			}
+			let config: Config = serde_json::from_str(r#"{"range":{"min":1,"max":5},"cached_range":{"min":2,"max":3}}"#).unwrap();
+			assert_eq!(1, config.range.read().min);
+			assert_eq!(3, config.cached_range.read().max);
+			let error = serde_json::from_str::<Config>(r#"{"range":{"min":5,"max":1},"cached_range":{"min":2,"max":3}}"#)
+				.err().unwrap();
+			assert!(error.to_string().starts_with("min must be less than max"));
+		}
+
+		#[test]
+		fn should_deserialize_with_seeds() {
+			let seed = CacheableReprSeed::new(|s: &String| !s.is_empty());
+			let mut json = serde_json::Deserializer::from_str(r#""config""#);
+			let repr = seed.deserialize(&mut json).unwrap();
+			assert_eq!(6, repr.lazy(|s| s.len()));
+			let seed = CacheableReprSeed::new(|s: &String| !s.is_empty());
+			let mut json = serde_json::Deserializer::from_str(r#""""#);
+			assert!(seed.deserialize(&mut json).is_err());
+		}
+
+		#[test]
+		fn should_round_trip_refinement_types() {
+			use crate::prelude::{Bounded, NonEmptyString, NonEmptyVec, Percentage, SortedVec};
+			#[derive(Debug, Serialize, Deserialize)]
+			struct Profile {
+				name: NonEmptyString,
+				level: Bounded<1, 5>,
+				progress: Percentage,
+				aliases: NonEmptyVec<String>,
+				scores: SortedVec<u32>,
+			}
+			let profile: Profile = serde_json::from_str(r#"{"name":"a","level":3,"progress":12.5,"aliases":["b"],"scores":[3,1,2]}"#).unwrap();
+			assert_eq!(&[1, 2, 3], &*profile.scores);
+			let json = serde_json::to_string(&profile).unwrap();
+			assert_eq!(r#"{"name":"a","level":3,"progress":12.5,"aliases":["b"],"scores":[1,2,3]}"#, json);
+			for (valid, invalid) in [(r#""a""#, r#""""#), ("3", "6"), ("12.5", "101"), (r#"["b"]"#, "[]")] {
+				let json = json.replacen(valid, invalid, 1);
+				assert!(serde_json::from_str::<Profile>(&json).is_err(), "{json}");
+			}
+		}
+
+		#[test]
+		fn should_only_restore_caches_saved_for_the_same_value() {
+			use crate::cache::persist::SavedCache;
+			use crate::cache::{CacheKey, CachedRead};
+			struct Longest;
+			impl CachedRead<Vec<String>> for Longest {
+				type Output = usize;
+				fn read(words: &Vec<String>) -> usize {
+					words.iter().map(String::len).max().unwrap_or(0)
+				}
+			}
+			let new_repr = |words: &[&str]| CacheableRepr::new(words.iter().map(|w| w.to_string()).collect(), |w: &Vec<String>| w.len() < 10);
+			let repr = new_repr(&["a", "abc"]);
+			assert!(repr.save_cache::<Longest>().is_none());
+			assert_eq!(3, repr.lazy_of::<Longest>());
+			let json = serde_json::to_string(&repr.save_cache::<Longest>().unwrap()).unwrap();
+
+			let restarted = new_repr(&["a", "abc"]);
+			assert!(restarted.restore_cache::<Longest>(serde_json::from_str(&json).unwrap()));
+			assert_eq!(3, restarted.lazy_of::<Longest>());
+			let stats = restarted.cache_stats()[&CacheKey::of_type::<Longest>()];
+			assert_eq!((1, 0), (stats.hits, stats.misses));
+
+			let changed = new_repr(&["a", "abcd"]);
+			let saved: SavedCache<usize> = serde_json::from_str(&json).unwrap();
+			assert!(!changed.restore_cache::<Longest>(saved));
+			assert_eq!(4, changed.lazy_of::<Longest>());
+		}
+	}
+
+	#[cfg(feature = "schemars")]
+	mod schema {
+		use schemars::{schema_for, JsonSchema};
+		use crate::{CacheableRepr, Repr};
+
+		#[test]
+		fn should_use_the_inner_schema() {
+			#[derive(Debug, JsonSchema)]
+			#[allow(dead_code)]
+			struct Range {
+				min: i32,
+				max: i32,
+			}
+			#[derive(JsonSchema)]
+			#[allow(dead_code)]
+			struct Config {
+				range: Repr<Range>,
+				name: CacheableRepr<String>,
+			}
+			let schema = schema_for!(Config);
+			let properties = schema.get("properties").unwrap();
+			assert_eq!(schema_for!(String).get("type"), properties["name"].get("type"));
+			assert_eq!(Some(&"#/$defs/Range".into()), properties["range"].get("$ref"));
+		}
+	}
+
+	#[cfg(feature = "validator")]
+	mod validation {
+		use validator::Validate;
+		use crate::{CacheableRepr, Repr};
+
+		#[derive(Debug, Validate)]
+		struct SignupData {
+			#[validate(email)]
+			mail: String,
+			#[validate(range(min = 18))]
+			age: u32,
+		}
+
+		#[test]
+		fn should_use_validate_as_the_invariant() {
+			let mut repr = CacheableRepr::from_validate(SignupData { mail: "alice@example.com".into(), age: 30 });
+			repr.write().age = 40;
+			assert_eq!(40, repr.lazy(|s| s.age));
+			assert!(repr.replace(SignupData { mail: "alice@example.com".into(), age: 17 }).is_err());
+		}
+
+		#[test]
+		fn should_use_validation_errors_as_the_violation_message() {
+			let repr = Repr::from_validate(SignupData { mail: "alice".into(), age: 30 });
+			let violation = repr.validate().unwrap_err();
+			assert!(violation.message().contains("mail"));
+			assert!(!violation.message().contains("age"));
+		}
+	}
+
+	#[cfg(feature = "garde")]
+	mod garde_validation {
+		use garde::Validate;
+		use crate::{CacheableRepr, Repr};
+
+		#[derive(Debug, Validate)]
+		struct User {
+			#[garde(length(min = 3))]
+			username: String,
+			#[garde(range(min = 18))]
+			age: u32,
+		}
+
+		#[test]
+		fn should_use_garde_as_the_invariant() {
+			let mut repr = CacheableRepr::from_garde(User { username: "alice".into(), age: 30 });
+			repr.write().age = 40;
+			assert_eq!(40, repr.lazy(|u| u.age));
+			assert!(repr.replace(User { username: "al".into(), age: 40 }).is_err());
+		}
+
+		#[test]
+		fn should_use_the_garde_report_as_the_violation_message() {
+			let repr = Repr::from_garde(User { username: "al".into(), age: 30 });
+			let violation = repr.validate().unwrap_err();
+			assert!(violation.message().contains("username"));
+			assert!(!violation.message().contains("age"));
+		}
+
+		#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+		#[test]
+		#[should_panic(expected = "too long")]
+		fn should_validate_with_the_garde_context() {
+			struct Limits {
+				max_len: usize,
+			}
+			#[derive(Debug, Validate)]
+			#[garde(context(Limits))]
+			struct Post {
+				#[garde(custom(|body: &str, limits: &Limits| {
+					if body.len() <= limits.max_len { Ok(()) } else { Err(garde::Error::new("too long")) }
+				}))]
+				body: String,
+			}
+			let mut repr = Repr::from_garde_with(Post { body: "hi".into() }, Limits { max_len: 5 });
+			repr.write().body.push_str("!!!");
+			repr.write().body.push('!');
+		}
+	}
+
+	#[cfg(feature = "proptest")]
+	mod property {
+		use proptest::prelude::*;
+		use crate::Repr;
+		use crate::proptest_support::check_operations;
+		use crate::tests::MinMax;
+
+		fn min_max() -> impl Strategy<Value = MinMax> {
+			(-100..100i32, -100..100i32).prop_map(|(min, max)| MinMax { min, max })
+		}
+
+		proptest! {
+			#[test]
+			fn should_only_generate_valid_values(repr in Repr::strategy(min_max(), |mm: &MinMax| mm.min < mm.max)) {
+				prop_assert!(repr.is_valid());
+			}
+
+			#[test]
+			fn should_pass_when_operations_preserve_the_invariant(shifts in prop::collection::vec(-10..10i32, 0..20)) {
+				let mut repr = Repr::new(MinMax { min: 1, max: 5 }, |mm: &MinMax| mm.min < mm.max);
+				check_operations(&mut repr, &shifts, |mm, shift| {
+					mm.min += shift;
+					mm.max += shift;
+				})?;
+				prop_assert_eq!(4, repr.max - repr.min);
+			}
+		}
+
+		#[test]
+		fn should_report_the_operation_that_violated_the_invariant() {
+			let mut repr = Repr::new(MinMax { min: 1, max: 5 }, |mm: &MinMax| mm.min < mm.max);
+			let err = check_operations(&mut repr, &[1, 2, 3], |mm, n| mm.min += n).unwrap_err();
+			assert!(err.to_string().contains("Operation 2 (3)"));
+			assert_eq!(4, repr.min);
+		}
+	}
+
+	#[cfg(feature = "quickcheck")]
+	mod arbitrary {
+		use quickcheck::{Arbitrary, Gen, QuickCheck};
+		use crate::Repr;
+		use crate::tests::Percentage;
+
+		impl Arbitrary for Percentage {
+			fn arbitrary(g: &mut Gen) -> Self {
+				Percentage(u8::arbitrary(g))
+			}
+			fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+				Box::new(self.0.shrink().map(Percentage))
+			}
+		}
+
+		#[test]
+		fn should_only_generate_valid_values() {
+			fn prop(repr: Repr<Percentage>) -> bool {
+				repr.is_valid()
+			}
+			QuickCheck::new().quickcheck(prop as fn(Repr<Percentage>) -> bool);
+		}
+
+		#[test]
+		fn should_only_shrink_to_valid_values() {
+			let repr = Repr::try_new(Percentage(80)).unwrap();
+			assert!(repr.shrink().all(|shrunk| shrunk.is_valid() && shrunk.0 < 80));
+		}
+	}
+
+	#[cfg(feature = "history")]
+	mod history {
+		use crate::tests::MinMax;
+		use crate::CacheableRepr;
+
+		#[test]
+		fn should_keep_the_last_committed_values() {
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 10 }, |mm| mm.min < mm.max).with_history(3);
+			for min in 2..=5 {
+				repr.write().min = min;
+			}
+			assert!(repr.set(MinMax { min: 10, max: 1 }).is_err());
+			let mins: Vec<i32> = repr.history().map(|snapshot| snapshot.value().min).collect();
+			assert_eq!(vec![3, 4, 5], mins);
+			let times: Vec<_> = repr.history().map(|snapshot| snapshot.at()).collect();
+			assert!(times.windows(2).all(|pair| pair[0] <= pair[1]));
+		}
+
+		#[test]
+		fn should_keep_nothing_without_capacity() {
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 10 }, |mm| mm.min < mm.max).with_history(0);
+			repr.write().min = 2;
+			assert_eq!(0, repr.history().len());
+		}
+	}
+	#[cfg(feature = "stream")]
+	mod events {
+		use futures::executor::block_on;
+		use futures::StreamExt;
+		use crate::CacheableRepr;
+
+		#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+		#[test]
+		fn should_only_send_committed_writes() {
+			use std::panic::{catch_unwind, AssertUnwindSafe};
+			use crate::tests::MinMax;
+			use crate::Repr;
+			let mut repr = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+			let events = repr.events();
 			repr.write().min = 2;
+			let _ = catch_unwind(AssertUnwindSafe(|| repr.write().min = 10));
+			assert!(repr.replace(MinMax { min: 5, max: 1 }).is_err());
+			drop(repr);
+			let values: Vec<i32> = block_on(events.map(|event| event.value().min).collect());
+			assert_eq!(vec![2], values);
+		}
+
+		#[test]
+		fn should_send_swaps_to_both_reprs() {
+			fn double(n: &i32) -> i32 {
+				n * 2
+			}
+			let mut a = CacheableRepr::new(1, |n| *n > 0);
+			let mut b = CacheableRepr::new(2, |n| *n > 0);
+			let mut a_events = a.events();
+			let mut b_events = b.events();
+			let dropped = a.events();
+			drop(dropped);
+			assert_eq!(2, a.lazy(double));
+			a.swap(&mut b).unwrap();
+			assert_eq!(4, a.lazy(double));
+			assert_eq!(2, **block_on(a_events.next()).unwrap().value());
+			assert_eq!(1, **block_on(b_events.next()).unwrap().value());
 		}
+	}
+	#[cfg(feature = "shared")]
+	mod shared {
+		use crate::shared::SharedReadGuard;
+		use crate::{Repr, SharedRepr};
 
 		#[tokio::test(flavor = "multi_thread")]
-		async fn counting_eager_cache_hits() {
-			#[derive(Debug, Clone)]
-			struct Person { name: String }
-			let mut repr = CacheableRepr::new(Person { name: "Alice and Bob together at last".into() }, |p| !p.name.is_empty());
-			static READ_SPY: AtomicU32 = AtomicU32::new(0);
-			fn expensive_read(p: &Person) -> usize {
-			  // Just for demonstration purposes.
-			  // Do not do side effects in your read functions!
-			  READ_SPY.fetch_add(1, Ordering::Relaxed);
-			  fib(p.name.len())
+		async fn should_retry_optimistic_writes_from_many_tasks() {
+			let counter = SharedRepr::new(Repr::new(0, |n: &i32| *n >= 0));
+			let tasks: Vec<_> = (0..8).map(|_| {
+				let counter = counter.clone();
+				tokio::spawn(async move {
+					loop {
+						let (next, version) = {
+							let guard = counter.read().await.unwrap();
+							(*guard + 1, SharedReadGuard::version(&guard))
+						};
+						tokio::task::yield_now().await;
+						if let Ok(value) = counter.try_write_if_version(version).await {
+							*value.unwrap() = next;
+							break;
+						}
+					}
+				})
+			}).collect();
+			for task in tasks {
+				task.await.unwrap();
 			}
-			let fib_of_name_len = repr.eager(expensive_read).await;
-			assert_eq!(832040, fib_of_name_len);
-			// this does not recompute the fibonacci number, it just gets it from the cache!
-			let fib_of_name_len2 = repr.eager(expensive_read).await;
-			assert_eq!(832040, fib_of_name_len2);
-			assert_eq!(1, READ_SPY.load(Ordering::Relaxed));
-			repr.write().name = "Alice".into();
-			// if we wait a bit we can see that a new value has been computed
-			tokio::time::sleep(Duration::from_millis(100)).await;
-			assert_eq!(2, READ_SPY.load(Ordering::Relaxed));
-			// Now when we fetch it again, we should see the new value without needing to recompute it
-			let fib_of_name_len3 = repr.eager(expensive_read).await;
-			assert_eq!(5, fib_of_name_len3);
-			assert_eq!(2, READ_SPY.load(Ordering::Relaxed));
-			fn fib(n: usize) -> usize {
-			  if n <= 1 { n } else { fib(n - 1) + fib(n - 2) }
+			assert_eq!(8, *counter.read().await.unwrap());
+			assert_eq!(8, counter.try_unwrap().unwrap().version());
+		}
+
+		#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_check_the_invariant_when_the_write_guard_is_dropped() {
+			use crate::tests::MinMax;
+			let repr = SharedRepr::new(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max));
+			let handle = repr.clone();
+			let result = tokio::spawn(async move {
+				handle.write().await.unwrap().min = 10;
+			}).await;
+			assert!(result.unwrap_err().is_panic());
+			assert!(repr.try_unwrap().is_ok());
+		}
+
+		#[cfg(not(all(feature = "release-unchecked", not(debug_assertions))))]
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_poison_every_handle_when_a_write_is_rejected() {
+			use crate::tests::MinMax;
+			let repr = SharedRepr::new(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max));
+			let writer = repr.clone();
+			let reader = repr.clone();
+			let result = tokio::spawn(async move {
+				writer.write().await.unwrap().min = 10;
+			}).await;
+			assert!(result.unwrap_err().is_panic());
+			assert!(reader.read().await.is_err());
+			// The value can still be repaired through the poisoned guard.
+			repr.write().await.unwrap_err().into_inner().min = 1;
+			assert_eq!(1, reader.read().await.unwrap_err().into_inner().min);
+			assert!(repr.try_write_if_version(1).await.unwrap().is_err());
+			drop(reader);
+			assert!(repr.try_unwrap().unwrap().is_valid());
+		}
+	}
+	#[cfg(feature = "actor")]
+	mod actor {
+		use crate::actor::ReprActor;
+		use crate::tests::MinMax;
+		use crate::Repr;
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_apply_messages_one_at_a_time() {
+			let counter = ReprActor::spawn(Repr::new(0, |n: &i32| *n >= 0));
+			let tasks: Vec<_> = (0..8).map(|_| {
+				let counter = counter.clone();
+				tokio::spawn(async move { counter.update(|n| *n += 1).await.unwrap() })
+			}).collect();
+			for task in tasks {
+				task.await.unwrap();
+			}
+			assert_eq!(8, counter.read(|n| *n).await);
+		}
+
+		#[tokio::test(flavor = "multi_thread")]
+		async fn should_reply_with_violations_and_keep_the_value() {
+			let actor = ReprActor::spawn(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max));
+			assert_eq!(10, actor.update(|mm| { mm.max = 10; mm.max }).await.unwrap());
+			assert!(actor.update(|mm| mm.min = 20).await.is_err());
+			let (rejected, _) = actor.set(MinMax { min: 3, max: 2 }).await.unwrap_err();
+			assert_eq!(3, rejected.min);
+			actor.set(MinMax { min: 2, max: 3 }).await.unwrap();
+			assert_eq!(MinMax { min: 2, max: 3 }, actor.read(|mm| *mm).await);
+		}
+	}
+	#[cfg(all(loom, feature = "eager-core"))]
+	mod loom_model {
+		use crate::cache::eager::{block_on, recompute, CacheableRead, EagerCache, EagerSettings, Priority, Snapshot, Upstream};
+		use crate::cache::spawner::{Spawner, Task};
+		use crate::cache::CacheKey;
+		use crate::sync::atomic::{AtomicU64, Ordering};
+		use crate::sync::lock::RwLock;
+		use crate::sync::{thread, Arc};
+		use crate::{CacheableRepr, EagerCacheLookup};
+		use core::time::Duration;
+
+		/// Runs every task on a new loom thread, so the model explores every interleaving of the
+		/// recomputations with each other and with the writer. Loom models at most
+		/// [`loom::MAX_THREADS`] threads, so each model spawns at most four tasks.
+		struct LoomSpawner;
+		impl Spawner for LoomSpawner {
+			fn spawn(&self, task: Task) {
+				thread::spawn(task);
+			}
+		}
+
+		/// Checks the model with a bounded number of preemptions, as the models that go through a
+		/// [`CacheableRepr`] can't be checked exhaustively in reasonable time.
+		fn model(f: impl Fn() + Send + Sync + 'static) {
+			let mut builder = loom::model::Builder::new();
+			builder.preemption_bound = Some(4);
+			builder.check(f);
+		}
+
+		fn settings() -> EagerSettings {
+			EagerSettings { spawner: Some(alloc::sync::Arc::new(LoomSpawner)), debounce: Duration::ZERO, discard_on_drop: true }
+		}
+
+		#[test]
+		fn should_never_observe_a_partially_recomputed_cache() {
+			loom::model(|| {
+				let cell = Arc::new(RwLock::new(None));
+				let generation = Arc::new(AtomicU64::new(0));
+				let writer = {
+					let cell = cell.clone();
+					let generation = generation.clone();
+					thread::spawn(move || recompute(&cell, &generation, 0, |n: &i32| Ok(n * 2), &21))
+				};
+				let seen = cell.read().clone();
+				assert!(seen.is_none() || seen == Some(Ok(42)));
+				assert!(matches!(writer.join().unwrap(), Ok(true)));
+				assert_eq!(Some(Ok(42)), *cell.read());
+			});
+		}
+
+		#[test]
+		fn should_discard_superseded_recomputations() {
+			loom::model(|| {
+				let cell = Arc::new(RwLock::new(None));
+				let generation = Arc::new(AtomicU64::new(0));
+				let stale = {
+					let cell = cell.clone();
+					let generation = generation.clone();
+					thread::spawn(move || recompute(&cell, &generation, 0, |n: &i32| Ok(n * 2), &1))
+				};
+				{
+					let mut writer = cell.write();
+					generation.fetch_add(1, Ordering::SeqCst);
+					*writer = None;
+				}
+				assert!(matches!(recompute(&cell, &generation, 1, |n: &i32| Ok(n * 2), &2), Ok(true)));
+				assert!(stale.join().unwrap().is_ok());
+				assert_eq!(Some(Ok(4)), *cell.read());
+			});
+		}
+
+		#[test]
+		fn should_recompute_the_latest_value_once_a_recomputation_completes() {
+			fn double(n: &i32) -> i32 { n * 2 }
+			model(|| {
+				let settings = settings();
+				let spawner = settings.spawner();
+				let cache = CacheableRead::<i32, i32>::new(double, true);
+				let (first, completion) = cache.update(&mut Snapshot::new(&1), &spawner).unwrap();
+				settings.schedule(vec![(first, vec![])]);
+				// Either the first recomputation hasn't taken the value yet and recomputes this one
+				// instead, or a second recomputation is needed.
+				let second = cache.update(&mut Snapshot::new(&2), &spawner);
+				assert_ne!(Some(2), cache.cached());
+				if let Some((second, completion)) = second {
+					settings.schedule(vec![(second, vec![])]);
+					block_on(completion);
+				}
+				block_on(completion);
+				assert_eq!(Some(4), cache.cached());
+			});
+		}
+
+		#[test]
+		fn should_wait_for_the_recomputation_of_an_upstream_cache() {
+			fn double(n: &i32) -> i32 { n * 2 }
+			model(|| {
+				let settings = settings();
+				let cache = CacheableRead::<i32, i32>::new(double, true);
+				let upstream = cache.upstream().downcast::<Upstream<i32>>().unwrap();
+				let (recomputation, _) = cache.update(&mut Snapshot::new(&21), &settings.spawner()).unwrap();
+				settings.schedule(vec![(recomputation, vec![])]);
+				assert_eq!(42, upstream.wait());
+			});
+		}
+
+		#[test]
+		fn should_start_low_priority_recomputations_once_the_others_complete() {
+			static URGENT: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+			fn urgent(n: &i32) -> i32 {
+				URGENT.store(*n, std::sync::atomic::Ordering::SeqCst);
+				*n
+			}
+			fn after_urgent(n: &i32) -> bool {
+				URGENT.load(std::sync::atomic::Ordering::SeqCst) == *n
 			}
+			model(|| {
+				let mut repr = CacheableRepr::new(1, |n: &i32| *n > 0).with_spawner(LoomSpawner);
+				block_on(repr.eager_with_priority(urgent, Priority::High));
+				block_on(repr.eager_with_priority(after_urgent, Priority::Low));
+				*repr.write() = 2;
+				assert!(block_on(repr.eager_fresh(after_urgent)));
+			});
+		}
+
+		#[test]
+		fn should_hand_derived_recomputations_back_once_their_input_completes() {
+			fn double(n: &i32) -> i32 { n * 2 }
+			fn label(n: &i32) -> i32 { n + 1 }
+			model(|| {
+				let mut repr = CacheableRepr::new(1, |n: &i32| *n > 0).with_spawner(LoomSpawner);
+				assert_eq!(2, block_on(repr.eager(double)));
+				*repr.write() = 2;
+				// The derived cache is computed while its input may still be being recomputed.
+				assert_eq!(5, block_on(repr.eager_derived(CacheKey::of(double), label)));
+			});
+		}
+	}
+
+	#[cfg(all(feature = "async-std", feature = "smol", feature = "rayon"))]
+	mod runtimes {
+		use crate::cache::spawner::{AsyncStdSpawner, SmolSpawner};
+		use crate::tests::MinMax;
+		use crate::{CacheableRepr, EagerCacheLookup};
+
+		#[test]
+		fn should_recompute_on_async_std() {
+			async_std::task::block_on(async {
+				let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+					.with_spawner(AsyncStdSpawner);
+				assert_eq!(1, repr.eager(|mm| mm.min).await);
+				repr.write().min = 4;
+				assert_eq!(4, repr.eager(|mm| mm.min).await);
+			});
+		}
+
+		#[test]
+		fn should_recompute_on_smol() {
+			smol::block_on(async {
+				let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+					.with_spawner(SmolSpawner);
+				assert_eq!(1, repr.eager(|mm| mm.min).await);
+				repr.write().min = 4;
+				assert_eq!(4, repr.eager(|mm| mm.min).await);
+			});
+		}
+
+
+		#[test]
+		fn should_recompute_on_rayon() {
+			let mut repr = CacheableRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+				.with_spawner(crate::cache::spawner::RayonSpawner);
+			fn min(mm: &MinMax) -> i32 { mm.min }
+			fn max(mm: &MinMax) -> i32 { mm.max }
+			assert_eq!(1, repr.eager_blocking(min));
+			assert_eq!(5, repr.eager_blocking(max));
+			repr.write().min = 4;
+			repr.write().max = 8;
+			assert_eq!(4, repr.eager_blocking(min));
+			assert_eq!(8, repr.eager_blocking(max));
 		}
 	}
 }
@@ -0,0 +1,234 @@
+//! Thread-safe representation invariants, whose lock guards check the invariant when a write is
+//! released. See [`ReprMutex`] and [`ReprRwLock`].
+use crate::sync::atomic::{AtomicBool, Ordering};
+use crate::sync::lock::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::Repr;
+use core::fmt::Debug;
+use core::ops::{Deref, DerefMut};
+use std::sync::{LockResult, PoisonError};
+
+/// Whether a thread panicked while holding a write guard. This is tracked here rather than by the
+/// underlying lock, because parking_lot's and tokio's locks are never poisoned.
+pub(crate) struct Poison(AtomicBool);
+impl Poison {
+	pub(crate) fn new() -> Self {
+		Self(AtomicBool::new(false))
+	}
+	/// Hands out the guard, in a [`PoisonError`] if the lock is poisoned.
+	pub(crate) fn result<G>(&self, guard: G) -> LockResult<G> {
+		if self.0.load(Ordering::Acquire) {
+			Err(PoisonError::new(guard))
+		} else {
+			Ok(guard)
+		}
+	}
+}
+
+/// Poisons the lock if it's dropped while panicking, i.e. if the thread panicked while holding the
+/// write guard or the invariant check of the write guard panicked.
+pub(crate) struct PoisonOnPanic<'a>(pub(crate) &'a Poison);
+impl Drop for PoisonOnPanic<'_> {
+	fn drop(&mut self) {
+		if std::thread::panicking() {
+			self.0 .0.store(true, Ordering::Release);
+		}
+	}
+}
+
+/// A representation invariant behind a [`std::sync::Mutex`], so it can be written from many
+/// threads without nesting a [`crate::repr::ReprMutator`] in a mutex guard. The invariant is
+/// checked when the guard is dropped.
+///
+/// Like a [`std::sync::Mutex`], the lock is poisoned if a thread panics while holding the guard,
+/// including when the invariant is violated. The guard is still handed out (in the
+/// [`PoisonError`]) so the value can be repaired. This is the same with the `parking_lot` feature,
+/// where the lock is a parking_lot mutex.
+/// ```rust
+/// use std::sync::Arc;
+/// use std::thread;
+/// use repr_rs::{Repr, ReprMutex};
+/// let total = Arc::new(ReprMutex::new(Repr::new(0, |n: &i32| *n >= 0)));
+/// let threads: Vec<_> = (0..4).map(|_| {
+///   let total = total.clone();
+///   thread::spawn(move || *total.lock().unwrap() += 1)
+/// }).collect();
+/// for thread in threads {
+///   thread.join().unwrap();
+/// }
+/// assert_eq!(4, *total.lock().unwrap());
+/// ```
+pub struct ReprMutex<T: Debug, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	inner: Mutex<Repr<T, I>>,
+	poison: Poison,
+}
+impl<T: Debug, I: Fn(&T) -> bool> ReprMutex<T, I> {
+	/// Puts the representation invariant behind a mutex.
+	pub fn new(repr: Repr<T, I>) -> Self {
+		Self {
+			inner: Mutex::new(repr),
+			poison: Poison::new(),
+		}
+	}
+	/// Blocks until the mutex is acquired, then borrows a mutable view of the value. The invariant
+	/// is checked when the guard is dropped, whether or not the value was written.
+	pub fn lock(&self) -> LockResult<ReprMutexGuard<'_, T, I>> {
+		self.poison.result(ReprMutexGuard::new(self.inner.lock(), &self.poison))
+	}
+	/// Consumes the mutex, returning the representation invariant. Poisoning is ignored: if a
+	/// write panicked, [`Repr::validate`] tells whether the value still satisfies the invariant.
+	pub fn into_inner(self) -> Repr<T, I> {
+		self.inner.into_inner()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for ReprMutex<T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self.inner.try_lock() {
+			Some(repr) => write!(f, "ReprMutex({:?})", repr.read()),
+			None => write!(f, "ReprMutex(<locked>)"),
+		}
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> From<Repr<T, I>> for ReprMutex<T, I> {
+	fn from(repr: Repr<T, I>) -> Self {
+		Self::new(repr)
+	}
+}
+
+/// A mutable view of the value of a [`ReprMutex`]. The invariant is checked when it's dropped,
+/// before the mutex is unlocked.
+pub struct ReprMutexGuard<'a, T: Debug, I: Fn(&T) -> bool> {
+	guard: MutexGuard<'a, Repr<T, I>>,
+	poison: &'a Poison,
+}
+impl<'a, T: Debug, I: Fn(&T) -> bool> ReprMutexGuard<'a, T, I> {
+	fn new(mut guard: MutexGuard<'a, Repr<T, I>>, poison: &'a Poison) -> Self {
+		guard.begin_write();
+		Self { guard, poison }
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for ReprMutexGuard<'_, T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		Debug::fmt(&**self, f)
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Deref for ReprMutexGuard<'_, T, I> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.guard.read_unchecked()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> DerefMut for ReprMutexGuard<'_, T, I> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.guard.inner.get_mut()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Drop for ReprMutexGuard<'_, T, I> {
+	fn drop(&mut self) {
+		let _poison = PoisonOnPanic(self.poison);
+		self.guard.check();
+	}
+}
+
+/// A representation invariant behind a [`std::sync::RwLock`]. Any number of threads can read the
+/// value at once, and the invariant is checked when a write guard is dropped. Poisoning works like
+/// with a [`ReprMutex`].
+/// ```rust
+/// use repr_rs::{Repr, ReprRwLock};
+/// #[derive(Debug)]
+/// struct MinMax { min: i32, max: i32 }
+/// let lock = ReprRwLock::new(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max));
+/// lock.write().unwrap().max = 10;
+/// let (a, b) = (lock.read().unwrap(), lock.read().unwrap());
+/// assert_eq!(a.max, b.max);
+/// ```
+pub struct ReprRwLock<T: Debug, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	inner: RwLock<Repr<T, I>>,
+	poison: Poison,
+}
+impl<T: Debug, I: Fn(&T) -> bool> ReprRwLock<T, I> {
+	/// Puts the representation invariant behind a read-write lock.
+	pub fn new(repr: Repr<T, I>) -> Self {
+		Self {
+			inner: RwLock::new(repr),
+			poison: Poison::new(),
+		}
+	}
+	/// Blocks until no thread is writing, then borrows a read-only view of the value.
+	pub fn read(&self) -> LockResult<ReprRwLockReadGuard<'_, T, I>> {
+		self.poison.result(ReprRwLockReadGuard { guard: self.inner.read() })
+	}
+	/// Blocks until no other thread is reading or writing, then borrows a mutable view of the
+	/// value. The invariant is checked when the guard is dropped.
+	pub fn write(&self) -> LockResult<ReprRwLockWriteGuard<'_, T, I>> {
+		self.poison.result(ReprRwLockWriteGuard::new(self.inner.write(), &self.poison))
+	}
+	/// Consumes the lock, returning the representation invariant. Poisoning is ignored: if a
+	/// write panicked, [`Repr::validate`] tells whether the value still satisfies the invariant.
+	pub fn into_inner(self) -> Repr<T, I> {
+		self.inner.into_inner()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for ReprRwLock<T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self.inner.try_read() {
+			Some(repr) => write!(f, "ReprRwLock({:?})", repr.read()),
+			None => write!(f, "ReprRwLock(<locked>)"),
+		}
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> From<Repr<T, I>> for ReprRwLock<T, I> {
+	fn from(repr: Repr<T, I>) -> Self {
+		Self::new(repr)
+	}
+}
+
+/// A read-only view of the value of a [`ReprRwLock`].
+pub struct ReprRwLockReadGuard<'a, T: Debug, I: Fn(&T) -> bool> {
+	guard: RwLockReadGuard<'a, Repr<T, I>>,
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for ReprRwLockReadGuard<'_, T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		Debug::fmt(&**self, f)
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Deref for ReprRwLockReadGuard<'_, T, I> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.guard.read()
+	}
+}
+
+/// A mutable view of the value of a [`ReprRwLock`]. The invariant is checked when it's dropped,
+/// before the lock is released.
+pub struct ReprRwLockWriteGuard<'a, T: Debug, I: Fn(&T) -> bool> {
+	guard: RwLockWriteGuard<'a, Repr<T, I>>,
+	poison: &'a Poison,
+}
+impl<'a, T: Debug, I: Fn(&T) -> bool> ReprRwLockWriteGuard<'a, T, I> {
+	fn new(mut guard: RwLockWriteGuard<'a, Repr<T, I>>, poison: &'a Poison) -> Self {
+		guard.begin_write();
+		Self { guard, poison }
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for ReprRwLockWriteGuard<'_, T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		Debug::fmt(&**self, f)
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Deref for ReprRwLockWriteGuard<'_, T, I> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.guard.read_unchecked()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> DerefMut for ReprRwLockWriteGuard<'_, T, I> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.guard.inner.get_mut()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Drop for ReprRwLockWriteGuard<'_, T, I> {
+	fn drop(&mut self) {
+		let _poison = PoisonOnPanic(self.poison);
+		self.guard.check();
+	}
+}
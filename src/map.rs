@@ -0,0 +1,398 @@
+//! Representation invariants over maps, which check inserted and modified entries on their own.
+//! See [`ReprMap`].
+use crate::Violation;
+use alloc::collections::BTreeMap;
+use core::fmt::Debug;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::hash::Hash;
+
+mod sealed {
+	pub trait Sealed {}
+}
+
+/// A map that a [`ReprMap`] can wrap, i.e. a [`HashMap`] (with the `std` feature) or a
+/// [`BTreeMap`]. This trait is sealed.
+pub trait Map: Debug + sealed::Sealed {
+	/// The type of the keys.
+	type Key: Debug;
+	/// The type of the values.
+	type Value: Debug;
+	#[doc(hidden)]
+	fn empty() -> Self;
+	#[doc(hidden)]
+	fn get(&self, key: &Self::Key) -> Option<&Self::Value>;
+	#[doc(hidden)]
+	fn get_mut(&mut self, key: &Self::Key) -> Option<&mut Self::Value>;
+	#[doc(hidden)]
+	fn insert(&mut self, key: Self::Key, value: Self::Value) -> Option<Self::Value>;
+	#[doc(hidden)]
+	fn remove_entry(&mut self, key: &Self::Key) -> Option<(Self::Key, Self::Value)>;
+	#[doc(hidden)]
+	fn all(&self, f: impl FnMut(&Self::Key, &Self::Value) -> bool) -> bool;
+}
+#[cfg(feature = "std")]
+impl<K, V> sealed::Sealed for HashMap<K, V> {}
+#[cfg(feature = "std")]
+impl<K: Debug + Eq + Hash, V: Debug> Map for HashMap<K, V> {
+	type Key = K;
+	type Value = V;
+	fn empty() -> Self {
+		Self::new()
+	}
+	fn get(&self, key: &K) -> Option<&V> {
+		HashMap::get(self, key)
+	}
+	fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+		HashMap::get_mut(self, key)
+	}
+	fn insert(&mut self, key: K, value: V) -> Option<V> {
+		HashMap::insert(self, key, value)
+	}
+	fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+		HashMap::remove_entry(self, key)
+	}
+	fn all(&self, mut f: impl FnMut(&K, &V) -> bool) -> bool {
+		self.iter().all(|(key, value)| f(key, value))
+	}
+}
+impl<K, V> sealed::Sealed for BTreeMap<K, V> {}
+impl<K: Debug + Ord, V: Debug> Map for BTreeMap<K, V> {
+	type Key = K;
+	type Value = V;
+	fn empty() -> Self {
+		Self::new()
+	}
+	fn get(&self, key: &K) -> Option<&V> {
+		BTreeMap::get(self, key)
+	}
+	fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+		BTreeMap::get_mut(self, key)
+	}
+	fn insert(&mut self, key: K, value: V) -> Option<V> {
+		BTreeMap::insert(self, key, value)
+	}
+	fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+		BTreeMap::remove_entry(self, key)
+	}
+	fn all(&self, mut f: impl FnMut(&K, &V) -> bool) -> bool {
+		self.iter().all(|(key, value)| f(key, value))
+	}
+}
+
+/// A [`ReprMap`] over a [`HashMap`].
+#[cfg(feature = "std")]
+pub type ReprHashMap<K, V, E = fn(&K, &V) -> bool, C = fn(&HashMap<K, V>) -> bool> = ReprMap<HashMap<K, V>, E, C>;
+/// A [`ReprMap`] over a [`BTreeMap`].
+pub type ReprBTreeMap<K, V, E = fn(&K, &V) -> bool, C = fn(&BTreeMap<K, V>) -> bool> = ReprMap<BTreeMap<K, V>, E, C>;
+
+/// A map with an invariant for every entry and an (optional) invariant for the whole map, e.g.
+/// that the values sum to 100%. Only the entries that are inserted or modified are checked against
+/// the entry invariant, followed by one check of the map invariant. Inserts and removals that
+/// would violate either invariant are rolled back and return the [`Violation`]. Values modified in
+/// place (through [`ReprMap::get_mut`] or [`ReprMap::entry`]) can't be rolled back: a violation
+/// panics when the mutable view is dropped, and the map keeps the modified value.
+/// ```rust
+/// use repr_rs::ReprBTreeMap;
+/// let mut shares = ReprBTreeMap::with_msg(|_: &&str, share: &u32| *share > 0, "invalid shares")
+///   .with_map_invariant(|shares| shares.values().sum::<u32>() <= 100);
+/// shares.insert("alice", 60).unwrap();
+/// shares.insert("bob", 40).unwrap();
+/// let (rejected, _violation) = shares.insert("carol", 10).unwrap_err();
+/// assert_eq!(10, rejected);
+/// assert!(shares.insert("alice", 0).is_err());
+/// *shares.get_mut(&"alice").unwrap() -= 10;
+/// assert_eq!(Some(&50), shares.read().get("alice"));
+/// ```
+pub struct ReprMap<M: Map, E: Fn(&M::Key, &M::Value) -> bool = fn(&<M as Map>::Key, &<M as Map>::Value) -> bool, C: Fn(&M) -> bool = fn(&M) -> bool> {
+	map: M,
+	entry: E,
+	collection: C,
+	violation_message: &'static str,
+}
+impl<M: Map, E: Fn(&M::Key, &M::Value) -> bool> ReprMap<M, E> {
+	/// Creates an empty map whose entries must satisfy the given invariant function.
+	pub fn new(entry: E) -> Self {
+		Self::with_msg(entry, "Invariant violated")
+	}
+	/// Creates an empty map whose entries must satisfy the given invariant function, with the given
+	/// violation message.
+	pub fn with_msg(entry: E, violation_message: &'static str) -> Self {
+		Self {
+			map: M::empty(),
+			entry,
+			collection: |_| true,
+			violation_message,
+		}
+	}
+}
+impl<M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> ReprMap<M, E, C> {
+	/// Adds an invariant for the whole map, which is checked after every write.
+	///
+	/// # Panics
+	/// If the current entries violate it.
+	pub fn with_map_invariant<D: Fn(&M) -> bool>(self, collection: D) -> ReprMap<M, E, D> {
+		let repr = ReprMap {
+			map: self.map,
+			entry: self.entry,
+			collection,
+			violation_message: self.violation_message,
+		};
+		if let Err(violation) = repr.validate() {
+			panic!("{}", violation);
+		}
+		repr
+	}
+	/// Borrows a read-only view of the map.
+	#[inline]
+	pub fn read(&self) -> &M {
+		&self.map
+	}
+	/// Inserts an entry if it satisfies the entry invariant and the map still satisfies the map
+	/// invariant, returning the value it replaced. Otherwise the new value is handed back alongside
+	/// the [`Violation`] and the map is left untouched.
+	pub fn insert(&mut self, key: M::Key, value: M::Value) -> Result<Option<M::Value>, (M::Value, Violation)> where M::Key: Clone {
+		if let Err(violation) = self.validate_entry(&key, &value) {
+			return Err((value, violation));
+		}
+		let replaced = self.map.insert(key.clone(), value);
+		if let Err(violation) = self.validate_map() {
+			let value = match replaced {
+				Some(replaced) => self.map.insert(key, replaced),
+				None => self.map.remove_entry(&key).map(|(_, value)| value),
+			};
+			return Err((value.expect("the entry was just inserted"), violation));
+		}
+		Ok(replaced)
+	}
+	/// Removes and returns the value of the entry for `key` if the map still satisfies the map
+	/// invariant without it. Otherwise the map is left untouched.
+	pub fn remove(&mut self, key: &M::Key) -> Result<Option<M::Value>, Violation> {
+		let Some((key, value)) = self.map.remove_entry(key) else {
+			return Ok(None);
+		};
+		if let Err(violation) = self.validate_map() {
+			self.map.insert(key, value);
+			return Err(violation);
+		}
+		Ok(Some(value))
+	}
+	/// Borrows a mutable view of the value of the entry for `key`. When the view is dropped, the
+	/// entry invariant is checked for that entry only, and then the map invariant is checked.
+	///
+	/// # Panics
+	/// When the view is dropped, if the modified value violates either invariant. The modified
+	/// value stays in the map.
+	pub fn get_mut<'k>(&mut self, key: &'k M::Key) -> Option<ReprMapEntryMutator<'_, 'k, M, E, C>> {
+		self.map.get(key)?;
+		Some(ReprMapEntryMutator { repr: self, key: EntryKey::Borrowed(key) })
+	}
+	/// Gets the entry for `key` for checked in-place insertion or modification. Inserted values are
+	/// checked like with [`ReprMap::insert`], and modified values like with [`ReprMap::get_mut`].
+	/// ```rust
+	/// use repr_rs::ReprBTreeMap;
+	/// let mut stock = ReprBTreeMap::new(|_: &&str, count: &u32| *count <= 10)
+	///   .with_map_invariant(|stock| stock.values().sum::<u32>() <= 15);
+	/// *stock.entry("apples").or_insert(0).unwrap() += 8;
+	/// stock.entry("apples").and_modify(|count| *count += 2);
+	/// assert!(stock.entry("pears").or_insert(6).is_err());
+	/// assert_eq!(Some(&10), stock.read().get("apples"));
+	/// assert_eq!(None, stock.read().get("pears"));
+	/// ```
+	pub fn entry(&mut self, key: M::Key) -> ReprMapEntry<'_, M, E, C> {
+		if self.map.get(&key).is_some() {
+			ReprMapEntry::Occupied(ReprMapOccupiedEntry { repr: self, key })
+		} else {
+			ReprMapEntry::Vacant(ReprMapVacantEntry { repr: self, key })
+		}
+	}
+	/// Consumes the representation invariant and returns the map.
+	pub fn into_inner(self) -> M {
+		self.map
+	}
+	/// Checks that every entry and the map satisfy their invariants without panicking.
+	pub fn validate(&self) -> Result<(), Violation> {
+		if !self.map.all(&self.entry) {
+			return Err(Violation::new(self.violation_message, &self.map));
+		}
+		self.validate_map()
+	}
+	/// Evaluates the invariants for the current entries without panicking.
+	pub fn is_valid(&self) -> bool {
+		self.map.all(&self.entry) && (self.collection)(&self.map)
+	}
+	fn validate_entry(&self, key: &M::Key, value: &M::Value) -> Result<(), Violation> {
+		if (self.entry)(key, value) {
+			Ok(())
+		} else {
+			Err(Violation::new(self.violation_message, &(key, value)))
+		}
+	}
+	fn validate_map(&self) -> Result<(), Violation> {
+		if (self.collection)(&self.map) {
+			Ok(())
+		} else {
+			Err(Violation::new(self.violation_message, &self.map))
+		}
+	}
+}
+impl<M: Map + Clone, E: Fn(&M::Key, &M::Value) -> bool + Clone, C: Fn(&M) -> bool + Clone> Clone for ReprMap<M, E, C> {
+	fn clone(&self) -> Self {
+		Self {
+			map: self.map.clone(),
+			entry: self.entry.clone(),
+			collection: self.collection.clone(),
+			violation_message: self.violation_message,
+		}
+	}
+}
+impl<M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> Debug for ReprMap<M, E, C> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "ReprMap({:?})", self.map)
+	}
+}
+
+/// An entry of a [`ReprMap`], created by [`ReprMap::entry`].
+pub enum ReprMapEntry<'a, M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> {
+	/// The map has an entry for the key.
+	Occupied(ReprMapOccupiedEntry<'a, M, E, C>),
+	/// The map has no entry for the key.
+	Vacant(ReprMapVacantEntry<'a, M, E, C>),
+}
+impl<'a, M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> ReprMapEntry<'a, M, E, C> {
+	/// The key of the entry.
+	pub fn key(&self) -> &M::Key {
+		match self {
+			Self::Occupied(entry) => entry.key(),
+			Self::Vacant(entry) => entry.key(),
+		}
+	}
+	/// Inserts `default` if the entry is vacant, and borrows a mutable view of the value. If the
+	/// value would violate either invariant, it's handed back alongside the [`Violation`].
+	pub fn or_insert(self, default: M::Value) -> Result<ReprMapEntryMutator<'a, 'a, M, E, C>, (M::Value, Violation)> where M::Key: Clone {
+		self.or_insert_with(|| default)
+	}
+	/// Like [`ReprMapEntry::or_insert`], but only computes the value if the entry is vacant.
+	pub fn or_insert_with(self, default: impl FnOnce() -> M::Value) -> Result<ReprMapEntryMutator<'a, 'a, M, E, C>, (M::Value, Violation)> where M::Key: Clone {
+		match self {
+			Self::Occupied(entry) => Ok(entry.into_mut()),
+			Self::Vacant(entry) => entry.insert(default()),
+		}
+	}
+	/// Modifies the value if the entry is occupied. The entry invariant and then the map invariant
+	/// are checked afterwards, like with [`ReprMap::get_mut`].
+	///
+	/// # Panics
+	/// If the modified value violates either invariant. The modified value stays in the map.
+	pub fn and_modify(mut self, f: impl FnOnce(&mut M::Value)) -> Self {
+		if let Self::Occupied(entry) = &mut self {
+			f(&mut entry.get_mut());
+		}
+		self
+	}
+}
+
+/// An entry of a [`ReprMap`] that has a value. See [`ReprMap::entry`].
+pub struct ReprMapOccupiedEntry<'a, M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> {
+	repr: &'a mut ReprMap<M, E, C>,
+	key: M::Key,
+}
+impl<'a, M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> ReprMapOccupiedEntry<'a, M, E, C> {
+	/// The key of the entry.
+	pub fn key(&self) -> &M::Key {
+		&self.key
+	}
+	/// Borrows the value of the entry.
+	pub fn get(&self) -> &M::Value {
+		self.repr.map.get(&self.key).expect("the entry is occupied")
+	}
+	/// Borrows a mutable view of the value, which is checked when it's dropped.
+	pub fn get_mut(&mut self) -> ReprMapEntryMutator<'_, '_, M, E, C> {
+		ReprMapEntryMutator { repr: self.repr, key: EntryKey::Borrowed(&self.key) }
+	}
+	/// Converts the entry into a mutable view of the value, which is checked when it's dropped.
+	pub fn into_mut(self) -> ReprMapEntryMutator<'a, 'a, M, E, C> {
+		ReprMapEntryMutator { repr: self.repr, key: EntryKey::Owned(self.key) }
+	}
+	/// Replaces the value, returning the old one. See [`ReprMap::insert`].
+	pub fn insert(&mut self, value: M::Value) -> Result<M::Value, (M::Value, Violation)> where M::Key: Clone {
+		self.repr.insert(self.key.clone(), value).map(|replaced| replaced.expect("the entry is occupied"))
+	}
+	/// Removes the entry, returning its value. See [`ReprMap::remove`].
+	pub fn remove(self) -> Result<M::Value, Violation> {
+		self.repr.remove(&self.key).map(|removed| removed.expect("the entry is occupied"))
+	}
+}
+
+/// An entry of a [`ReprMap`] that has no value. See [`ReprMap::entry`].
+pub struct ReprMapVacantEntry<'a, M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> {
+	repr: &'a mut ReprMap<M, E, C>,
+	key: M::Key,
+}
+impl<'a, M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> ReprMapVacantEntry<'a, M, E, C> {
+	/// The key of the entry.
+	pub fn key(&self) -> &M::Key {
+		&self.key
+	}
+	/// Takes the key back out of the entry.
+	pub fn into_key(self) -> M::Key {
+		self.key
+	}
+	/// Inserts the value, and borrows a mutable view of it. See [`ReprMap::insert`].
+	pub fn insert(self, value: M::Value) -> Result<ReprMapEntryMutator<'a, 'a, M, E, C>, (M::Value, Violation)> where M::Key: Clone {
+		self.repr.insert(self.key.clone(), value)?;
+		Ok(ReprMapEntryMutator { repr: self.repr, key: EntryKey::Owned(self.key) })
+	}
+}
+
+/// The key of a [`ReprMapEntryMutator`], which is owned when it was taken from an entry.
+enum EntryKey<'k, K> {
+	Borrowed(&'k K),
+	Owned(K),
+}
+impl<K> EntryKey<'_, K> {
+	fn get(&self) -> &K {
+		match self {
+			Self::Borrowed(key) => key,
+			Self::Owned(key) => key,
+		}
+	}
+}
+
+/// A mutable view of the value of one entry of a [`ReprMap`]. The entry invariant is checked for
+/// this entry, and then the map invariant, when it's dropped. A violation panics, and isn't rolled
+/// back.
+pub struct ReprMapEntryMutator<'a, 'k, M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> {
+	repr: &'a mut ReprMap<M, E, C>,
+	key: EntryKey<'k, M::Key>,
+}
+impl<M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> Debug for ReprMapEntryMutator<'_, '_, M, E, C> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		Debug::fmt(&**self, f)
+	}
+}
+impl<M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> Deref for ReprMapEntryMutator<'_, '_, M, E, C> {
+	type Target = M::Value;
+	fn deref(&self) -> &Self::Target {
+		self.repr.map.get(self.key.get()).expect("the entry exists while it's borrowed")
+	}
+}
+impl<M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> DerefMut for ReprMapEntryMutator<'_, '_, M, E, C> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.repr.map.get_mut(self.key.get()).expect("the entry exists while it's borrowed")
+	}
+}
+impl<M: Map, E: Fn(&M::Key, &M::Value) -> bool, C: Fn(&M) -> bool> Drop for ReprMapEntryMutator<'_, '_, M, E, C> {
+	fn drop(&mut self) {
+		if crate::repr::panicking() {
+			return;
+		}
+		let result = self.repr.validate_entry(self.key.get(), &**self)
+			.and_then(|()| self.repr.validate_map());
+		if let Err(violation) = result {
+			panic!("{}", violation);
+		}
+	}
+}
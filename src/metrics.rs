@@ -0,0 +1,81 @@
+//! Push-based metrics of representation invariants, so checks and caches can be monitored with
+//! e.g. prometheus or `metrics`. See [`crate::Repr::with_metrics`].
+use crate::cache::stats::CacheStats;
+use crate::cache::CacheKey;
+use crate::Violation;
+use core::panic::RefUnwindSafe;
+use core::time::Duration;
+
+/// Receives the metrics of a representation invariant. Every method does nothing by default, so
+/// implementations only override what they collect. Implementations are [`RefUnwindSafe`] so
+/// that the representation invariants reporting to them are too.
+/// ```rust
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+/// use repr_rs::CacheableRepr;
+/// use repr_rs::cache::CacheKey;
+/// use repr_rs::metrics::Metrics;
+/// #[derive(Default)]
+/// struct Counters { checks: AtomicU64, hits: AtomicU64, misses: AtomicU64 }
+/// impl Metrics for Counters {
+///   fn check(&self, _duration: std::time::Duration) {
+///     self.checks.fetch_add(1, Ordering::Relaxed);
+///   }
+///   fn cache_read(&self, _key: &CacheKey, hit: bool) {
+///     let counter = if hit { &self.hits } else { &self.misses };
+///     counter.fetch_add(1, Ordering::Relaxed);
+///   }
+/// }
+/// fn double(n: &i32) -> i32 { n * 2 }
+/// let counters = Arc::new(Counters::default());
+/// let mut repr = CacheableRepr::new(1, |n| *n > 0).with_metrics(counters.clone());
+/// repr.lazy(double);
+/// repr.lazy(double);
+/// repr.set(2).unwrap();
+/// assert_eq!(1, counters.checks.load(Ordering::Relaxed));
+/// assert_eq!((1, 1), (counters.hits.load(Ordering::Relaxed), counters.misses.load(Ordering::Relaxed)));
+/// ```
+pub trait Metrics: Send + Sync + RefUnwindSafe {
+	/// The invariant was checked after a write, which took `duration`. Durations are only measured
+	/// with the `std` feature, and not on wasm, where there is no monotonic clock.
+	fn check(&self, _duration: Duration) {}
+	/// A write violated the invariant. This is reported before the violation panics (or is
+	/// returned).
+	fn violation(&self, _violation: &Violation) {}
+	/// A cached read was served from the cache of the read function (`hit`), or had to call it.
+	/// Concurrent reads of the same lazy cache may be reported as each other's.
+	fn cache_read(&self, _key: &CacheKey, _hit: bool) {}
+	/// How many eager cache recomputations are outstanding right after a write.
+	fn pending_eager_tasks(&self, _pending: usize) {}
+}
+impl<M: Metrics + ?Sized> Metrics for alloc::sync::Arc<M> {
+	fn check(&self, duration: Duration) {
+		(**self).check(duration);
+	}
+	fn violation(&self, violation: &Violation) {
+		(**self).violation(violation);
+	}
+	fn cache_read(&self, key: &CacheKey, hit: bool) {
+		(**self).cache_read(key, hit);
+	}
+	fn pending_eager_tasks(&self, pending: usize) {
+		(**self).pending_eager_tasks(pending);
+	}
+}
+
+/// Reads a cache, reporting to the metrics whether the read was a hit by comparing the statistics
+/// of the cache before and after it.
+pub(crate) fn observe<X>(metrics: Option<&dyn Metrics>, key: &CacheKey, stats: impl Fn() -> CacheStats, read: impl FnOnce() -> X) -> X {
+	let Some(metrics) = metrics else {
+		return read();
+	};
+	let before = stats();
+	let result = read();
+	let after = stats();
+	if after.hits > before.hits {
+		metrics.cache_read(key, true);
+	} else if after.misses > before.misses {
+		metrics.cache_read(key, false);
+	}
+	result
+}
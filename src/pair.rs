@@ -0,0 +1,180 @@
+use crate::Violation;
+use core::fmt::Debug;
+use core::ops::{Deref, DerefMut};
+
+/// Wraps two values and ensures that an invariant spanning both of them is maintained while
+/// allowing either value to be mutated. The invariant is checked after every mutation.
+/// ```rust
+/// use repr_rs::ReprPair;
+/// let mut account = ReprPair::with_msg(
+///   0i64,
+///   Vec::<i64>::new(),
+///   |balance, ledger| *balance == ledger.iter().sum::<i64>(),
+///   "the balance must equal the sum of the ledger entries",
+/// );
+/// {
+///   let mut account = account.write();
+///   account.0 += 10;
+///   account.1.push(10);
+/// }
+/// assert_eq!(10, *account.first());
+/// ```
+pub struct ReprPair<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> {
+	inner: (A, B),
+	invariant: I,
+	violation_message: &'static str,
+}
+impl<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> ReprPair<A, B, I> {
+	/// Creates a new representation invariant over the given values and invariant function.
+	pub const fn new(first: A, second: B, invariant: I) -> Self {
+		Self::with_msg(first, second, invariant, "Invariant violated")
+	}
+	/// Creates a new representation invariant over the given values, invariant function, and
+	/// violation message.
+	pub const fn with_msg(first: A, second: B, invariant: I, violation_message: &'static str) -> Self {
+		Self {
+			inner: (first, second),
+			invariant,
+			violation_message,
+		}
+	}
+	/// Borrows a read-only view of both values.
+	#[inline]
+	pub fn read(&self) -> (&A, &B) {
+		(&self.inner.0, &self.inner.1)
+	}
+	/// Borrows a read-only view of the first value.
+	#[inline]
+	pub fn first(&self) -> &A {
+		&self.inner.0
+	}
+	/// Borrows a read-only view of the second value.
+	#[inline]
+	pub fn second(&self) -> &B {
+		&self.inner.1
+	}
+	/// Borrows a mutable view of both values as a tuple. The invariant is checked when the view
+	/// is dropped.
+	#[inline]
+	pub fn write(&mut self) -> ReprPairMutator<'_, A, B, I> {
+		ReprPairMutator { repr: self }
+	}
+	/// Borrows a mutable view of the first value. The invariant is checked when the view is dropped.
+	/// ```rust
+	/// use repr_rs::ReprPair;
+	/// let mut range = ReprPair::new(1, 5, |min, max| min < max);
+	/// *range.write_first() = 4;
+	/// assert_eq!((&4, &5), range.read());
+	/// ```
+	#[inline]
+	pub fn write_first(&mut self) -> ReprPairFirstMutator<'_, A, B, I> {
+		ReprPairFirstMutator { repr: self }
+	}
+	/// Borrows a mutable view of the second value. The invariant is checked when the view is dropped.
+	/// ```should_panic
+	/// use repr_rs::ReprPair;
+	/// let mut range = ReprPair::new(1, 5, |min, max| min < max);
+	/// // panics because min must be less than max
+	/// *range.write_second() = 0;
+	/// ```
+	#[inline]
+	pub fn write_second(&mut self) -> ReprPairSecondMutator<'_, A, B, I> {
+		ReprPairSecondMutator { repr: self }
+	}
+	/// Consumes the representation invariant and returns the inner values.
+	#[inline]
+	pub fn into_inner(self) -> (A, B) {
+		self.inner
+	}
+	/// Checks that the invariant holds for the current values without panicking.
+	pub fn validate(&self) -> Result<(), Violation> {
+		if self.is_valid() {
+			Ok(())
+		} else {
+			Err(Violation::new(self.violation_message, &self.inner))
+		}
+	}
+	/// Evaluates the invariant for the current values without panicking.
+	pub fn is_valid(&self) -> bool {
+		(self.invariant)(&self.inner.0, &self.inner.1)
+	}
+	fn check(&self) {
+		if crate::repr::panicking() {
+			return;
+		}
+		if let Err(violation) = self.validate() {
+			panic!("{}", violation);
+		}
+	}
+}
+
+impl<A: Debug + Clone, B: Debug + Clone, I: Fn(&A, &B) -> bool + Clone> Clone for ReprPair<A, B, I> {
+	fn clone(&self) -> Self {
+		Self::with_msg(self.inner.0.clone(), self.inner.1.clone(), self.invariant.clone(), self.violation_message)
+	}
+}
+impl<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> Debug for ReprPair<A, B, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "ReprPair({:?}, {:?})", self.inner.0, self.inner.1)
+	}
+}
+
+pub struct ReprPairMutator<'a, A: Debug, B: Debug, I: Fn(&A, &B) -> bool> {
+	repr: &'a mut ReprPair<A, B, I>,
+}
+impl<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> Deref for ReprPairMutator<'_, A, B, I> {
+	type Target = (A, B);
+	fn deref(&self) -> &Self::Target {
+		&self.repr.inner
+	}
+}
+impl<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> DerefMut for ReprPairMutator<'_, A, B, I> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.repr.inner
+	}
+}
+impl<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> Drop for ReprPairMutator<'_, A, B, I> {
+	fn drop(&mut self) {
+		self.repr.check();
+	}
+}
+
+pub struct ReprPairFirstMutator<'a, A: Debug, B: Debug, I: Fn(&A, &B) -> bool> {
+	repr: &'a mut ReprPair<A, B, I>,
+}
+impl<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> Deref for ReprPairFirstMutator<'_, A, B, I> {
+	type Target = A;
+	fn deref(&self) -> &Self::Target {
+		&self.repr.inner.0
+	}
+}
+impl<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> DerefMut for ReprPairFirstMutator<'_, A, B, I> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.repr.inner.0
+	}
+}
+impl<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> Drop for ReprPairFirstMutator<'_, A, B, I> {
+	fn drop(&mut self) {
+		self.repr.check();
+	}
+}
+
+pub struct ReprPairSecondMutator<'a, A: Debug, B: Debug, I: Fn(&A, &B) -> bool> {
+	repr: &'a mut ReprPair<A, B, I>,
+}
+impl<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> Deref for ReprPairSecondMutator<'_, A, B, I> {
+	type Target = B;
+	fn deref(&self) -> &Self::Target {
+		&self.repr.inner.1
+	}
+}
+impl<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> DerefMut for ReprPairSecondMutator<'_, A, B, I> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.repr.inner.1
+	}
+}
+impl<A: Debug, B: Debug, I: Fn(&A, &B) -> bool> Drop for ReprPairSecondMutator<'_, A, B, I> {
+	fn drop(&mut self) {
+		self.repr.check();
+	}
+}
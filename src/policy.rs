@@ -0,0 +1,64 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+static DEFAULT_DETERMINISM_CHECKS: AtomicUsize = AtomicUsize::new(10);
+
+/// Whether the checks after writes are compiled out, with the `release-unchecked` feature in a
+/// release build.
+pub(crate) const UNCHECKED: bool = cfg!(all(feature = "release-unchecked", not(debug_assertions)));
+
+/// Sets how many extra times the invariant is evaluated after each check (in debug builds) to
+/// detect non-deterministic invariants. This applies to every representation invariant that hasn't
+/// set its own count with [`crate::Repr::with_determinism_checks`]. The default is 10.
+/// ```rust
+/// repr_rs::policy::set_default_determinism_checks(2);
+/// assert_eq!(2, repr_rs::policy::default_determinism_checks());
+/// ```
+pub fn set_default_determinism_checks(checks: usize) {
+	DEFAULT_DETERMINISM_CHECKS.store(checks, Ordering::Relaxed);
+}
+/// Gets the global default set by [`set_default_determinism_checks`].
+pub fn default_determinism_checks() -> usize {
+	DEFAULT_DETERMINISM_CHECKS.load(Ordering::Relaxed)
+}
+
+/// Controls when a representation invariant is checked after a mutation.
+///
+/// Different deployments of the same code often need different cost/safety tradeoffs, so the
+/// policy can be chosen per [`crate::Repr`] (or [`crate::CacheableRepr`]) without changing any of
+/// the code that mutates it.
+/// ```rust
+/// use repr_rs::{CheckPolicy, Repr};
+/// #[derive(Debug)]
+/// struct MinMax { min: i32, max: i32 }
+/// let mut repr = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+///   .with_check_policy(CheckPolicy::Never);
+/// // Not checked, so this doesn't panic.
+/// repr.write().min = 6;
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum CheckPolicy {
+	/// Check the invariant after every mutation. This is the default.
+	#[default]
+	Always,
+	/// Check the invariant after every mutation, but only when `debug_assertions` are enabled.
+	DebugOnly,
+	/// Check the invariant after every nth mutation. A sample rate of 0 or 1 checks every mutation.
+	Sampled(usize),
+	/// Never check the invariant.
+	Never,
+}
+impl CheckPolicy {
+	/// Whether the invariant should be checked for the given (1-indexed) mutation.
+	pub(crate) const fn should_check(self, mutation: u64) -> bool {
+		if UNCHECKED {
+			return false;
+		}
+		match self {
+			CheckPolicy::Always => true,
+			CheckPolicy::DebugOnly => cfg!(debug_assertions),
+			CheckPolicy::Sampled(0) => true,
+			CheckPolicy::Sampled(n) => mutation.is_multiple_of(n as u64),
+			CheckPolicy::Never => false,
+		}
+	}
+}
@@ -0,0 +1,89 @@
+//! Reusable invariant functions for common constraints. Functions without parameters (like
+//! [`positive`]) can be passed directly as the invariant function, and the others return one.
+//! ```rust
+//! use repr_rs::predicate::{in_range, non_empty, strictly_increasing};
+//! use repr_rs::Repr;
+//! let mut level = Repr::new(3, in_range(1..=5));
+//! assert!(level.set(6).is_err());
+//! let mut versions = Repr::new(vec![1, 2, 3], strictly_increasing);
+//! assert!(versions.set(vec![1, 1]).is_err());
+//! let mut names = Repr::new(vec!["a"], non_empty);
+//! assert!(names.set(Vec::new()).is_err());
+//! ```
+use core::ops::RangeBounds;
+
+/// Whether the value is within `range`.
+pub fn in_range<T: PartialOrd>(range: impl RangeBounds<T>) -> impl Fn(&T) -> bool {
+	move |value| range.contains(value)
+}
+/// Whether the number is greater than zero (its default value).
+pub fn positive<T: PartialOrd + Default>(value: &T) -> bool {
+	*value > T::default()
+}
+/// Whether the number is greater than or equal to zero (its default value).
+pub fn non_negative<T: PartialOrd + Default>(value: &T) -> bool {
+	*value >= T::default()
+}
+/// Whether the floating-point number is neither infinite nor NaN.
+pub fn finite<T: Copy + Into<f64>>(value: &T) -> bool {
+	(*value).into().is_finite()
+}
+
+/// Whether the length of the string in bytes is within `range`.
+pub fn len_in_bytes<S: AsRef<str> + ?Sized>(range: impl RangeBounds<usize>) -> impl Fn(&S) -> bool {
+	move |s| range.contains(&s.as_ref().len())
+}
+/// Whether the number of characters in the string is within `range`.
+pub fn char_count_in<S: AsRef<str> + ?Sized>(range: impl RangeBounds<usize>) -> impl Fn(&S) -> bool {
+	move |s| range.contains(&s.as_ref().chars().count())
+}
+/// Whether the string has at least one character that isn't whitespace.
+pub fn not_blank<S: AsRef<str> + ?Sized>(s: &S) -> bool {
+	!s.as_ref().trim().is_empty()
+}
+/// Whether the string only has ASCII characters.
+pub fn ascii<S: AsRef<str> + ?Sized>(s: &S) -> bool {
+	s.as_ref().is_ascii()
+}
+/// Whether the bytes are valid UTF-8.
+pub fn utf8<B: AsRef<[u8]> + ?Sized>(bytes: &B) -> bool {
+	core::str::from_utf8(bytes.as_ref()).is_ok()
+}
+/// Whether the string matches `regex` anywhere. Anchor the pattern (`^...$`) to match the whole
+/// string.
+/// ```rust
+/// use regex::Regex;
+/// use repr_rs::predicate::matches;
+/// use repr_rs::Repr;
+/// let mut code = Repr::new("NZ".to_string(), matches(Regex::new("^[A-Z]{2}$").unwrap()));
+/// assert!(code.set("NZL".to_string()).is_err());
+/// ```
+#[cfg(feature = "regex")]
+pub fn matches<S: AsRef<str> + ?Sized>(regex: regex::Regex) -> impl Fn(&S) -> bool {
+	move |s| regex.is_match(s.as_ref())
+}
+
+/// Whether the collection has at least one element.
+pub fn non_empty<C: AsRef<[E]> + ?Sized, E>(items: &C) -> bool {
+	!items.as_ref().is_empty()
+}
+/// Whether the number of elements in the collection is within `range`.
+pub fn len_in<C: AsRef<[E]> + ?Sized, E>(range: impl RangeBounds<usize>) -> impl Fn(&C) -> bool {
+	move |items| range.contains(&items.as_ref().len())
+}
+/// Whether every element is less than or equal to the next one.
+pub fn increasing<C: AsRef<[E]> + ?Sized, E: PartialOrd>(items: &C) -> bool {
+	items.as_ref().windows(2).all(|pair| pair[0] <= pair[1])
+}
+/// Whether every element is less than the next one.
+pub fn strictly_increasing<C: AsRef<[E]> + ?Sized, E: PartialOrd>(items: &C) -> bool {
+	items.as_ref().windows(2).all(|pair| pair[0] < pair[1])
+}
+/// Whether every element is greater than or equal to the next one.
+pub fn decreasing<C: AsRef<[E]> + ?Sized, E: PartialOrd>(items: &C) -> bool {
+	items.as_ref().windows(2).all(|pair| pair[0] >= pair[1])
+}
+/// Whether every element is greater than the next one.
+pub fn strictly_decreasing<C: AsRef<[E]> + ?Sized, E: PartialOrd>(items: &C) -> bool {
+	items.as_ref().windows(2).all(|pair| pair[0] > pair[1])
+}
@@ -0,0 +1,367 @@
+//! Commonly used items, and ready-made refinement types: newtypes over a [`Repr`] with a canonical
+//! invariant, whose methods keep the invariant.
+//! ```rust
+//! use repr_rs::prelude::*;
+//! let mut volume = Bounded::<0, 10>::new(7).unwrap();
+//! volume = volume.saturating_add(5);
+//! assert_eq!(10, volume.get());
+//! let mut name = NonEmptyString::new("repr".to_string()).unwrap();
+//! name.push_str("-rs");
+//! assert_eq!("repr-rs", &*name);
+//! ```
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Display};
+use core::ops::Deref;
+
+pub use crate::{CacheableRepr, Invariant, Repr, Violation};
+
+fn guard<T: Debug>(value: T, invariant: fn(&T) -> bool, violation_message: &'static str) -> Result<Repr<T>, Violation> {
+	let repr = Repr::with_msg(value, invariant, violation_message);
+	repr.validate()?;
+	Ok(repr)
+}
+
+/// An integer in the inclusive range `MIN..=MAX`. Arithmetic either fails or saturates at the
+/// bounds, so the value never leaves the range. (Const generics can't depend on another generic,
+/// so this is always an [`i64`].)
+/// ```rust
+/// use repr_rs::prelude::Bounded;
+/// type Level = Bounded<1, 5>;
+/// let level = Level::new(4).unwrap();
+/// assert!(level.checked_add(2).is_err());
+/// assert_eq!(5, level.saturating_add(2).get());
+/// assert_eq!(1, level.saturating_sub(10).get());
+/// assert!(Level::new(0).is_err());
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Bounded<const MIN: i64, const MAX: i64>(Repr<i64>);
+impl<const MIN: i64, const MAX: i64> Bounded<MIN, MAX> {
+	/// The smallest value.
+	pub const MIN: i64 = MIN;
+	/// The largest value.
+	pub const MAX: i64 = MAX;
+	/// Creates a bounded integer, failing if the value is out of bounds.
+	pub fn new(value: i64) -> Result<Self, Violation> {
+		guard(value, |value| (MIN..=MAX).contains(value), "the value is out of bounds").map(Self)
+	}
+	/// The value.
+	pub fn get(&self) -> i64 {
+		*self.0.read()
+	}
+	/// Sets the value if it's within bounds. Otherwise the new value is handed back alongside the
+	/// [`Violation`].
+	pub fn set(&mut self, value: i64) -> Result<(), (i64, Violation)> {
+		self.0.set(value)
+	}
+	/// Adds `rhs`, failing if the sum is out of bounds.
+	pub fn checked_add(&self, rhs: i64) -> Result<Self, Violation> {
+		match self.get().checked_add(rhs) {
+			Some(sum) => Self::new(sum),
+			None => Err(Violation::new("the value is out of bounds", &rhs)),
+		}
+	}
+	/// Subtracts `rhs`, failing if the difference is out of bounds.
+	pub fn checked_sub(&self, rhs: i64) -> Result<Self, Violation> {
+		match self.get().checked_sub(rhs) {
+			Some(difference) => Self::new(difference),
+			None => Err(Violation::new("the value is out of bounds", &rhs)),
+		}
+	}
+	/// Adds `rhs`, saturating at the bounds.
+	pub fn saturating_add(&self, rhs: i64) -> Self {
+		self.saturate(self.get().saturating_add(rhs))
+	}
+	/// Subtracts `rhs`, saturating at the bounds.
+	pub fn saturating_sub(&self, rhs: i64) -> Self {
+		self.saturate(self.get().saturating_sub(rhs))
+	}
+	fn saturate(&self, value: i64) -> Self {
+		let mut saturated = self.clone();
+		*saturated.0.write() = value.clamp(MIN, MAX);
+		saturated
+	}
+}
+impl<const MIN: i64, const MAX: i64> TryFrom<i64> for Bounded<MIN, MAX> {
+	type Error = Violation;
+	fn try_from(value: i64) -> Result<Self, Self::Error> {
+		Self::new(value)
+	}
+}
+impl<const MIN: i64, const MAX: i64> Debug for Bounded<MIN, MAX> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "Bounded({:?})", self.get())
+	}
+}
+impl<const MIN: i64, const MAX: i64> Display for Bounded<MIN, MAX> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		Display::fmt(&self.get(), f)
+	}
+}
+
+/// A string that is never empty. Strings can only grow through its methods.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NonEmptyString(Repr<String>);
+impl NonEmptyString {
+	/// Creates a non-empty string, failing if the string is empty.
+	pub fn new(value: String) -> Result<Self, Violation> {
+		guard(value, |value| !value.is_empty(), "the string is empty").map(Self)
+	}
+	/// Borrows the string.
+	pub fn as_str(&self) -> &str {
+		self.0.read()
+	}
+	/// Appends a character.
+	pub fn push(&mut self, c: char) {
+		self.0.write().push(c);
+	}
+	/// Appends a string slice.
+	pub fn push_str(&mut self, s: &str) {
+		self.0.write().push_str(s);
+	}
+	/// Sets the string if it isn't empty. Otherwise the new string is handed back alongside the
+	/// [`Violation`].
+	pub fn set(&mut self, value: String) -> Result<(), (String, Violation)> {
+		self.0.set(value)
+	}
+	/// Returns the string.
+	pub fn into_inner(self) -> String {
+		self.0.into_inner()
+	}
+}
+impl TryFrom<String> for NonEmptyString {
+	type Error = Violation;
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		Self::new(value)
+	}
+}
+impl Deref for NonEmptyString {
+	type Target = str;
+	fn deref(&self) -> &Self::Target {
+		self.as_str()
+	}
+}
+impl Debug for NonEmptyString {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "NonEmptyString({:?})", self.as_str())
+	}
+}
+impl Display for NonEmptyString {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		Display::fmt(self.as_str(), f)
+	}
+}
+
+/// A vector that always has at least one element, so [`NonEmptyVec::first`] and
+/// [`NonEmptyVec::last`] don't return an [`Option`].
+/// ```rust
+/// use repr_rs::prelude::NonEmptyVec;
+/// let mut stack = NonEmptyVec::singleton(1);
+/// stack.push(2);
+/// assert_eq!(Some(2), stack.pop());
+/// assert_eq!(None, stack.pop());
+/// assert_eq!(&1, stack.last());
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NonEmptyVec<T: Debug>(Repr<Vec<T>>);
+impl<T: Debug> NonEmptyVec<T> {
+	/// Creates a non-empty vector, failing if the vector is empty.
+	pub fn new(value: Vec<T>) -> Result<Self, Violation> {
+		guard(value, |value| !value.is_empty(), "the vector is empty").map(Self)
+	}
+	/// Creates a vector with a single element.
+	pub fn singleton(value: T) -> Self {
+		Self(Repr::with_msg(alloc::vec![value], |value| !value.is_empty(), "the vector is empty"))
+	}
+	/// Borrows the elements.
+	pub fn as_slice(&self) -> &[T] {
+		self.0.read()
+	}
+	/// The first element.
+	pub fn first(&self) -> &T {
+		&self.as_slice()[0]
+	}
+	/// The last element.
+	pub fn last(&self) -> &T {
+		&self.as_slice()[self.as_slice().len() - 1]
+	}
+	/// Appends an element.
+	pub fn push(&mut self, value: T) {
+		self.0.write().push(value);
+	}
+	/// Removes and returns the last element, unless it is the only one.
+	pub fn pop(&mut self) -> Option<T> {
+		if self.as_slice().len() == 1 {
+			return None;
+		}
+		self.0.write().pop()
+	}
+	/// Sets the elements if there is at least one. Otherwise the new vector is handed back alongside
+	/// the [`Violation`].
+	pub fn set(&mut self, value: Vec<T>) -> Result<(), (Vec<T>, Violation)> {
+		self.0.set(value)
+	}
+	/// Returns the elements.
+	pub fn into_inner(self) -> Vec<T> {
+		self.0.into_inner()
+	}
+}
+impl<T: Debug> TryFrom<Vec<T>> for NonEmptyVec<T> {
+	type Error = Violation;
+	fn try_from(value: Vec<T>) -> Result<Self, Self::Error> {
+		Self::new(value)
+	}
+}
+impl<T: Debug> Deref for NonEmptyVec<T> {
+	type Target = [T];
+	fn deref(&self) -> &Self::Target {
+		self.as_slice()
+	}
+}
+impl<T: Debug> Debug for NonEmptyVec<T> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "NonEmptyVec({:?})", self.as_slice())
+	}
+}
+
+/// A finite percentage from 0 to 100 (inclusive).
+/// ```rust
+/// use repr_rs::prelude::Percentage;
+/// let discount = Percentage::new(25.0).unwrap();
+/// assert_eq!(30.0, discount.of(120.0));
+/// assert_eq!(75.0, discount.complement().get());
+/// assert!(discount.checked_add(Percentage::new(80.0).unwrap()).is_err());
+/// assert!(Percentage::new(f64::NAN).is_err());
+/// ```
+#[derive(Clone, PartialEq, PartialOrd)]
+pub struct Percentage(Repr<f64>);
+impl Percentage {
+	/// Creates a percentage, failing if the value isn't between 0 and 100.
+	pub fn new(value: f64) -> Result<Self, Violation> {
+		guard(value, |value| (0.0..=100.0).contains(value), "the percentage isn't between 0 and 100").map(Self)
+	}
+	/// The percentage, from 0 to 100.
+	pub fn get(&self) -> f64 {
+		*self.0.read()
+	}
+	/// Sets the percentage if it's between 0 and 100. Otherwise the new value is handed back
+	/// alongside the [`Violation`].
+	pub fn set(&mut self, value: f64) -> Result<(), (f64, Violation)> {
+		self.0.set(value)
+	}
+	/// This percentage of `value`.
+	pub fn of(&self, value: f64) -> f64 {
+		value * self.get() / 100.0
+	}
+	/// The rest of 100%.
+	pub fn complement(&self) -> Self {
+		self.with(100.0 - self.get())
+	}
+	/// Adds another percentage, failing if the sum is over 100.
+	pub fn checked_add(&self, rhs: Self) -> Result<Self, Violation> {
+		Self::new(self.get() + rhs.get())
+	}
+	/// Adds another percentage, saturating at 100.
+	pub fn saturating_add(&self, rhs: Self) -> Self {
+		self.with((self.get() + rhs.get()).min(100.0))
+	}
+	fn with(&self, value: f64) -> Self {
+		let mut percentage = self.clone();
+		*percentage.0.write() = value;
+		percentage
+	}
+}
+impl TryFrom<f64> for Percentage {
+	type Error = Violation;
+	fn try_from(value: f64) -> Result<Self, Self::Error> {
+		Self::new(value)
+	}
+}
+impl Debug for Percentage {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "Percentage({:?})", self.get())
+	}
+}
+impl Display for Percentage {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "{}%", self.get())
+	}
+}
+
+/// A vector that is always sorted. Elements are inserted at their sorted position, and any vector
+/// can be turned into one by sorting it.
+/// ```rust
+/// use repr_rs::prelude::SortedVec;
+/// let mut scores = SortedVec::from(vec![30, 10]);
+/// scores.insert(20);
+/// assert_eq!(&[10, 20, 30], &*scores);
+/// assert!(scores.contains(&20));
+/// assert_eq!(10, scores.remove(0));
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SortedVec<T: Debug + Ord>(Repr<Vec<T>>);
+impl<T: Debug + Ord> SortedVec<T> {
+	/// Creates an empty sorted vector.
+	pub fn new() -> Self {
+		Self::from(Vec::new())
+	}
+	/// Borrows the elements, in order.
+	pub fn as_slice(&self) -> &[T] {
+		self.0.read()
+	}
+	/// Inserts an element at its sorted position (after any equal elements), returning the
+	/// position.
+	pub fn insert(&mut self, value: T) -> usize {
+		let index = self.as_slice().partition_point(|item| *item <= value);
+		self.0.write().insert(index, value);
+		index
+	}
+	/// Removes and returns the element at `index`.
+	///
+	/// # Panics
+	/// If `index` is out of bounds.
+	pub fn remove(&mut self, index: usize) -> T {
+		self.0.write().remove(index)
+	}
+	/// Whether the vector contains an element equal to `value`, found with a binary search.
+	pub fn contains(&self, value: &T) -> bool {
+		self.as_slice().binary_search(value).is_ok()
+	}
+	/// Keeps only the elements for which `keep` returns true.
+	pub fn retain(&mut self, keep: impl FnMut(&T) -> bool) {
+		self.0.write().retain(keep);
+	}
+	/// Returns the elements, in order.
+	pub fn into_inner(self) -> Vec<T> {
+		self.0.into_inner()
+	}
+}
+impl<T: Debug + Ord> Default for SortedVec<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+impl<T: Debug + Ord> From<Vec<T>> for SortedVec<T> {
+	fn from(mut value: Vec<T>) -> Self {
+		value.sort();
+		Self(Repr::with_msg(value, |value| value.is_sorted(), "the vector isn't sorted"))
+	}
+}
+impl<T: Debug + Ord> Extend<T> for SortedVec<T> {
+	fn extend<It: IntoIterator<Item = T>>(&mut self, iter: It) {
+		let mut items = self.0.write();
+		items.extend(iter);
+		items.sort();
+	}
+}
+impl<T: Debug + Ord> Deref for SortedVec<T> {
+	type Target = [T];
+	fn deref(&self) -> &Self::Target {
+		self.as_slice()
+	}
+}
+impl<T: Debug + Ord> Debug for SortedVec<T> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "SortedVec({:?})", self.as_slice())
+	}
+}
@@ -0,0 +1,66 @@
+//! Helpers for property testing representation invariants with [`proptest`].
+use crate::Repr;
+use proptest::strategy::Strategy;
+use proptest::test_runner::TestCaseError;
+use std::fmt::Debug;
+
+impl<T: Debug + 'static, I: Fn(&T) -> bool + Clone + 'static> Repr<T, I> {
+	/// Creates a [`Strategy`] that generates representation invariants from the values generated by
+	/// the inner strategy. Values that violate the invariant are rejected and regenerated, so every
+	/// generated (and shrunk) value satisfies the invariant.
+	/// ```rust
+	/// use proptest::prelude::*;
+	/// use repr_rs::Repr;
+	/// proptest! {
+	///   fn doubles_stay_even(repr in Repr::strategy(0..1000i32, |n: &i32| n % 2 == 0)) {
+	///     prop_assert_eq!(0, (*repr * 2) % 2);
+	///   }
+	/// }
+	/// doubles_stay_even();
+	/// ```
+	pub fn strategy(inner: impl Strategy<Value = T>, invariant: I) -> impl Strategy<Value = Self> {
+		let filter = invariant.clone();
+		inner
+			.prop_filter("Invariant violated", move |value| filter(value))
+			.prop_map(move |value| Repr::new(value, invariant.clone()))
+	}
+}
+
+/// Applies each operation to the value of a representation invariant in turn, failing the test
+/// case with the offending operation if any of them leaves the value in a state that violates the
+/// invariant. Operations are applied to a clone of the value, so a violation doesn't panic and the
+/// representation invariant keeps the last valid value.
+/// ```rust
+/// use proptest::prelude::*;
+/// use repr_rs::Repr;
+/// use repr_rs::proptest_support::check_operations;
+/// #[derive(Debug, Clone)]
+/// enum Op { Push(u8), Pop }
+/// proptest! {
+///   fn ops_keep_the_stack_bounded(ops in prop::collection::vec(prop_oneof![
+///     any::<u8>().prop_map(Op::Push),
+///     Just(Op::Pop),
+///   ], 0..50)) {
+///     let mut stack = Repr::new(Vec::new(), |v: &Vec<u8>| v.len() <= 100);
+///     check_operations(&mut stack, &ops, |stack, op| match op {
+///       Op::Push(n) => stack.push(*n),
+///       Op::Pop => { stack.pop(); },
+///     })?;
+///   }
+/// }
+/// ops_keep_the_stack_bounded();
+/// ```
+pub fn check_operations<T: Debug + Clone, I: Fn(&T) -> bool, O: Debug>(
+	repr: &mut Repr<T, I>,
+	operations: &[O],
+	apply: impl Fn(&mut T, &O),
+) -> Result<(), TestCaseError> {
+	for (i, operation) in operations.iter().enumerate() {
+		let mut value = repr.read().clone();
+		apply(&mut value, operation);
+		if let Err((_, violation)) = repr.replace(value) {
+			return Err(TestCaseError::fail(format!("Operation {} ({:?}) violated the invariant: {}", i, operation, violation)));
+		}
+	}
+	Ok(())
+}
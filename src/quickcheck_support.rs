@@ -0,0 +1,40 @@
+//! [`quickcheck::Arbitrary`] support for representation invariants with trait-based invariants.
+use crate::{Invariant, Repr};
+use quickcheck::{Arbitrary, Gen};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static ARBITRARY_RETRIES: AtomicUsize = AtomicUsize::new(1000);
+
+/// Sets how many values are generated when creating an arbitrary representation invariant before
+/// giving up because none of them satisfied the invariant. The default is 1000.
+/// ```rust
+/// repr_rs::quickcheck_support::set_arbitrary_retries(50);
+/// assert_eq!(50, repr_rs::quickcheck_support::arbitrary_retries());
+/// ```
+pub fn set_arbitrary_retries(retries: usize) {
+	ARBITRARY_RETRIES.store(retries, Ordering::Relaxed);
+}
+/// Gets the retry budget set by [`set_arbitrary_retries`].
+pub fn arbitrary_retries() -> usize {
+	ARBITRARY_RETRIES.load(Ordering::Relaxed)
+}
+
+/// Generates values with rejection sampling: arbitrary values are generated until one satisfies the
+/// invariant. Shrinking only yields values that satisfy the invariant.
+///
+/// # Panics
+/// Panics if no valid value was generated within the retry budget (see [`set_arbitrary_retries`]).
+impl<T: Invariant + Arbitrary> Arbitrary for Repr<T> {
+	fn arbitrary(g: &mut Gen) -> Self {
+		let retries = arbitrary_retries();
+		for _ in 0..retries {
+			if let Ok(repr) = Repr::try_new(T::arbitrary(g)) {
+				return repr;
+			}
+		}
+		panic!("Could not generate a value satisfying the invariant ({}) in {} attempts", T::VIOLATION_MESSAGE, retries);
+	}
+	fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+		Box::new(self.read().shrink().filter_map(|value| Repr::try_new(value).ok()))
+	}
+}
@@ -0,0 +1,202 @@
+//! Representation invariants for read-heavy values, whose reads never wait for writes. See
+//! [`ArcRepr`] and [`crate::Repr::split`].
+use crate::repr::ReprMutator;
+use crate::{Repr, Violation};
+use alloc::sync::Arc;
+use core::fmt::Debug;
+use core::panic::AssertUnwindSafe;
+use core::sync::atomic::Ordering;
+use crossbeam_epoch::{self as epoch, Atomic, Owned};
+
+/// An atomically replaceable [`Arc`], whose replaced values are freed once no reader can still be
+/// loading them.
+struct Slot<T>(Atomic<Arc<T>>);
+impl<T: Send + Sync + 'static> Slot<T> {
+	fn new(value: Arc<T>) -> Self {
+		Self(Atomic::new(value))
+	}
+	fn load(&self) -> Arc<T> {
+		let guard = epoch::pin();
+		let current = self.0.load(Ordering::Acquire, &guard);
+		// Safety: the pointer is never null, and a replaced value is only destroyed once every
+		// thread that was pinned when it was replaced has unpinned.
+		unsafe { current.deref() }.clone()
+	}
+	fn store(&self, value: Arc<T>) {
+		let guard = epoch::pin();
+		let old = self.0.swap(Owned::new(value), Ordering::AcqRel, &guard);
+		// Safety: the old value is no longer reachable, so only threads that are already pinned
+		// can be loading it.
+		unsafe { guard.defer_destroy(old) };
+	}
+}
+impl<T> Drop for Slot<T> {
+	fn drop(&mut self) {
+		// Safety: the slot is borrowed mutably, so no other thread can load the value anymore.
+		unsafe {
+			drop(self.0.load(Ordering::Relaxed, epoch::unprotected()).into_owned());
+		}
+	}
+}
+
+/// A representation invariant whose readers load an [`Arc`] snapshot of the value without ever
+/// waiting for a lock, while writers copy the value, modify and check the copy, and then swap it in
+/// (read-copy-update). This suits values that are read far more often than they are written:
+/// reads don't contend with each other or with writes, and a snapshot stays valid (and unchanged)
+/// for as long as it's held, but every write clones the value.
+///
+/// Replaced values are freed once no reader can still be loading them, using epoch-based
+/// reclamation.
+/// ```rust
+/// use std::thread;
+/// use repr_rs::ArcRepr;
+/// #[derive(Debug, Clone)]
+/// struct MinMax { min: i32, max: i32 }
+/// let repr = ArcRepr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+/// let before = repr.load();
+/// thread::scope(|scope| {
+///   scope.spawn(|| repr.update(|mm| mm.max = 10).unwrap());
+///   scope.spawn(|| assert!(repr.load().max >= 5));
+/// });
+/// assert!(repr.update(|mm| mm.min = 20).is_err());
+/// assert_eq!(10, repr.load().max);
+/// assert_eq!(5, before.max);
+/// ```
+pub struct ArcRepr<T: Debug, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	current: Slot<T>,
+	invariant: I,
+	violation_message: &'static str,
+}
+impl<T: Debug + Send + Sync + 'static, I: Fn(&T) -> bool> ArcRepr<T, I> {
+	/// Creates a new representation invariant with the given value and invariant function.
+	pub fn new(value: T, invariant: I) -> Self {
+		Self::with_msg(value, invariant, "Invariant violated")
+	}
+	/// Creates a new representation invariant with the given value, invariant function, and
+	/// violation message.
+	pub fn with_msg(value: T, invariant: I, violation_message: &'static str) -> Self {
+		Self {
+			current: Slot::new(Arc::new(value)),
+			invariant,
+			violation_message,
+		}
+	}
+	/// Loads a snapshot of the current value. Later writes don't change the snapshot.
+	pub fn load(&self) -> Arc<T> {
+		self.current.load()
+	}
+	/// Copies the current value, applies `update` to the copy and, if the copy satisfies the
+	/// invariant, swaps it in and returns it. If another thread swapped in a value in the meantime,
+	/// the update is applied (and checked) again to a copy of that value, so `update` may be called
+	/// more than once. If the updated copy violates the invariant, the value is left untouched.
+	pub fn update(&self, mut update: impl FnMut(&mut T)) -> Result<Arc<T>, Violation> where T: Clone {
+		let guard = epoch::pin();
+		let mut current = self.current.0.load(Ordering::Acquire, &guard);
+		loop {
+			// Safety: see `Slot::load`.
+			let mut value = T::clone(unsafe { current.deref() });
+			update(&mut value);
+			if !(self.invariant)(&value) {
+				return Err(Violation::new(self.violation_message, &value));
+			}
+			let value = Arc::new(value);
+			match self.current.0.compare_exchange(current, Owned::new(value.clone()), Ordering::AcqRel, Ordering::Acquire, &guard) {
+				Ok(_) => {
+					// Safety: see `Slot::store`.
+					unsafe { guard.defer_destroy(current) };
+					return Ok(value);
+				}
+				Err(race) => current = race.current,
+			}
+		}
+	}
+	/// Replaces the value with a new one if it satisfies the invariant. Otherwise the new value is
+	/// handed back alongside the [`Violation`] and the current value is left untouched.
+	pub fn store(&self, value: T) -> Result<(), (T, Violation)> {
+		if !(self.invariant)(&value) {
+			let violation = Violation::new(self.violation_message, &value);
+			return Err((value, violation));
+		}
+		self.current.store(Arc::new(value));
+		Ok(())
+	}
+	/// Consumes the representation invariant and returns a snapshot of the value.
+	pub fn into_inner(self) -> Arc<T> {
+		self.load()
+	}
+}
+impl<T: Debug + Send + Sync + 'static, I: Fn(&T) -> bool> Debug for ArcRepr<T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "ArcRepr({:?})", self.load())
+	}
+}
+
+/// The writing half of a representation invariant that was [`Repr::split`]. Every write that passes
+/// the invariant check is published to the [`ReadHandle`]s.
+pub struct WriteHandle<T: Debug, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	repr: Repr<T, I>,
+	published: Arc<Slot<T>>,
+}
+impl<T: Debug + Clone + Send + Sync + 'static, I: Fn(&T) -> bool> WriteHandle<T, I> {
+	pub(crate) fn new(mut repr: Repr<T, I>) -> (Self, ReadHandle<T>) {
+		let published = Arc::new(Slot::new(Arc::new(repr.read().clone())));
+		// The slot only holds values, so it's exactly as unwind safe as the value, which is all the
+		// unwind safety of a `Repr` relies on.
+		let publisher = AssertUnwindSafe(published.clone());
+		repr.after_write(move |value| publisher.store(Arc::new(value.clone())));
+		let reader = ReadHandle {
+			published: published.clone(),
+		};
+		(Self { repr, published }, reader)
+	}
+	/// Borrows a read-only view of the value. This is always the last published value, as there is
+	/// no other writer.
+	#[inline]
+	pub fn read(&self) -> &T {
+		self.repr.read()
+	}
+	/// Borrows a mutable view of the value. The invariant is checked when the view is dropped, and
+	/// if it holds the value is published. See [`Repr::write`].
+	pub fn write(&mut self) -> ReprMutator<'_, T, I> {
+		self.repr.write()
+	}
+	/// Sets and publishes the value if it satisfies the invariant. See [`Repr::set`].
+	pub fn set(&mut self, value: T) -> Result<(), (T, Violation)> {
+		self.repr.set(value)
+	}
+	/// Creates another handle that reads the published values.
+	pub fn reader(&self) -> ReadHandle<T> {
+		ReadHandle {
+			published: self.published.clone(),
+		}
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for WriteHandle<T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "WriteHandle({:?})", self.repr.read())
+	}
+}
+
+/// The reading half of a representation invariant that was [`Repr::split`]. Reads load the last
+/// value that passed the invariant check without waiting for the writer.
+pub struct ReadHandle<T> {
+	published: Arc<Slot<T>>,
+}
+impl<T: Send + Sync + 'static> ReadHandle<T> {
+	/// Loads a snapshot of the last published value. Later writes don't change the snapshot.
+	pub fn load(&self) -> Arc<T> {
+		self.published.load()
+	}
+}
+impl<T> Clone for ReadHandle<T> {
+	fn clone(&self) -> Self {
+		Self {
+			published: self.published.clone(),
+		}
+	}
+}
+impl<T: Debug + Send + Sync + 'static> Debug for ReadHandle<T> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "ReadHandle({:?})", self.load())
+	}
+}
@@ -0,0 +1,70 @@
+//! Shared, read-only representation invariants. See [`ReprReader`].
+use crate::{Repr, Violation};
+use alloc::sync::Arc;
+use core::fmt::Debug;
+use core::ops::Deref;
+
+/// A cloneable, read-only handle to a representation invariant, created with [`Repr::reader`].
+/// Cloning the handle only clones an [`Arc`], so it can be handed to any number of components that
+/// need to read the value. Nothing can be written through it, and the representation invariant
+/// can't be taken back out of it, so those components are statically prevented from mutating or
+/// consuming the value.
+/// ```rust
+/// use repr_rs::Repr;
+/// use repr_rs::reader::ReprReader;
+/// #[derive(Debug)]
+/// struct MinMax { min: i32, max: i32 }
+/// fn render(range: ReprReader<MinMax, impl Fn(&MinMax) -> bool>) -> String {
+///   format!("{}..{}", range.min, range.max)
+/// }
+/// let reader = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max).reader();
+/// assert_eq!("1..5", render(reader.clone()));
+/// assert_eq!(1, reader.read().min);
+/// ```
+pub struct ReprReader<T: Debug, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	inner: Arc<Repr<T, I>>,
+}
+impl<T: Debug, I: Fn(&T) -> bool> ReprReader<T, I> {
+	pub(crate) fn new(repr: Repr<T, I>) -> Self {
+		Self {
+			inner: Arc::new(repr),
+		}
+	}
+	/// Borrows a read-only view of the value. See [`Repr::read`].
+	#[inline]
+	pub fn read(&self) -> &T {
+		self.inner.read()
+	}
+	/// Checks whether the value satisfies the invariant. See [`Repr::validate`].
+	pub fn validate(&self) -> Result<(), Violation> {
+		self.inner.validate()
+	}
+	/// The version of the value. See [`Repr::version`]. As nothing can write the value anymore,
+	/// this never changes.
+	pub fn version(&self) -> u64 {
+		self.inner.version()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Clone for ReprReader<T, I> {
+	fn clone(&self) -> Self {
+		Self {
+			inner: self.inner.clone(),
+		}
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Deref for ReprReader<T, I> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.read()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for ReprReader<T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "ReprReader({:?})", self.read())
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> From<Repr<T, I>> for ReprReader<T, I> {
+	fn from(repr: Repr<T, I>) -> Self {
+		Self::new(repr)
+	}
+}
@@ -1,15 +1,121 @@
-use std::cell::UnsafeCell;
-use std::fmt::{Debug, Display};
-use std::hash::{Hash, Hasher};
-use std::ops::{Deref, DerefMut};
+use core::borrow::Borrow;
+use core::cell::UnsafeCell;
+use core::fmt::{Debug, Display};
+use core::hash::{Hash, Hasher};
+use core::ops::{Deref, DerefMut};
+use core::panic::{RefUnwindSafe, UnwindSafe};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use crate::audit::{Audit, AuditRecord, Pending};
+use crate::policy::{self, CheckPolicy};
+use crate::cache::stats::Stopwatch;
+use crate::lens::{Lens, ReprAt};
+use crate::metrics::Metrics;
+use crate::{Invariant, Violation};
+
+pub(crate) type Explanation<T> = dyn Fn(&T) -> String + Send + Sync + RefUnwindSafe;
+type Observer<T> = dyn Fn(&T) + Send + Sync + RefUnwindSafe;
+type SlowCheck<T> = dyn Fn(&T, Duration) + Send + Sync + RefUnwindSafe;
+
+/// Detects writes that leave the value as it was. The functions are set when change detection is
+/// enabled, which is the only place that knows `T: Clone + PartialEq`.
+struct ChangeDetection<T> {
+	snapshot: fn(&T) -> T,
+	eq: fn(&T, &T) -> bool,
+	/// The value before the write that is in progress.
+	before: Option<T>,
+}
+
+/// The version a representation invariant actually had when a write expected another one. See
+/// [`Repr::try_write_if_version`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CurrentVersion(pub u64);
+impl Display for CurrentVersion {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "The value was written since it was read (it is now at version {})", self.0)
+	}
+}
+impl core::error::Error for CurrentVersion {}
 
 /// Wraps a value and ensures that an invariant is maintained while allowing that value to be
 /// mutated. The invariant is checked after every mutation.
 /// See [`crate::CacheableRepr`] for a version of this struct that supports caching.
-pub struct Repr<T: Debug, I: Fn(&T) -> bool> {
+///
+/// With the feature `release-unchecked` enabled, the checks after writes through mutable views
+/// (including projections) are compiled out of release builds (i.e. when `debug_assertions` are
+/// disabled), so `Repr` has no runtime cost there. Everything configured beyond the invariant (check
+/// policies, hooks, sinks, metrics, etc.) lives behind a single pointer that is only allocated once
+/// something is configured, so a plain `Repr` is only one pointer bigger than its value, invariant,
+/// message and [`Repr::version`]. The methods that hand back a [`Violation`]
+/// instead of panicking ([`Repr::replace`], [`Repr::set`], [`Repr::swap`], [`Repr::validate`] and
+/// [`Repr::try_new`]) always evaluate the invariant, as their result depends on it.
+///
+/// The invariant type defaults to a function pointer, so `Repr<T>` is a representation invariant
+/// whose invariant comes from an [`Invariant`] implementation (or any plain function).
+///
+/// `Repr` owns its value, so unsized values (`str`, `[T]`, `dyn Trait`) go behind a `Box` (e.g.
+/// `Repr<Box<str>>`). To check part of the value as a slice or trait object, use
+/// [`ReprMutator::project`], and for unsized values that are owned elsewhere, see [`crate::guard`].
+pub struct Repr<T: Debug, I: Fn(&T) -> bool = fn(&T) -> bool> {
 	pub(crate) inner: UnsafeCell<T>,
 	invariant: I,
 	violation_message: &'static str,
+	version: u64,
+	/// Allocated the first time anything else is configured.
+	extension: Option<Box<Extension<T>>>,
+}
+
+/// Everything a representation invariant can be configured with beyond its invariant and message,
+/// and the bookkeeping that goes with it. It lives behind a single pointer that stays empty until
+/// something is configured, so a plain `Repr` is only one pointer bigger than its value,
+/// invariant, message and version.
+struct Extension<T> {
+	explanation: Option<Arc<Explanation<T>>>,
+	check_policy: CheckPolicy,
+	determinism_checks: Option<usize>,
+	read_check_policy: CheckPolicy,
+	mutations: u64,
+	reads: AtomicUsize,
+	poisoned: bool,
+	observers: Vec<Box<Observer<T>>>,
+	before_write: Vec<Box<Observer<T>>>,
+	after_write: Vec<Box<Observer<T>>>,
+	audit: Audit<T>,
+	metrics: Option<Arc<dyn Metrics>>,
+	slow_check: Option<(Duration, Arc<SlowCheck<T>>)>,
+	change_detection: Option<ChangeDetection<T>>,
+	#[cfg(feature = "stream")]
+	events: crate::events::Subscribers<T>,
+	#[cfg(all(feature = "history", not(target_arch = "wasm32")))]
+	history: crate::history::History<T>,
+}
+impl<T: Debug> Extension<T> {
+	const fn new() -> Self {
+		Self {
+			explanation: None,
+			check_policy: CheckPolicy::Always,
+			determinism_checks: None,
+			read_check_policy: CheckPolicy::Never,
+			mutations: 0,
+			reads: AtomicUsize::new(0),
+			poisoned: false,
+			observers: Vec::new(),
+			before_write: Vec::new(),
+			after_write: Vec::new(),
+			audit: Audit::new(),
+			metrics: None,
+			slow_check: None,
+			change_detection: None,
+			#[cfg(feature = "stream")]
+			events: crate::events::Subscribers::new(),
+			#[cfg(all(feature = "history", not(target_arch = "wasm32")))]
+			history: crate::history::History::new(),
+		}
+	}
 }
 impl<T: Debug, I: Fn(&T) -> bool> Repr<T, I> {
 	/// Creates a new representation invariant with the given value and invariant function.
@@ -27,6 +133,8 @@ impl<T: Debug, I: Fn(&T) -> bool> Repr<T, I> {
 			inner: UnsafeCell::new(inner),
 			invariant,
 			violation_message: "Invariant violated",
+			version: 0,
+			extension: None,
 		}
 	}
 	/// Creates a new representation invariant with the given value, invariant function, and violation message.
@@ -45,8 +153,128 @@ impl<T: Debug, I: Fn(&T) -> bool> Repr<T, I> {
 			inner: UnsafeCell::new(inner),
 			invariant,
 			violation_message,
+			version: 0,
+			extension: None,
 		}
 	}
+	/// Sets the [`CheckPolicy`] that decides when the invariant is checked after a mutation.
+	/// ```rust
+	/// use repr_rs::{CheckPolicy, Repr};
+	/// #[derive(Debug)]
+	/// struct MinMax { min: i32, max: i32 }
+	/// let mut repr = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+	///   .with_check_policy(CheckPolicy::Sampled(2));
+	/// // Only every second mutation is checked.
+	/// repr.write().min = 6;
+	/// repr.write().min = 4;
+	/// ```
+	pub fn with_check_policy(mut self, check_policy: CheckPolicy) -> Self {
+		self.extension().check_policy = check_policy;
+		self
+	}
+	/// Sets how many extra times the invariant is evaluated after each check (in debug builds) to
+	/// detect non-deterministic invariants. Setting this to 0 disables the detection, which is
+	/// useful for expensive invariants. Defaults to [`crate::policy::default_determinism_checks`].
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// #[derive(Debug)]
+	/// struct MinMax { min: i32, max: i32 }
+	/// let mut repr = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max)
+	///   .with_determinism_checks(1);
+	/// repr.write().min = 4;
+	/// ```
+	pub fn with_determinism_checks(mut self, checks: usize) -> Self {
+		self.extension().determinism_checks = Some(checks);
+		self
+	}
+	/// Disables the non-determinism detector for this representation invariant. Use this for
+	/// invariants that are intentionally impure (e.g. they consult a config snapshot).
+	/// ```rust
+	/// use std::sync::atomic::{AtomicBool, Ordering};
+	/// use repr_rs::Repr;
+	/// static STRICT: AtomicBool = AtomicBool::new(false);
+	/// let mut repr = Repr::new(5, |n| !STRICT.load(Ordering::Relaxed) || *n < 10)
+	///   .without_determinism_check();
+	/// *repr.write() = 20;
+	/// ```
+	pub fn without_determinism_check(self) -> Self {
+		self.with_determinism_checks(0)
+	}
+	/// Sets the [`CheckPolicy`] that decides when the invariant is re-checked on [`Repr::read`].
+	/// By default reads are never checked, but if the value contains interior mutability or is
+	/// shared with FFI it can be corrupted outside of [`Repr::write`], and this catches that early.
+	/// ```rust
+	/// use std::cell::Cell;
+	/// use repr_rs::{CheckPolicy, Repr};
+	/// let repr = Repr::new(Cell::new(1), |n| n.get() > 0)
+	///   .with_read_check_policy(CheckPolicy::Always);
+	/// repr.read().set(2);
+	/// assert_eq!(2, repr.read().get());
+	/// ```
+	pub fn with_read_check_policy(mut self, read_check_policy: CheckPolicy) -> Self {
+		self.extension().read_check_policy = read_check_policy;
+		self
+	}
+	/// Sets a function that explains why the invariant was violated. When set, the explanation is
+	/// used as the violation message instead of the static message.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let mut repr = Repr::new(vec![1, 2, 3], |v| v.len() <= 3)
+	///   .with_explanation(|v| format!("at most 3 elements are allowed, but there were {}", v.len()));
+	/// let (_, violation) = repr.set(vec![1, 2, 3, 4]).unwrap_err();
+	/// assert_eq!("at most 3 elements are allowed, but there were 4", violation.message());
+	/// ```
+	pub fn with_explanation(mut self, explanation: impl Fn(&T) -> String + Send + Sync + RefUnwindSafe + 'static) -> Self {
+		self.extension().explanation = Some(Arc::new(explanation));
+		self
+	}
+	/// Skips writes that leave the value as it was: the invariant isn't checked, the
+	/// [`Repr::version`] isn't incremented, observers aren't notified, and the caches of a
+	/// [`crate::CacheableRepr`] aren't invalidated. This makes idempotent updates free, at the cost
+	/// of cloning the value when a mutable view is handed out. Hooks registered with
+	/// [`Repr::before_write`] still run, because they run before the value is known to be unchanged.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let mut repr = Repr::new(vec![1, 2], |v: &Vec<i32>| !v.is_empty())
+	///   .with_change_detection();
+	/// repr.write().sort();
+	/// repr.set(vec![1, 2]).unwrap();
+	/// assert_eq!(0, repr.version());
+	/// repr.write().push(3);
+	/// assert_eq!(1, repr.version());
+	/// ```
+	pub fn with_change_detection(mut self) -> Self where T: Clone + PartialEq {
+		self.extension().change_detection = Some(ChangeDetection {
+			snapshot: T::clone,
+			eq: T::eq,
+			before: None,
+		});
+		self
+	}
+	/// Keeps the last `capacity` committed values (starting with the current one) with the time
+	/// they were written, so when the invariant is finally violated, [`Repr::history`] shows the
+	/// trajectory that led there. The value is cloned on every write. The history isn't copied
+	/// into clones.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let mut repr = Repr::new(vec![1], |v: &Vec<i32>| v.len() <= 3).with_history(2);
+	/// repr.set(vec![1, 2]).unwrap();
+	/// repr.set(vec![1, 2, 3]).unwrap();
+	/// assert!(repr.set(vec![1, 2, 3, 4]).is_err());
+	/// let trajectory: Vec<&Vec<i32>> = repr.history().map(|snapshot| snapshot.value()).collect();
+	/// assert_eq!(vec![&vec![1, 2], &vec![1, 2, 3]], trajectory);
+	/// ```
+	#[cfg(all(feature = "history", not(target_arch = "wasm32")))]
+	pub fn with_history(mut self, capacity: usize) -> Self where T: Clone {
+		let extension = self.extension.get_or_insert_with(|| Box::new(Extension::new()));
+		extension.history.enable(capacity, self.inner.get_mut());
+		self
+	}
+	/// The values kept since [`Repr::with_history`] was set, oldest first.
+	#[cfg(all(feature = "history", not(target_arch = "wasm32")))]
+	pub fn history(&self) -> impl DoubleEndedIterator<Item = &crate::history::Snapshot<T>> + ExactSizeIterator {
+		self.extension.as_ref().map(|extension| extension.history.snapshots.iter()).unwrap_or_default()
+	}
 	/// Borrows a read-only view of the value in the representation invariant.
 	/// ```rust
 	/// use repr_rs::Repr;
@@ -59,9 +287,14 @@ impl<T: Debug, I: Fn(&T) -> bool> Repr<T, I> {
 	/// ```
 	#[inline]
 	pub fn read(&self) -> &T {
-		// Safety: borrowing rules ensure that T is valid, and because this is an immutable borrow
-		// of the Repr, no mutable borrows can take place.
-		unsafe { &*self.inner.get() }
+		let data = self.read_unchecked();
+		if let Some(extension) = self.extension.as_deref().filter(|extension| extension.read_check_policy != CheckPolicy::Never) {
+			let reads = extension.reads.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+			if extension.read_check_policy.should_check(reads as u64) {
+				self.assert_holds(data);
+			}
+		}
+		data
 	}
 	/// Borrows a mutable view of the value in the representation invariant.
 	/// ```rust
@@ -96,12 +329,53 @@ impl<T: Debug, I: Fn(&T) -> bool> Repr<T, I> {
 	/// assert_eq!(5, view.max);
 	/// ```
 	#[inline]
-	pub fn write(&mut self) -> ReprMutator<T, I> {
-		// Can be `const` when `const_mut_refs` is stabilised.
+	pub fn write(&mut self) -> ReprMutator<'_, T, I> {
+		self.begin_write();
 		ReprMutator {
 			repr: self,
 		}
 	}
+	/// Borrows a mutable view of the value, like [`Repr::write`], but only if the
+	/// [`Repr::version`] is still `expected`. Otherwise the value was written since it was read, and
+	/// the current version is returned so the caller can read the value again and retry.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let mut repr = Repr::new(1, |n| *n > 0);
+	/// let seen = repr.version();
+	/// *repr.write() = 2;
+	/// let Err(current) = repr.try_write_if_version(seen) else { unreachable!() };
+	/// assert_eq!(repr.version(), current.0);
+	/// *repr.try_write_if_version(current.0).unwrap() += 1;
+	/// assert_eq!(3, *repr.read());
+	/// ```
+	pub fn try_write_if_version(&mut self, expected: u64) -> Result<ReprMutator<'_, T, I>, CurrentVersion> {
+		if self.version != expected {
+			return Err(CurrentVersion(self.version));
+		}
+		Ok(self.write())
+	}
+	/// Borrows a mutable view of part of the value, focused by a [`Lens`]. The invariant of the
+	/// whole value is checked when the view is dropped.
+	/// ```rust
+	/// use repr_rs::{lens, Repr};
+	/// #[derive(Debug)]
+	/// struct Tls { cert: String }
+	/// #[derive(Debug)]
+	/// struct Server { tls: Tls }
+	/// #[derive(Debug)]
+	/// struct Config { server: Server }
+	/// let mut repr = Repr::new(
+	///   Config { server: Server { tls: Tls { cert: "cert.pem".into() } } },
+	///   |c| c.server.tls.cert.ends_with(".pem"),
+	/// );
+	/// *repr.at(lens!(Config => server.tls.cert)) = "new.pem".into();
+	/// // or, by chaining lenses
+	/// *repr.at(lens!(Config => server)).at(lens!(Server => tls.cert)) = "newer.pem".into();
+	/// assert_eq!("newer.pem", repr.read().server.tls.cert);
+	/// ```
+	pub fn at<L: Lens<T>>(&mut self, lens: L) -> ReprAt<ReprMutator<'_, T, I>, L> {
+		ReprAt::new(self.write(), lens)
+	}
 	/// Consumes the representation invariant and returns the inner value.
 	/// ```rust
 	/// use repr_rs::Repr;
@@ -115,14 +389,652 @@ impl<T: Debug, I: Fn(&T) -> bool> Repr<T, I> {
 	pub fn into_inner(self) -> T {
 		self.inner.into_inner()
 	}
-	pub(crate) fn check(&mut self) {
-		let data = self.inner.get_mut();
-		assert!((self.invariant)(data), "{}\nState was: {:?}", self.violation_message, data);
-		// In debug mode
-		for _ in 0..10 {
+	/// Consumes the representation invariant, transforms the value, and wraps the result in a new
+	/// representation invariant with the given invariant function.
+	///
+	/// # Panics
+	/// Panics if the transformed value violates the new invariant. See [`Repr::try_map`] for a
+	/// non-panicking version.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let repr = Repr::new(vec![3, 1, 2], |v| !v.is_empty());
+	/// let sorted = repr.map(
+	///   |mut v| { v.sort(); v },
+	///   |v| v.is_sorted(),
+	/// );
+	/// assert_eq!(vec![1, 2, 3], *sorted.read());
+	/// ```
+	pub fn map<U: Debug, J: Fn(&U) -> bool>(self, f: impl FnOnce(T) -> U, invariant: J) -> Repr<U, J> {
+		match self.try_map(f, invariant) {
+			Ok(repr) => repr,
+			Err((_, violation)) => panic!("{}", violation),
+		}
+	}
+	/// Consumes the representation invariant, transforms the value, and wraps the result in a new
+	/// representation invariant with the given invariant function. If the transformed value
+	/// violates the new invariant it is handed back alongside the [`Violation`].
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let repr = Repr::new(-5, |n| *n != 0);
+	/// let (rejected, _violation) = repr.try_map(|n| n * 2, |n| *n > 0).unwrap_err();
+	/// assert_eq!(-10, rejected);
+	/// ```
+	pub fn try_map<U: Debug, J: Fn(&U) -> bool>(self, f: impl FnOnce(T) -> U, invariant: J) -> Result<Repr<U, J>, (U, Violation)> {
+		let mapped = Repr::new(f(self.into_inner()), invariant);
+		match mapped.validate() {
+			Ok(()) => Ok(mapped),
+			Err(violation) => Err((mapped.into_inner(), violation)),
+		}
+	}
+	/// Checks the invariant after a write through a mutable view. Returns whether the value may
+	/// have changed, i.e. whether caches of it need to be invalidated.
+	pub(crate) fn check(&mut self) -> bool {
+		let Some(extension) = self.extension.as_deref_mut() else {
+			if panicking() {
+				self.extension().poisoned = true;
+				return true;
+			}
+			self.verify(None);
+			self.committed();
+			return true;
+		};
+		let before = extension.change_detection.as_mut().and_then(|detection| detection.before.take());
+		let audit = extension.audit.take();
+		if panicking() {
+			// The value may only be partially mutated, and panicking again would abort the process.
+			extension.poisoned = true;
+			return true;
+		}
+		if let (Some(before), Some(detection)) = (before, &extension.change_detection) {
+			if (detection.eq)(&before, self.inner.get_mut()) {
+				return false;
+			}
+		}
+		self.verify(audit);
+		self.committed();
+		true
+	}
+	fn verify(&mut self, audit: Option<Pending>) {
+		let checked = match self.extension.as_deref_mut() {
+			Some(extension) => {
+				extension.mutations = extension.mutations.wrapping_add(1);
+				extension.check_policy.should_check(extension.mutations)
+			}
+			None => CheckPolicy::Always.should_check(1),
+		};
+		let data = self.read_unchecked();
+		let timed = self.extension.as_deref().is_some_and(|extension| extension.metrics.is_some() || extension.slow_check.is_some());
+		if audit.is_none() && !timed {
+			if checked {
+				self.assert_holds(data);
+			}
+		} else {
+			let outcome = if checked { self.measure(data) } else { Ok(()) };
+			if let (Some(audit), Some(extension)) = (audit, &self.extension) {
+				extension.audit.emit(audit, data, outcome.as_ref().map(drop));
+			}
+			if let Err(violation) = outcome {
+				panic!("{}", violation);
+			}
+		}
+		if !checked || !cfg!(debug_assertions) {
+			return;
+		}
+		let checks = self.extension.as_deref().and_then(|extension| extension.determinism_checks).unwrap_or_else(policy::default_determinism_checks);
+		for _ in 0..checks {
 			debug_assert!((self.invariant)(data), "Invariants should be deterministic! The invariant function for this Repr is not deterministic.");
 		}
 	}
+	/// Evaluates the invariant for a write, reporting the check to the metrics and warning if it
+	/// was slow.
+	fn measure(&self, data: &T) -> Result<(), Violation> {
+		let Some(extension) = self.extension.as_deref().filter(|extension| extension.metrics.is_some() || extension.slow_check.is_some()) else {
+			return self.evaluate(data);
+		};
+		let stopwatch = Stopwatch::start();
+		let outcome = self.evaluate(data);
+		let took = stopwatch.elapsed();
+		if let Some((threshold, warn)) = &extension.slow_check {
+			if took > *threshold {
+				warn(data, took);
+			}
+		}
+		if let Some(metrics) = &extension.metrics {
+			metrics.check(took);
+			if let Err(violation) = &outcome {
+				metrics.violation(violation);
+			}
+		}
+		outcome
+	}
+	/// Called after every write that was committed (i.e. that didn't violate the invariant).
+	fn committed(&mut self) {
+		self.version = self.version.wrapping_add(1);
+		let Some(extension) = self.extension.as_deref_mut() else {
+			return;
+		};
+		let data = self.inner.get_mut();
+		for hook in &extension.after_write {
+			hook(data);
+		}
+		for observer in &extension.observers {
+			observer(data);
+		}
+		#[cfg(feature = "stream")]
+		extension.events.publish(data);
+		#[cfg(all(feature = "history", not(target_arch = "wasm32")))]
+		extension.history.record(data);
+	}
+	/// Called before every write, before the mutable view is handed out.
+	pub(crate) fn begin_write(&mut self) {
+		self.begin_replace();
+		if let Some(detection) = self.extension.as_deref_mut().and_then(|extension| extension.change_detection.as_mut()) {
+			detection.before = Some((detection.snapshot)(self.inner.get_mut()));
+		}
+	}
+	/// Called before a write that replaces the whole value. These writes don't finish in
+	/// [`Repr::check`], so no snapshot is taken for change detection.
+	fn begin_replace(&mut self) {
+		let Some(extension) = self.extension.as_deref_mut() else {
+			return;
+		};
+		let data = self.inner.get_mut();
+		for hook in &extension.before_write {
+			hook(data);
+		}
+		extension.audit.begin(data);
+	}
+	/// The extension, allocated if nothing was configured yet.
+	fn extension(&mut self) -> &mut Extension<T> {
+		self.extension.get_or_insert_with(|| Box::new(Extension::new()))
+	}
+	/// Takes the write that is being audited, if any.
+	fn take_audit(&mut self) -> Option<Pending> {
+		self.extension.as_deref_mut().and_then(|extension| extension.audit.take())
+	}
+	/// Sends the record of an audited write to the audit sinks.
+	fn emit_audit(&self, audit: Option<Pending>, after: &T, outcome: Result<(), &Violation>) {
+		if let (Some(audit), Some(extension)) = (audit, &self.extension) {
+			extension.audit.emit(audit, after, outcome);
+		}
+	}
+	/// The metrics the checks are reported to, if any.
+	pub(crate) fn metrics(&self) -> Option<&Arc<dyn Metrics>> {
+		self.extension.as_deref().and_then(|extension| extension.metrics.as_ref())
+	}
+	/// Reports the checks of this representation invariant to the given [`Metrics`]. With a
+	/// [`crate::CacheableRepr`], cache reads and outstanding eager recomputations are reported too.
+	/// The metrics are copied into clones.
+	pub fn with_metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+		self.extension().metrics = Some(Arc::new(metrics));
+		self
+	}
+	/// Calls `warn` with the value and how long the check took whenever checking the invariant after
+	/// a write takes longer than `threshold`, so invariants that grow expensive (e.g. because they
+	/// walk a collection that keeps growing) don't go unnoticed. Checks are only timed with the
+	/// `std` feature, and not on wasm, where there is no monotonic clock.
+	/// ```rust
+	/// use std::sync::{Arc, Mutex};
+	/// use std::time::Duration;
+	/// use repr_rs::Repr;
+	/// let slow_checks = Arc::new(Mutex::new(Vec::new()));
+	/// let recorded = slow_checks.clone();
+	/// let mut repr = Repr::new(vec![1], |v: &Vec<i32>| v.iter().all(|n| *n > 0))
+	///   .with_slow_check_warning(Duration::from_secs(1), move |v, took| {
+	///     recorded.lock().unwrap().push((v.len(), took));
+	///   });
+	/// repr.write().push(2);
+	/// // Checking two elements is fast, so nothing was recorded.
+	/// assert!(slow_checks.lock().unwrap().is_empty());
+	/// ```
+	pub fn with_slow_check_warning(mut self, threshold: Duration, warn: impl Fn(&T, Duration) + Send + Sync + RefUnwindSafe + 'static) -> Self {
+		self.extension().slow_check = Some((threshold, Arc::new(warn)));
+		self
+	}
+	/// Attaches a sink that receives an [`AuditRecord`] of every write: the value before and
+	/// after it, whether it satisfied the invariant, and how long it took. Writes that violate the
+	/// invariant are recorded before the violation panics (or is returned). Sinks are copied into
+	/// clones.
+	/// ```rust
+	/// use std::sync::{Arc, Mutex};
+	/// use repr_rs::Repr;
+	/// let trail = Arc::new(Mutex::new(Vec::new()));
+	/// let sink = trail.clone();
+	/// let mut repr = Repr::new(1, |n| *n > 0).with_audit_sink(move |record| {
+	///   sink.lock().unwrap().push(format!("{} -> {} ({})", record.before, record.after, record.outcome.is_ok()));
+	/// });
+	/// *repr.write() = 2;
+	/// assert!(repr.set(-1).is_err());
+	/// assert_eq!(vec!["1 -> 2 (true)", "2 -> -1 (false)"], *trail.lock().unwrap());
+	/// ```
+	pub fn with_audit_sink(mut self, sink: impl Fn(AuditRecord<'_, T>) + Send + Sync + RefUnwindSafe + 'static) -> Self {
+		self.extension().audit.sinks.push(Arc::new(sink));
+		self
+	}
+	/// Registers a hook that is called with the current value before every write, before the
+	/// mutable view is handed out (or the value is replaced), for cross-cutting concerns like
+	/// locking external resources or starting a timer. See [`Repr::after_write`].
+	/// ```rust
+	/// use std::sync::{Arc, Mutex};
+	/// use repr_rs::Repr;
+	/// let log = Arc::new(Mutex::new(Vec::new()));
+	/// let mut repr = Repr::new(1, |n| *n > 0);
+	/// let before = log.clone();
+	/// repr.before_write(move |n| before.lock().unwrap().push(format!("writing {n}")));
+	/// let after = log.clone();
+	/// repr.after_write(move |n| after.lock().unwrap().push(format!("wrote {n}")));
+	/// *repr.write() += 1;
+	/// assert_eq!(vec!["writing 1", "wrote 2"], *log.lock().unwrap());
+	/// ```
+	pub fn before_write(&mut self, hook: impl Fn(&T) + Send + Sync + RefUnwindSafe + 'static) {
+		self.extension().before_write.push(Box::new(hook));
+	}
+	/// Registers a hook that is called with the new value after every write, once the invariant
+	/// check has passed, e.g. to sync the value to disk. It isn't called if the write violates the
+	/// invariant. After-write hooks are called before the observers registered with
+	/// [`Repr::on_change`]. See [`Repr::before_write`].
+	pub fn after_write(&mut self, hook: impl Fn(&T) + Send + Sync + RefUnwindSafe + 'static) {
+		self.extension().after_write.push(Box::new(hook));
+	}
+	/// Registers an observer that is called with the new value after every write that passes the
+	/// invariant check (including [`Repr::replace`], [`Repr::set`] and [`Repr::swap`]), e.g. for
+	/// logging or busting caches outside of the representation invariant. Observers are called in
+	/// the order they were registered, and aren't copied into clones.
+	/// ```rust
+	/// use std::sync::{Arc, Mutex};
+	/// use repr_rs::Repr;
+	/// let log = Arc::new(Mutex::new(Vec::new()));
+	/// let mut repr = Repr::new(1, |n| *n > 0);
+	/// let observed = log.clone();
+	/// repr.on_change(move |n| observed.lock().unwrap().push(*n));
+	/// *repr.write() = 2;
+	/// assert!(repr.set(-1).is_err());
+	/// repr.set(3).unwrap();
+	/// assert_eq!(vec![2, 3], *log.lock().unwrap());
+	/// ```
+	pub fn on_change(&mut self, observer: impl Fn(&T) + Send + Sync + RefUnwindSafe + 'static) {
+		self.extension().observers.push(Box::new(observer));
+	}
+	/// Subscribes to the writes committed from now on. Every write that passes the invariant check
+	/// (including [`Repr::replace`], [`Repr::set`] and [`Repr::swap`]) sends a
+	/// [`crate::events::ChangeEvent`] with a snapshot of the new value, so downstream systems can
+	/// react to validated state changes without wrapping every write. The value is cloned once per
+	/// write, and only while there are streams.
+	/// ```rust
+	/// # tokio::runtime::Runtime::new().unwrap().block_on(async move {
+	/// use futures::StreamExt;
+	/// use repr_rs::Repr;
+	/// let mut repr = Repr::new(1, |n| *n > 0);
+	/// let mut events = repr.events();
+	/// *repr.write() = 2;
+	/// repr.set(3).unwrap();
+	/// assert_eq!(2, **events.next().await.unwrap().value());
+	/// assert_eq!(3, **events.next().await.unwrap().value());
+	/// drop(repr);
+	/// assert!(events.next().await.is_none());
+	/// # })
+	/// ```
+	#[cfg(feature = "stream")]
+	pub fn events(&mut self) -> crate::events::ChangeStream<T> where T: Clone {
+		self.extension().events.subscribe()
+	}
+	/// Creates a [`tokio::sync::watch`] channel that always holds a snapshot of the latest value that
+	/// passed the invariant check, so async consumers can observe the value without borrowing the
+	/// representation invariant. The value is cloned once per write, and only while there are
+	/// receivers. Once the representation invariant is dropped, the receivers see the channel close.
+	/// ```rust
+	/// # tokio::runtime::Runtime::new().unwrap().block_on(async move {
+	/// use repr_rs::Repr;
+	/// let mut repr = Repr::new(1, |n| *n > 0);
+	/// let mut snapshots = repr.snapshot_channel();
+	/// let consumer = tokio::spawn(async move {
+	///   while snapshots.changed().await.is_ok() {}
+	///   **snapshots.borrow()
+	/// });
+	/// *repr.write() = 2;
+	/// assert!(repr.set(-1).is_err());
+	/// drop(repr);
+	/// assert_eq!(2, consumer.await.unwrap());
+	/// # })
+	/// ```
+	#[cfg(feature = "watch")]
+	pub fn snapshot_channel(&mut self) -> tokio::sync::watch::Receiver<Arc<T>> where T: Clone + Send + Sync + 'static {
+		let (sender, receiver) = tokio::sync::watch::channel(Arc::new(self.read_unchecked().clone()));
+		// The value is cloned before anything is sent, and sending replaces the value under the
+		// channel's own lock, so a panic can't leave the channel half updated.
+		let sender = core::panic::AssertUnwindSafe(sender);
+		self.after_write(move |value| {
+			if !sender.is_closed() {
+				sender.send_replace(Arc::new(value.clone()));
+			}
+		});
+		receiver
+	}
+	/// Exposes the value as a [`futures_signals::signal::ReadOnlyMutable`], which is updated with a
+	/// copy of the new value after every write that passes the invariant check. Its signals (e.g.
+	/// `signal_cloned`) work with [`futures_signals::signal::SignalExt`], so reactive UIs can bind
+	/// directly to the value, and only ever see values that satisfy the invariant.
+	/// ```rust
+	/// use futures_signals::signal::SignalExt;
+	/// use repr_rs::Repr;
+	/// #[derive(Debug, Clone)]
+	/// struct MinMax { min: i32, max: i32 }
+	/// let mut repr = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+	/// let range = repr.signal();
+	/// let mut labels = range.signal_ref(|mm| format!("{}..{}", mm.min, mm.max)).to_stream();
+	/// repr.write().max = 10;
+	/// # futures::executor::block_on(async move {
+	/// use futures::StreamExt;
+	/// assert_eq!(Some("1..10".to_string()), labels.next().await);
+	/// # })
+	/// ```
+	#[cfg(feature = "signals")]
+	pub fn signal(&mut self) -> futures_signals::signal::ReadOnlyMutable<T> where T: Clone + Send + Sync + 'static {
+		self.derived_signal(T::clone)
+	}
+	/// Exposes a value derived from the value as a [`futures_signals::signal::ReadOnlyMutable`]. The
+	/// derived value is computed once per write that passes the invariant check (rather than once
+	/// per signal), and is shared by all of its signals. See [`Repr::signal`].
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let mut repr = Repr::new(vec![3, 1, 2], |v: &Vec<i32>| !v.is_empty());
+	/// let largest = repr.derived_signal(|v| v.iter().copied().max());
+	/// repr.write().push(5);
+	/// assert_eq!(Some(5), largest.get());
+	/// ```
+	#[cfg(feature = "signals")]
+	pub fn derived_signal<R: Send + Sync + 'static>(&mut self, derive: impl Fn(&T) -> R + Send + Sync + RefUnwindSafe + 'static) -> futures_signals::signal::ReadOnlyMutable<R> {
+		let mutable = futures_signals::signal::Mutable::new(derive(self.read_unchecked()));
+		let signals = mutable.read_only();
+		self.after_write(move |value| mutable.set(derive(value)));
+		signals
+	}
+	/// Turns the representation invariant into a cloneable, read-only handle, for handing read access
+	/// to components that must not write or consume the value. See [`crate::reader::ReprReader`].
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let reader = Repr::new(1, |n: &i32| *n > 0).reader();
+	/// let other = reader.clone();
+	/// assert_eq!(1, *other.read());
+	/// ```
+	pub fn reader(self) -> crate::reader::ReprReader<T, I> {
+		crate::reader::ReprReader::new(self)
+	}
+	/// Splits the representation invariant into a single writer and any number of readers, for the
+	/// common single-writer, many-readers topology. Every write that passes the invariant check is
+	/// published (by cloning the value), and the [`crate::rcu::ReadHandle`]s load the last published
+	/// value without waiting for the writer, so readers only ever see values that satisfy the
+	/// invariant.
+	/// ```rust
+	/// use std::thread;
+	/// use repr_rs::Repr;
+	/// let (mut writer, reader) = Repr::new(vec![1], |v: &Vec<i32>| !v.is_empty()).split();
+	/// let readers: Vec<_> = (0..2).map(|_| {
+	///   let reader = reader.clone();
+	///   thread::spawn(move || assert!(!reader.load().is_empty()))
+	/// }).collect();
+	/// writer.write().push(2);
+	/// for reader in readers {
+	///   reader.join().unwrap();
+	/// }
+	/// assert_eq!(vec![1, 2], *reader.load());
+	/// ```
+	#[cfg(feature = "rcu")]
+	pub fn split(self) -> (crate::rcu::WriteHandle<T, I>, crate::rcu::ReadHandle<T>) where T: Clone + Send + Sync + 'static {
+		crate::rcu::WriteHandle::new(self)
+	}
+	/// Replaces the value with a new one if it satisfies the invariant, returning the old value.
+	/// If the new value violates the invariant it is handed back alongside the [`Violation`] and
+	/// the current value is left untouched. The invariant is always evaluated, regardless of the
+	/// [`CheckPolicy`] and of the `release-unchecked` feature.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// #[derive(Debug)]
+	/// struct MinMax { min: i32, max: i32 }
+	/// let mut repr = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+	/// let old = repr.replace(MinMax { min: 2, max: 3 }).unwrap();
+	/// assert_eq!(1, old.min);
+	/// let (rejected, _violation) = repr.replace(MinMax { min: 3, max: 2 }).unwrap_err();
+	/// assert_eq!(3, rejected.min);
+	/// assert_eq!(2, repr.read().min);
+	/// ```
+	pub fn replace(&mut self, value: T) -> Result<T, (T, Violation)> {
+		let detection = self.extension.as_deref().and_then(|extension| extension.change_detection.as_ref());
+		if detection.is_some_and(|detection| (detection.eq)(&value, self.read_unchecked())) {
+			return Ok(value);
+		}
+		self.begin_replace();
+		let audit = self.take_audit();
+		let outcome = self.measure(&value);
+		self.emit_audit(audit, &value, outcome.as_ref().map(drop));
+		if let Err(violation) = outcome {
+			return Err((value, violation));
+		}
+		let old = core::mem::replace(self.inner.get_mut(), value);
+		self.committed();
+		Ok(old)
+	}
+	/// Sets the value to a new one if it satisfies the invariant. If the new value violates the
+	/// invariant it is handed back alongside the [`Violation`]. See [`Repr::replace`].
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let mut repr = Repr::new(1, |n| *n > 0);
+	/// assert!(repr.set(2).is_ok());
+	/// assert!(repr.set(-2).is_err());
+	/// assert_eq!(2, *repr.read());
+	/// ```
+	pub fn set(&mut self, value: T) -> Result<(), (T, Violation)> {
+		self.replace(value).map(drop)
+	}
+	/// Swaps the values of two representation invariants, as long as each value satisfies the
+	/// other's invariant. If either would be violated neither value is changed. Like
+	/// [`Repr::replace`], this always evaluates both invariants.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let mut small = Repr::new(1, |n| *n < 10);
+	/// let mut positive = Repr::new(5, |n| *n > 0);
+	/// small.swap(&mut positive).unwrap();
+	/// assert_eq!(5, *small.read());
+	/// assert_eq!(1, *positive.read());
+	///
+	/// let mut big = Repr::new(50, |n| *n > 0);
+	/// assert!(small.swap(&mut big).is_err());
+	/// assert_eq!(5, *small.read());
+	/// assert_eq!(50, *big.read());
+	/// ```
+	pub fn swap<J: Fn(&T) -> bool>(&mut self, other: &mut Repr<T, J>) -> Result<(), Violation> {
+		self.begin_replace();
+		other.begin_replace();
+		let audits = (self.take_audit(), other.take_audit());
+		let outcome = self.measure(other.read_unchecked()).and_then(|()| other.measure(self.read_unchecked()));
+		self.emit_audit(audits.0, other.read_unchecked(), outcome.as_ref().map(drop));
+		other.emit_audit(audits.1, self.read_unchecked(), outcome.as_ref().map(drop));
+		outcome?;
+		core::mem::swap(self.inner.get_mut(), other.inner.get_mut());
+		self.committed();
+		other.committed();
+		Ok(())
+	}
+	/// Checks that the invariant holds for the current value without panicking. Unlike the check
+	/// after a mutation, this ignores the [`CheckPolicy`] and the `release-unchecked` feature and
+	/// always evaluates the invariant, so it can be used to re-assert the invariant at arbitrary points (e.g. after an FFI call).
+	/// ```rust
+	/// use std::cell::Cell;
+	/// use repr_rs::Repr;
+	/// let repr = Repr::with_msg(Cell::new(1), |n| n.get() > 0, "must be positive");
+	/// assert!(repr.validate().is_ok());
+	/// repr.read().set(-1);
+	/// let violation = repr.validate().unwrap_err();
+	/// assert_eq!("must be positive", violation.message());
+	/// ```
+	pub fn validate(&self) -> Result<(), Violation> {
+		self.evaluate(self.read_unchecked())
+	}
+	/// Evaluates the invariant for the current value without panicking. This is a cheaper
+	/// alternative to [`Repr::validate`] when the details of the violation aren't needed.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let repr = Repr::new(1, |n| *n > 0);
+	/// assert!(repr.is_valid());
+	/// ```
+	pub fn is_valid(&self) -> bool {
+		(self.invariant)(self.read_unchecked())
+	}
+	/// How many writes have been committed, starting from 0. Writes that violated the invariant
+	/// (or were interrupted by a panic) don't count. Comparing versions is a cheap way to tell
+	/// whether data derived from the value elsewhere is stale. Clones start at the same version.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let mut repr = Repr::new(1, |n| *n > 0);
+	/// let seen = repr.version();
+	/// *repr.write() = 2;
+	/// assert!(repr.set(-1).is_err());
+	/// assert_eq!(seen + 1, repr.version());
+	/// ```
+	pub fn version(&self) -> u64 {
+		self.version
+	}
+	/// Whether a panic occurred while the value was being mutated. The invariant isn't checked when
+	/// a mutable view is dropped during a panic (because panicking again would abort the process),
+	/// so a poisoned value may be partially mutated and may violate the invariant. Panics can only
+	/// be detected with the `std` feature, so without it a value is never poisoned.
+	/// ```rust
+	/// # #[cfg(feature = "std")] {
+	/// use repr_rs::Repr;
+	/// use std::panic::{catch_unwind, AssertUnwindSafe};
+	/// let mut repr = Repr::new(vec![1, 2], |v| v.len() % 2 == 0);
+	/// let _ = catch_unwind(AssertUnwindSafe(|| {
+	///   let mut v = repr.write();
+	///   v.push(3);
+	///   panic!("interrupted before pushing the second element");
+	/// }));
+	/// assert!(repr.is_poisoned());
+	/// assert!(!repr.is_valid());
+	/// # }
+	/// ```
+	pub fn is_poisoned(&self) -> bool {
+		self.extension.as_deref().is_some_and(|extension| extension.poisoned)
+	}
+	/// Clears the poisoned flag (see [`Repr::is_poisoned`]) if the value satisfies the invariant.
+	/// ```rust
+	/// # #[cfg(feature = "std")] {
+	/// use repr_rs::Repr;
+	/// use std::panic::{catch_unwind, AssertUnwindSafe};
+	/// let mut repr = Repr::new(vec![1, 2], |v| v.len() % 2 == 0);
+	/// let _ = catch_unwind(AssertUnwindSafe(|| {
+	///   let mut v = repr.write();
+	///   v.push(3);
+	///   panic!("interrupted before pushing the second element");
+	/// }));
+	/// assert!(repr.clear_poison().is_err());
+	/// repr.set(vec![]).unwrap();
+	/// assert!(repr.clear_poison().is_ok());
+	/// assert!(!repr.is_poisoned());
+	/// # }
+	/// ```
+	pub fn clear_poison(&mut self) -> Result<(), Violation> {
+		self.validate()?;
+		if let Some(extension) = self.extension.as_deref_mut() {
+			extension.poisoned = false;
+		}
+		Ok(())
+	}
+	#[inline]
+	pub(crate) fn read_unchecked(&self) -> &T {
+		// Safety: borrowing rules ensure that T is valid, and because this is an immutable borrow
+		// of the Repr, no mutable borrows can take place.
+		unsafe { &*self.inner.get() }
+	}
+	pub(crate) fn evaluate(&self, data: &T) -> Result<(), Violation> {
+		if (self.invariant)(data) {
+			Ok(())
+		} else if let Some(explanation) = self.extension.as_deref().and_then(|extension| extension.explanation.as_ref()) {
+			Err(Violation::new(explanation(data), data))
+		} else {
+			Err(Violation::new(self.violation_message, data))
+		}
+	}
+	fn assert_holds(&self, data: &T) {
+		if !cfg!(feature = "std") {
+			// Without std, only the violation message is used to keep the panic small.
+			assert!((self.invariant)(data), "{}", self.violation_message);
+			return;
+		}
+		if let Err(violation) = self.evaluate(data) {
+			panic!("{}", violation);
+		}
+	}
+}
+
+impl<T: Debug + Default, I: Fn(&T) -> bool> Repr<T, I> {
+	/// Takes the value out of the representation invariant, leaving [`Default::default`] in its
+	/// place. Fails (leaving the value untouched) if the default value violates the invariant.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// let mut repr = Repr::new(vec![1, 2, 3], |v| v.len() < 10);
+	/// assert_eq!(vec![1, 2, 3], repr.take().unwrap());
+	/// assert!(repr.read().is_empty());
+	///
+	/// let mut non_empty = Repr::new(vec![1, 2, 3], |v| !v.is_empty());
+	/// assert!(non_empty.take().is_err());
+	/// assert_eq!(3, non_empty.read().len());
+	/// ```
+	pub fn take(&mut self) -> Result<T, Violation> {
+		self.replace(T::default()).map_err(|(_, violation)| violation)
+	}
+}
+
+impl<T: Invariant + Default> Default for Repr<T> {
+	/// Creates a representation invariant over the default value, using the invariant from its
+	/// [`Invariant`] implementation.
+	///
+	/// # Panics
+	/// Panics if the default value violates the invariant.
+	fn default() -> Self {
+		match Repr::try_new(T::default()) {
+			Ok(repr) => repr,
+			Err(violation) => panic!("{}", violation),
+		}
+	}
+}
+
+impl<T: Invariant> Repr<T> {
+	/// Creates a representation invariant over the value, using the invariant from its
+	/// [`Invariant`] implementation. Fails if the value violates the invariant.
+	///
+	/// This is the `TryFrom<T>` conversion for trait-based invariants; `TryFrom` itself can't be
+	/// implemented because it would overlap with the blanket implementation in `core`.
+	/// ```rust
+	/// use repr_rs::{Invariant, Repr, Violation};
+	/// #[derive(Debug)]
+	/// struct MinMax { min: i32, max: i32 }
+	/// impl Invariant for MinMax {
+	///   fn invariant(&self) -> bool {
+	///     self.min < self.max
+	///   }
+	/// }
+	/// fn parse(min: i32, max: i32) -> Result<Repr<MinMax>, Violation> {
+	///   let repr = Repr::try_new(MinMax { min, max })?;
+	///   Ok(repr)
+	/// }
+	/// assert!(parse(1, 5).is_ok());
+	/// assert!(parse(5, 1).is_err());
+	/// ```
+	pub fn try_new(value: T) -> Result<Self, Violation> {
+		let repr = Repr::with_msg(value, T::invariant as fn(&T) -> bool, T::VIOLATION_MESSAGE);
+		repr.validate()?;
+		Ok(repr)
+	}
+}
+
+/// Whether the current thread is panicking. This can't be detected without std, so it's always
+/// false there.
+#[inline]
+pub(crate) fn panicking() -> bool {
+	#[cfg(feature = "std")]
+	return std::thread::panicking();
+	#[cfg(not(feature = "std"))]
+	return false;
 }
 
 /// # Safety
@@ -133,6 +1045,23 @@ unsafe impl<T: Debug + Sync, I: Fn(&T) -> bool + Sync> Sync for Repr<T, I> {}
 /// # Safety
 /// We exclusively own the repr here, so we can safely  implement Send for this type.
 unsafe impl<T: Debug + Send, I: Fn(&T) -> bool + Send> Send for Repr<T, I> {}
+/// The value can only be mutated through a [`ReprMutator`], which borrows the representation
+/// invariant mutably and so is never [`UnwindSafe`]. Catching a panic (e.g. an invariant violation)
+/// with [`std::panic::catch_unwind`] while the value is being mutated therefore takes an explicit
+/// [`core::panic::AssertUnwindSafe`], and is observable afterwards by design: the mutator is
+/// dropped during the unwinding, which marks the representation invariant as poisoned (see
+/// [`Repr::is_poisoned`]) instead of checking the possibly partially mutated value. Everything else
+/// a representation invariant holds (the hooks, audit sinks and [`Metrics`] it's configured with)
+/// is required to be [`RefUnwindSafe`], so it's exactly as unwind safe as its value and invariant.
+impl<T: Debug + UnwindSafe, I: Fn(&T) -> bool + UnwindSafe> UnwindSafe for Repr<T, I> {}
+/// See the [`UnwindSafe`] implementation.
+impl<T: Debug + RefUnwindSafe, I: Fn(&T) -> bool + RefUnwindSafe> RefUnwindSafe for Repr<T, I> {}
+// The implementations above rely on the configuration being as unwind safe as the value.
+const _: fn() = extension_is_ref_unwind_safe::<()>;
+fn extension_is_ref_unwind_safe<T: Debug + RefUnwindSafe>() {
+	fn assert<X: RefUnwindSafe>() {}
+	assert::<Extension<T>>();
+}
 
 impl<T: Debug, I: Fn(&T) -> bool> AsRef<T> for Repr<T, I> {
 	#[inline]
@@ -141,10 +1070,38 @@ impl<T: Debug, I: Fn(&T) -> bool> AsRef<T> for Repr<T, I> {
 	}
 }
 
+/// Reads through a representation invariant, so `repr.field` is equivalent to `repr.read().field`.
+/// Mutation is still only possible through [`Repr::write`].
+impl<T: Debug, I: Fn(&T) -> bool> Deref for Repr<T, I> {
+	type Target = T;
+	#[inline]
+	fn deref(&self) -> &T {
+		self.read()
+	}
+}
+
 impl<T: Debug + Clone, I: Fn(&T) -> bool + Clone> Clone for Repr<T, I> {
 	fn clone(&self) -> Self {
 		let inner = self.read().clone();
-		Self::with_msg(inner, self.invariant.clone(), self.violation_message)
+		let mut clone = Self::with_msg(inner, self.invariant.clone(), self.violation_message);
+		clone.version = self.version;
+		clone.extension = self.extension.as_deref().map(|extension| {
+			let mut copy = Extension::new();
+			copy.explanation = extension.explanation.clone();
+			copy.check_policy = extension.check_policy;
+			copy.determinism_checks = extension.determinism_checks;
+			copy.read_check_policy = extension.read_check_policy;
+			copy.audit.sinks = extension.audit.sinks.clone();
+			copy.metrics = extension.metrics.clone();
+			copy.slow_check = extension.slow_check.clone();
+			copy.change_detection = extension.change_detection.as_ref().map(|detection| ChangeDetection {
+				snapshot: detection.snapshot,
+				eq: detection.eq,
+				before: None,
+			});
+			Box::new(copy)
+		});
+		clone
 	}
 }
 impl<T: Debug + Hash, I: Fn(&T) -> bool> Hash for Repr<T, I> {
@@ -158,14 +1115,41 @@ impl<T: Debug + PartialEq, I: Fn(&T) -> bool> PartialEq for Repr<T, I> {
 	}
 }
 impl<T: Debug + Eq, I: Fn(&T) -> bool> Eq for Repr<T, I> {}
+impl<T: Debug + PartialOrd, I: Fn(&T) -> bool> PartialOrd for Repr<T, I> {
+	fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+		self.read().partial_cmp(other.read())
+	}
+}
+impl<T: Debug + Ord, I: Fn(&T) -> bool> Ord for Repr<T, I> {
+	fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+		self.read().cmp(other.read())
+	}
+}
+impl<T: Debug + PartialEq, I: Fn(&T) -> bool> PartialEq<T> for Repr<T, I> {
+	fn eq(&self, other: &T) -> bool {
+		self.read() == other
+	}
+}
+impl<T: Debug + PartialOrd, I: Fn(&T) -> bool> PartialOrd<T> for Repr<T, I> {
+	fn partial_cmp(&self, other: &T) -> Option<core::cmp::Ordering> {
+		self.read().partial_cmp(other)
+	}
+}
+/// Hashing, equality, and ordering of a representation invariant all delegate to the inner value,
+/// so it can be used to look up maps keyed by the inner type.
+impl<T: Debug, I: Fn(&T) -> bool> Borrow<T> for Repr<T, I> {
+	fn borrow(&self) -> &T {
+		self.read()
+	}
+}
 
 impl<T: Debug, I: Fn(&T) -> bool> Debug for Repr<T, I> {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		write!(f, "Repr({:?})", self.read())
 	}
 }
 impl <T: Debug + Display, I: Fn(&T) -> bool> Display for Repr<T, I> {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		write!(f, "{}", self.read())
 	}
 }
@@ -188,6 +1172,48 @@ impl<'a, T: Debug, I: Fn(&T) -> bool> DerefMut for ReprMutator<'a, T, I> {
 		self.repr.inner.get_mut()
 	}
 }
+impl<T: Debug, I: Fn(&T) -> bool> ReprMutator<'_, T, I> {
+	/// Narrows this mutable view to a part of the value, which has its own invariant. The
+	/// sub-invariant is checked when the projection is dropped, and the invariant of the whole
+	/// value is still checked once when this view is dropped.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// #[derive(Debug)]
+	/// struct MinMax { min: i32, max: i32 }
+	/// #[derive(Debug)]
+	/// struct Config { range: MinMax, name: String }
+	/// let mut repr = Repr::new(
+	///   Config { range: MinMax { min: 1, max: 5 }, name: "config".into() },
+	///   |c| !c.name.is_empty(),
+	/// );
+	/// let mut config = repr.write();
+	/// let mut range = config.project(|c| &mut c.range, |mm| mm.min < mm.max);
+	/// range.min = 4;
+	/// ```
+	pub fn project<U: Debug + ?Sized, J: Fn(&U) -> bool>(&mut self, f: impl FnOnce(&mut T) -> &mut U, invariant: J) -> ReprProjection<'_, U, J> {
+		ReprProjection::new(f(self.deref_mut()), invariant, "Invariant violated")
+	}
+	/// Like [`ReprMutator::project`], with the message to panic with if the sub-invariant is
+	/// violated.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// #[derive(Debug)]
+	/// struct MinMax { min: i32, max: i32 }
+	/// #[derive(Debug)]
+	/// struct Config { range: MinMax, name: String }
+	/// let mut repr = Repr::new(
+	///   Config { range: MinMax { min: 1, max: 5 }, name: "config".into() },
+	///   |c| !c.name.is_empty(),
+	/// );
+	/// let mut config = repr.write();
+	/// let mut range = config.project_with_msg(|c| &mut c.range, |mm| mm.min < mm.max, "min must be less than max");
+	/// // Setting this to 6 would panic with "min must be less than max".
+	/// range.min = 4;
+	/// ```
+	pub fn project_with_msg<U: Debug + ?Sized, J: Fn(&U) -> bool>(&mut self, f: impl FnOnce(&mut T) -> &mut U, invariant: J, violation_message: &'static str) -> ReprProjection<'_, U, J> {
+		ReprProjection::new(f(self.deref_mut()), invariant, violation_message)
+	}
+}
 impl<T: Debug, I: Fn(&T) -> bool> Drop for ReprMutator<'_, T, I> {
 	fn drop(&mut self) {
 		self.repr.check();
@@ -208,12 +1234,54 @@ impl<'a, T: Debug + PartialEq> PartialEq for ReprMutator<'a, T, fn(&T) -> bool>
 }
 impl<'a, T: Debug + Eq> Eq for ReprMutator<'a, T, fn(&T) -> bool> {}
 impl<'a, T: Debug + PartialOrd> PartialOrd for ReprMutator<'a, T, fn(&T) -> bool> {
-	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+	fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
 		self.deref().partial_cmp(other.deref())
 	}
 }
 impl<'a, T: Debug + Ord> Ord for ReprMutator<'a, T, fn(&T) -> bool> {
-	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+	fn cmp(&self, other: &Self) -> core::cmp::Ordering {
 		self.deref().cmp(other.deref())
 	}
 }
+
+/// A mutable view of part of a value guarded by a representation invariant, created by
+/// [`ReprMutator::project`]. The sub-invariant is checked when the projection is dropped, unless
+/// checks are compiled out with the `release-unchecked` feature.
+pub struct ReprProjection<'a, U: Debug + ?Sized, J: Fn(&U) -> bool> {
+	inner: &'a mut U,
+	invariant: J,
+	violation_message: &'static str,
+}
+impl<'a, U: Debug + ?Sized, J: Fn(&U) -> bool> ReprProjection<'a, U, J> {
+	pub(crate) fn new(inner: &'a mut U, invariant: J, violation_message: &'static str) -> Self {
+		Self { inner, invariant, violation_message }
+	}
+	/// Narrows this projection further, to a part of the projected value with its own invariant.
+	pub fn project<V: Debug + ?Sized, K: Fn(&V) -> bool>(&mut self, f: impl FnOnce(&mut U) -> &mut V, invariant: K) -> ReprProjection<'_, V, K> {
+		ReprProjection::new(f(self.inner), invariant, "Invariant violated")
+	}
+	/// Like [`ReprProjection::project`], with the message to panic with if the sub-invariant is
+	/// violated.
+	pub fn project_with_msg<V: Debug + ?Sized, K: Fn(&V) -> bool>(&mut self, f: impl FnOnce(&mut U) -> &mut V, invariant: K, violation_message: &'static str) -> ReprProjection<'_, V, K> {
+		ReprProjection::new(f(self.inner), invariant, violation_message)
+	}
+}
+impl<U: Debug + ?Sized, J: Fn(&U) -> bool> Deref for ReprProjection<'_, U, J> {
+	type Target = U;
+	fn deref(&self) -> &Self::Target {
+		self.inner
+	}
+}
+impl<U: Debug + ?Sized, J: Fn(&U) -> bool> DerefMut for ReprProjection<'_, U, J> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.inner
+	}
+}
+impl<U: Debug + ?Sized, J: Fn(&U) -> bool> Drop for ReprProjection<'_, U, J> {
+	fn drop(&mut self) {
+		if panicking() || policy::UNCHECKED {
+			return;
+		}
+		assert!((self.invariant)(self.inner), "{}", Violation::new(self.violation_message, self.inner));
+	}
+}
@@ -0,0 +1,37 @@
+use crate::{CacheableRepr, Repr};
+use schemars::{JsonSchema, Schema, SchemaGenerator};
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+/// Representation invariants have the same schema as their inner value, since they serialize
+/// transparently.
+impl<T: Debug + JsonSchema, I: Fn(&T) -> bool> JsonSchema for Repr<T, I> {
+	fn inline_schema() -> bool {
+		T::inline_schema()
+	}
+	fn schema_name() -> Cow<'static, str> {
+		T::schema_name()
+	}
+	fn schema_id() -> Cow<'static, str> {
+		T::schema_id()
+	}
+	fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+		T::json_schema(generator)
+	}
+}
+/// Representation invariants have the same schema as their inner value, since they serialize
+/// transparently.
+impl<T: Debug + JsonSchema + 'static, I: Fn(&T) -> bool> JsonSchema for CacheableRepr<T, I> {
+	fn inline_schema() -> bool {
+		T::inline_schema()
+	}
+	fn schema_name() -> Cow<'static, str> {
+		T::schema_name()
+	}
+	fn schema_id() -> Cow<'static, str> {
+		T::schema_id()
+	}
+	fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+		T::json_schema(generator)
+	}
+}
@@ -0,0 +1,151 @@
+use crate::prelude::{Bounded, NonEmptyString, NonEmptyVec, Percentage, SortedVec};
+use crate::{CacheableRepr, Invariant, Repr};
+use serde::de::{DeserializeSeed, Error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+/// Representation invariants serialize transparently as their inner value.
+impl<T: Debug + Serialize, I: Fn(&T) -> bool> Serialize for Repr<T, I> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.read().serialize(serializer)
+	}
+}
+/// Representation invariants serialize transparently as their inner value. Caches aren't
+/// serialized.
+impl<T: Debug + Serialize + 'static, I: Fn(&T) -> bool> Serialize for CacheableRepr<T, I> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.read().serialize(serializer)
+	}
+}
+
+/// Representation invariants over types implementing [`Invariant`] deserialize transparently from
+/// their inner value, failing if the value violates the invariant.
+impl<'de, T: Invariant + Deserialize<'de>> Deserialize<'de> for Repr<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Repr::try_new(T::deserialize(deserializer)?).map_err(D::Error::custom)
+	}
+}
+/// Representation invariants over types implementing [`Invariant`] deserialize transparently from
+/// their inner value, failing if the value violates the invariant.
+impl<'de, T: Invariant + Deserialize<'de> + 'static> Deserialize<'de> for CacheableRepr<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		CacheableRepr::try_new(T::deserialize(deserializer)?).map_err(D::Error::custom)
+	}
+}
+
+/// Deserializes a [`Repr`] with the given invariant function, failing if the decoded value violates
+/// the invariant. Use this when the invariant is a closure rather than an [`Invariant`]
+/// implementation.
+/// ```rust
+/// use serde::de::DeserializeSeed;
+/// use repr_rs::serde_support::ReprSeed;
+/// let seed = ReprSeed::with_msg(|n: &i32| *n > 0, "must be positive");
+/// let mut json = serde_json::Deserializer::from_str("5");
+/// assert_eq!(5, *seed.deserialize(&mut json).unwrap().read());
+///
+/// let seed = ReprSeed::with_msg(|n: &i32| *n > 0, "must be positive");
+/// let mut json = serde_json::Deserializer::from_str("-5");
+/// assert!(seed.deserialize(&mut json).is_err());
+/// ```
+pub struct ReprSeed<T, I> {
+	invariant: I,
+	violation_message: &'static str,
+	_marker: PhantomData<fn() -> T>,
+}
+impl<T: Debug, I: Fn(&T) -> bool> ReprSeed<T, I> {
+	pub const fn new(invariant: I) -> Self {
+		Self::with_msg(invariant, "Invariant violated")
+	}
+	pub const fn with_msg(invariant: I, violation_message: &'static str) -> Self {
+		Self {
+			invariant,
+			violation_message,
+			_marker: PhantomData,
+		}
+	}
+}
+impl<'de, T: Debug + Deserialize<'de>, I: Fn(&T) -> bool> DeserializeSeed<'de> for ReprSeed<T, I> {
+	type Value = Repr<T, I>;
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+		let repr = Repr::with_msg(T::deserialize(deserializer)?, self.invariant, self.violation_message);
+		repr.validate().map_err(D::Error::custom)?;
+		Ok(repr)
+	}
+}
+
+/// Deserializes a [`CacheableRepr`] with the given invariant function, failing if the decoded value
+/// violates the invariant. See [`ReprSeed`].
+pub struct CacheableReprSeed<T, I> {
+	inner: ReprSeed<T, I>,
+}
+impl<T: Debug, I: Fn(&T) -> bool> CacheableReprSeed<T, I> {
+	pub const fn new(invariant: I) -> Self {
+		Self { inner: ReprSeed::new(invariant) }
+	}
+	pub const fn with_msg(invariant: I, violation_message: &'static str) -> Self {
+		Self { inner: ReprSeed::with_msg(invariant, violation_message) }
+	}
+}
+impl<'de, T: Debug + Deserialize<'de> + 'static, I: Fn(&T) -> bool> DeserializeSeed<'de> for CacheableReprSeed<T, I> {
+	type Value = CacheableRepr<T, I>;
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+		self.inner.deserialize(deserializer).map(CacheableRepr::from)
+	}
+}
+
+/// The refinement types in [`crate::prelude`] serialize transparently as their inner value.
+impl<const MIN: i64, const MAX: i64> Serialize for Bounded<MIN, MAX> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.get().serialize(serializer)
+	}
+}
+/// The refinement types in [`crate::prelude`] deserialize transparently from their inner value,
+/// failing if the value violates their invariant.
+impl<'de, const MIN: i64, const MAX: i64> Deserialize<'de> for Bounded<MIN, MAX> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Self::new(i64::deserialize(deserializer)?).map_err(D::Error::custom)
+	}
+}
+impl Serialize for NonEmptyString {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.as_str().serialize(serializer)
+	}
+}
+impl<'de> Deserialize<'de> for NonEmptyString {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Self::new(String::deserialize(deserializer)?).map_err(D::Error::custom)
+	}
+}
+impl<T: Debug + Serialize> Serialize for NonEmptyVec<T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.as_slice().serialize(serializer)
+	}
+}
+impl<'de, T: Debug + Deserialize<'de>> Deserialize<'de> for NonEmptyVec<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Self::new(Vec::deserialize(deserializer)?).map_err(D::Error::custom)
+	}
+}
+impl Serialize for Percentage {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.get().serialize(serializer)
+	}
+}
+impl<'de> Deserialize<'de> for Percentage {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Self::new(f64::deserialize(deserializer)?).map_err(D::Error::custom)
+	}
+}
+impl<T: Debug + Ord + Serialize> Serialize for SortedVec<T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.as_slice().serialize(serializer)
+	}
+}
+/// Unlike the other refinement types, a [`SortedVec`] deserializes from any sequence, which is
+/// sorted.
+impl<'de, T: Debug + Ord + Deserialize<'de>> Deserialize<'de> for SortedVec<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Vec::deserialize(deserializer).map(Self::from)
+	}
+}
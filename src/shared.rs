@@ -0,0 +1,177 @@
+//! Representation invariants shared between tasks. See [`SharedRepr`].
+use crate::lock::{Poison, PoisonOnPanic};
+use crate::repr::CurrentVersion;
+use crate::Repr;
+use alloc::sync::Arc;
+use core::fmt::Debug;
+use core::ops::{Deref, DerefMut};
+use std::sync::LockResult;
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A representation invariant with shared ownership, behind an async read-write lock. Cloning a
+/// `SharedRepr` clones the handle, not the value, so it can be handed to other tasks. The invariant
+/// is checked when a write guard is dropped, like with [`Repr::write`].
+///
+/// The lock doesn't depend on a runtime, so this works with any executor.
+///
+/// Like a [`crate::ReprMutex`], the value is poisoned if a task panics while holding a write guard,
+/// including when the invariant is violated. Every handle then gets its guards in a
+/// [`std::sync::PoisonError`], so the value can be repaired.
+/// ```rust
+/// use repr_rs::{Repr, SharedRepr};
+/// #[derive(Debug)]
+/// struct MinMax { min: i32, max: i32 }
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let repr = SharedRepr::new(Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max));
+/// let handle = repr.clone();
+/// tokio::spawn(async move {
+///   handle.write().await.unwrap().max = 10;
+/// }).await.unwrap();
+/// assert_eq!(10, repr.read().await.unwrap().max);
+/// # })
+/// ```
+pub struct SharedRepr<T: Debug, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	inner: Arc<Shared<T, I>>,
+}
+struct Shared<T: Debug, I: Fn(&T) -> bool> {
+	lock: RwLock<Repr<T, I>>,
+	poison: Poison,
+}
+impl<T: Debug, I: Fn(&T) -> bool> SharedRepr<T, I> {
+	/// Shares the representation invariant.
+	pub fn new(repr: Repr<T, I>) -> Self {
+		Self {
+			inner: Arc::new(Shared {
+				lock: RwLock::new(repr),
+				poison: Poison::new(),
+			}),
+		}
+	}
+	/// Waits until no task is writing, then borrows a read-only view of the value.
+	pub async fn read(&self) -> LockResult<SharedReadGuard<'_, T, I>> {
+		self.inner.poison.result(SharedReadGuard {
+			guard: self.inner.lock.read().await,
+		})
+	}
+	/// Waits until no other task is reading or writing, then borrows a mutable view of the value.
+	/// The invariant is checked when the guard is dropped.
+	pub async fn write(&self) -> LockResult<SharedWriteGuard<'_, T, I>> {
+		self.inner.poison.result(SharedWriteGuard::new(self.inner.lock.write().await, &self.inner.poison))
+	}
+	/// Borrows a mutable view of the value like [`SharedRepr::write`], but only if the version is
+	/// still `expected` (e.g. the one read with [`SharedReadGuard::version`]). Otherwise another
+	/// task wrote the value in the meantime, and the current version is returned. See
+	/// [`Repr::try_write_if_version`]. Poisoning is reported like with [`SharedRepr::write`].
+	/// ```rust
+	/// use repr_rs::{Repr, SharedRepr};
+	/// use repr_rs::shared::SharedReadGuard;
+	/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+	/// let counter = SharedRepr::new(Repr::new(0, |n: &i32| *n >= 0));
+	/// loop {
+	///   let (next, version) = {
+	///     let guard = counter.read().await.unwrap();
+	///     (*guard + 1, SharedReadGuard::version(&guard))
+	///   };
+	///   if let Ok(value) = counter.try_write_if_version(version).await {
+	///     *value.unwrap() = next;
+	///     break;
+	///   }
+	/// }
+	/// assert_eq!(1, *counter.read().await.unwrap());
+	/// # })
+	/// ```
+	pub async fn try_write_if_version(&self, expected: u64) -> Result<LockResult<SharedWriteGuard<'_, T, I>>, CurrentVersion> {
+		let guard = self.inner.lock.write().await;
+		let version = guard.version();
+		if version != expected {
+			return Err(CurrentVersion(version));
+		}
+		Ok(self.inner.poison.result(SharedWriteGuard::new(guard, &self.inner.poison)))
+	}
+	/// Returns the representation invariant if this is the only handle to it. Otherwise the handle
+	/// is handed back. Poisoning is ignored: if a write panicked, [`Repr::validate`] tells whether
+	/// the value still satisfies the invariant.
+	pub fn try_unwrap(self) -> Result<Repr<T, I>, Self> {
+		Arc::try_unwrap(self.inner)
+			.map(|shared| shared.lock.into_inner())
+			.map_err(|inner| Self { inner })
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Clone for SharedRepr<T, I> {
+	fn clone(&self) -> Self {
+		Self {
+			inner: self.inner.clone(),
+		}
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for SharedRepr<T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		// Formatting mustn't wait for a writer.
+		match self.inner.lock.try_read() {
+			Ok(repr) => write!(f, "SharedRepr({:?})", repr.read()),
+			Err(_) => write!(f, "SharedRepr(<locked>)"),
+		}
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> From<Repr<T, I>> for SharedRepr<T, I> {
+	fn from(repr: Repr<T, I>) -> Self {
+		Self::new(repr)
+	}
+}
+
+/// A read-only view of the value of a [`SharedRepr`]. Writers wait until it's dropped.
+pub struct SharedReadGuard<'a, T: Debug, I: Fn(&T) -> bool> {
+	guard: RwLockReadGuard<'a, Repr<T, I>>,
+}
+impl<T: Debug, I: Fn(&T) -> bool> SharedReadGuard<'_, T, I> {
+	/// The version of the value that is borrowed. See [`Repr::version`]. This is an associated
+	/// function so it can't shadow a method of the value.
+	pub fn version(guard: &Self) -> u64 {
+		guard.guard.version()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for SharedReadGuard<'_, T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		Debug::fmt(&**self, f)
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Deref for SharedReadGuard<'_, T, I> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.guard.read()
+	}
+}
+
+/// A mutable view of the value of a [`SharedRepr`]. The invariant is checked when it's dropped.
+pub struct SharedWriteGuard<'a, T: Debug, I: Fn(&T) -> bool> {
+	guard: RwLockWriteGuard<'a, Repr<T, I>>,
+	poison: &'a Poison,
+}
+impl<'a, T: Debug, I: Fn(&T) -> bool> SharedWriteGuard<'a, T, I> {
+	fn new(mut guard: RwLockWriteGuard<'a, Repr<T, I>>, poison: &'a Poison) -> Self {
+		guard.begin_write();
+		Self { guard, poison }
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Debug for SharedWriteGuard<'_, T, I> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		Debug::fmt(&**self, f)
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Deref for SharedWriteGuard<'_, T, I> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.guard.read_unchecked()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> DerefMut for SharedWriteGuard<'_, T, I> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.guard.inner.get_mut()
+	}
+}
+impl<T: Debug, I: Fn(&T) -> bool> Drop for SharedWriteGuard<'_, T, I> {
+	fn drop(&mut self) {
+		let _poison = PoisonOnPanic(self.poison);
+		self.guard.check();
+	}
+}
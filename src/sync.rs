@@ -0,0 +1,123 @@
+//! The synchronisation primitives used by this crate. When compiled with `--cfg loom`, these are
+//! [loom](https://docs.rs/loom)'s instrumented versions, so the concurrency of the caches can be
+//! exhaustively model-checked. Wrappers built on top of this crate can use this module to get the
+//! same behaviour. The model tests can be run with
+//! `RUSTFLAGS="--cfg loom" cargo test --features eager loom`.
+//! ```rust
+//! use repr_rs::sync::{Arc, RwLock};
+//! let cell = Arc::new(RwLock::new(1));
+//! *cell.write().unwrap() += 1;
+//! assert_eq!(2, *cell.read().unwrap());
+//! ```
+#[cfg(loom)]
+pub use loom::sync::{atomic, Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(loom)]
+pub use loom::thread;
+
+#[cfg(not(loom))]
+pub use std::sync::{atomic, Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(not(loom))]
+pub use std::thread;
+
+/// The locks of the eager caches and of [`crate::ReprMutex`] and [`crate::ReprRwLock`]. With the
+/// `parking_lot` feature (and without loom) these are [parking_lot](https://docs.rs/parking_lot)'s
+/// locks, which are faster and are never poisoned. Otherwise they're the locks above, with their
+/// poisoning ignored, so [`crate::ReprMutex`] and [`crate::ReprRwLock`] track poisoning themselves
+/// and behave the same with either.
+pub(crate) mod lock {
+	#[cfg(not(all(feature = "parking_lot", not(loom))))]
+	use std::sync::PoisonError;
+	#[cfg(all(feature = "parking_lot", not(loom)))]
+	pub(crate) use parking_lot::{MutexGuard, RwLockReadGuard, RwLockWriteGuard};
+	#[cfg(not(all(feature = "parking_lot", not(loom))))]
+	pub(crate) use super::{MutexGuard, RwLockReadGuard, RwLockWriteGuard};
+
+	#[cfg(all(feature = "parking_lot", not(loom)))]
+	type Inner<T> = parking_lot::Mutex<T>;
+	#[cfg(not(all(feature = "parking_lot", not(loom))))]
+	type Inner<T> = super::Mutex<T>;
+	#[cfg(all(feature = "parking_lot", not(loom)))]
+	type InnerRw<T> = parking_lot::RwLock<T>;
+	#[cfg(not(all(feature = "parking_lot", not(loom))))]
+	type InnerRw<T> = super::RwLock<T>;
+
+	pub(crate) struct Mutex<T>(Inner<T>);
+	impl<T> Mutex<T> {
+		pub(crate) fn new(value: T) -> Self {
+			Self(Inner::new(value))
+		}
+		/// Locks the mutex, ignoring poisoning.
+		pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+			#[cfg(all(feature = "parking_lot", not(loom)))]
+			return self.0.lock();
+			#[cfg(not(all(feature = "parking_lot", not(loom))))]
+			self.0.lock().unwrap_or_else(PoisonError::into_inner)
+		}
+		/// Locks the mutex if it isn't locked, ignoring poisoning.
+		pub(crate) fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+			#[cfg(all(feature = "parking_lot", not(loom)))]
+			return self.0.try_lock();
+			#[cfg(not(all(feature = "parking_lot", not(loom))))]
+			match self.0.try_lock() {
+				Ok(guard) => Some(guard),
+				Err(std::sync::TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+				Err(std::sync::TryLockError::WouldBlock) => None,
+			}
+		}
+		pub(crate) fn into_inner(self) -> T {
+			#[cfg(all(feature = "parking_lot", not(loom)))]
+			return self.0.into_inner();
+			#[cfg(not(all(feature = "parking_lot", not(loom))))]
+			self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+		}
+	}
+
+	impl<T: Default> Default for Mutex<T> {
+		fn default() -> Self {
+			Self::new(T::default())
+		}
+	}
+
+	pub(crate) struct RwLock<T>(InnerRw<T>);
+	impl<T> RwLock<T> {
+		pub(crate) fn new(value: T) -> Self {
+			Self(InnerRw::new(value))
+		}
+		/// Locks for reading, ignoring poisoning.
+		pub(crate) fn read(&self) -> RwLockReadGuard<'_, T> {
+			#[cfg(all(feature = "parking_lot", not(loom)))]
+			return self.0.read();
+			#[cfg(not(all(feature = "parking_lot", not(loom))))]
+			self.0.read().unwrap_or_else(PoisonError::into_inner)
+		}
+		/// Locks for reading if no thread is writing, ignoring poisoning.
+		pub(crate) fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+			#[cfg(all(feature = "parking_lot", not(loom)))]
+			return self.0.try_read();
+			#[cfg(not(all(feature = "parking_lot", not(loom))))]
+			match self.0.try_read() {
+				Ok(guard) => Some(guard),
+				Err(std::sync::TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+				Err(std::sync::TryLockError::WouldBlock) => None,
+			}
+		}
+		/// Locks for writing, ignoring poisoning.
+		pub(crate) fn write(&self) -> RwLockWriteGuard<'_, T> {
+			#[cfg(all(feature = "parking_lot", not(loom)))]
+			return self.0.write();
+			#[cfg(not(all(feature = "parking_lot", not(loom))))]
+			self.0.write().unwrap_or_else(PoisonError::into_inner)
+		}
+		pub(crate) fn into_inner(self) -> T {
+			#[cfg(all(feature = "parking_lot", not(loom)))]
+			return self.0.into_inner();
+			#[cfg(not(all(feature = "parking_lot", not(loom))))]
+			self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+		}
+	}
+	impl<T: Default> Default for RwLock<T> {
+		fn default() -> Self {
+			Self::new(T::default())
+		}
+	}
+}
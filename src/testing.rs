@@ -0,0 +1,121 @@
+//! Tools for discovering operations that break a representation invariant.
+use crate::{Repr, Violation};
+use alloc::vec::Vec;
+use core::fmt::{Debug, Display};
+
+/// The longest sequence of operations applied in a single fuzzing iteration.
+const MAX_SEQUENCE_LEN: usize = 32;
+
+/// A sequence of operations that leaves a value in a state that violates its invariant. See
+/// [`fuzz_operations`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzFailure {
+	sequence: Vec<usize>,
+	violation: Violation,
+}
+impl FuzzFailure {
+	/// The indices (into the operations passed to [`fuzz_operations`]) of the operations that, when
+	/// applied in order, violate the invariant.
+	pub fn sequence(&self) -> &[usize] {
+		&self.sequence
+	}
+	/// The violation caused by the last operation in the sequence.
+	pub fn violation(&self) -> &Violation {
+		&self.violation
+	}
+}
+impl Display for FuzzFailure {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "Applying operations {:?} violated the invariant: {}", self.sequence, self.violation)
+	}
+}
+impl core::error::Error for FuzzFailure {}
+
+/// Applies random sequences of operations to copies of the value of a representation invariant,
+/// looking for a sequence that violates the invariant. Each iteration starts from the current value
+/// and applies up to 32 operations. The representation invariant itself is not modified.
+///
+/// If a violating sequence is found, it is shrunk to a minimal failing sequence (one where removing
+/// any single operation no longer violates the invariant) before being returned. The random
+/// sequences are deterministic, so failures are reproducible.
+/// ```rust
+/// use repr_rs::Repr;
+/// use repr_rs::testing::fuzz_operations;
+/// #[derive(Debug, Clone)]
+/// struct MinMax { min: i32, max: i32 }
+/// let repr = Repr::new(MinMax { min: 1, max: 5 }, |mm| mm.min < mm.max);
+/// let failure = fuzz_operations(&repr, &[
+///   |mm| mm.max += 1,
+///   |mm| mm.min += 1,
+///   |mm| { mm.min -= 1; mm.max -= 1; },
+/// ], 100).unwrap_err();
+/// // Incrementing min four times is the shortest way to violate the invariant.
+/// assert_eq!(&[1, 1, 1, 1], failure.sequence());
+/// ```
+pub fn fuzz_operations<T: Debug + Clone, I: Fn(&T) -> bool>(
+	repr: &Repr<T, I>,
+	operations: &[fn(&mut T)],
+	iterations: usize,
+) -> Result<(), FuzzFailure> {
+	if operations.is_empty() {
+		return Ok(());
+	}
+	let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+	for _ in 0..iterations {
+		let len = 1 + rng.below(MAX_SEQUENCE_LEN);
+		let sequence: Vec<usize> = (0..len).map(|_| rng.below(operations.len())).collect();
+		if let Some((sequence, violation)) = run(repr, operations, &sequence) {
+			return Err(shrink(repr, operations, sequence, violation));
+		}
+	}
+	Ok(())
+}
+
+/// Applies the sequence to a copy of the current value, returning the prefix of the sequence that
+/// violated the invariant (if any).
+fn run<T: Debug + Clone, I: Fn(&T) -> bool>(
+	repr: &Repr<T, I>,
+	operations: &[fn(&mut T)],
+	sequence: &[usize],
+) -> Option<(Vec<usize>, Violation)> {
+	let mut value = repr.read().clone();
+	for (i, &op) in sequence.iter().enumerate() {
+		operations[op](&mut value);
+		if let Err(violation) = repr.evaluate(&value) {
+			return Some((sequence[..=i].to_vec(), violation));
+		}
+	}
+	None
+}
+
+fn shrink<T: Debug + Clone, I: Fn(&T) -> bool>(
+	repr: &Repr<T, I>,
+	operations: &[fn(&mut T)],
+	mut sequence: Vec<usize>,
+	mut violation: Violation,
+) -> FuzzFailure {
+	let mut i = 0;
+	while i < sequence.len() {
+		let mut candidate = sequence.clone();
+		candidate.remove(i);
+		match run(repr, operations, &candidate) {
+			Some((shorter, shorter_violation)) => {
+				sequence = shorter;
+				violation = shorter_violation;
+				i = 0;
+			}
+			None => i += 1,
+		}
+	}
+	FuzzFailure { sequence, violation }
+}
+
+struct XorShift(u64);
+impl XorShift {
+	fn below(&mut self, n: usize) -> usize {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		(self.0 % n as u64) as usize
+	}
+}
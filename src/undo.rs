@@ -0,0 +1,114 @@
+//! Undo and redo for representation invariants. See [`UndoableRepr`].
+use crate::repr::ReprMutator;
+use crate::{Repr, Violation};
+use alloc::collections::VecDeque;
+use core::fmt::Debug;
+
+/// A representation invariant that keeps the values it had before its most recent writes, so
+/// they can be undone and redone. Restored values are validated like any other write, so an
+/// invariant that has become stricter (e.g. one that consults a config) can't be bypassed by
+/// undoing.
+/// ```rust
+/// use repr_rs::Repr;
+/// use repr_rs::undo::UndoableRepr;
+/// let mut text = UndoableRepr::new(Repr::new(String::new(), |s: &String| s.len() < 20))
+///   .with_history_depth(10);
+/// text.write().push_str("hello");
+/// text.write().push_str(" world");
+/// assert!(text.undo().unwrap());
+/// assert_eq!("hello", text.read());
+/// assert!(text.redo().unwrap());
+/// assert_eq!("hello world", text.read());
+/// assert!(!text.redo().unwrap());
+/// ```
+pub struct UndoableRepr<T: Debug, I: Fn(&T) -> bool = fn(&T) -> bool> {
+	repr: Repr<T, I>,
+	undo: VecDeque<T>,
+	redo: VecDeque<T>,
+	depth: usize,
+}
+impl<T: Debug + Clone, I: Fn(&T) -> bool> UndoableRepr<T, I> {
+	/// Starts keeping the history of the representation invariant, from its current value. By
+	/// default the last 100 writes can be undone.
+	pub const fn new(repr: Repr<T, I>) -> Self {
+		Self {
+			repr,
+			undo: VecDeque::new(),
+			redo: VecDeque::new(),
+			depth: 100,
+		}
+	}
+	/// Sets how many writes can be undone. The oldest values are forgotten beyond that.
+	pub fn with_history_depth(mut self, depth: usize) -> Self {
+		self.depth = depth;
+		self.undo.truncate(depth);
+		self.redo.truncate(depth);
+		self
+	}
+	/// Borrows a read-only view of the value.
+	#[inline]
+	pub fn read(&self) -> &T {
+		self.repr.read()
+	}
+	/// Borrows a mutable view of the value, like [`Repr::write`]. The current value is kept so
+	/// the write can be undone, and the writes that were undone can no longer be redone.
+	pub fn write(&mut self) -> ReprMutator<'_, T, I> {
+		let value = self.repr.read().clone();
+		self.record(value);
+		self.repr.write()
+	}
+	/// Sets the value to a new one if it satisfies the invariant, like [`Repr::set`]. The write
+	/// can be undone.
+	pub fn set(&mut self, value: T) -> Result<(), (T, Violation)> {
+		let old = self.repr.replace(value)?;
+		self.record(old);
+		Ok(())
+	}
+	/// Restores the value from before the most recent write. Returns whether there was a write to
+	/// undo. If the restored value violates the invariant the value is left untouched, and the
+	/// write can still be undone once the invariant allows it.
+	pub fn undo(&mut self) -> Result<bool, Violation> {
+		Self::restore(&mut self.repr, &mut self.undo, &mut self.redo)
+	}
+	/// Restores the value from before the most recent undo. Returns whether there was an undo to
+	/// redo. Like [`UndoableRepr::undo`], the restored value must satisfy the invariant.
+	pub fn redo(&mut self) -> Result<bool, Violation> {
+		Self::restore(&mut self.repr, &mut self.redo, &mut self.undo)
+	}
+	/// Whether there's a write to undo.
+	pub fn can_undo(&self) -> bool {
+		!self.undo.is_empty()
+	}
+	/// Whether there's an undo to redo.
+	pub fn can_redo(&self) -> bool {
+		!self.redo.is_empty()
+	}
+	/// Stops keeping the history, returning the representation invariant.
+	pub fn into_inner(self) -> Repr<T, I> {
+		self.repr
+	}
+	fn record(&mut self, value: T) {
+		self.redo.clear();
+		if self.depth == 0 {
+			return;
+		}
+		self.undo.truncate(self.depth - 1);
+		self.undo.push_front(value);
+	}
+	/// Moves the repr to the most recent value in `from`, keeping its current value in `to`.
+	fn restore(repr: &mut Repr<T, I>, from: &mut VecDeque<T>, to: &mut VecDeque<T>) -> Result<bool, Violation> {
+		let Some(value) = from.pop_front() else {
+			return Ok(false);
+		};
+		match repr.replace(value) {
+			Ok(current) => {
+				to.push_front(current);
+				Ok(true)
+			}
+			Err((value, violation)) => {
+				from.push_front(value);
+				Err(violation)
+			}
+		}
+	}
+}
@@ -0,0 +1,39 @@
+use crate::{CacheableRepr, Repr};
+use std::fmt::Debug;
+use validator::Validate;
+
+impl<T: Debug + Validate + 'static> Repr<T> {
+	/// Creates a new representation invariant whose invariant is the value's [`Validate`]
+	/// implementation. When the invariant is violated, the [`validator::ValidationErrors`] are used
+	/// as the violation message.
+	/// ```rust
+	/// use repr_rs::Repr;
+	/// use validator::Validate;
+	/// #[derive(Debug, Validate)]
+	/// struct SignupData {
+	///   #[validate(email)]
+	///   mail: String,
+	/// }
+	/// let mut repr = Repr::from_validate(SignupData { mail: "alice@example.com".into() });
+	/// let (_, violation) = repr.set(SignupData { mail: "alice".into() }).unwrap_err();
+	/// assert!(violation.message().starts_with("mail: Validation error: email"));
+	/// ```
+	pub fn from_validate(value: T) -> Self {
+		Repr::new(value, (|value: &T| value.validate().is_ok()) as fn(&T) -> bool)
+			.with_explanation(explain_validation_errors)
+	}
+}
+impl<T: Debug + Validate + 'static> CacheableRepr<T> {
+	/// Creates a new representation invariant whose invariant is the value's [`Validate`]
+	/// implementation. See [`Repr::from_validate`].
+	pub fn from_validate(value: T) -> Self {
+		Self::from(Repr::from_validate(value))
+	}
+}
+
+fn explain_validation_errors<T: Validate>(value: &T) -> String {
+	match value.validate() {
+		Ok(()) => String::from("Invariant violated"),
+		Err(errors) => errors.to_string(),
+	}
+}
@@ -0,0 +1,415 @@
+//! Representation invariants over vectors, which check new and modified elements on their own.
+//! See [`ReprVec`].
+use crate::Violation;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::ops::{Deref, DerefMut, Range};
+
+/// A vector with an invariant for every element and an (optional) invariant for the whole
+/// collection, e.g. sortedness. Wrapping a [`Vec`] in a [`crate::Repr`] checks every element again
+/// after every write, whereas a `ReprVec` only checks the elements that were added or modified,
+/// followed by one check of the collection invariant. Bulk writes ([`ReprVec::extend`] and
+/// [`ReprVec::write`]) check the collection invariant once for the whole batch.
+///
+/// Sortedness and uniqueness are built in (see [`ReprVec::with_sorted_elements`] and
+/// [`ReprVec::with_unique_elements`]), and are checked only around the elements that were written.
+/// ```rust
+/// use repr_rs::ReprVec;
+/// let mut scores = ReprVec::with_msg(|score: &u32| *score <= 100, "scores are percentages")
+///   .with_collection_invariant(|scores| scores.is_sorted());
+/// scores.push(50).unwrap();
+/// scores.extend([60, 70]).unwrap();
+/// let (rejected, violation) = scores.push(101).unwrap_err();
+/// assert_eq!(101, rejected);
+/// assert_eq!("scores are percentages", violation.message());
+/// assert!(scores.push(10).is_err());
+/// assert_eq!(&[50, 60, 70], scores.read());
+/// ```
+pub struct ReprVec<T: Debug, E: Fn(&T) -> bool = fn(&T) -> bool, C: Fn(&[T]) -> bool = fn(&[T]) -> bool> {
+	items: Vec<T>,
+	element: E,
+	collection: C,
+	order: Order<T>,
+	violation_message: &'static str,
+}
+
+/// The built-in invariants, which only need to be checked around the elements that were written.
+struct Order<T> {
+	sorted: Option<fn(&T, &T) -> bool>,
+	unique: Option<fn(&T, &T) -> bool>,
+}
+impl<T> Order<T> {
+	fn holds(&self, items: &[T], written: Range<usize>) -> bool {
+		// The other elements were already in order, so only the neighbours of the written elements
+		// (or of the gap left by a removal) need to be compared.
+		let mut neighbours = written.start.saturating_sub(1)..written.end.min(items.len().saturating_sub(1));
+		match (self.sorted, self.unique) {
+			(None, None) => true,
+			(Some(le), None) => neighbours.all(|i| le(&items[i], &items[i + 1])),
+			// Duplicates in a sorted vector are always neighbours.
+			(Some(le), Some(eq)) => neighbours.all(|i| le(&items[i], &items[i + 1]) && !eq(&items[i], &items[i + 1])),
+			(None, Some(eq)) => items[written.clone()].iter().enumerate().all(|(i, item)| {
+				items.iter().enumerate().all(|(j, other)| written.start + i == j || !eq(item, other))
+			}),
+		}
+	}
+}
+impl<T> Clone for Order<T> {
+	fn clone(&self) -> Self {
+		Self {
+			sorted: self.sorted,
+			unique: self.unique,
+		}
+	}
+}
+
+impl<T: Debug, E: Fn(&T) -> bool> ReprVec<T, E> {
+	/// Creates an empty vector whose elements must satisfy the given invariant function.
+	pub fn new(element: E) -> Self {
+		Self::with_msg(element, "Invariant violated")
+	}
+	/// Creates an empty vector whose elements must satisfy the given invariant function, with the
+	/// given violation message.
+	pub fn with_msg(element: E, violation_message: &'static str) -> Self {
+		Self {
+			items: Vec::new(),
+			element,
+			collection: |_| true,
+			order: Order { sorted: None, unique: None },
+			violation_message,
+		}
+	}
+}
+impl<T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> ReprVec<T, E, C> {
+	/// Adds an invariant for the whole collection, which is checked after every write.
+	///
+	/// # Panics
+	/// If the current elements violate it.
+	pub fn with_collection_invariant<D: Fn(&[T]) -> bool>(self, collection: D) -> ReprVec<T, E, D> {
+		let repr = ReprVec {
+			items: self.items,
+			element: self.element,
+			collection,
+			order: self.order,
+			violation_message: self.violation_message,
+		};
+		repr.check();
+		repr
+	}
+	/// Adds the built-in invariant that the elements are sorted, i.e. that every element is less
+	/// than or equal to the next one. Unlike a collection invariant like `|items| items.is_sorted()`,
+	/// which compares every pair of neighbours after every write, only the neighbours of the
+	/// elements that were written are compared.
+	///
+	/// # Panics
+	/// If the current elements aren't sorted.
+	/// ```rust
+	/// use repr_rs::ReprVec;
+	/// let mut timestamps = ReprVec::new(|t: &u64| *t > 0).with_sorted_elements();
+	/// timestamps.extend([10, 20, 30]).unwrap();
+	/// timestamps.insert(1, 15).unwrap();
+	/// assert!(timestamps.insert(1, 25).is_err());
+	/// assert!(timestamps.remove(2).is_ok());
+	/// assert_eq!(&[10, 15, 30], timestamps.read());
+	/// ```
+	pub fn with_sorted_elements(mut self) -> Self where T: PartialOrd {
+		self.order.sorted = Some(|a, b| a <= b);
+		self.check();
+		self
+	}
+	/// Adds the built-in invariant that no two elements are equal. Each written element is compared
+	/// with every other element, so a write costs O(n) rather than the O(n²) of comparing every pair
+	/// of elements. Combined with [`ReprVec::with_sorted_elements`], only the neighbours of the
+	/// written elements are compared.
+	///
+	/// # Panics
+	/// If the current elements aren't unique.
+	/// ```rust
+	/// use repr_rs::ReprVec;
+	/// let mut tags = ReprVec::new(|tag: &&str| !tag.is_empty()).with_unique_elements();
+	/// tags.extend(["b", "a"]).unwrap();
+	/// assert!(tags.push("a").is_err());
+	/// assert!(tags.extend(["c", "c"]).is_err());
+	/// assert_eq!(&["b", "a"], tags.read());
+	/// ```
+	pub fn with_unique_elements(mut self) -> Self where T: PartialEq {
+		self.order.unique = Some(|a, b| a == b);
+		self.check();
+		self
+	}
+	/// Borrows a read-only view of the elements.
+	#[inline]
+	pub fn read(&self) -> &[T] {
+		&self.items
+	}
+	/// Appends an element if it satisfies the element invariant and the collection still satisfies
+	/// the collection invariant. Otherwise the element is handed back alongside the [`Violation`]
+	/// and the vector is left untouched.
+	pub fn push(&mut self, item: T) -> Result<(), (T, Violation)> {
+		let index = self.items.len();
+		self.insert(index, item)
+	}
+	/// Inserts an element at `index` if it satisfies the element invariant and the collection still
+	/// satisfies the collection invariant. Otherwise the element is handed back alongside the
+	/// [`Violation`] and the vector is left untouched.
+	///
+	/// # Panics
+	/// If `index` is greater than the length of the vector.
+	pub fn insert(&mut self, index: usize, item: T) -> Result<(), (T, Violation)> {
+		if let Err(violation) = self.validate_element(&item) {
+			return Err((item, violation));
+		}
+		self.items.insert(index, item);
+		if let Err(violation) = self.validate_collection(index..index + 1) {
+			return Err((self.items.remove(index), violation));
+		}
+		Ok(())
+	}
+	/// Appends every element of `items`, checking each of them against the element invariant and
+	/// then checking the collection invariant once. If any check fails, none of the elements are
+	/// appended (and the remaining ones aren't consumed).
+	pub fn extend(&mut self, items: impl IntoIterator<Item = T>) -> Result<(), Violation> {
+		let len = self.items.len();
+		for item in items {
+			if let Err(violation) = self.validate_element(&item) {
+				self.items.truncate(len);
+				return Err(violation);
+			}
+			self.items.push(item);
+		}
+		if let Err(violation) = self.validate_collection(len..self.items.len()) {
+			self.items.truncate(len);
+			return Err(violation);
+		}
+		Ok(())
+	}
+	/// Removes and returns the element at `index` if the collection still satisfies the collection
+	/// invariant without it. Otherwise the vector is left untouched.
+	///
+	/// # Panics
+	/// If `index` is out of bounds.
+	pub fn remove(&mut self, index: usize) -> Result<T, Violation> {
+		let item = self.items.remove(index);
+		if let Err(violation) = self.validate_collection(index..index) {
+			self.items.insert(index, item);
+			return Err(violation);
+		}
+		Ok(item)
+	}
+	/// Removes and returns the last element, like [`ReprVec::remove`].
+	pub fn pop(&mut self) -> Result<Option<T>, Violation> {
+		match self.items.len() {
+			0 => Ok(None),
+			len => self.remove(len - 1).map(Some),
+		}
+	}
+	/// Borrows a mutable view of the element at `index`. When the view is dropped, the element
+	/// invariant is checked for that element only, and then the collection invariant is checked.
+	/// ```should_panic
+	/// use repr_rs::ReprVec;
+	/// let mut positive = ReprVec::new(|n: &i32| *n > 0);
+	/// positive.extend([1, 2, 3]).unwrap();
+	/// *positive.get_mut(1).unwrap() *= 10;
+	/// // panics because the element must be positive
+	/// *positive.get_mut(1).unwrap() = -1;
+	/// ```
+	pub fn get_mut(&mut self, index: usize) -> Option<ReprVecElementMutator<'_, T, E, C>> {
+		if index < self.items.len() {
+			Some(ReprVecElementMutator { repr: self, index })
+		} else {
+			None
+		}
+	}
+	/// Iterates over mutable views of the elements. Each element is checked against the element
+	/// invariant when its view is dropped, and the collection invariant is checked once when the
+	/// iterator is dropped, so a pass over every element doesn't need one big write followed by a
+	/// full check.
+	///
+	/// The views borrow the iterator, so it is used with `while let` rather than a `for` loop.
+	/// ```rust
+	/// use repr_rs::ReprVec;
+	/// let mut scores = ReprVec::new(|score: &u32| *score <= 100)
+	///   .with_collection_invariant(|scores| scores.is_sorted());
+	/// scores.extend([10, 20, 30]).unwrap();
+	/// let mut iter = scores.iter_mut();
+	/// while let Some(mut score) = iter.next() {
+	///   *score *= 2;
+	/// }
+	/// drop(iter);
+	/// assert_eq!(&[20, 40, 60], scores.read());
+	/// ```
+	pub fn iter_mut(&mut self) -> ReprVecIterMut<'_, T, E, C> {
+		ReprVecIterMut { repr: self, next: 0 }
+	}
+	/// Borrows a mutable view of the whole vector, for bulk writes. When the view is dropped, every
+	/// element is checked against the element invariant, and the collection invariant is checked
+	/// once.
+	pub fn write(&mut self) -> ReprVecMutator<'_, T, E, C> {
+		ReprVecMutator { repr: self }
+	}
+	/// Consumes the representation invariant and returns the elements.
+	pub fn into_inner(self) -> Vec<T> {
+		self.items
+	}
+	/// Checks that every element and the collection satisfy their invariants without panicking.
+	pub fn validate(&self) -> Result<(), Violation> {
+		self.items.iter().try_for_each(|item| self.validate_element(item))?;
+		self.validate_collection(0..self.items.len())
+	}
+	/// Evaluates the invariants for the current elements without panicking.
+	pub fn is_valid(&self) -> bool {
+		self.items.iter().all(&self.element) && self.order.holds(&self.items, 0..self.items.len()) && (self.collection)(&self.items)
+	}
+	fn validate_element(&self, item: &T) -> Result<(), Violation> {
+		if (self.element)(item) {
+			Ok(())
+		} else {
+			Err(Violation::new(self.violation_message, item))
+		}
+	}
+	/// Checks the collection invariants after the elements in `written` were written.
+	fn validate_collection(&self, written: Range<usize>) -> Result<(), Violation> {
+		if self.order.holds(&self.items, written) && (self.collection)(&self.items) {
+			Ok(())
+		} else {
+			Err(Violation::new(self.violation_message, &self.items))
+		}
+	}
+	fn check(&self) {
+		if crate::repr::panicking() {
+			return;
+		}
+		if let Err(violation) = self.validate() {
+			panic!("{}", violation);
+		}
+	}
+}
+impl<T: Debug + Clone, E: Fn(&T) -> bool + Clone, C: Fn(&[T]) -> bool + Clone> Clone for ReprVec<T, E, C> {
+	fn clone(&self) -> Self {
+		Self {
+			items: self.items.clone(),
+			element: self.element.clone(),
+			collection: self.collection.clone(),
+			order: self.order.clone(),
+			violation_message: self.violation_message,
+		}
+	}
+}
+impl<T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> Debug for ReprVec<T, E, C> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "ReprVec({:?})", self.items)
+	}
+}
+
+/// A mutable view of one element of a [`ReprVec`]. The element invariant is checked for this
+/// element, and then the collection invariant, when it's dropped.
+pub struct ReprVecElementMutator<'a, T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> {
+	repr: &'a mut ReprVec<T, E, C>,
+	index: usize,
+}
+impl<T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> Deref for ReprVecElementMutator<'_, T, E, C> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		&self.repr.items[self.index]
+	}
+}
+impl<T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> DerefMut for ReprVecElementMutator<'_, T, E, C> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.repr.items[self.index]
+	}
+}
+impl<T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> Drop for ReprVecElementMutator<'_, T, E, C> {
+	fn drop(&mut self) {
+		if crate::repr::panicking() {
+			return;
+		}
+		let result = self.repr.validate_element(&self.repr.items[self.index])
+			.and_then(|()| self.repr.validate_collection(self.index..self.index + 1));
+		if let Err(violation) = result {
+			panic!("{}", violation);
+		}
+	}
+}
+
+/// A mutable view of all the elements of a [`ReprVec`]. Every element and the collection are
+/// checked when it's dropped.
+pub struct ReprVecMutator<'a, T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> {
+	repr: &'a mut ReprVec<T, E, C>,
+}
+impl<T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> Deref for ReprVecMutator<'_, T, E, C> {
+	type Target = Vec<T>;
+	fn deref(&self) -> &Self::Target {
+		&self.repr.items
+	}
+}
+impl<T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> DerefMut for ReprVecMutator<'_, T, E, C> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.repr.items
+	}
+}
+impl<T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> Drop for ReprVecMutator<'_, T, E, C> {
+	fn drop(&mut self) {
+		self.repr.check();
+	}
+}
+
+/// An iterator over mutable views of the elements of a [`ReprVec`], created with
+/// [`ReprVec::iter_mut`]. The collection invariant is checked when it's dropped.
+pub struct ReprVecIterMut<'a, T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> {
+	repr: &'a mut ReprVec<T, E, C>,
+	next: usize,
+}
+impl<T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> ReprVecIterMut<'_, T, E, C> {
+	/// Borrows a mutable view of the next element, which is checked against the element invariant
+	/// when it's dropped.
+	#[allow(clippy::should_implement_trait)] // the views borrow the iterator, which `Iterator` can't express
+	pub fn next(&mut self) -> Option<ReprVecItemMut<'_, T, E>> {
+		let ReprVec { items, element, violation_message, .. } = &mut *self.repr;
+		let item = items.get_mut(self.next)?;
+		self.next += 1;
+		Some(ReprVecItemMut {
+			item,
+			element,
+			violation_message,
+		})
+	}
+}
+impl<T: Debug, E: Fn(&T) -> bool, C: Fn(&[T]) -> bool> Drop for ReprVecIterMut<'_, T, E, C> {
+	fn drop(&mut self) {
+		if crate::repr::panicking() {
+			return;
+		}
+		if let Err(violation) = self.repr.validate_collection(0..self.next) {
+			panic!("{}", violation);
+		}
+	}
+}
+
+/// A mutable view of an element of a [`ReprVec`], yielded by a [`ReprVecIterMut`]. The element
+/// invariant is checked when it's dropped.
+pub struct ReprVecItemMut<'a, T: Debug, E: Fn(&T) -> bool> {
+	item: &'a mut T,
+	element: &'a E,
+	violation_message: &'static str,
+}
+impl<T: Debug, E: Fn(&T) -> bool> Deref for ReprVecItemMut<'_, T, E> {
+	type Target = T;
+	fn deref(&self) -> &Self::Target {
+		self.item
+	}
+}
+impl<T: Debug, E: Fn(&T) -> bool> DerefMut for ReprVecItemMut<'_, T, E> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.item
+	}
+}
+impl<T: Debug, E: Fn(&T) -> bool> Drop for ReprVecItemMut<'_, T, E> {
+	fn drop(&mut self) {
+		if crate::repr::panicking() {
+			return;
+		}
+		if !(self.element)(self.item) {
+			panic!("{}", Violation::new(self.violation_message, &*self.item));
+		}
+	}
+}
@@ -0,0 +1,36 @@
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use core::error::Error;
+use core::fmt::{Debug, Display};
+
+/// Describes a representation invariant that doesn't hold. This is returned by the non-panicking
+/// APIs (e.g. [`crate::Repr::validate`]), and its [`Display`] output is the same message that the
+/// panicking APIs use.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Violation {
+	message: Cow<'static, str>,
+	state: String,
+}
+impl Violation {
+	pub(crate) fn new<T: Debug + ?Sized>(message: impl Into<Cow<'static, str>>, state: &T) -> Self {
+		Self {
+			message: message.into(),
+			state: format!("{:?}", state),
+		}
+	}
+	/// The violation message of the representation invariant that was violated.
+	pub fn message(&self) -> &str {
+		&self.message
+	}
+	/// The [`Debug`] representation of the value that violated the invariant.
+	pub fn state(&self) -> &str {
+		&self.state
+	}
+}
+impl Display for Violation {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "{}\nState was: {}", self.message, self.state)
+	}
+}
+impl Error for Violation {}